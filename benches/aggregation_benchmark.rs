@@ -7,6 +7,8 @@ use project::aggregations::{
     normal_sum, parallel_sum, normal_count, parallel_count,
     normal_avg, parallel_avg, normal_min, parallel_min,
     normal_max, parallel_max, normal_distinct_count, parallel_distinct_count,
+    normal_approx_distinct_count, parallel_approx_distinct_count, adaptive_approx_distinct_count,
+    DEFAULT_PRECISION,
 };
 
 fn generate_random_data(size: usize) -> Vec<i32> {
@@ -58,6 +60,11 @@ fn bench_aggregations(c: &mut Criterion) {
     group.bench_function("Normal Distinct Count (Large)", |b| b.iter(|| normal_distinct_count(black_box(&large_data))));
     group.bench_function("Parallel Distinct Count (Large)", |b| b.iter(|| parallel_distinct_count(black_box(&large_data))));
     group.bench_function("Adaptive Distinct Count (Large)", |b| b.iter(|| adaptive_distinct_count(black_box(&large_data))));
+
+    // Approximate Distinct Count benchmarks
+    group.bench_function("Normal Approx Distinct Count (Large)", |b| b.iter(|| normal_approx_distinct_count(black_box(&large_data), DEFAULT_PRECISION)));
+    group.bench_function("Parallel Approx Distinct Count (Large)", |b| b.iter(|| parallel_approx_distinct_count(black_box(&large_data), DEFAULT_PRECISION)));
+    group.bench_function("Adaptive Approx Distinct Count (Large)", |b| b.iter(|| adaptive_approx_distinct_count(black_box(&large_data), DEFAULT_PRECISION)));
     
     group.finish();
 }
@@ -1,5 +1,7 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use project::aggregations::AggregateFunction;
 use project::group_by::{sequential_group_by, parallel_group_by, adaptive_group_by};
+use project::types::Value;
 use rand::Rng;
 
 #[derive(Clone, Debug)]
@@ -24,18 +26,21 @@ fn bench_group_by(c: &mut Criterion) {
     for size in data_sizes.iter() {
         let data = generate_random_data(*size);
 
+        let aggregates = [AggregateFunction::Avg];
+        let key_func = |r: &Record| (r.id, vec![Value::Float64(r.value)]);
+
         let mut group = c.benchmark_group(format!("Group By (size {})", size));
 
         group.bench_function("Sequential Group By", |b| {
-            b.iter(|| sequential_group_by(black_box(&data), |r| (r.id, r.value)))
+            b.iter(|| sequential_group_by(black_box(&data), key_func, &aggregates))
         });
 
         group.bench_function("Parallel Group By", |b| {
-            b.iter(|| parallel_group_by(black_box(&data), |r| (r.id, r.value)))
+            b.iter(|| parallel_group_by(black_box(&data), key_func, &aggregates))
         });
 
         group.bench_function("Adaptive Group By", |b| {
-            b.iter(|| adaptive_group_by(black_box(&data), |r| (r.id, r.value)))
+            b.iter(|| adaptive_group_by(black_box(&data), key_func, &aggregates))
         });
 
         group.finish();
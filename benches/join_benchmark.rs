@@ -1,5 +1,10 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use project::joins::{Record, sequential_hash_join, parallel_hash_join, sequential_merge_join, parallel_merge_join};
+use project::joins::{
+    JoinType, Record, sequential_hash_join, parallel_hash_join, sequential_merge_join, parallel_merge_join,
+    sequential_outer_hash_join, parallel_outer_hash_join, sequential_hash_join_by, parallel_hash_join_by,
+    sequential_band_join_by, parallel_band_join_by, parallel_nested_loop_join_by,
+};
+use std::ops::Bound;
 use rand::{Rng, distributions::Alphanumeric};
 
 fn generate_random_records(size: usize, id_range: std::ops::Range<i32>) -> Vec<Record> {
@@ -29,6 +34,44 @@ fn bench_joins(c: &mut Criterion) {
             b.iter(|| parallel_hash_join(black_box(&left_data), black_box(&right_data)))
         });
 
+        group.bench_function("Sequential Full Outer Hash Join", |b| {
+            b.iter(|| sequential_outer_hash_join(black_box(&left_data), black_box(&right_data), JoinType::Full))
+        });
+
+        group.bench_function("Parallel Full Outer Hash Join", |b| {
+            b.iter(|| parallel_outer_hash_join(black_box(&left_data), black_box(&right_data), JoinType::Full))
+        });
+
+        // Composite key of a number and a character
+        let key = |r: &Record| (r.id % 100, r.value.as_bytes()[0]);
+        group.bench_function("Sequential Hash Join on Composite Key", |b| {
+            b.iter(|| sequential_hash_join_by(black_box(&left_data), black_box(&right_data), key, key))
+        });
+
+        group.bench_function("Parallel Hash Join on Composite Key", |b| {
+            b.iter(|| parallel_hash_join_by(black_box(&left_data), black_box(&right_data), key, key))
+        });
+
+        // Each right record matches the left ids within 2 of its own
+        let window = |r: &Record| (Bound::Included(r.id - 2), Bound::Included(r.id + 2));
+        group.bench_function("Sequential Band Join", |b| {
+            b.iter(|| sequential_band_join_by(black_box(&left_data), black_box(&right_data), |l| l.id, window))
+        });
+
+        group.bench_function("Parallel Band Join", |b| {
+            b.iter(|| parallel_band_join_by(black_box(&left_data), black_box(&right_data), |l| l.id, window))
+        });
+
+        if *left_size <= 10_000 {
+            group.bench_function("Parallel Nested Loop Join", |b| {
+                b.iter(|| {
+                    parallel_nested_loop_join_by(black_box(&left_data), black_box(&right_data), |l, r| {
+                        (l.id - r.id).abs() <= 2
+                    })
+                })
+            });
+        }
+
         group.bench_function("Sequential Merge Join", |b| {
             b.iter(|| {
                 let mut left = left_data.clone();
@@ -1,127 +1,1556 @@
 use rayon::prelude::*;
+use std::any::Any;
+use std::borrow::Cow;
+use std::collections::hash_map::{DefaultHasher, RandomState};
 use std::collections::HashSet;
+use std::fmt;
+use std::hash::{BuildHasher, Hash, Hasher};
 
-const PARALLELIZATION_THRESHOLD: usize = 50000;
+use crate::batch::{Column, ColumnData};
+use crate::calibration::thresholds;
+use crate::error::EngineError;
+use crate::execution::{self, ExecutionReport, ExecutionStrategy};
+use crate::types::{DataType, Decimal, Value};
+use std::cmp::Ordering;
 
-fn should_parallelize(data_size: usize) -> bool {
-    data_size > PARALLELIZATION_THRESHOLD
+pub(crate) const PARALLELIZATION_THRESHOLD: usize = 50000; // Default, see calibration::Thresholds
+
+// Numbers the aggregations accept, with the SQL type of their SUM: BIGINT (i64) for every
+// integer type, DOUBLE (f64) for floats and a decimal for decimals
+pub trait Numeric: Copy + PartialOrd + Send + Sync {
+    type Sum: SumType;
+
+    // Fails for values SUM cannot represent, i.e. u64 values above i64::MAX
+    fn to_sum(self) -> Result<Self::Sum, EngineError>;
+    fn to_f64(self) -> f64;
+    // A total order, with NaN after every other float (before them if its sign bit is set)
+    fn compare(&self, other: &Self) -> Ordering;
 }
 
-pub fn adaptive_sum(data: &[i32]) -> (i32, String) {
-    if should_parallelize(data.len()) {
-        (parallel_sum(data), "Parallel".to_string())
-    } else {
-        (normal_sum(data), "Sequential".to_string())
+pub trait SumType: Copy + Send + Sync + fmt::Debug + PartialEq {
+    const ZERO: Self;
+
+    fn checked_add(self, other: Self) -> Option<Self>;
+    // The sum divided by the number of values it adds up
+    fn average(self, count: usize) -> f64;
+}
+
+macro_rules! integer_numeric {
+    ($($t:ty),*) => {
+        $(
+            impl Numeric for $t {
+                type Sum = i64;
+
+                #[allow(clippy::useless_conversion)]
+                fn to_sum(self) -> Result<i64, EngineError> {
+                    i64::try_from(self).map_err(|_| overflow())
+                }
+
+                fn to_f64(self) -> f64 {
+                    self as f64
+                }
+
+                fn compare(&self, other: &Self) -> Ordering {
+                    self.cmp(other)
+                }
+            }
+        )*
+    };
+}
+
+integer_numeric!(i8, i16, i32, i64, u8, u16, u32, u64);
+
+impl Numeric for f32 {
+    type Sum = f64;
+
+    fn to_sum(self) -> Result<f64, EngineError> {
+        Ok(self as f64)
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn compare(&self, other: &Self) -> Ordering {
+        self.total_cmp(other)
     }
 }
 
-pub fn adaptive_count(data: &[i32]) -> (usize, String) {
-    // Count is always faster sequentially based on the benchmark results
-    (normal_count(data), "Sequential".to_string())
+impl Numeric for f64 {
+    type Sum = f64;
+
+    fn to_sum(self) -> Result<f64, EngineError> {
+        Ok(self)
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn compare(&self, other: &Self) -> Ordering {
+        self.total_cmp(other)
+    }
 }
 
-pub fn adaptive_avg(data: &[i32]) -> (f64, String) {
-    if should_parallelize(data.len()) {
-        (parallel_avg(data), "Parallel".to_string())
-    } else {
-        (normal_avg(data), "Sequential".to_string())
+// Only the slice aggregations see decimals: SQL has no decimal column and reads DECIMAL as Float64
+impl Numeric for Decimal {
+    type Sum = Decimal;
+
+    fn to_sum(self) -> Result<Decimal, EngineError> {
+        Ok(self)
+    }
+
+    fn to_f64(self) -> f64 {
+        Decimal::to_f64(&self)
+    }
+
+    fn compare(&self, other: &Self) -> Ordering {
+        self.cmp(other)
     }
 }
 
-pub fn adaptive_min(data: &[i32]) -> (i32, String) {
-    if should_parallelize(data.len()) {
-        (parallel_min(data), "Parallel".to_string())
-    } else {
-        (normal_min(data), "Sequential".to_string())
+impl SumType for i64 {
+    const ZERO: Self = 0;
+
+    fn checked_add(self, other: Self) -> Option<Self> {
+        i64::checked_add(self, other)
+    }
+
+    fn average(self, count: usize) -> f64 {
+        self as f64 / count as f64
     }
 }
 
-pub fn adaptive_max(data: &[i32]) -> (i32, String) {
-    if should_parallelize(data.len()) {
-        (parallel_max(data), "Parallel".to_string())
-    } else {
-        (normal_max(data), "Sequential".to_string())
+impl SumType for f64 {
+    const ZERO: Self = 0.0;
+
+    fn checked_add(self, other: Self) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn average(self, count: usize) -> f64 {
+        self / count as f64
     }
 }
 
-pub fn adaptive_distinct_count(data: &[i32]) -> (usize, String) {
-    if should_parallelize(data.len()) {
-        (parallel_distinct_count(data), "Parallel".to_string())
-    } else {
-        (normal_distinct_count(data), "Sequential".to_string())
+impl SumType for Decimal {
+    const ZERO: Self = Decimal::ZERO;
+
+    fn checked_add(self, other: Self) -> Option<Self> {
+        Decimal::checked_add(self, other)
+    }
+
+    fn average(self, count: usize) -> f64 {
+        self.to_f64() / count as f64
     }
 }
 
-pub fn normal_sum(data: &[i32]) -> i32 {
-    data.iter().sum()
+fn overflow() -> EngineError {
+    EngineError::Execution("numeric overflow in SUM".to_string())
+}
+
+fn add<S: SumType>(sum: S, value: S) -> Result<S, EngineError> {
+    sum.checked_add(value).ok_or_else(overflow)
+}
+
+// In the total order of `Numeric::compare`, as SQL MIN and MAX use, so NaN is the largest float
+// and the result does not depend on the order of the input or how it is split
+fn min_of<T: Numeric>(a: T, b: T) -> T {
+    if b.compare(&a) == Ordering::Less { b } else { a }
+}
+
+fn max_of<T: Numeric>(a: T, b: T) -> T {
+    if b.compare(&a) == Ordering::Greater { b } else { a }
+}
+
+// Every aggregate outputs a single row
+fn run<T, R>(data: &[T], sequential: impl FnOnce() -> R, parallel: impl FnOnce() -> R) -> (R, ExecutionReport) {
+    let report = ExecutionReport::for_input(data.len(), thresholds().aggregation);
+    execution::run_adaptive(report, sequential, parallel, |_| 1)
+}
+
+// SUM and AVG fail on overflow instead of wrapping
+pub fn adaptive_sum<T: Numeric>(data: &[T]) -> Result<(T::Sum, ExecutionReport), EngineError> {
+    let (sum, report) = run(data, || normal_sum(data), || parallel_sum(data));
+    Ok((sum?, report))
+}
+
+pub fn adaptive_count<T>(data: &[T]) -> (usize, ExecutionReport) {
+    // Count is always faster sequentially based on the benchmark results
+    let report = ExecutionReport::new(ExecutionStrategy::Sequential, data.len(), usize::MAX);
+    execution::measure(report, || normal_count(data), |_| 1)
 }
 
-pub fn parallel_sum(data: &[i32]) -> i32 {
-    data.par_iter().sum()
+// AVG, MIN and MAX of no values are undefined and return None
+pub fn adaptive_avg<T: Numeric>(data: &[T]) -> Result<(Option<f64>, ExecutionReport), EngineError> {
+    let (avg, report) = run(data, || normal_avg(data), || parallel_avg(data));
+    Ok((avg?, report))
 }
 
-pub fn normal_count(data: &[i32]) -> usize {
+pub fn adaptive_min<T: Numeric>(data: &[T]) -> (Option<T>, ExecutionReport) {
+    run(data, || normal_min(data), || parallel_min(data))
+}
+
+pub fn adaptive_max<T: Numeric>(data: &[T]) -> (Option<T>, ExecutionReport) {
+    run(data, || normal_max(data), || parallel_max(data))
+}
+
+pub fn adaptive_distinct_count<T: Hash + Eq + Sync>(data: &[T]) -> (usize, ExecutionReport) {
+    let (count, report) = run(data, || normal_distinct_count(data), || parallel_distinct_count(data));
+    (count, report.with_chunk_size(distinct_chunk_size(data.len())))
+}
+
+pub fn normal_sum<T: Numeric>(data: &[T]) -> Result<T::Sum, EngineError> {
+    data.iter().try_fold(T::Sum::ZERO, |sum, &x| add(sum, x.to_sum()?))
+}
+
+pub fn parallel_sum<T: Numeric>(data: &[T]) -> Result<T::Sum, EngineError> {
+    data.par_iter()
+        .try_fold(|| T::Sum::ZERO, |sum, &x| add(sum, x.to_sum()?))
+        .try_reduce(|| T::Sum::ZERO, add)
+}
+
+pub fn normal_count<T>(data: &[T]) -> usize {
     data.len()
 }
 
-pub fn parallel_count(data: &[i32]) -> usize {
+pub fn parallel_count<T: Sync>(data: &[T]) -> usize {
     data.par_iter().count()
 }
 
-pub fn normal_avg(data: &[i32]) -> f64 {
-    let sum: i32 = data.iter().sum();
-    sum as f64 / data.len() as f64
+// Both modes add up through SUM, so they agree and share its overflow check
+pub fn normal_avg<T: Numeric>(data: &[T]) -> Result<Option<f64>, EngineError> {
+    if data.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(normal_sum(data)?.average(data.len())))
 }
 
-pub fn parallel_avg(data: &[i32]) -> f64 {
-    let (sum, count) = data.par_iter()
-        .map(|&x| (x as i64, 1usize))
-        .reduce(
-            || (0, 0),
-            |(sum1, count1), (sum2, count2)| (sum1 + sum2, count1 + count2)
+pub fn parallel_avg<T: Numeric>(data: &[T]) -> Result<Option<f64>, EngineError> {
+    if data.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(parallel_sum(data)?.average(data.len())))
+}
+
+pub fn normal_min<T: Numeric>(data: &[T]) -> Option<T> {
+    data.iter().copied().reduce(min_of)
+}
+
+pub fn parallel_min<T: Numeric>(data: &[T]) -> Option<T> {
+    data.par_iter().copied().reduce_with(min_of)
+}
+
+pub fn normal_max<T: Numeric>(data: &[T]) -> Option<T> {
+    data.iter().copied().reduce(max_of)
+}
+
+pub fn parallel_max<T: Numeric>(data: &[T]) -> Option<T> {
+    data.par_iter().copied().reduce_with(max_of)
+}
+
+// Statistics
+
+// Count, mean and sum of squared deviations from the mean of some values. Values are added with
+// Welford's method and partial moments of separate tasks are combined with Chan's formula, so
+// the result does not depend on how the input was split beyond rounding.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Moments {
+    pub count: u64,
+    pub mean: f64,
+    pub m2: f64,
+}
+
+impl Moments {
+    pub fn push(&mut self, x: f64) {
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    pub fn merge(&mut self, other: &Moments) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            *self = *other;
+            return;
+        }
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+        let weight = self.count as f64 * other.count as f64 / count as f64;
+        self.mean += delta * other.count as f64 / count as f64;
+        self.m2 += other.m2 + delta * delta * weight;
+        self.count = count;
+    }
+
+    // Population statistics need one value, sample statistics two
+    pub fn var_pop(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.m2 / self.count as f64)
+    }
+
+    pub fn var_samp(&self) -> Option<f64> {
+        (self.count > 1).then(|| self.m2 / (self.count - 1) as f64)
+    }
+
+    pub fn stddev_pop(&self) -> Option<f64> {
+        self.var_pop().map(f64::sqrt)
+    }
+
+    pub fn stddev_samp(&self) -> Option<f64> {
+        self.var_samp().map(f64::sqrt)
+    }
+}
+
+// Moments of pairs of values plus their co-moment, the sum of products of both deviations
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CoMoments {
+    pub count: u64,
+    pub mean_x: f64,
+    pub mean_y: f64,
+    pub m2_x: f64,
+    pub m2_y: f64,
+    pub c_xy: f64,
+}
+
+impl CoMoments {
+    pub fn push(&mut self, x: f64, y: f64) {
+        self.count += 1;
+        let n = self.count as f64;
+        let dx = x - self.mean_x;
+        let dy = y - self.mean_y;
+        self.mean_x += dx / n;
+        self.mean_y += dy / n;
+        self.m2_x += dx * (x - self.mean_x);
+        self.m2_y += dy * (y - self.mean_y);
+        self.c_xy += dx * (y - self.mean_y);
+    }
+
+    pub fn merge(&mut self, other: &CoMoments) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            *self = *other;
+            return;
+        }
+        let count = self.count + other.count;
+        let dx = other.mean_x - self.mean_x;
+        let dy = other.mean_y - self.mean_y;
+        let weight = self.count as f64 * other.count as f64 / count as f64;
+        self.mean_x += dx * other.count as f64 / count as f64;
+        self.mean_y += dy * other.count as f64 / count as f64;
+        self.m2_x += other.m2_x + dx * dx * weight;
+        self.m2_y += other.m2_y + dy * dy * weight;
+        self.c_xy += other.c_xy + dx * dy * weight;
+        self.count = count;
+    }
+
+    pub fn covar_pop(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.c_xy / self.count as f64)
+    }
+
+    // Undefined when either side does not vary
+    pub fn corr(&self) -> Option<f64> {
+        (self.m2_x > 0.0 && self.m2_y > 0.0).then(|| self.c_xy / (self.m2_x * self.m2_y).sqrt())
+    }
+
+    // REGR_SLOPE(dependent, independent): the slope of the least-squares line that predicts the
+    // dependent argument from the independent one. Pairs are pushed in SQL argument order, so
+    // the dependent values are in `x` and the independent ones in `y`, and the slope is
+    // cov(x, y) / var(y).
+    pub fn regr_slope(&self) -> Option<f64> {
+        (self.m2_y > 0.0).then(|| self.c_xy / self.m2_y)
+    }
+}
+
+pub fn adaptive_moments<T: Numeric>(data: &[T]) -> (Moments, ExecutionReport) {
+    run(data, || normal_moments(data), || parallel_moments(data))
+}
+
+pub fn normal_moments<T: Numeric>(data: &[T]) -> Moments {
+    let mut moments = Moments::default();
+    data.iter().for_each(|&x| moments.push(x.to_f64()));
+    moments
+}
+
+pub fn parallel_moments<T: Numeric>(data: &[T]) -> Moments {
+    data.par_iter()
+        .fold(Moments::default, |mut moments, &x| {
+            moments.push(x.to_f64());
+            moments
+        })
+        .reduce(Moments::default, |mut a, b| {
+            a.merge(&b);
+            a
+        })
+}
+
+pub fn adaptive_co_moments<T: Numeric>(pairs: &[(T, T)]) -> (CoMoments, ExecutionReport) {
+    run(pairs, || normal_co_moments(pairs), || parallel_co_moments(pairs))
+}
+
+pub fn normal_co_moments<T: Numeric>(pairs: &[(T, T)]) -> CoMoments {
+    let mut moments = CoMoments::default();
+    pairs.iter().for_each(|&(x, y)| moments.push(x.to_f64(), y.to_f64()));
+    moments
+}
+
+pub fn parallel_co_moments<T: Numeric>(pairs: &[(T, T)]) -> CoMoments {
+    pairs
+        .par_iter()
+        .fold(CoMoments::default, |mut moments, &(x, y)| {
+            moments.push(x.to_f64(), y.to_f64());
+            moments
+        })
+        .reduce(CoMoments::default, |mut a, b| {
+            a.merge(&b);
+            a
+        })
+}
+
+// Percentiles
+
+// Candidates below this count are selected sequentially by `parallel_select`
+const SELECTION_CUTOFF: usize = 10000;
+
+// Index of PERCENTILE_DISC: the first value whose cumulative share reaches the fraction
+fn disc_index(len: usize, fraction: f64) -> usize {
+    ((fraction.clamp(0.0, 1.0) * len as f64).ceil() as usize).clamp(1, len) - 1
+}
+
+// The two indices PERCENTILE_CONT interpolates between, and the weight of the upper one
+fn cont_position(len: usize, fraction: f64) -> (usize, usize, f64) {
+    let position = fraction.clamp(0.0, 1.0) * (len - 1) as f64;
+    let low = position.floor() as usize;
+    (low, position.ceil() as usize, position - low as f64)
+}
+
+fn interpolate(low: f64, high: f64, weight: f64) -> f64 {
+    low + (high - low) * weight
+}
+
+// The value at index `k` if `values` were sorted
+fn select<T: Numeric>(values: &mut [T], k: usize) -> T {
+    *values.select_nth_unstable_by(k, T::compare).1
+}
+
+// Quickselect that partitions the candidates around a pivot in parallel, keeping only the side
+// that holds index `k`, until few enough are left to select sequentially
+fn parallel_select<T: Numeric>(data: &[T], mut k: usize) -> T {
+    let mut candidates = Cow::Borrowed(data);
+    loop {
+        if candidates.len() <= SELECTION_CUTOFF {
+            return select(&mut candidates.into_owned(), k);
+        }
+        let mut sample = [candidates[0], candidates[candidates.len() / 2], candidates[candidates.len() - 1]];
+        let pivot = select(&mut sample, 1);
+
+        let less: Vec<T> = candidates
+            .par_iter()
+            .filter(|x| x.compare(&pivot) == Ordering::Less)
+            .copied()
+            .collect();
+        if k < less.len() {
+            candidates = Cow::Owned(less);
+            continue;
+        }
+        let equal = candidates.par_iter().filter(|x| x.compare(&pivot) == Ordering::Equal).count();
+        if k < less.len() + equal {
+            return pivot;
+        }
+        k -= less.len() + equal;
+        candidates = Cow::Owned(
+            candidates
+                .par_iter()
+                .filter(|x| x.compare(&pivot) == Ordering::Greater)
+                .copied()
+                .collect(),
         );
-    sum as f64 / count as f64
+    }
 }
 
-pub fn normal_min(data: &[i32]) -> i32 {
-    *data.iter().min().unwrap_or(&i32::MAX)
+// Percentiles take a fraction between 0 and 1, e.g. 0.95 for p95; MEDIAN is PERCENTILE_CONT(0.5).
+// PERCENTILE_CONT interpolates between the two closest values, PERCENTILE_DISC returns one of them.
+pub fn adaptive_percentile_cont<T: Numeric>(data: &[T], fraction: f64) -> (Option<f64>, ExecutionReport) {
+    run(
+        data,
+        || normal_percentile_cont(data, fraction),
+        || parallel_percentile_cont(data, fraction),
+    )
 }
 
-pub fn parallel_min(data: &[i32]) -> i32 {
-    data.par_iter().copied().min().unwrap_or(i32::MAX)
+pub fn normal_percentile_cont<T: Numeric>(data: &[T], fraction: f64) -> Option<f64> {
+    if data.is_empty() {
+        return None;
+    }
+    let (low, high, weight) = cont_position(data.len(), fraction);
+    let mut values = data.to_vec();
+    let low_value = select(&mut values, low);
+    // Selection leaves the larger values after the low one
+    let high_value = if high == low { low_value } else { select(&mut values[low + 1..], 0) };
+    Some(interpolate(low_value.to_f64(), high_value.to_f64(), weight))
 }
 
-pub fn normal_max(data: &[i32]) -> i32 {
-    *data.iter().max().unwrap_or(&i32::MIN)
+pub fn parallel_percentile_cont<T: Numeric>(data: &[T], fraction: f64) -> Option<f64> {
+    if data.is_empty() {
+        return None;
+    }
+    let (low, high, weight) = cont_position(data.len(), fraction);
+    let low_value = parallel_select(data, low);
+    let high_value = if high == low { low_value } else { parallel_select(data, high) };
+    Some(interpolate(low_value.to_f64(), high_value.to_f64(), weight))
 }
 
-pub fn parallel_max(data: &[i32]) -> i32 {
-    data.par_iter().copied().max().unwrap_or(i32::MIN)
+pub fn adaptive_percentile_disc<T: Numeric>(data: &[T], fraction: f64) -> (Option<T>, ExecutionReport) {
+    run(
+        data,
+        || normal_percentile_disc(data, fraction),
+        || parallel_percentile_disc(data, fraction),
+    )
 }
 
-pub fn normal_distinct_count(data: &[i32]) -> usize {
-    let mut set = HashSet::new();
-    data.iter().for_each(|&x| {
-        set.insert(x);
-    });
-    set.len()
+pub fn normal_percentile_disc<T: Numeric>(data: &[T], fraction: f64) -> Option<T> {
+    (!data.is_empty()).then(|| select(&mut data.to_vec(), disc_index(data.len(), fraction)))
 }
 
-pub fn parallel_distinct_count(data: &[i32]) -> usize {
-    data.par_chunks(data.len() / rayon::current_num_threads().max(1))
-        .map(|chunk| {
-            let mut set = HashSet::new();
-            chunk.iter().for_each(|&x| {
-                set.insert(x);
-            });
-            set
+pub fn parallel_percentile_disc<T: Numeric>(data: &[T], fraction: f64) -> Option<T> {
+    (!data.is_empty()).then(|| parallel_select(data, disc_index(data.len(), fraction)))
+}
+
+// Centroids a t-digest keeps are in the order of this; larger is more accurate and slower
+pub const DEFAULT_COMPRESSION: f64 = 100.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Centroid {
+    mean: f64,
+    weight: f64,
+}
+
+// A merging t-digest: a sketch of a distribution as weighted centroids, which are small near
+// the extremes and large around the median so that tail percentiles stay accurate. Digests of
+// separate chunks merge into one of the same accuracy. NaNs are ignored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TDigest {
+    compression: f64,
+    centroids: Vec<Centroid>,
+    // Values not merged into the centroids yet
+    buffer: Vec<f64>,
+    count: u64,
+    min: f64,
+    max: f64,
+}
+
+impl TDigest {
+    pub fn new(compression: f64) -> Self {
+        TDigest {
+            compression,
+            centroids: Vec::new(),
+            buffer: Vec::new(),
+            count: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn push(&mut self, x: f64) {
+        if x.is_nan() {
+            return;
+        }
+        self.buffer.push(x);
+        self.count += 1;
+        self.min = self.min.min(x);
+        self.max = self.max.max(x);
+        if self.buffer.len() >= (self.compression as usize * 5).max(16) {
+            self.compress(&[], &[]);
+        }
+    }
+
+    pub fn merge(&mut self, other: &TDigest) {
+        if other.count == 0 {
+            return;
+        }
+        self.count += other.count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.compress(&other.centroids, &other.buffer);
+    }
+
+    // Folds the buffer and the given centroids and values into the centroids. Neighbours are
+    // merged while the result stays below 4 * total * q * (1 - q) / compression at both of its
+    // ends, q being the share of the weight before it.
+    fn compress(&mut self, centroids: &[Centroid], values: &[f64]) {
+        let buffer = std::mem::take(&mut self.buffer);
+        let mut all = std::mem::take(&mut self.centroids);
+        all.extend_from_slice(centroids);
+        all.extend(buffer.iter().chain(values).map(|&mean| Centroid { mean, weight: 1.0 }));
+        if all.is_empty() {
+            return;
+        }
+        all.sort_by(|a, b| a.mean.total_cmp(&b.mean));
+
+        let total = self.count as f64;
+        let mut before = 0.0;
+        let mut current = all[0];
+        for centroid in &all[1..] {
+            let proposed = current.weight + centroid.weight;
+            let (q0, q2) = (before / total, (before + proposed) / total);
+            let limit = 4.0 * total * (q0 * (1.0 - q0)).min(q2 * (1.0 - q2)) / self.compression;
+            if proposed <= limit {
+                current.mean += (centroid.mean - current.mean) * centroid.weight / proposed;
+                current.weight = proposed;
+            } else {
+                before += current.weight;
+                self.centroids.push(current);
+                current = *centroid;
+            }
+        }
+        self.centroids.push(current);
+    }
+
+    // Interpolates between the centres of neighbouring centroids, and towards the minimum and
+    // maximum beyond the first and last one
+    pub fn quantile(&self, fraction: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        if !self.buffer.is_empty() {
+            let mut digest = self.clone();
+            digest.compress(&[], &[]);
+            return digest.quantile(fraction);
+        }
+        let total = self.count as f64;
+        let target = fraction.clamp(0.0, 1.0) * total;
+        let (mut before, mut previous_center, mut previous_mean) = (0.0, 0.0, self.min);
+        for centroid in &self.centroids {
+            let center = before + centroid.weight / 2.0;
+            if target < center {
+                let weight = (target - previous_center) / (center - previous_center);
+                return Some(interpolate(previous_mean, centroid.mean, weight));
+            }
+            (before, previous_center, previous_mean) = (before + centroid.weight, center, centroid.mean);
+        }
+        let weight = (target - previous_center) / (total - previous_center);
+        Some(interpolate(previous_mean, self.max, weight))
+    }
+}
+
+pub fn adaptive_approx_percentile<T: Numeric>(data: &[T], fraction: f64) -> (Option<f64>, ExecutionReport) {
+    run(
+        data,
+        || normal_approx_percentile(data, fraction),
+        || parallel_approx_percentile(data, fraction),
+    )
+}
+
+pub fn normal_approx_percentile<T: Numeric>(data: &[T], fraction: f64) -> Option<f64> {
+    let mut digest = TDigest::new(DEFAULT_COMPRESSION);
+    data.iter().for_each(|&x| digest.push(x.to_f64()));
+    digest.quantile(fraction)
+}
+
+// Every rayon task sketches its part of the input and the digests are merged
+pub fn parallel_approx_percentile<T: Numeric>(data: &[T], fraction: f64) -> Option<f64> {
+    let new = || TDigest::new(DEFAULT_COMPRESSION);
+    data.par_iter()
+        .fold(new, |mut digest, &x| {
+            digest.push(x.to_f64());
+            digest
+        })
+        .reduce(new, |mut a, b| {
+            a.merge(&b);
+            a
         })
+        .quantile(fraction)
+}
+
+// Approximate distinct counts
+
+// 2^14 registers, for a standard error of about 0.8% in 16 KiB
+pub const DEFAULT_PRECISION: u8 = 14;
+pub const MIN_PRECISION: u8 = 4;
+pub const MAX_PRECISION: u8 = 18;
+
+// A HyperLogLog sketch: 2^precision registers, each holding the longest run of leading zeros
+// seen among the hashes routed to it. Its size does not depend on the input, and two sketches
+// of the same precision merge by taking the larger of every register.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HyperLogLog {
+    precision: u8,
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    pub fn new(precision: u8) -> Result<Self, EngineError> {
+        if !(MIN_PRECISION..=MAX_PRECISION).contains(&precision) {
+            return Err(EngineError::Plan(format!(
+                "HyperLogLog precision must be between {} and {}, not {}",
+                MIN_PRECISION, MAX_PRECISION, precision
+            )));
+        }
+        Ok(HyperLogLog {
+            precision,
+            registers: vec![0; 1 << precision],
+        })
+    }
+
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    pub fn insert<T: Hash + ?Sized>(&mut self, value: &T) {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        self.insert_hash(hasher.finish());
+    }
+
+    // The top bits of the hash pick the register, the rest give the run of zeros
+    fn insert_hash(&mut self, hash: u64) {
+        let index = (hash >> (64 - self.precision)) as usize;
+        // The guard bit caps the run when the remaining bits are all zero
+        let rest = (hash << self.precision) | (1 << (self.precision - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        self.registers[index] = self.registers[index].max(rank);
+    }
+
+    pub fn merge(&mut self, other: &HyperLogLog) -> Result<(), EngineError> {
+        if other.precision != self.precision {
+            return Err(EngineError::Execution(format!(
+                "cannot merge HyperLogLog sketches of precision {} and {}",
+                other.precision, self.precision
+            )));
+        }
+        self.merge_registers(other);
+        Ok(())
+    }
+
+    fn merge_registers(&mut self, other: &HyperLogLog) {
+        for (register, &other) in self.registers.iter_mut().zip(&other.registers) {
+            *register = (*register).max(other);
+        }
+    }
+
+    // The harmonic mean estimate, with linear counting while many registers are still empty
+    pub fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let raw = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        let estimate = if raw <= 2.5 * m && zeros > 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        };
+        estimate.round() as u64
+    }
+}
+
+pub fn adaptive_approx_distinct_count<T: Hash + Sync>(
+    data: &[T],
+    precision: u8,
+) -> Result<(u64, ExecutionReport), EngineError> {
+    let (count, report) = run(
+        data,
+        || normal_approx_distinct_count(data, precision),
+        || parallel_approx_distinct_count(data, precision),
+    );
+    Ok((count?, report))
+}
+
+pub fn normal_approx_distinct_count<T: Hash>(data: &[T], precision: u8) -> Result<u64, EngineError> {
+    sequential_sketch(data, precision, |sketch, x| sketch.insert(x))
+}
+
+// Every rayon task fills one sketch, so memory stays the same however large its part is
+pub fn parallel_approx_distinct_count<T: Hash + Sync>(data: &[T], precision: u8) -> Result<u64, EngineError> {
+    parallel_sketch(data, precision, |sketch, x| sketch.insert(x))
+}
+
+// `insert` adds one value to a sketch, and decides what of it gets hashed
+fn sequential_sketch<T>(
+    data: &[T],
+    precision: u8,
+    insert: impl Fn(&mut HyperLogLog, &T),
+) -> Result<u64, EngineError> {
+    let mut sketch = HyperLogLog::new(precision)?;
+    data.iter().for_each(|x| insert(&mut sketch, x));
+    Ok(sketch.estimate())
+}
+
+fn parallel_sketch<T: Sync>(
+    data: &[T],
+    precision: u8,
+    insert: impl Fn(&mut HyperLogLog, &T) + Sync,
+) -> Result<u64, EngineError> {
+    let empty = HyperLogLog::new(precision)?;
+    let sketch = data
+        .par_iter()
+        .fold(
+            || empty.clone(),
+            |mut sketch, x| {
+                insert(&mut sketch, x);
+                sketch
+            },
+        )
         .reduce(
-            || HashSet::new(),
-            |mut acc, x| {
-                acc.extend(x);
-                acc
+            || empty.clone(),
+            |mut a, b| {
+                a.merge_registers(&b);
+                a
             },
+        );
+    Ok(sketch.estimate())
+}
+
+pub fn normal_distinct_count<T: Hash + Eq>(data: &[T]) -> usize {
+    data.iter().collect::<HashSet<_>>().len()
+}
+
+// One chunk per thread, and never empty chunks, even for fewer values than threads
+fn distinct_chunk_size(len: usize) -> usize {
+    len.div_ceil(rayon::current_num_threads()).max(1)
+}
+
+// The key space is split into one hash partition per thread. Every chunk of the input scatters
+// its values into per-partition buckets, then every partition counts the distinct values of its
+// buckets on its own. Partitions hold disjoint values, so their counts add up without locks or
+// merging sets.
+pub fn parallel_distinct_count<T: Hash + Eq + Sync>(data: &[T]) -> usize {
+    if data.is_empty() {
+        return 0;
+    }
+    let partitions = rayon::current_num_threads().max(1);
+    let state = RandomState::new();
+    // Independent of the hashes inside the sets, which have random states of their own
+    let partition_of = |x: &T| state.hash_one(x) as usize % partitions;
+
+    let scattered: Vec<Vec<Vec<&T>>> = data
+        .par_chunks(distinct_chunk_size(data.len()))
+        .map(|chunk| {
+            let mut buckets = vec![Vec::new(); partitions];
+            for x in chunk {
+                buckets[partition_of(x)].push(x);
+            }
+            buckets
+        })
+        .collect();
+
+    (0..partitions)
+        .into_par_iter()
+        .map(|partition| {
+            let mut set = HashSet::new();
+            for buckets in &scattered {
+                set.extend(buckets[partition].iter().copied());
+            }
+            set.len()
+        })
+        .sum()
+}
+
+// Aggregates over a column of a record batch
+
+// Percentile functions carry their fraction, which the planner checks is between 0 and 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AggregateFunction {
+    Count,
+    CountDistinct,
+    Sum,
+    Avg,
+    Min,
+    Max,
+    VarPop,
+    VarSamp,
+    StddevPop,
+    StddevSamp,
+    CovarPop,
+    Corr,
+    RegrSlope,
+    PercentileCont(f64),
+    PercentileDisc(f64),
+    ApproxPercentile(f64),
+    // Carries the HyperLogLog precision
+    ApproxCountDistinct(u8),
+}
+
+// Fractions are never NaN
+impl Eq for AggregateFunction {}
+
+impl AggregateFunction {
+    // Number of arguments the function is called with; COUNT(*) is COUNT without one
+    pub fn arity(&self) -> usize {
+        match self {
+            AggregateFunction::CovarPop | AggregateFunction::Corr | AggregateFunction::RegrSlope => 2,
+            _ => 1,
+        }
+    }
+
+    // `args` is empty for `COUNT(*)`
+    pub fn output_type(&self, args: &[DataType]) -> Result<DataType, EngineError> {
+        if args.len() != self.arity() && !(*self == AggregateFunction::Count && args.is_empty()) {
+            return Err(EngineError::Plan(format!("{:?} takes {} argument(s)", self, self.arity())));
+        }
+        match (self, args) {
+            (
+                AggregateFunction::Count | AggregateFunction::CountDistinct | AggregateFunction::ApproxCountDistinct(_),
+                _,
+            ) => Ok(DataType::Int64),
+            // SUM of integers is a BIGINT
+            (AggregateFunction::Sum, [arg]) if arg.is_integer() => Ok(DataType::Int64),
+            (AggregateFunction::Sum, [DataType::Float64]) => Ok(DataType::Float64),
+            (AggregateFunction::Min | AggregateFunction::Max | AggregateFunction::PercentileDisc(_), [arg]) => Ok(*arg),
+            (func, args) if func.is_statistic() && args.iter().all(DataType::is_numeric) => Ok(DataType::Float64),
+            (func, args) => {
+                let args: Vec<String> = args.iter().map(DataType::to_string).collect();
+                Err(EngineError::Unsupported(format!("{:?} over {}", func, args.join(", "))))
+            }
+        }
+    }
+
+    // Counts are 0 rather than NULL over no values
+    pub fn nullable(&self) -> bool {
+        !matches!(
+            self,
+            AggregateFunction::Count | AggregateFunction::CountDistinct | AggregateFunction::ApproxCountDistinct(_)
+        )
+    }
+
+    // Aggregates over numbers that always return a Float64
+    fn is_statistic(&self) -> bool {
+        !matches!(
+            self,
+            AggregateFunction::Count
+                | AggregateFunction::CountDistinct
+                | AggregateFunction::Sum
+                | AggregateFunction::Min
+                | AggregateFunction::Max
+                | AggregateFunction::PercentileDisc(_)
+                | AggregateFunction::ApproxCountDistinct(_)
         )
-        .len()
-}
\ No newline at end of file
+    }
+
+    // A fresh accumulator; COUNT(*) is a COUNT fed one non-NULL value per row
+    pub fn accumulator(&self) -> Box<dyn Accumulator> {
+        match self {
+            AggregateFunction::Count => Box::new(CountAccumulator::default()),
+            AggregateFunction::CountDistinct => Box::new(DistinctCountAccumulator::default()),
+            AggregateFunction::Sum => Box::new(SumAccumulator::default()),
+            AggregateFunction::Avg => Box::new(AvgAccumulator::default()),
+            AggregateFunction::Min => Box::new(MinMaxAccumulator::new(Ordering::Less)),
+            AggregateFunction::Max => Box::new(MinMaxAccumulator::new(Ordering::Greater)),
+            AggregateFunction::VarPop
+            | AggregateFunction::VarSamp
+            | AggregateFunction::StddevPop
+            | AggregateFunction::StddevSamp => Box::new(MomentsAccumulator::new(*self)),
+            AggregateFunction::CovarPop | AggregateFunction::Corr | AggregateFunction::RegrSlope => {
+                Box::new(CoMomentsAccumulator::new(*self))
+            }
+            AggregateFunction::PercentileCont(fraction) => Box::new(PercentileContAccumulator {
+                fraction: *fraction,
+                values: Vec::new(),
+            }),
+            AggregateFunction::PercentileDisc(fraction) => Box::new(PercentileDiscAccumulator {
+                fraction: *fraction,
+                values: Vec::new(),
+            }),
+            AggregateFunction::ApproxPercentile(fraction) => Box::new(ApproxPercentileAccumulator {
+                fraction: *fraction,
+                digest: TDigest::new(DEFAULT_COMPRESSION),
+            }),
+            // The planner rejects other precisions already
+            AggregateFunction::ApproxCountDistinct(precision) => Box::new(ApproxDistinctCountAccumulator {
+                sketch: HyperLogLog::new((*precision).clamp(MIN_PRECISION, MAX_PRECISION)).expect("valid precision"),
+            }),
+        }
+    }
+}
+
+// Running state of one aggregate over one group.
+//
+// Rows are fed one at a time with `update`, which gets one value per argument of the function
+// and skips rows with a NULL argument. Partial states built over
+// different parts of the input, e.g. by parallel tasks, are combined with `merge`; `finalize`
+// gives the result, NULL for every aggregate but COUNT when no value was seen.
+pub trait Accumulator: Send + fmt::Debug {
+    fn update(&mut self, args: &[Value]) -> Result<(), EngineError>;
+    // `other` must come from the same aggregate function
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), EngineError>;
+    fn finalize(&self) -> Result<Value, EngineError>;
+    fn as_any(&self) -> &dyn Any;
+}
+
+fn downcast<T: 'static>(other: &dyn Accumulator) -> Result<&T, EngineError> {
+    other
+        .as_any()
+        .downcast_ref()
+        .ok_or_else(|| EngineError::Execution(format!("cannot merge {:?} into {}", other, std::any::type_name::<T>())))
+}
+
+#[derive(Debug, Default)]
+pub struct CountAccumulator {
+    count: i64,
+}
+
+impl Accumulator for CountAccumulator {
+    fn update(&mut self, args: &[Value]) -> Result<(), EngineError> {
+        let value = &args[0];
+        if !value.is_null() {
+            self.count += 1;
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), EngineError> {
+        self.count += downcast::<Self>(other)?.count;
+        Ok(())
+    }
+
+    fn finalize(&self) -> Result<Value, EngineError> {
+        Ok(Value::Int64(self.count))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DistinctCountAccumulator {
+    seen: HashSet<Value>,
+}
+
+impl Accumulator for DistinctCountAccumulator {
+    fn update(&mut self, args: &[Value]) -> Result<(), EngineError> {
+        let value = &args[0];
+        if !value.is_null() && !self.seen.contains(value) {
+            self.seen.insert(value.clone());
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), EngineError> {
+        self.seen.extend(downcast::<Self>(other)?.seen.iter().cloned());
+        Ok(())
+    }
+
+    fn finalize(&self) -> Result<Value, EngineError> {
+        Ok(Value::Int64(self.seen.len() as i64))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// Exact BIGINT sum of integers, or a float sum once a float is seen
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NumericSum {
+    Int(i64),
+    Float(f64),
+}
+
+impl NumericSum {
+    fn from_value(value: &Value) -> Result<Self, EngineError> {
+        match value {
+            Value::Int32(v) => Ok(NumericSum::Int(*v as i64)),
+            Value::Int64(v) => Ok(NumericSum::Int(*v)),
+            Value::Float64(v) => Ok(NumericSum::Float(*v)),
+            other => Err(EngineError::Execution(format!("cannot add up {}", other.data_type()))),
+        }
+    }
+
+    fn add(self, other: NumericSum) -> Result<Self, EngineError> {
+        match (self, other) {
+            (NumericSum::Int(a), NumericSum::Int(b)) => Ok(NumericSum::Int(add(a, b)?)),
+            (a, b) => Ok(NumericSum::Float(a.as_f64() + b.as_f64())),
+        }
+    }
+
+    fn as_f64(&self) -> f64 {
+        match self {
+            NumericSum::Int(v) => *v as f64,
+            NumericSum::Float(v) => *v,
+        }
+    }
+
+    fn to_value(self) -> Value {
+        match self {
+            NumericSum::Int(v) => Value::Int64(v),
+            NumericSum::Float(v) => Value::Float64(v),
+        }
+    }
+}
+
+fn add_to(sum: &mut Option<NumericSum>, value: NumericSum) -> Result<(), EngineError> {
+    *sum = Some(match *sum {
+        Some(sum) => sum.add(value)?,
+        None => value,
+    });
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+pub struct SumAccumulator {
+    sum: Option<NumericSum>,
+}
+
+impl Accumulator for SumAccumulator {
+    fn update(&mut self, args: &[Value]) -> Result<(), EngineError> {
+        let value = &args[0];
+        if value.is_null() {
+            return Ok(());
+        }
+        add_to(&mut self.sum, NumericSum::from_value(value)?)
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), EngineError> {
+        match downcast::<Self>(other)?.sum {
+            Some(other) => add_to(&mut self.sum, other),
+            None => Ok(()),
+        }
+    }
+
+    fn finalize(&self) -> Result<Value, EngineError> {
+        Ok(self.sum.map_or(Value::Null, NumericSum::to_value))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct AvgAccumulator {
+    sum: Option<NumericSum>,
+    count: i64,
+}
+
+impl Accumulator for AvgAccumulator {
+    fn update(&mut self, args: &[Value]) -> Result<(), EngineError> {
+        let value = &args[0];
+        if value.is_null() {
+            return Ok(());
+        }
+        self.count += 1;
+        add_to(&mut self.sum, NumericSum::from_value(value)?)
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), EngineError> {
+        let other = downcast::<Self>(other)?;
+        self.count += other.count;
+        match other.sum {
+            Some(sum) => add_to(&mut self.sum, sum),
+            None => Ok(()),
+        }
+    }
+
+    fn finalize(&self) -> Result<Value, EngineError> {
+        Ok(match self.sum {
+            Some(sum) => Value::Float64(sum.as_f64() / self.count as f64),
+            None => Value::Null,
+        })
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// MIN keeps the value every other one compares greater to, MAX the opposite
+#[derive(Debug)]
+pub struct MinMaxAccumulator {
+    keep: Ordering,
+    value: Option<Value>,
+}
+
+impl MinMaxAccumulator {
+    fn new(keep: Ordering) -> Self {
+        MinMaxAccumulator { keep, value: None }
+    }
+}
+
+impl Accumulator for MinMaxAccumulator {
+    fn update(&mut self, args: &[Value]) -> Result<(), EngineError> {
+        let value = &args[0];
+        if !value.is_null() && self.value.as_ref().is_none_or(|current| value.cmp(current) == self.keep) {
+            self.value = Some(value.clone());
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), EngineError> {
+        if let Some(value) = &downcast::<Self>(other)?.value {
+            self.update(std::slice::from_ref(value))?;
+        }
+        Ok(())
+    }
+
+    fn finalize(&self) -> Result<Value, EngineError> {
+        Ok(self.value.clone().unwrap_or(Value::Null))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+fn statistic_arg(value: &Value) -> Result<f64, EngineError> {
+    value
+        .as_f64()
+        .ok_or_else(|| EngineError::Execution(format!("cannot compute statistics over {}", value.data_type())))
+}
+
+fn moments_value(func: AggregateFunction, moments: &Moments) -> Value {
+    let value = match func {
+        AggregateFunction::VarPop => moments.var_pop(),
+        AggregateFunction::VarSamp => moments.var_samp(),
+        AggregateFunction::StddevPop => moments.stddev_pop(),
+        _ => moments.stddev_samp(),
+    };
+    value.map_or(Value::Null, Value::Float64)
+}
+
+fn co_moments_value(func: AggregateFunction, moments: &CoMoments) -> Value {
+    let value = match func {
+        AggregateFunction::CovarPop => moments.covar_pop(),
+        AggregateFunction::Corr => moments.corr(),
+        _ => moments.regr_slope(),
+    };
+    value.map_or(Value::Null, Value::Float64)
+}
+
+// VAR_POP, VAR_SAMP, STDDEV_POP and STDDEV_SAMP
+#[derive(Debug)]
+pub struct MomentsAccumulator {
+    func: AggregateFunction,
+    moments: Moments,
+}
+
+impl MomentsAccumulator {
+    fn new(func: AggregateFunction) -> Self {
+        MomentsAccumulator {
+            func,
+            moments: Moments::default(),
+        }
+    }
+}
+
+impl Accumulator for MomentsAccumulator {
+    fn update(&mut self, args: &[Value]) -> Result<(), EngineError> {
+        if !args[0].is_null() {
+            self.moments.push(statistic_arg(&args[0])?);
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), EngineError> {
+        self.moments.merge(&downcast::<Self>(other)?.moments);
+        Ok(())
+    }
+
+    fn finalize(&self) -> Result<Value, EngineError> {
+        Ok(moments_value(self.func, &self.moments))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// COVAR_POP, CORR and REGR_SLOPE over pairs where neither value is NULL
+#[derive(Debug)]
+pub struct CoMomentsAccumulator {
+    func: AggregateFunction,
+    moments: CoMoments,
+}
+
+impl CoMomentsAccumulator {
+    fn new(func: AggregateFunction) -> Self {
+        CoMomentsAccumulator {
+            func,
+            moments: CoMoments::default(),
+        }
+    }
+}
+
+impl Accumulator for CoMomentsAccumulator {
+    fn update(&mut self, args: &[Value]) -> Result<(), EngineError> {
+        if !args[0].is_null() && !args[1].is_null() {
+            self.moments.push(statistic_arg(&args[0])?, statistic_arg(&args[1])?);
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), EngineError> {
+        self.moments.merge(&downcast::<Self>(other)?.moments);
+        Ok(())
+    }
+
+    fn finalize(&self) -> Result<Value, EngineError> {
+        Ok(co_moments_value(self.func, &self.moments))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// Exact percentiles keep every value of the group and select at the end
+#[derive(Debug)]
+pub struct PercentileContAccumulator {
+    fraction: f64,
+    values: Vec<f64>,
+}
+
+impl Accumulator for PercentileContAccumulator {
+    fn update(&mut self, args: &[Value]) -> Result<(), EngineError> {
+        if !args[0].is_null() {
+            self.values.push(statistic_arg(&args[0])?);
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), EngineError> {
+        self.values.extend_from_slice(&downcast::<Self>(other)?.values);
+        Ok(())
+    }
+
+    fn finalize(&self) -> Result<Value, EngineError> {
+        Ok(normal_percentile_cont(&self.values, self.fraction).map_or(Value::Null, Value::Float64))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// Works on any type with an order, like MIN and MAX
+#[derive(Debug)]
+pub struct PercentileDiscAccumulator {
+    fraction: f64,
+    values: Vec<Value>,
+}
+
+impl Accumulator for PercentileDiscAccumulator {
+    fn update(&mut self, args: &[Value]) -> Result<(), EngineError> {
+        if !args[0].is_null() {
+            self.values.push(args[0].clone());
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), EngineError> {
+        self.values.extend_from_slice(&downcast::<Self>(other)?.values);
+        Ok(())
+    }
+
+    fn finalize(&self) -> Result<Value, EngineError> {
+        if self.values.is_empty() {
+            return Ok(Value::Null);
+        }
+        let mut values = self.values.clone();
+        let k = disc_index(values.len(), self.fraction);
+        Ok(values.select_nth_unstable(k).1.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct ApproxPercentileAccumulator {
+    fraction: f64,
+    digest: TDigest,
+}
+
+impl Accumulator for ApproxPercentileAccumulator {
+    fn update(&mut self, args: &[Value]) -> Result<(), EngineError> {
+        if !args[0].is_null() {
+            self.digest.push(statistic_arg(&args[0])?);
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), EngineError> {
+        self.digest.merge(&downcast::<Self>(other)?.digest);
+        Ok(())
+    }
+
+    fn finalize(&self) -> Result<Value, EngineError> {
+        Ok(self.digest.quantile(self.fraction).map_or(Value::Null, Value::Float64))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct ApproxDistinctCountAccumulator {
+    sketch: HyperLogLog,
+}
+
+impl Accumulator for ApproxDistinctCountAccumulator {
+    fn update(&mut self, args: &[Value]) -> Result<(), EngineError> {
+        if !args[0].is_null() {
+            self.sketch.insert(&args[0]);
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, other: &dyn Accumulator) -> Result<(), EngineError> {
+        self.sketch.merge(&downcast::<Self>(other)?.sketch)
+    }
+
+    fn finalize(&self) -> Result<Value, EngineError> {
+        Ok(Value::Int64(self.sketch.estimate() as i64))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// Feeds every value of the column to a fresh accumulator of the function
+pub fn accumulate(func: AggregateFunction, column: &Column) -> Result<Value, EngineError> {
+    let mut accumulator = func.accumulator();
+    for i in 0..column.len() {
+        accumulator.update(&[column.value(i)])?;
+    }
+    accumulator.finalize()
+}
+
+// NULLs are skipped; over no values COUNT is 0 and every other aggregate is NULL
+pub fn adaptive_aggregate(
+    func: AggregateFunction,
+    column: Option<&Column>,
+    num_rows: usize,
+) -> Result<(Value, ExecutionReport), EngineError> {
+    // Shortcuts that never parallelize
+    let sequential = |rows_in: usize, run: &dyn Fn() -> Result<Value, EngineError>| {
+        let report = ExecutionReport::new(ExecutionStrategy::Sequential, rows_in, usize::MAX);
+        let (value, report) = execution::measure(report, run, |_| 1);
+        Ok((value?, report))
+    };
+    let column = match (func, column) {
+        // Count is always faster sequentially based on the benchmark results
+        (AggregateFunction::Count, column) => {
+            return sequential(num_rows, &|| {
+                let count = column.map_or(num_rows, |column| column.len() - column.null_count());
+                Ok(Value::Int64(count as i64))
+            });
+        }
+        (_, Some(column)) => column,
+        (func, None) => return Err(EngineError::Plan(format!("{:?} needs an argument", func))),
+    };
+    // Rejects unsupported column types, and aggregates of two columns
+    func.output_type(&[column.data_type()])?;
+
+    match (func, column.data()) {
+        (AggregateFunction::CountDistinct, ColumnData::Int32(values)) => {
+            let (count, report) = adaptive_distinct_count(&non_null(column, values));
+            Ok((Value::Int64(count as i64), report))
+        }
+        (AggregateFunction::CountDistinct, ColumnData::Int64(values)) => {
+            let (count, report) = adaptive_distinct_count(&non_null(column, values));
+            Ok((Value::Int64(count as i64), report))
+        }
+        (AggregateFunction::ApproxCountDistinct(precision), ColumnData::Int32(values)) => {
+            approx_distinct_values(&non_null(column, values), precision, Value::Int32)
+        }
+        (AggregateFunction::ApproxCountDistinct(precision), ColumnData::Int64(values)) => {
+            approx_distinct_values(&non_null(column, values), precision, Value::Int64)
+        }
+        (AggregateFunction::CountDistinct | AggregateFunction::ApproxCountDistinct(_), _) => {
+            sequential(column.len(), &|| accumulate(func, column))
+        }
+        (_, ColumnData::Int32(values)) => aggregate_numeric(func, &non_null(column, values), Value::Int32, Value::Int64),
+        (_, ColumnData::Int64(values)) => aggregate_numeric(func, &non_null(column, values), Value::Int64, Value::Int64),
+        (_, ColumnData::Float64(values)) => {
+            aggregate_numeric(func, &non_null(column, values), Value::Float64, Value::Float64)
+        }
+        // Other types go through the accumulator of the function
+        _ => sequential(column.len(), &|| accumulate(func, column)),
+    }
+}
+
+// Hashes every value as the `Value` the accumulator of GROUP BY sees, so both give the same estimate
+fn approx_distinct_values<T: Copy + Sync>(
+    data: &[T],
+    precision: u8,
+    to_value: fn(T) -> Value,
+) -> Result<(Value, ExecutionReport), EngineError> {
+    let (count, report) = run(
+        data,
+        || sequential_sketch(data, precision, |sketch, &x| sketch.insert(&to_value(x))),
+        || parallel_sketch(data, precision, |sketch, &x| sketch.insert(&to_value(x))),
+    );
+    Ok((Value::Int64(count? as i64), report))
+}
+
+// The values of the rows that are not NULL
+fn non_null<'a, T: Copy>(column: &Column, values: &'a [T]) -> Cow<'a, [T]> {
+    if column.null_count() == 0 {
+        return Cow::Borrowed(values);
+    }
+    Cow::Owned(
+        values
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !column.is_null(*i))
+            .map(|(_, v)| *v)
+            .collect(),
+    )
+}
+
+fn aggregate_numeric<T: Numeric>(
+    func: AggregateFunction,
+    values: &[T],
+    value: fn(T) -> Value,
+    sum_value: fn(T::Sum) -> Value,
+) -> Result<(Value, ExecutionReport), EngineError> {
+    Ok(match func {
+        AggregateFunction::Sum => {
+            let (sum, report) = adaptive_sum(values)?;
+            (if values.is_empty() { Value::Null } else { sum_value(sum) }, report)
+        }
+        AggregateFunction::Avg => {
+            let (avg, report) = adaptive_avg(values)?;
+            (avg.map_or(Value::Null, Value::Float64), report)
+        }
+        AggregateFunction::Min => {
+            let (min, report) = adaptive_min(values);
+            (min.map_or(Value::Null, value), report)
+        }
+        AggregateFunction::Max => {
+            let (max, report) = adaptive_max(values);
+            (max.map_or(Value::Null, value), report)
+        }
+        AggregateFunction::VarPop
+        | AggregateFunction::VarSamp
+        | AggregateFunction::StddevPop
+        | AggregateFunction::StddevSamp => {
+            let (moments, report) = adaptive_moments(values);
+            (moments_value(func, &moments), report)
+        }
+        AggregateFunction::PercentileCont(fraction) => {
+            let (percentile, report) = adaptive_percentile_cont(values, fraction);
+            (percentile.map_or(Value::Null, Value::Float64), report)
+        }
+        AggregateFunction::PercentileDisc(fraction) => {
+            let (percentile, report) = adaptive_percentile_disc(values, fraction);
+            (percentile.map_or(Value::Null, value), report)
+        }
+        AggregateFunction::ApproxPercentile(fraction) => {
+            let (percentile, report) = adaptive_approx_percentile(values, fraction);
+            (percentile.map_or(Value::Null, Value::Float64), report)
+        }
+        _ => unreachable!(),
+    })
+}
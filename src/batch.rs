@@ -0,0 +1,562 @@
+use crate::catalog::{Field, Schema};
+use crate::error::EngineError;
+use crate::types::{DataType, Row, Value};
+
+type Result<T> = std::result::Result<T, EngineError>;
+
+// One bit per row, set when the row holds a value and unset when it is NULL
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Bitmap {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl Bitmap {
+    pub fn new_set(len: usize) -> Self {
+        let mut bitmap = Bitmap {
+            words: vec![u64::MAX; len.div_ceil(64)],
+            len,
+        };
+        if !len.is_multiple_of(64) {
+            *bitmap.words.last_mut().unwrap() = (1 << (len % 64)) - 1;
+        }
+        bitmap
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, i: usize) -> bool {
+        assert!(i < self.len, "bit {} out of range for a bitmap of {} bits", i, self.len);
+        self.words[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    pub fn set(&mut self, i: usize, value: bool) {
+        assert!(i < self.len, "bit {} out of range for a bitmap of {} bits", i, self.len);
+        if value {
+            self.words[i / 64] |= 1 << (i % 64);
+        } else {
+            self.words[i / 64] &= !(1 << (i % 64));
+        }
+    }
+
+    pub fn push(&mut self, value: bool) {
+        if self.len.is_multiple_of(64) {
+            self.words.push(0);
+        }
+        self.len += 1;
+        self.set(self.len - 1, value);
+    }
+
+    pub fn count_set(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn count_unset(&self) -> usize {
+        self.len - self.count_set()
+    }
+}
+
+impl FromIterator<bool> for Bitmap {
+    fn from_iter<I: IntoIterator<Item = bool>>(iter: I) -> Self {
+        let mut bitmap = Bitmap::default();
+        for value in iter {
+            bitmap.push(value);
+        }
+        bitmap
+    }
+}
+
+// One typed vector per column, so operators work on contiguous primitive data. `Null` is the
+// column of an untyped `NULL` literal and only carries a length.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnData {
+    Int32(Vec<i32>),
+    Int64(Vec<i64>),
+    Float64(Vec<f64>),
+    Utf8(Vec<String>),
+    Boolean(Vec<bool>),
+    Date(Vec<i32>),
+    Null(Vec<()>),
+}
+
+// Applies the same generic expression to the vector inside column data, keeping its type
+macro_rules! map_data {
+    ($data:expr, $values:ident => $body:expr) => {
+        match $data {
+            ColumnData::Int32($values) => ColumnData::Int32($body),
+            ColumnData::Int64($values) => ColumnData::Int64($body),
+            ColumnData::Float64($values) => ColumnData::Float64($body),
+            ColumnData::Utf8($values) => ColumnData::Utf8($body),
+            ColumnData::Boolean($values) => ColumnData::Boolean($body),
+            ColumnData::Date($values) => ColumnData::Date($body),
+            ColumnData::Null($values) => ColumnData::Null($body),
+        }
+    };
+}
+
+impl ColumnData {
+    pub fn data_type(&self) -> DataType {
+        match self {
+            ColumnData::Int32(_) => DataType::Int32,
+            ColumnData::Int64(_) => DataType::Int64,
+            ColumnData::Float64(_) => DataType::Float64,
+            ColumnData::Utf8(_) => DataType::Utf8,
+            ColumnData::Boolean(_) => DataType::Boolean,
+            ColumnData::Date(_) => DataType::Date,
+            ColumnData::Null(_) => DataType::Null,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            ColumnData::Int32(v) | ColumnData::Date(v) => v.len(),
+            ColumnData::Int64(v) => v.len(),
+            ColumnData::Float64(v) => v.len(),
+            ColumnData::Utf8(v) => v.len(),
+            ColumnData::Boolean(v) => v.len(),
+            ColumnData::Null(v) => v.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// Column data plus a validity bitmap; no bitmap means no row is NULL. The data slot of a NULL
+// row holds an arbitrary value that operators must not look at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    data: ColumnData,
+    validity: Option<Bitmap>,
+}
+
+impl From<ColumnData> for Column {
+    fn from(data: ColumnData) -> Self {
+        Column::new(data)
+    }
+}
+
+impl Column {
+    pub fn new(data: ColumnData) -> Self {
+        let validity = match data {
+            ColumnData::Null(ref v) => Some(Bitmap::from_iter(v.iter().map(|_| false))),
+            _ => None,
+        };
+        Column { data, validity }
+    }
+
+    pub fn with_validity(data: ColumnData, validity: Bitmap) -> Self {
+        assert_eq!(data.len(), validity.len(), "validity bitmap must cover every row");
+        let validity = (validity.count_unset() > 0).then_some(validity);
+        Column { data, validity }
+    }
+
+    pub fn empty(data_type: DataType) -> Self {
+        Self::with_capacity(data_type, 0)
+    }
+
+    pub fn with_capacity(data_type: DataType, capacity: usize) -> Self {
+        let data = match data_type {
+            DataType::Int32 => ColumnData::Int32(Vec::with_capacity(capacity)),
+            DataType::Int64 => ColumnData::Int64(Vec::with_capacity(capacity)),
+            DataType::Float64 => ColumnData::Float64(Vec::with_capacity(capacity)),
+            DataType::Utf8 => ColumnData::Utf8(Vec::with_capacity(capacity)),
+            DataType::Boolean => ColumnData::Boolean(Vec::with_capacity(capacity)),
+            DataType::Date => ColumnData::Date(Vec::with_capacity(capacity)),
+            DataType::Null => ColumnData::Null(Vec::with_capacity(capacity)),
+        };
+        Column { data, validity: None }
+    }
+
+    pub fn from_values(data_type: DataType, values: &[Value]) -> Result<Self> {
+        let mut column = Self::with_capacity(data_type, values.len());
+        for value in values {
+            column.push(value.clone())?;
+        }
+        Ok(column)
+    }
+
+    pub fn push(&mut self, value: Value) -> Result<()> {
+        let valid = !matches!(value, Value::Null);
+        match (&mut self.data, value) {
+            (ColumnData::Int32(values), Value::Int32(v)) => values.push(v),
+            (ColumnData::Int64(values), Value::Int64(v)) => values.push(v),
+            (ColumnData::Float64(values), Value::Float64(v)) => values.push(v),
+            (ColumnData::Utf8(values), Value::Utf8(v)) => values.push(v),
+            (ColumnData::Boolean(values), Value::Boolean(v)) => values.push(v),
+            (ColumnData::Date(values), Value::Date(v)) => values.push(v),
+            (data, Value::Null) => push_default(data),
+            (data, value) => {
+                return Err(EngineError::SchemaMismatch(format!(
+                    "cannot store {} in a {} column",
+                    value.data_type(),
+                    data.data_type()
+                )))
+            }
+        }
+        match (&mut self.validity, valid) {
+            (Some(validity), valid) => validity.push(valid),
+            (None, true) => {}
+            (None, false) => {
+                let mut validity = Bitmap::new_set(self.data.len() - 1);
+                validity.push(false);
+                self.validity = Some(validity);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn data(&self) -> &ColumnData {
+        &self.data
+    }
+
+    pub fn validity(&self) -> Option<&Bitmap> {
+        self.validity.as_ref()
+    }
+
+    pub fn data_type(&self) -> DataType {
+        self.data.data_type()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_null(&self, i: usize) -> bool {
+        self.validity.as_ref().is_some_and(|validity| !validity.get(i))
+    }
+
+    pub fn null_count(&self) -> usize {
+        self.validity.as_ref().map_or(0, Bitmap::count_unset)
+    }
+
+    pub fn value(&self, i: usize) -> Value {
+        if self.is_null(i) {
+            return Value::Null;
+        }
+        match &self.data {
+            ColumnData::Int32(v) => Value::Int32(v[i]),
+            ColumnData::Int64(v) => Value::Int64(v[i]),
+            ColumnData::Float64(v) => Value::Float64(v[i]),
+            ColumnData::Utf8(v) => Value::Utf8(v[i].clone()),
+            ColumnData::Boolean(v) => Value::Boolean(v[i]),
+            ColumnData::Date(v) => Value::Date(v[i]),
+            ColumnData::Null(_) => Value::Null,
+        }
+    }
+
+    pub fn take(&self, indices: &[usize]) -> Column {
+        Column {
+            data: map_data!(&self.data, values => indices.iter().map(|&i| &values[i]).cloned().collect()),
+            validity: self
+                .validity
+                .as_ref()
+                .map(|validity| indices.iter().map(|&i| validity.get(i)).collect()),
+        }
+        .normalized()
+    }
+
+    // Like `take`, with NULL for every `None`
+    pub fn take_nullable(&self, indices: &[Option<usize>]) -> Column {
+        Column {
+            data: map_data!(&self.data, values => indices
+                .iter()
+                .map(|i| i.map(|i| &values[i]).cloned().unwrap_or_default())
+                .collect()),
+            validity: Some(indices.iter().map(|i| i.is_some_and(|i| !self.is_null(i))).collect()),
+        }
+        .normalized()
+    }
+
+    pub fn filter(&self, mask: &[bool]) -> Column {
+        Column {
+            data: map_data!(&self.data, values => values
+                .iter()
+                .zip(mask)
+                .filter(|(_, keep)| **keep)
+                .map(|(v, _)| v)
+                .cloned()
+                .collect()),
+            validity: self.validity.as_ref().map(|validity| {
+                (0..validity.len())
+                    .filter(|&i| mask[i])
+                    .map(|i| validity.get(i))
+                    .collect()
+            }),
+        }
+        .normalized()
+    }
+
+    pub fn slice(&self, offset: usize, len: usize) -> Column {
+        Column {
+            data: map_data!(&self.data, values => values[offset..offset + len].to_vec()),
+            validity: self
+                .validity
+                .as_ref()
+                .map(|validity| (offset..offset + len).map(|i| validity.get(i)).collect()),
+        }
+        .normalized()
+    }
+
+    pub fn append(&mut self, other: &Column) -> Result<()> {
+        let len = self.len();
+        match (&mut self.data, &other.data) {
+            (ColumnData::Int32(a), ColumnData::Int32(b)) | (ColumnData::Date(a), ColumnData::Date(b)) => {
+                a.extend_from_slice(b)
+            }
+            (ColumnData::Int64(a), ColumnData::Int64(b)) => a.extend_from_slice(b),
+            (ColumnData::Float64(a), ColumnData::Float64(b)) => a.extend_from_slice(b),
+            (ColumnData::Utf8(a), ColumnData::Utf8(b)) => a.extend_from_slice(b),
+            (ColumnData::Boolean(a), ColumnData::Boolean(b)) => a.extend_from_slice(b),
+            (ColumnData::Null(a), ColumnData::Null(b)) => a.extend_from_slice(b),
+            (a, b) => {
+                return Err(EngineError::SchemaMismatch(format!(
+                    "cannot append a {} column to a {} column",
+                    b.data_type(),
+                    a.data_type()
+                )))
+            }
+        }
+        if self.validity.is_some() || other.validity.is_some() {
+            let mut validity = self.validity.take().unwrap_or_else(|| Bitmap::new_set(len));
+            for i in 0..other.len() {
+                validity.push(!other.is_null(i));
+            }
+            self.validity = Some(validity);
+        }
+        Ok(())
+    }
+
+    // Drops a bitmap without unset bits, so equal columns compare equal
+    fn normalized(mut self) -> Self {
+        if self.validity.as_ref().is_some_and(|validity| validity.count_unset() == 0) {
+            self.validity = None;
+        }
+        self
+    }
+}
+
+fn push_default(data: &mut ColumnData) {
+    match data {
+        ColumnData::Int32(values) | ColumnData::Date(values) => values.push(0),
+        ColumnData::Int64(values) => values.push(0),
+        ColumnData::Float64(values) => values.push(0.0),
+        ColumnData::Utf8(values) => values.push(String::new()),
+        ColumnData::Boolean(values) => values.push(false),
+        ColumnData::Null(values) => values.push(()),
+    }
+}
+
+// A set of equally long columns described by a schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordBatch {
+    schema: Schema,
+    columns: Vec<Column>,
+    num_rows: usize,
+}
+
+impl RecordBatch {
+    pub fn try_new(schema: Schema, columns: Vec<Column>) -> Result<Self> {
+        if schema.fields.len() != columns.len() {
+            return Err(EngineError::SchemaMismatch(format!(
+                "schema has {} columns, batch has {}",
+                schema.fields.len(),
+                columns.len()
+            )));
+        }
+        let num_rows = columns.first().map_or(0, Column::len);
+        for (field, column) in schema.fields.iter().zip(&columns) {
+            if field.data_type != column.data_type() {
+                return Err(EngineError::SchemaMismatch(format!(
+                    "column {} is {}, expected {}",
+                    field.name,
+                    column.data_type(),
+                    field.data_type
+                )));
+            }
+            if !field.nullable && column.null_count() > 0 {
+                return Err(EngineError::SchemaMismatch(format!(
+                    "column {} is not nullable but holds {} NULLs",
+                    field.name,
+                    column.null_count()
+                )));
+            }
+            if column.len() != num_rows {
+                return Err(EngineError::SchemaMismatch(format!(
+                    "column {} has {} rows, expected {}",
+                    field.name,
+                    column.len(),
+                    num_rows
+                )));
+            }
+        }
+        Ok(RecordBatch {
+            schema,
+            columns,
+            num_rows,
+        })
+    }
+
+    // For operators that already guarantee matching types and lengths
+    pub(crate) fn from_parts(schema: Schema, columns: Vec<Column>, num_rows: usize) -> Self {
+        debug_assert!(columns.iter().all(|c| c.len() == num_rows));
+        RecordBatch {
+            schema,
+            columns,
+            num_rows,
+        }
+    }
+
+    pub fn empty(schema: Schema) -> Self {
+        let columns = schema.fields.iter().map(|f| Column::empty(f.data_type)).collect();
+        RecordBatch {
+            schema,
+            columns,
+            num_rows: 0,
+        }
+    }
+
+    // Checks every row against the schema so operators can trust the declared types
+    pub fn from_rows(schema: Schema, rows: Vec<Row>) -> Result<Self> {
+        let num_rows = rows.len();
+        let mut columns: Vec<Column> = schema
+            .fields
+            .iter()
+            .map(|f| Column::with_capacity(f.data_type, num_rows))
+            .collect();
+        for (i, row) in rows.into_iter().enumerate() {
+            if row.len() != columns.len() {
+                return Err(EngineError::SchemaMismatch(format!(
+                    "row {} has {} values, schema has {} columns",
+                    i,
+                    row.len(),
+                    columns.len()
+                )));
+            }
+            for (column, value) in columns.iter_mut().zip(row) {
+                column.push(value)?;
+            }
+        }
+        let mut batch = Self::try_new(schema, columns)?;
+        batch.num_rows = num_rows;
+        Ok(batch)
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    pub fn column(&self, i: usize) -> &Column {
+        &self.columns[i]
+    }
+
+    pub fn column_by_name(&self, name: &str) -> Option<&Column> {
+        self.schema.index_of(name).map(|i| &self.columns[i])
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.num_rows
+    }
+
+    pub fn num_columns(&self) -> usize {
+        self.columns.len()
+    }
+
+    pub fn row(&self, i: usize) -> Row {
+        self.columns.iter().map(|c| c.value(i)).collect()
+    }
+
+    pub fn rows(&self) -> Vec<Row> {
+        (0..self.num_rows).map(|i| self.row(i)).collect()
+    }
+
+    // Same data under a different schema, e.g. to rename columns
+    pub fn with_schema(self, schema: Schema) -> Result<Self> {
+        let num_rows = self.num_rows;
+        let mut batch = Self::try_new(schema, self.columns)?;
+        batch.num_rows = num_rows;
+        Ok(batch)
+    }
+
+    pub fn project(&self, indices: &[usize]) -> RecordBatch {
+        RecordBatch {
+            schema: Schema::new(indices.iter().map(|&i| self.schema.fields[i].clone()).collect()),
+            columns: indices.iter().map(|&i| self.columns[i].clone()).collect(),
+            num_rows: self.num_rows,
+        }
+    }
+
+    pub fn take(&self, indices: &[usize]) -> RecordBatch {
+        RecordBatch {
+            schema: self.schema.clone(),
+            columns: self.columns.iter().map(|c| c.take(indices)).collect(),
+            num_rows: indices.len(),
+        }
+    }
+
+    // Like `take`, with a row of NULLs for every `None`; the fields become nullable
+    pub fn take_nullable(&self, indices: &[Option<usize>]) -> RecordBatch {
+        let mut schema = self.schema.clone();
+        if indices.iter().any(Option::is_none) {
+            schema.fields.iter_mut().for_each(|field| field.nullable = true);
+        }
+        RecordBatch {
+            schema,
+            columns: self.columns.iter().map(|c| c.take_nullable(indices)).collect(),
+            num_rows: indices.len(),
+        }
+    }
+
+    pub fn slice(&self, offset: usize, len: usize) -> RecordBatch {
+        let offset = offset.min(self.num_rows);
+        let len = len.min(self.num_rows - offset);
+        RecordBatch {
+            schema: self.schema.clone(),
+            columns: self.columns.iter().map(|c| c.slice(offset, len)).collect(),
+            num_rows: len,
+        }
+    }
+
+    // Columns of `self` followed by the columns of `other`, row by row
+    pub fn merge_columns(&self, other: &RecordBatch) -> RecordBatch {
+        assert_eq!(self.num_rows, other.num_rows, "merged batches must have the same number of rows");
+        let mut fields: Vec<Field> = self.schema.fields.clone();
+        fields.extend(other.schema.fields.iter().cloned());
+        let mut columns = self.columns.clone();
+        columns.extend(other.columns.iter().cloned());
+        Self::from_parts(Schema::new(fields), columns, self.num_rows)
+    }
+
+    pub fn concat(schema: Schema, batches: &[RecordBatch]) -> Result<RecordBatch> {
+        let mut result = Self::empty(schema);
+        for batch in batches {
+            if batch.num_columns() != result.num_columns() {
+                return Err(EngineError::SchemaMismatch(format!(
+                    "cannot concatenate a batch of {} columns onto {} columns",
+                    batch.num_columns(),
+                    result.num_columns()
+                )));
+            }
+            for (column, other) in result.columns.iter_mut().zip(&batch.columns) {
+                column.append(other)?;
+            }
+            result.num_rows += batch.num_rows;
+        }
+        Ok(result)
+    }
+}
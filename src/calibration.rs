@@ -0,0 +1,359 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use crate::aggregations::{self, AggregateFunction};
+use crate::batch::{ColumnData, RecordBatch};
+use crate::catalog::{Field, Schema};
+use crate::csv::{self, CsvOptions};
+use crate::error::EngineError;
+use crate::expressions::{self, ScalarExpr};
+use crate::group_by;
+use crate::joins;
+use crate::json::{self, JsonOptions};
+use crate::parquet;
+use crate::parser::parse_expr;
+use crate::scans;
+use crate::sorting;
+use crate::types::DataType;
+
+type Result<T> = std::result::Result<T, EngineError>;
+
+// Input sizes above which each adaptive operator runs in parallel. Sizes are rows, except for
+// the file parsers which count bytes of text; `usize::MAX` means never.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Thresholds {
+    pub scan: usize,
+    pub aggregation: usize,
+    pub group_by: usize,
+    pub join: usize,
+    pub sort: usize,
+    pub expression: usize,
+    pub csv: usize,
+    pub json: usize,
+    pub parquet: usize,
+}
+
+impl Thresholds {
+    // The hand-tuned values every operator started with
+    pub const DEFAULT: Thresholds = Thresholds {
+        scan: scans::PARALLELIZATION_THRESHOLD,
+        aggregation: aggregations::PARALLELIZATION_THRESHOLD,
+        group_by: group_by::PARALLELIZATION_THRESHOLD,
+        join: joins::PARALLELIZATION_THRESHOLD,
+        sort: sorting::PARALLELIZATION_THRESHOLD,
+        expression: expressions::PARALLELIZATION_THRESHOLD,
+        csv: csv::PARALLELIZATION_THRESHOLD,
+        json: json::PARALLELIZATION_THRESHOLD,
+        parquet: parquet::PARALLELIZATION_THRESHOLD,
+    };
+
+    fn entries(&self) -> [(&'static str, usize); 9] {
+        [
+            ("scan", self.scan),
+            ("aggregation", self.aggregation),
+            ("group_by", self.group_by),
+            ("join", self.join),
+            ("sort", self.sort),
+            ("expression", self.expression),
+            ("csv", self.csv),
+            ("json", self.json),
+            ("parquet", self.parquet),
+        ]
+    }
+
+    fn entry_mut(&mut self, name: &str) -> Option<&mut usize> {
+        match name {
+            "scan" => Some(&mut self.scan),
+            "aggregation" => Some(&mut self.aggregation),
+            "group_by" => Some(&mut self.group_by),
+            "join" => Some(&mut self.join),
+            "sort" => Some(&mut self.sort),
+            "expression" => Some(&mut self.expression),
+            "csv" => Some(&mut self.csv),
+            "json" => Some(&mut self.json),
+            "parquet" => Some(&mut self.parquet),
+            _ => None,
+        }
+    }
+
+    // `name = value` lines; `#` starts a comment and keys left out keep their default
+    pub fn parse(text: &str) -> Result<Thresholds> {
+        let mut thresholds = Thresholds::DEFAULT;
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |message: String| EngineError::Plan(format!("threshold config line {}: {}", i + 1, message));
+            let (name, value) = line
+                .split_once('=')
+                .ok_or_else(|| error(format!("expected name = value, found '{}'", line)))?;
+            let (name, value) = (name.trim(), value.trim());
+            let entry = thresholds
+                .entry_mut(name)
+                .ok_or_else(|| error(format!("unknown operator '{}'", name)))?;
+            *entry = match value {
+                "never" => usize::MAX,
+                value => value
+                    .replace('_', "")
+                    .parse()
+                    .map_err(|_| error(format!("bad threshold '{}' for {}", value, name)))?,
+            };
+        }
+        Ok(thresholds)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Thresholds> {
+        Thresholds::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        fs::write(path, self.to_string())?;
+        Ok(())
+    }
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Thresholds::DEFAULT
+    }
+}
+
+impl std::fmt::Display for Thresholds {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "# Input sizes above which operators run in parallel (rows; bytes for csv and json)")?;
+        for (name, value) in self.entries() {
+            match value {
+                usize::MAX => writeln!(f, "{} = never", name)?,
+                value => writeln!(f, "{} = {}", name, value)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+static THRESHOLDS: RwLock<Thresholds> = RwLock::new(Thresholds::DEFAULT);
+
+// The thresholds every adaptive operator decides with
+pub fn thresholds() -> Thresholds {
+    *THRESHOLDS.read().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn set_thresholds(thresholds: Thresholds) {
+    *THRESHOLDS.write().unwrap_or_else(|e| e.into_inner()) = thresholds;
+}
+
+pub fn default_config_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".project_thresholds"))
+}
+
+// Applies the thresholds saved at `path`. Returns false when there is no such file.
+pub fn load_config(path: impl AsRef<Path>) -> Result<bool> {
+    if !path.as_ref().exists() {
+        return Ok(false);
+    }
+    set_thresholds(Thresholds::load(path)?);
+    Ok(true)
+}
+
+// Calibrates with the default sizes, then saves the thresholds to `path` and applies them
+pub fn recalibrate(path: impl AsRef<Path>) -> Result<Thresholds> {
+    let thresholds = calibrate(&CALIBRATION_SIZES)?;
+    thresholds.save(path)?;
+    set_thresholds(thresholds);
+    Ok(thresholds)
+}
+
+// Calibration
+
+// Input sizes tried by `calibrate`, in rows
+pub const CALIBRATION_SIZES: [usize; 6] = [1_000, 4_000, 16_000, 64_000, 256_000, 1_000_000];
+const REPETITIONS: usize = 3; // Runs per variant and size; the fastest one counts
+
+// Times the sequential and parallel variant of every operator on this machine at each of the
+// sizes and returns the learned thresholds, without applying them. Parquet reads are timed on
+// a temporary file.
+pub fn calibrate(sizes: &[usize]) -> Result<Thresholds> {
+    let mut sizes = sizes.to_vec();
+    sizes.sort_unstable();
+    sizes.dedup();
+    if sizes.is_empty() {
+        return Err(EngineError::Plan("calibration needs at least one input size".to_string()));
+    }
+
+    let numbers = |n: usize| -> Vec<i32> { (0..n).map(|i| (i as i32).wrapping_mul(7919) % 1000).collect() };
+    // k: one of 100 group keys, v: values to aggregate and filter, id: unique join key
+    let batch = |n: usize| -> RecordBatch {
+        let schema = Schema::new(vec![
+            Field::new("k", DataType::Int32, false),
+            Field::new("v", DataType::Int32, false),
+            Field::new("id", DataType::Int32, false),
+        ]);
+        let columns = vec![
+            ColumnData::Int32((0..n as i32).map(|i| i % 100).collect()).into(),
+            ColumnData::Int32(numbers(n)).into(),
+            ColumnData::Int32((0..n as i32).collect()).into(),
+        ];
+        RecordBatch::try_new(schema, columns).unwrap()
+    };
+
+    let aggregation = learn(&sizes, |n| {
+        let data = numbers(n);
+        (
+            time(|| aggregations::normal_sum(&data)),
+            time(|| aggregations::parallel_sum(&data)),
+        )
+    });
+    let scan = learn(&sizes, |n| {
+        let data = numbers(n);
+        (
+            time(|| scans::normal_scan_with_filter(&data, |x| x + 1, |x| x % 3 == 0)),
+            time(|| scans::parallel_scan_with_filter(&data, |x| x + 1, |x| x % 3 == 0)),
+        )
+    });
+    let expression = learn(&sizes, |n| {
+        let batch = batch(n);
+        let predicate = ScalarExpr::bind_schema(&parse_expr("v * 2 + 1 > 1000").unwrap(), batch.schema()).unwrap();
+        (
+            time(|| expressions::sequential_evaluate(&predicate, &batch)),
+            time(|| expressions::parallel_evaluate(&predicate, &batch)),
+        )
+    });
+    let group_by = learn(&sizes, |n| {
+        let batch = batch(n);
+        let aggregates = [(AggregateFunction::Sum, vec![1])];
+        (
+            time(|| group_by::sequential_group_by_batch(&batch, &[0], &aggregates)),
+            time(|| group_by::parallel_group_by_batch(&batch, &[0], &aggregates)),
+        )
+    });
+    // The join threshold applies to both inputs together; every row finds one match by id
+    let join = learn(&sizes, |n| {
+        let (left, right) = (batch(n / 2), batch(n - n / 2));
+        (
+            time(|| joins::sequential_hash_join_batches(&left, &right, &[2], &[2])),
+            time(|| joins::parallel_hash_join_batches(&left, &right, &[2], &[2])),
+        )
+    });
+    let sort = learn(&sizes, |n| {
+        let data = numbers(n);
+        (
+            time(|| sorting::sequential_merge_sort(&data)),
+            time(|| sorting::parallel_merge_sort(&data)),
+        )
+    });
+
+    // Parsers decide on bytes, so each size is replaced by the length of its generated text
+    let csv_texts: Vec<String> = sizes
+        .iter()
+        .map(|&n| {
+            let mut text = String::from("k,v,name\n");
+            for (i, v) in numbers(n).iter().enumerate() {
+                writeln!(text, "{},{},\"name {}\"", i % 100, v, i).unwrap();
+            }
+            text
+        })
+        .collect();
+    let csv = learn_bytes(&csv_texts, |text| {
+        let options = CsvOptions::default();
+        (
+            time(|| csv::sequential_parse_csv(text, &options)),
+            time(|| csv::parallel_parse_csv(text, &options)),
+        )
+    });
+    let json_texts: Vec<String> = sizes
+        .iter()
+        .map(|&n| {
+            let mut text = String::new();
+            for (i, v) in numbers(n).iter().enumerate() {
+                writeln!(text, "{{\"k\": {}, \"v\": {}, \"user\": {{\"name\": \"name {}\"}}}}", i % 100, v, i).unwrap();
+            }
+            text
+        })
+        .collect();
+    let json = learn_bytes(&json_texts, |text| {
+        let options = JsonOptions::default();
+        (
+            time(|| json::sequential_parse_json(text, &options)),
+            time(|| json::parallel_parse_json(text, &options)),
+        )
+    });
+
+    // Reads of every row group of a file, split into eight of them
+    let path = std::env::temp_dir().join(format!("project_calibration_{}.parquet", std::process::id()));
+    let mut failure = None;
+    let parquet = learn(&sizes, |n| {
+        parquet_times(&path, &batch(n)).unwrap_or_else(|e| {
+            failure.get_or_insert(e);
+            (Duration::ZERO, Duration::ZERO)
+        })
+    });
+    let _ = fs::remove_file(&path);
+    if let Some(e) = failure {
+        return Err(e);
+    }
+
+    Ok(Thresholds {
+        scan,
+        aggregation,
+        group_by,
+        join,
+        sort,
+        expression,
+        csv,
+        json,
+        parquet,
+    })
+}
+
+// Fastest of a few runs
+fn time<T>(mut run: impl FnMut() -> T) -> Duration {
+    (0..REPETITIONS)
+        .map(|_| {
+            let start = Instant::now();
+            std::hint::black_box(run());
+            start.elapsed()
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+fn parquet_times(path: &Path, batch: &RecordBatch) -> Result<(Duration, Duration)> {
+    parquet::write_parquet(path, batch, batch.num_rows().div_ceil(8).max(1))?;
+    let table = parquet::ParquetTable::try_new(path)?;
+    let projection: Vec<usize> = (0..batch.num_columns()).collect();
+    let row_groups: Vec<usize> = (0..table.num_row_groups()).collect();
+    Ok((
+        time(|| table.sequential_read(&projection, &row_groups)),
+        time(|| table.parallel_read(&projection, &row_groups)),
+    ))
+}
+
+// The largest size below the point from which the parallel variant is faster at every larger
+// size, so that operators parallelize inputs bigger than it. When parallel wins at every size,
+// the smallest one is the floor: a single measurement says nothing about smaller inputs.
+// `bench` returns the sequential and the parallel time for a size; `sizes` is not empty.
+fn learn(sizes: &[usize], mut bench: impl FnMut(usize) -> (Duration, Duration)) -> usize {
+    let slower: Vec<bool> = sizes
+        .iter()
+        .map(|&n| {
+            let (sequential, parallel) = bench(n);
+            parallel >= sequential
+        })
+        .collect();
+    match slower.iter().rposition(|&slower| slower) {
+        // Parallel did not even win at the largest size
+        Some(i) if i == sizes.len() - 1 => usize::MAX,
+        Some(i) => sizes[i],
+        None => sizes[0],
+    }
+}
+
+fn learn_bytes(texts: &[String], bench: impl Fn(&str) -> (Duration, Duration)) -> usize {
+    let lengths: Vec<usize> = texts.iter().map(String::len).collect();
+    let mut texts = texts.iter();
+    learn(&lengths, |_| bench(texts.next().unwrap()))
+}
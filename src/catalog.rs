@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::error::EngineError;
+use crate::batch::RecordBatch;
+use crate::csv::{read_csv, CsvOptions};
+use crate::expressions::ScalarExpr;
+use crate::json::{read_json, JsonOptions};
+use crate::parquet::ParquetTable;
+use crate::scans;
+use crate::types::DataType;
+
+type Result<T> = std::result::Result<T, EngineError>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub data_type: DataType,
+    pub nullable: bool,
+}
+
+impl Field {
+    pub fn new(name: &str, data_type: DataType, nullable: bool) -> Self {
+        Field {
+            name: name.to_string(),
+            data_type,
+            nullable,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Schema {
+    pub fields: Vec<Field>,
+}
+
+impl Schema {
+    pub fn new(fields: Vec<Field>) -> Self {
+        Schema { fields }
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.fields.iter().position(|f| f.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn field(&self, name: &str) -> Result<&Field> {
+        self.index_of(name)
+            .map(|i| &self.fields[i])
+            .ok_or_else(|| EngineError::ColumnNotFound(name.to_string()))
+    }
+}
+
+// Where the rows of a table come from.
+//
+// A scan gets the columns to read (all of them when `projection` is None) and conjuncts of the
+// WHERE clause bound to the full table schema. Filters only let a provider skip data it can
+// rule out; the rows it returns are filtered again.
+pub trait TableProvider: fmt::Debug + Send + Sync {
+    fn schema(&self) -> &Schema;
+
+    fn scan(&self, projection: Option<&[usize]>, filters: &[ScalarExpr]) -> Result<RecordBatch>;
+}
+
+// In-memory tables
+impl TableProvider for RecordBatch {
+    fn schema(&self) -> &Schema {
+        RecordBatch::schema(self)
+    }
+
+    fn scan(&self, projection: Option<&[usize]>, _filters: &[ScalarExpr]) -> Result<RecordBatch> {
+        let all: Vec<usize> = (0..self.num_columns()).collect();
+        Ok(scans::adaptive_scan_batch(self, projection.unwrap_or(&all)).0)
+    }
+}
+
+// Named tables that queries resolve `FROM` clauses against. Names are case-insensitive.
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    tables: HashMap<String, Arc<dyn TableProvider>>,
+}
+
+impl Catalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Registering under an existing name replaces the previous table
+    pub fn register_table(&mut self, name: &str, table: RecordBatch) {
+        self.register_provider(name, Arc::new(table));
+    }
+
+    pub fn register_provider(&mut self, name: &str, provider: Arc<dyn TableProvider>) {
+        self.tables.insert(name.to_lowercase(), provider);
+    }
+
+    // Loads a CSV file with a header row, inferring the column types
+    pub fn register_csv(&mut self, name: &str, path: impl AsRef<Path>) -> Result<()> {
+        self.register_csv_with_options(name, path, &CsvOptions::default())
+    }
+
+    pub fn register_csv_with_options(&mut self, name: &str, path: impl AsRef<Path>, options: &CsvOptions) -> Result<()> {
+        let table = read_csv(path, options)?;
+        self.register_table(name, table);
+        Ok(())
+    }
+
+    pub fn register_json(&mut self, name: &str, path: impl AsRef<Path>) -> Result<()> {
+        self.register_json_with_options(name, path, &JsonOptions::default())
+    }
+
+    pub fn register_json_with_options(&mut self, name: &str, path: impl AsRef<Path>, options: &JsonOptions) -> Result<()> {
+        let table = read_json(path, options)?;
+        self.register_table(name, table);
+        Ok(())
+    }
+
+    // Reads only the footer of the file; scans read the row groups they need
+    pub fn register_parquet(&mut self, name: &str, path: impl AsRef<Path>) -> Result<()> {
+        let table = ParquetTable::try_new(path)?;
+        self.register_provider(name, Arc::new(table));
+        Ok(())
+    }
+
+    // Loads a file in the format given by its extension
+    pub fn register_file(&mut self, name: &str, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+        match extension.to_ascii_lowercase().as_str() {
+            "csv" => self.register_csv(name, path),
+            "parquet" => self.register_parquet(name, path),
+            "json" | "ndjson" | "jsonl" => self.register_json(name, path),
+            "tsv" => self.register_csv_with_options(
+                name,
+                path,
+                &CsvOptions {
+                    delimiter: '\t',
+                    ..CsvOptions::default()
+                },
+            ),
+            _ => Err(EngineError::Unsupported(format!("file format of {}", path.display()))),
+        }
+    }
+
+    pub fn deregister_table(&mut self, name: &str) -> Option<Arc<dyn TableProvider>> {
+        self.tables.remove(&name.to_lowercase())
+    }
+
+    pub fn table(&self, name: &str) -> Result<&dyn TableProvider> {
+        self.tables
+            .get(&name.to_lowercase())
+            .map(|table| table.as_ref())
+            .ok_or_else(|| EngineError::TableNotFound(name.to_string()))
+    }
+
+    pub fn schema(&self, name: &str) -> Result<&Schema> {
+        self.table(name).map(|table| table.schema())
+    }
+
+    pub fn table_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.tables.keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
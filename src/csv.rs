@@ -0,0 +1,385 @@
+use std::borrow::Cow;
+use std::fs;
+use std::path::Path;
+
+use rayon::prelude::*;
+
+use crate::batch::{Column, RecordBatch};
+use crate::calibration::thresholds;
+use crate::catalog::{Field, Schema};
+use crate::error::EngineError;
+use crate::execution::{self, ExecutionReport};
+use crate::expressions::cast_value;
+use crate::types::{parse_date, DataType, Value};
+
+type Result<T> = std::result::Result<T, EngineError>;
+
+pub(crate) const PARALLELIZATION_THRESHOLD: usize = 1 << 20; // Default bytes of input before parsing is split across threads
+
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    pub has_header: bool,
+    // Delimiter and quote must be ASCII
+    pub delimiter: char,
+    pub quote: char,
+    // Unquoted fields equal to one of these are NULL; a quoted field never is
+    pub null_values: Vec<String>,
+    // Records sampled to infer column types when no schema is given
+    pub infer_rows: usize,
+    pub schema: Option<Schema>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            has_header: true,
+            delimiter: ',',
+            quote: '"',
+            null_values: vec![String::new(), "NULL".to_string()],
+            infer_rows: 1000,
+            schema: None,
+        }
+    }
+}
+
+pub fn read_csv(path: impl AsRef<Path>, options: &CsvOptions) -> Result<RecordBatch> {
+    let text = fs::read_to_string(path)?;
+    Ok(adaptive_parse_csv(&text, options)?.0)
+}
+
+pub fn adaptive_parse_csv(text: &str, options: &CsvOptions) -> Result<(RecordBatch, ExecutionReport)> {
+    let report = ExecutionReport::for_input(text.len(), thresholds().csv)
+        .with_chunk_size(text.len().div_ceil(rayon::current_num_threads()));
+    let (output, report) = execution::run_adaptive(
+        report,
+        || sequential_parse_csv(text, options),
+        || parallel_parse_csv(text, options),
+        |output| output.as_ref().map_or(0, RecordBatch::num_rows),
+    );
+    Ok((output?, report))
+}
+
+pub fn sequential_parse_csv(text: &str, options: &CsvOptions) -> Result<RecordBatch> {
+    let (schema, body, first_line) = prepare(text, options)?;
+    let columns = parse_chunk(body, first_line, &schema, options)?;
+    RecordBatch::try_new(schema, columns)
+}
+
+// Splits the records after the header into one chunk per thread and parses the chunks
+// independently; the columns of every chunk are appended in order
+pub fn parallel_parse_csv(text: &str, options: &CsvOptions) -> Result<RecordBatch> {
+    let (schema, body, first_line) = prepare(text, options)?;
+    let starts = chunk_starts(body, first_line, options, rayon::current_num_threads());
+    let chunks = starts
+        .par_iter()
+        .enumerate()
+        .map(|(i, &(start, line))| {
+            let end = starts.get(i + 1).map_or(body.len(), |&(end, _)| end);
+            parse_chunk(&body[start..end], line, &schema, options)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut columns: Vec<Column> = schema.fields.iter().map(|f| Column::empty(f.data_type)).collect();
+    for chunk in &chunks {
+        for (column, part) in columns.iter_mut().zip(chunk) {
+            column.append(part)?;
+        }
+    }
+    RecordBatch::try_new(schema, columns)
+}
+
+// Reads the header and resolves the schema. Returns the text after the header and the line
+// it starts on.
+fn prepare<'a>(text: &'a str, options: &CsvOptions) -> Result<(Schema, &'a str, usize)> {
+    if !options.delimiter.is_ascii() || !options.quote.is_ascii() {
+        return Err(EngineError::Unsupported("non-ASCII CSV delimiter or quote".to_string()));
+    }
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+
+    let mut header: Option<Vec<String>> = None;
+    let mut line = 1;
+    let mut body = text;
+    if options.has_header {
+        let (end, next_line) = parse_records(text, 1, options, |fields, _| {
+            if is_blank(fields) {
+                return Ok(true);
+            }
+            header = Some(fields.iter().map(|f| f.text.trim().to_string()).collect());
+            Ok(false)
+        })?;
+        body = &text[end..];
+        line = next_line;
+    }
+
+    let schema = match &options.schema {
+        Some(schema) => schema.clone(),
+        None => infer_schema(body, line, header.as_deref(), options)?,
+    };
+    if let Some(header) = &header {
+        if header.len() != schema.fields.len() {
+            return Err(EngineError::SchemaMismatch(format!(
+                "header has {} columns, schema has {}",
+                header.len(),
+                schema.fields.len()
+            )));
+        }
+    }
+    Ok((schema, body, line))
+}
+
+// Widest type seen in the first `infer_rows` records of every column. Columns without a single
+// non-NULL value in the sample are read as text.
+fn infer_schema(body: &str, first_line: usize, header: Option<&[String]>, options: &CsvOptions) -> Result<Schema> {
+    let mut types: Vec<Option<DataType>> = header.map(|h| vec![None; h.len()]).unwrap_or_default();
+    let mut sampled = 0;
+    parse_records(body, first_line, options, |fields, line| {
+        // Before the first record of a file without header the width is unknown, so blank lines
+        // are skipped there too
+        if is_blank(fields) && types.len() != 1 {
+            return Ok(true);
+        }
+        if header.is_none() && sampled == 0 {
+            types = vec![None; fields.len()];
+        }
+        check_width(fields.len(), types.len(), line)?;
+        for (field, data_type) in fields.iter().zip(types.iter_mut()) {
+            if !is_null(field, options) {
+                let seen = infer_type(&field.text);
+                *data_type = Some(data_type.map_or(seen, |current| widen(current, seen)));
+            }
+        }
+        sampled += 1;
+        Ok(sampled < options.infer_rows)
+    })?;
+
+    let fields = types
+        .into_iter()
+        .enumerate()
+        .map(|(i, data_type)| {
+            let name = header.map_or_else(|| format!("column_{}", i + 1), |h| h[i].clone());
+            Field::new(&name, data_type.unwrap_or(DataType::Utf8), true)
+        })
+        .collect();
+    Ok(Schema::new(fields))
+}
+
+fn infer_type(text: &str) -> DataType {
+    let text = text.trim();
+    if let Ok(v) = text.parse::<i64>() {
+        return if i32::try_from(v).is_ok() {
+            DataType::Int32
+        } else {
+            DataType::Int64
+        };
+    }
+    // Rust also parses words like "inf" and "nan" as floats, which are more likely to be text
+    let numeric = text.starts_with(|c: char| c.is_ascii_digit() || matches!(c, '-' | '+' | '.'));
+    if numeric && text.parse::<f64>().is_ok() {
+        return DataType::Float64;
+    }
+    if text.eq_ignore_ascii_case("true") || text.eq_ignore_ascii_case("false") {
+        return DataType::Boolean;
+    }
+    if parse_date(text).is_some() {
+        return DataType::Date;
+    }
+    DataType::Utf8
+}
+
+pub(crate) fn widen(a: DataType, b: DataType) -> DataType {
+    match (a, b) {
+        (a, b) if a == b => a,
+        (a, b) if a.is_integer() && b.is_integer() => DataType::Int64,
+        (a, b) if a.is_numeric() && b.is_numeric() => DataType::Float64,
+        _ => DataType::Utf8,
+    }
+}
+
+fn parse_chunk(text: &str, first_line: usize, schema: &Schema, options: &CsvOptions) -> Result<Vec<Column>> {
+    let mut columns: Vec<Column> = schema.fields.iter().map(|f| Column::empty(f.data_type)).collect();
+    parse_records(text, first_line, options, |fields, line| {
+        if is_blank(fields) && columns.len() != 1 {
+            return Ok(true);
+        }
+        check_width(fields.len(), columns.len(), line)?;
+        for (i, (field, column)) in fields.iter().zip(columns.iter_mut()).enumerate() {
+            let data_type = schema.fields[i].data_type;
+            let value = if is_null(field, options) {
+                Value::Null
+            } else if data_type == DataType::Utf8 {
+                Value::Utf8(field.text.to_string())
+            } else {
+                cast_value(&Value::Utf8(field.text.to_string()), data_type).map_err(|_| {
+                    EngineError::Execution(format!(
+                        "line {}: cannot read '{}' as {} for column {}",
+                        line, field.text, data_type, schema.fields[i].name
+                    ))
+                })?
+            };
+            column.push(value)?;
+        }
+        Ok(true)
+    })?;
+    Ok(columns)
+}
+
+fn check_width(found: usize, expected: usize, line: usize) -> Result<()> {
+    if found == expected {
+        Ok(())
+    } else {
+        Err(EngineError::SchemaMismatch(format!(
+            "line {}: expected {} fields, found {}",
+            line, expected, found
+        )))
+    }
+}
+
+// A line with nothing on it. Files of several columns skip it; in a file of one column it is a
+// record of one empty field, which is NULL unless `null_values` leaves out the empty string.
+fn is_blank(fields: &[CsvField]) -> bool {
+    fields.len() == 1 && !fields[0].quoted && fields[0].text.trim().is_empty()
+}
+
+fn is_null(field: &CsvField, options: &CsvOptions) -> bool {
+    !field.quoted && options.null_values.iter().any(|marker| *marker == field.text)
+}
+
+// Byte offsets and line numbers of roughly equal chunks of the text. Chunks start right after a
+// newline that is outside of quotes, so no record is split between two chunks. Quotes are read
+// as `parse_records` reads them: they only open a quoted field at the start of a field.
+fn chunk_starts(text: &str, first_line: usize, options: &CsvOptions, chunks: usize) -> Vec<(usize, usize)> {
+    let bytes = text.as_bytes();
+    let (delimiter, quote) = (options.delimiter as u8, options.quote as u8);
+    let target = bytes.len().div_ceil(chunks.max(1)).max(1);
+    let mut starts = vec![(0, first_line)];
+    let mut quoted = false;
+    let mut field_start = true;
+    let mut line = first_line;
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        if quoted {
+            if b == quote {
+                // A doubled quote stands for one quote and keeps the field open
+                if bytes.get(i + 1) == Some(&quote) {
+                    i += 1;
+                } else {
+                    quoted = false;
+                }
+            } else if b == b'\n' {
+                line += 1;
+            }
+        } else if b == quote && field_start {
+            quoted = true;
+            field_start = false;
+        } else if b == b'\n' {
+            line += 1;
+            field_start = true;
+            let start = i + 1;
+            if start < bytes.len() && start >= starts.last().unwrap().0 + target {
+                starts.push((start, line));
+            }
+        } else {
+            field_start = b == delimiter;
+        }
+        i += 1;
+    }
+    starts
+}
+
+struct CsvField<'a> {
+    text: Cow<'a, str>,
+    quoted: bool,
+}
+
+// Calls `on_record` with the fields and starting line of every record until it returns false.
+// Blank lines are records of one empty field, quoted fields may span lines and a doubled quote inside quotes
+// stands for one quote. Returns the byte offset and line number where parsing stopped.
+fn parse_records<'a, F>(text: &'a str, first_line: usize, options: &CsvOptions, mut on_record: F) -> Result<(usize, usize)>
+where
+    F: FnMut(&[CsvField<'a>], usize) -> Result<bool>,
+{
+    let bytes = text.as_bytes();
+    let (delimiter, quote) = (options.delimiter as u8, options.quote as u8);
+    let mut pos = 0;
+    let mut line = first_line;
+    let mut fields = Vec::new();
+
+    while pos < bytes.len() {
+        let record_line = line;
+        fields.clear();
+        loop {
+            if bytes.get(pos) == Some(&quote) {
+                pos += 1;
+                let mut value = String::new();
+                let mut start = pos;
+                loop {
+                    match bytes.get(pos) {
+                        None => {
+                            return Err(EngineError::Execution(format!(
+                                "line {}: unterminated quoted field",
+                                record_line
+                            )))
+                        }
+                        Some(&b) if b == quote => {
+                            value.push_str(&text[start..pos]);
+                            if bytes.get(pos + 1) == Some(&quote) {
+                                value.push(options.quote);
+                                pos += 2;
+                                start = pos;
+                            } else {
+                                pos += 1;
+                                break;
+                            }
+                        }
+                        Some(b'\n') => {
+                            line += 1;
+                            pos += 1;
+                        }
+                        Some(_) => pos += 1,
+                    }
+                }
+                fields.push(CsvField {
+                    text: Cow::Owned(value),
+                    quoted: true,
+                });
+            } else {
+                let start = pos;
+                while pos < bytes.len() && bytes[pos] != delimiter && bytes[pos] != b'\n' {
+                    pos += 1;
+                }
+                let end = if pos > start && bytes[pos - 1] == b'\r' { pos - 1 } else { pos };
+                fields.push(CsvField {
+                    text: Cow::Borrowed(&text[start..end]),
+                    quoted: false,
+                });
+            }
+
+            match bytes.get(pos) {
+                Some(&b) if b == delimiter => pos += 1,
+                Some(b'\n') => {
+                    pos += 1;
+                    line += 1;
+                    break;
+                }
+                Some(b'\r') if bytes.get(pos + 1) == Some(&b'\n') => {
+                    pos += 2;
+                    line += 1;
+                    break;
+                }
+                None => break,
+                Some(_) => {
+                    return Err(EngineError::Execution(format!(
+                        "line {}: unexpected character after closing quote",
+                        line
+                    )))
+                }
+            }
+        }
+
+        if !on_record(&fields, record_line)? {
+            break;
+        }
+    }
+    Ok((pos, line))
+}
@@ -0,0 +1,699 @@
+use sqlparser::ast::{BinaryOperator, Expr, Statement};
+
+use crate::aggregations::AggregateFunction;
+use crate::batch::{Column, ColumnData, RecordBatch};
+use crate::catalog::{Catalog, Field, Schema};
+use crate::error::EngineError;
+use crate::execution::ExecutionContext;
+use crate::expressions::{self, ScalarExpr};
+use crate::group_by;
+use crate::joins::{self, BandBound, JoinType};
+use crate::parser::{parse_create_table_from, parse_sql};
+use crate::optimizer;
+use crate::plan::{
+    grouping_positions, output_name, parse_aggregate, parse_grouping, split_conjunction, table_fields, LogicalPlan, PlanField, ProjectionItem, SortKey,
+};
+use crate::scans;
+use crate::sorting::{self, SortColumn};
+use crate::types::{DataType, Row};
+
+type Result<T> = std::result::Result<T, EngineError>;
+
+// The output of a query, named after the select list
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultSet {
+    batch: RecordBatch,
+}
+
+impl ResultSet {
+    pub fn batch(&self) -> &RecordBatch {
+        &self.batch
+    }
+
+    pub fn into_batch(self) -> RecordBatch {
+        self.batch
+    }
+
+    pub fn columns(&self) -> Vec<String> {
+        self.batch.schema().fields.iter().map(|f| f.name.clone()).collect()
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.batch.num_rows()
+    }
+
+    pub fn rows(&self) -> Vec<Row> {
+        self.batch.rows()
+    }
+}
+
+// Entry point for running SQL text against the tables of a catalog.
+//
+// A query is parsed, converted into a `LogicalPlan`, resolved against the catalog and every
+// plan node is executed by the operator modules, passing record batches between them.
+// Everything runs inside the engine's execution context.
+#[derive(Debug, Default)]
+pub struct Engine {
+    catalog: Catalog,
+    context: ExecutionContext,
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_catalog(catalog: Catalog) -> Self {
+        Engine {
+            catalog,
+            context: ExecutionContext::default(),
+        }
+    }
+
+    pub fn with_context(context: ExecutionContext) -> Self {
+        Engine {
+            catalog: Catalog::default(),
+            context,
+        }
+    }
+
+    pub fn context(&self) -> &ExecutionContext {
+        &self.context
+    }
+
+    pub fn set_context(&mut self, context: ExecutionContext) {
+        self.context = context;
+    }
+
+    pub fn catalog(&self) -> &Catalog {
+        &self.catalog
+    }
+
+    pub fn catalog_mut(&mut self) -> &mut Catalog {
+        &mut self.catalog
+    }
+
+    pub fn register_table(&mut self, name: &str, table: RecordBatch) {
+        self.catalog.register_table(name, table);
+    }
+
+    // Unknown tables and columns are reported here, before anything runs
+    pub fn create_logical_plan(&self, sql: &str) -> Result<LogicalPlan> {
+        let plan = match parse_sql(sql)? {
+            Statement::Query(query) => LogicalPlan::try_from(query.as_ref())?,
+            other => return Err(EngineError::Unsupported(format!("statement: {}", other))),
+        };
+        plan.schema(&self.catalog)?;
+        Ok(plan)
+    }
+
+    pub fn execute(&self, sql: &str) -> Result<ResultSet> {
+        self.execute_plan(&self.create_logical_plan(sql)?)
+    }
+
+    // Like `execute`, but also runs statements that change the catalog.
+    // `CREATE TABLE name FROM 'path'` loads a file as a new table and returns no columns.
+    pub fn execute_statement(&mut self, sql: &str) -> Result<ResultSet> {
+        if let Some((name, path)) = parse_create_table_from(sql)? {
+            if self.catalog.table(&name).is_ok() {
+                return Err(EngineError::Plan(format!("table {} already exists", name)));
+            }
+            let (catalog, context) = (&mut self.catalog, &self.context);
+            context.install(|| catalog.register_file(&name, &path))?;
+            return Ok(ResultSet {
+                batch: RecordBatch::empty(Schema::default()),
+            });
+        }
+        self.execute(sql)
+    }
+
+    pub fn execute_plan(&self, plan: &LogicalPlan) -> Result<ResultSet> {
+        let plan = optimizer::optimize(plan, &self.catalog)?;
+        let batch = self.context.install(|| self.run(&plan))?.batch;
+        Ok(ResultSet { batch })
+    }
+
+    fn run(&self, plan: &LogicalPlan) -> Result<Relation> {
+        let fields = plan.schema(&self.catalog)?;
+        let batch = match plan {
+            LogicalPlan::Scan {
+                table,
+                alias,
+                projection,
+                filters,
+            } => {
+                let fields = table_fields(&self.catalog, table, alias)?;
+                let filters = filters
+                    .iter()
+                    .map(|filter| ScalarExpr::bind(filter, &fields))
+                    .collect::<Result<Vec<_>>>()?;
+                self.catalog.table(table)?.scan(projection.as_deref(), &filters)?
+            }
+            LogicalPlan::Join {
+                left,
+                right,
+                join_type,
+                on,
+            } => {
+                let left = self.run(left)?;
+                let right = self.run(right)?;
+                execute_join(left, right, *join_type, on.as_ref())?
+            }
+            LogicalPlan::SemiJoin {
+                left,
+                right,
+                left_keys,
+                right_keys,
+                on,
+                anti,
+                null_aware,
+            } => {
+                let left = self.run(left)?;
+                let right = self.run(right)?;
+                let keys: Vec<(&Expr, &Expr)> = left_keys.iter().zip(right_keys).collect();
+                execute_semi_join(left, right, keys, on.as_ref(), *anti, *null_aware)?
+            }
+            LogicalPlan::Filter { input, predicate } => filter(&self.run(input)?, predicate)?,
+            LogicalPlan::Aggregate {
+                input,
+                group_by,
+                grouping_sets,
+                aggregates,
+            } => execute_aggregate(&self.run(input)?, group_by, grouping_sets.as_deref(), aggregates)?,
+            LogicalPlan::Sort { input, keys } => execute_sort(&self.run(input)?, keys)?,
+            LogicalPlan::Projection { input, items } => execute_projection(&self.run(input)?, items)?,
+            LogicalPlan::Limit {
+                input,
+                limit,
+                offset,
+            } => self.run(input)?.batch.slice(*offset, limit.unwrap_or(usize::MAX)),
+            LogicalPlan::Distinct { input } => distinct(&self.run(input)?.batch),
+            LogicalPlan::Union { left, right, all } => {
+                let left = self.run(left)?.batch;
+                let right = self.run(right)?.batch;
+                let batch = RecordBatch::concat(left.schema().clone(), &[left, right])?;
+                if *all {
+                    batch
+                } else {
+                    distinct(&batch)
+                }
+            }
+        };
+        // Operators name their output columns themselves; the plan decides the final names
+        let schema = Schema::new(
+            fields
+                .iter()
+                .map(|f| Field::new(&f.name, f.data_type, f.nullable))
+                .collect(),
+        );
+        Ok(Relation {
+            batch: batch.with_schema(schema)?,
+            fields,
+        })
+    }
+}
+
+// Execution
+
+#[derive(Debug, Clone)]
+struct Relation {
+    fields: Vec<PlanField>,
+    batch: RecordBatch,
+}
+
+// Evaluates an expression for every row of the input into a new column
+fn evaluate(input: &Relation, expr: &Expr) -> Result<Column> {
+    let bound = ScalarExpr::bind(expr, &input.fields)?;
+    Ok(expressions::adaptive_evaluate(&bound, &input.batch)?.0)
+}
+
+// Appends the expressions that are not plain columns to the input batch, so operators can
+// refer to every expression by column index
+fn with_columns(input: &Relation, exprs: &[&Expr]) -> Result<(RecordBatch, Vec<usize>)> {
+    let mut indices = Vec::new();
+    let mut fields = Vec::new();
+    let mut columns = Vec::new();
+    for expr in exprs {
+        if let ScalarExpr::Column { index, .. } = ScalarExpr::bind(expr, &input.fields)? {
+            indices.push(index);
+            continue;
+        }
+        let column = evaluate(input, expr)?;
+        indices.push(input.batch.num_columns() + columns.len());
+        fields.push(Field::new(&output_name(expr), column.data_type(), true));
+        columns.push(column);
+    }
+    let extra = RecordBatch::from_parts(Schema::new(fields), columns, input.batch.num_rows());
+    Ok((input.batch.merge_columns(&extra), indices))
+}
+
+fn filter(input: &Relation, predicate: &Expr) -> Result<RecordBatch> {
+    let predicate = ScalarExpr::bind(predicate, &input.fields)?;
+    Ok(scans::adaptive_scan_with_predicate(&input.batch, &predicate)?.0)
+}
+
+fn execute_join(left: Relation, right: Relation, join_type: JoinType, on: Option<&Expr>) -> Result<RecordBatch> {
+    let mut left_keys = Vec::new();
+    let mut right_keys = Vec::new();
+    let mut residual = Vec::new();
+
+    // Equalities between one side and the other become hash keys, everything else is
+    // filtered after the join, or answered by a band or nested-loop join without any key
+    for conjunct in on.map(split_conjunction).unwrap_or_default() {
+        match join_key(conjunct, &left.fields, &right.fields) {
+            Some((l, r)) => {
+                left_keys.push(l);
+                right_keys.push(r);
+            }
+            None => residual.push(conjunct.clone()),
+        }
+    }
+
+    let mut fields = left.fields.clone();
+    fields.extend(right.fields.iter().cloned());
+
+    // Without equalities there is nothing to hash on
+    if left_keys.is_empty() && !residual.is_empty() {
+        let pairs = theta_join_pairs(&left, &right, &fields, residual)?;
+        let padded = joins::pad_unmatched(&pairs, left.batch.num_rows(), right.batch.num_rows(), join_type);
+        return Ok(joins::take_outer_pairs(&left.batch, &right.batch, padded));
+    }
+
+    let (left_batch, left_on) = with_columns(&left, &left_keys)?;
+    let (right_batch, right_on) = with_columns(&right, &right_keys)?;
+
+    // An outer join only knows which rows are unmatched once the other conditions have been
+    // checked, so it joins on the keys, filters the pairs and pads the unmatched rows after
+    if join_type != JoinType::Inner && !residual.is_empty() {
+        let widths = (left.batch.num_columns(), right.batch.num_columns());
+        let matched = matching_pairs(&left_batch, &right_batch, &left_on, &right_on, widths, fields, &residual)?;
+        let padded = joins::pad_unmatched(&matched, left.batch.num_rows(), right.batch.num_rows(), join_type);
+        return Ok(joins::take_outer_pairs(&left.batch, &right.batch, padded));
+    }
+
+    let (joined, _) = match join_type {
+        JoinType::Inner => joins::adaptive_hash_join_batches(&left_batch, &right_batch, &left_on, &right_on),
+        _ => joins::adaptive_outer_hash_join_batches(&left_batch, &right_batch, &left_on, &right_on, join_type),
+    };
+
+    // Drop computed key columns again
+    let right_start = left_batch.num_columns();
+    let projection: Vec<usize> = (0..left.batch.num_columns())
+        .chain(right_start..right_start + right.batch.num_columns())
+        .collect();
+    filter_all(joined.project(&projection), fields, &residual)
+}
+
+// `l = r` with one side over the left columns and the other over the right ones, as the pair
+// of left and right key
+fn join_key<'a>(conjunct: &'a Expr, left: &[PlanField], right: &[PlanField]) -> Option<(&'a Expr, &'a Expr)> {
+    if let Expr::BinaryOp {
+        left: l,
+        op: BinaryOperator::Eq,
+        right: r,
+    } = conjunct
+    {
+        if ScalarExpr::bind(l, left).is_ok() && ScalarExpr::bind(r, right).is_ok() {
+            return Some((l, r));
+        }
+        if ScalarExpr::bind(r, left).is_ok() && ScalarExpr::bind(l, right).is_ok() {
+            return Some((r, l));
+        }
+    }
+    None
+}
+
+// Row numbers of the left and right rows whose keys are equal and that pass every predicate.
+// The batches hold the original columns, `widths` of them, followed by the key columns;
+// `fields` describe the original columns of both sides for the predicates.
+fn matching_pairs(
+    left_batch: &RecordBatch,
+    right_batch: &RecordBatch,
+    left_on: &[usize],
+    right_on: &[usize],
+    widths: (usize, usize),
+    fields: Vec<PlanField>,
+    predicates: &[Expr],
+) -> Result<Vec<(usize, usize)>> {
+    let row_numbers = |n: usize| {
+        let schema = Schema::new(vec![Field::new("row", DataType::Int64, false)]);
+        RecordBatch::from_parts(schema, vec![ColumnData::Int64((0..n as i64).collect()).into()], n)
+    };
+    let left_batch = left_batch.merge_columns(&row_numbers(left_batch.num_rows()));
+    let right_batch = right_batch.merge_columns(&row_numbers(right_batch.num_rows()));
+    let (joined, _) = joins::adaptive_hash_join_batches(&left_batch, &right_batch, left_on, right_on);
+
+    // The original columns of both sides, as the predicates see them, then both row numbers
+    let (left_width, right_width) = widths;
+    let right_start = left_batch.num_columns();
+    let projection: Vec<usize> = (0..left_width)
+        .chain(right_start..right_start + right_width)
+        .chain([right_start - 1, joined.num_columns() - 1])
+        .collect();
+    let pairs = filter_all(joined.project(&projection), fields, predicates)?;
+    let rows = |column: &Column| match column.data() {
+        ColumnData::Int64(rows) => rows.iter().map(|&i| i as usize).collect::<Vec<_>>(),
+        _ => unreachable!("row number column"),
+    };
+    Ok(rows(pairs.column(left_width + right_width))
+        .into_iter()
+        .zip(rows(pairs.column(left_width + right_width + 1)))
+        .collect())
+}
+
+// Row numbers of the left and right rows that pass every condition of a join without
+// equalities between its sides. A range of one side's expression between bounds on the other,
+// from BETWEEN or comparisons, is answered by a band join and the other conditions are checked
+// on its pairs; without one, a nested-loop join checks every pair.
+fn theta_join_pairs(left: &Relation, right: &Relation, fields: &[PlanField], mut conditions: Vec<Expr>) -> Result<Vec<(usize, usize)>> {
+    let Some(band) = band_condition(&mut conditions, &left.fields, &right.fields) else {
+        let predicate = ScalarExpr::bind(&conjunction(conditions), fields)?;
+        return Ok(joins::adaptive_nested_loop_join_pairs(&left.batch, &right.batch, &predicate)?.0);
+    };
+
+    let (points, bounds) = if band.point_on_left { (left, right) } else { (right, left) };
+    let bound_exprs: Vec<&Expr> = band.lower.iter().chain(&band.upper).map(|(expr, _)| expr).collect();
+    let (point_batch, point_on) = with_columns(points, &[&band.point])?;
+    let (bound_batch, bound_on) = with_columns(bounds, &bound_exprs)?;
+    let mut bound_on = bound_on.into_iter();
+    let mut bound = |(_, inclusive): &(Expr, bool)| BandBound {
+        column: bound_on.next().expect("one column per bound"),
+        inclusive: *inclusive,
+    };
+    let lower = band.lower.as_ref().map(&mut bound);
+    let upper = band.upper.as_ref().map(&mut bound);
+    let (pairs, _) = joins::adaptive_band_join_pairs(&point_batch, &bound_batch, point_on[0], lower, upper);
+    let pairs: Vec<(usize, usize)> = match band.point_on_left {
+        true => pairs,
+        false => pairs.into_iter().map(|(point, bound)| (bound, point)).collect(),
+    };
+
+    if conditions.is_empty() {
+        return Ok(pairs);
+    }
+    let predicate = ScalarExpr::bind(&conjunction(conditions), fields)?;
+    Ok(joins::adaptive_filter_pairs(&left.batch, &right.batch, &pairs, &predicate)?.0)
+}
+
+// A join condition that keeps `point`, over one side, between bounds over the other side, each
+// bound with whether it is inclusive
+struct Band {
+    point_on_left: bool,
+    point: Expr,
+    lower: Option<(Expr, bool)>,
+    upper: Option<(Expr, bool)>,
+}
+
+// Takes the range conditions on the first expression that has one out of `conditions`, e.g.
+// both halves of `a.ts >= b.start AND a.ts < b.end`, and combines them into one band
+fn band_condition(conditions: &mut Vec<Expr>, left: &[PlanField], right: &[PlanField]) -> Option<Band> {
+    let mut band: Option<Band> = None;
+    let mut rest = Vec::new();
+    for condition in conditions.drain(..) {
+        let Some(range) = range_condition(&condition, left, right) else {
+            rest.push(condition);
+            continue;
+        };
+        match &mut band {
+            None => band = Some(range),
+            Some(band) if band.point_on_left == range.point_on_left && band.point == range.point => {
+                let fits = |end: &Option<(Expr, bool)>, other: &Option<(Expr, bool)>| end.is_none() || other.is_none();
+                if !(fits(&band.lower, &range.lower) && fits(&band.upper, &range.upper)) {
+                    rest.push(condition);
+                    continue;
+                }
+                band.lower = band.lower.take().or(range.lower);
+                band.upper = band.upper.take().or(range.upper);
+            }
+            Some(_) => rest.push(condition),
+        }
+    }
+    *conditions = rest;
+    band
+}
+
+// `point BETWEEN low AND high`, or `point` compared with an expression, where the point is over
+// one side and the bounds over the other
+fn range_condition(condition: &Expr, left: &[PlanField], right: &[PlanField]) -> Option<Band> {
+    let binds = |expr: &Expr, fields: &[PlanField]| ScalarExpr::bind(expr, fields).is_ok();
+    let point_on_left = |point: &Expr, bounds: &[&Expr]| {
+        if binds(point, left) && bounds.iter().all(|bound| binds(bound, right)) {
+            Some(true)
+        } else if binds(point, right) && bounds.iter().all(|bound| binds(bound, left)) {
+            Some(false)
+        } else {
+            None
+        }
+    };
+    match condition {
+        Expr::Between {
+            expr,
+            negated: false,
+            low,
+            high,
+        } => Some(Band {
+            point_on_left: point_on_left(expr, &[low, high])?,
+            point: *expr.clone(),
+            lower: Some((*low.clone(), true)),
+            upper: Some((*high.clone(), true)),
+        }),
+        Expr::BinaryOp { left: point, op, right: bound } => {
+            let (lower, upper) = match op {
+                BinaryOperator::Gt => (Some((*bound.clone(), false)), None),
+                BinaryOperator::GtEq => (Some((*bound.clone(), true)), None),
+                BinaryOperator::Lt => (None, Some((*bound.clone(), false))),
+                BinaryOperator::LtEq => (None, Some((*bound.clone(), true))),
+                _ => return None,
+            };
+            Some(Band {
+                point_on_left: point_on_left(point, &[bound])?,
+                point: *point.clone(),
+                lower,
+                upper,
+            })
+        }
+        Expr::Nested(inner) => range_condition(inner, left, right),
+        _ => None,
+    }
+}
+
+fn conjunction(conditions: Vec<Expr>) -> Expr {
+    conditions
+        .into_iter()
+        .reduce(|left, right| Expr::BinaryOp {
+            left: Box::new(left),
+            op: BinaryOperator::And,
+            right: Box::new(right),
+        })
+        .expect("at least one condition")
+}
+
+fn execute_semi_join(
+    left: Relation,
+    right: Relation,
+    keys: Vec<(&Expr, &Expr)>,
+    on: Option<&Expr>,
+    anti: bool,
+    null_aware: bool,
+) -> Result<RecordBatch> {
+    let (mut left_keys, mut right_keys): (Vec<&Expr>, Vec<&Expr>) = keys.into_iter().unzip();
+    let mut right_filters = Vec::new();
+    let mut residual = Vec::new();
+
+    // Names in a subquery resolve against its own tables first, so conditions over the right
+    // side alone filter it before the join
+    for conjunct in on.map(split_conjunction).unwrap_or_default() {
+        if ScalarExpr::bind(conjunct, &right.fields).is_ok() {
+            right_filters.push(conjunct.clone());
+            continue;
+        }
+        match join_key(conjunct, &left.fields, &right.fields) {
+            Some((l, r)) => {
+                left_keys.push(l);
+                right_keys.push(r);
+            }
+            None => residual.push(conjunct.clone()),
+        }
+    }
+    if null_aware && (left_keys.len() != 1 || !residual.is_empty()) {
+        return Err(EngineError::Unsupported("NOT IN with a correlated subquery".to_string()));
+    }
+
+    let right = Relation {
+        batch: filter_all(right.batch, right.fields.clone(), &right_filters)?,
+        fields: right.fields,
+    };
+    let (left_batch, left_on) = with_columns(&left, &left_keys)?;
+    let (right_batch, right_on) = with_columns(&right, &right_keys)?;
+    let left_width = left.batch.num_columns();
+
+    if residual.is_empty() {
+        let (kept, _) = match anti {
+            false => joins::adaptive_semi_join_batches(&left_batch, &right_batch, &left_on, &right_on),
+            true => joins::adaptive_anti_join_batches(&left_batch, &right_batch, &left_on, &right_on, null_aware),
+        };
+        return Ok(kept.project(&(0..left_width).collect::<Vec<_>>()));
+    }
+
+    // Other conditions on both sides are checked pair by pair
+    let mut fields = left.fields.clone();
+    fields.extend(right.fields.iter().cloned());
+    let pairs = match left_keys.is_empty() {
+        true => theta_join_pairs(&left, &right, &fields, residual)?,
+        false => {
+            let widths = (left_width, right.batch.num_columns());
+            matching_pairs(&left_batch, &right_batch, &left_on, &right_on, widths, fields, &residual)?
+        }
+    };
+    let mut matched = vec![false; left.batch.num_rows()];
+    for (l, _) in pairs {
+        matched[l] = true;
+    }
+    let rows: Vec<usize> = (0..matched.len()).filter(|&i| matched[i] != anti).collect();
+    Ok(left.batch.take(&rows))
+}
+
+// Keeps the rows of `batch`, described by `fields` in its leading columns, that pass every
+// predicate
+fn filter_all(batch: RecordBatch, fields: Vec<PlanField>, predicates: &[Expr]) -> Result<RecordBatch> {
+    predicates
+        .iter()
+        .try_fold(Relation { fields, batch }, |relation, predicate| {
+            let batch = filter(&relation, predicate)?;
+            Ok(Relation { batch, ..relation })
+        })
+        .map(|relation| relation.batch)
+}
+
+fn execute_aggregate(
+    input: &Relation,
+    group_by: &[Expr],
+    grouping_sets: Option<&[Vec<usize>]>,
+    aggregates: &[Expr],
+) -> Result<RecordBatch> {
+    // GROUPING(...) calls are answered from the grouping id, the others are aggregated
+    let mut calls = Vec::new();
+    for expr in aggregates {
+        if parse_grouping(expr).is_none() {
+            calls.push(parse_aggregate(expr).expect("collected aggregate")?);
+        }
+    }
+
+    // Group keys and aggregate arguments become columns of one batch
+    let mut exprs: Vec<&Expr> = group_by.iter().collect();
+    exprs.extend(calls.iter().flat_map(|call| &call.args));
+    let (batch, indices) = with_columns(input, &exprs)?;
+
+    let (keys, args) = indices.split_at(group_by.len());
+    let mut args = args.iter().copied();
+    let mut funcs: Vec<(AggregateFunction, Vec<usize>)> = Vec::new();
+    for call in &calls {
+        funcs.push((call.func, args.by_ref().take(call.args.len()).collect()));
+    }
+
+    let output = match grouping_sets {
+        Some(sets) => group_by::adaptive_group_by_sets_batch(&batch, keys, sets, &funcs)?.0,
+        None => group_by::adaptive_group_by_batch(&batch, keys, &funcs)?.0,
+    };
+    if grouping_sets.is_none() && calls.len() == aggregates.len() {
+        return Ok(output);
+    }
+
+    // The grouping id column is dropped. Without grouping sets every key is grouped and
+    // GROUPING(...) is always 0.
+    let grouping_ids: Vec<i64> = match grouping_sets {
+        Some(_) => match output.column(output.num_columns() - 1).data() {
+            ColumnData::Int64(ids) => ids.clone(),
+            _ => unreachable!("grouping id column"),
+        },
+        None => vec![0; output.num_rows()],
+    };
+    let mut fields = output.schema().fields[..group_by.len()].to_vec();
+    let mut columns = output.columns()[..group_by.len()].to_vec();
+    let mut aggregated = group_by.len()..;
+    for expr in aggregates {
+        match parse_grouping(expr) {
+            Some(args) => {
+                let positions = grouping_positions(&args?, group_by, &input.fields)?;
+                let values = grouping_ids
+                    .iter()
+                    .map(|id| {
+                        positions.iter().fold(0, |bits, &key| {
+                            let left_out = id >> (group_by.len() - 1 - key) & 1;
+                            bits << 1 | left_out as i32
+                        })
+                    })
+                    .collect();
+                fields.push(Field::new(&output_name(expr), DataType::Int32, false));
+                columns.push(ColumnData::Int32(values).into());
+            }
+            None => {
+                let i = aggregated.next().expect("aggregate column");
+                fields.push(output.schema().fields[i].clone());
+                columns.push(output.column(i).clone());
+            }
+        }
+    }
+    Ok(RecordBatch::from_parts(Schema::new(fields), columns, output.num_rows()))
+}
+
+fn execute_sort(input: &Relation, keys: &[SortKey]) -> Result<RecordBatch> {
+    let exprs: Vec<&Expr> = keys.iter().map(|key| &key.expr).collect();
+    let (batch, indices) = with_columns(input, &exprs)?;
+    let columns: Vec<SortColumn> = indices
+        .into_iter()
+        .zip(keys)
+        .map(|(index, key)| SortColumn {
+            index,
+            descending: key.descending,
+            nulls_first: key.nulls_first,
+        })
+        .collect();
+
+    let (sorted, _) = sorting::adaptive_sort_batch(&batch, &columns);
+    let projection: Vec<usize> = (0..input.batch.num_columns()).collect();
+    Ok(sorted.project(&projection))
+}
+
+// Keeps the first occurrence of every row, in input order
+fn distinct(batch: &RecordBatch) -> RecordBatch {
+    let rows: Vec<usize> = (0..batch.num_rows()).collect();
+    let (groups, _) = group_by::adaptive_group_rows(&rows, |&i| batch.row(i));
+    let first_seen: Vec<usize> = groups
+        .into_iter()
+        .filter_map(|(_, members)| members.into_iter().min())
+        .collect();
+    let (first_seen, _) = sorting::adaptive_merge_sort(&first_seen);
+    batch.take(&first_seen)
+}
+
+fn execute_projection(input: &Relation, items: &[ProjectionItem]) -> Result<RecordBatch> {
+    let mut fields = Vec::new();
+    let mut columns = Vec::new();
+    for item in items {
+        match item {
+            ProjectionItem::Expr(expr, name) => {
+                let column = evaluate(input, expr)?;
+                fields.push(Field::new(name, column.data_type(), true));
+                columns.push(column);
+            }
+            ProjectionItem::Wildcard | ProjectionItem::QualifiedWildcard(_) => {
+                for (i, field) in input.fields.iter().enumerate() {
+                    let included = field.expr.is_none()
+                        && match item {
+                            ProjectionItem::QualifiedWildcard(qualifier) => field
+                                .relation
+                                .as_deref()
+                                .is_some_and(|relation| relation.eq_ignore_ascii_case(qualifier)),
+                            _ => true,
+                        };
+                    if included {
+                        fields.push(input.batch.schema().fields[i].clone());
+                        columns.push(input.batch.column(i).clone());
+                    }
+                }
+            }
+        }
+    }
+    Ok(RecordBatch::from_parts(Schema::new(fields), columns, input.batch.num_rows()))
+}
@@ -0,0 +1,47 @@
+use std::fmt;
+use std::io;
+
+use sqlparser::parser::ParserError;
+
+#[derive(Debug)]
+pub enum EngineError {
+    Parse(ParserError),
+    TableNotFound(String),
+    ColumnNotFound(String),
+    AmbiguousColumn(String),
+    SchemaMismatch(String),
+    Unsupported(String),
+    Plan(String),
+    Execution(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineError::Parse(e) => write!(f, "parse error: {}", e),
+            EngineError::TableNotFound(name) => write!(f, "table not found: {}", name),
+            EngineError::ColumnNotFound(name) => write!(f, "column not found: {}", name),
+            EngineError::AmbiguousColumn(name) => write!(f, "ambiguous column reference: {}", name),
+            EngineError::SchemaMismatch(msg) => write!(f, "schema mismatch: {}", msg),
+            EngineError::Unsupported(what) => write!(f, "unsupported: {}", what),
+            EngineError::Plan(msg) => write!(f, "planning error: {}", msg),
+            EngineError::Execution(msg) => write!(f, "execution error: {}", msg),
+            EngineError::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<ParserError> for EngineError {
+    fn from(e: ParserError) -> Self {
+        EngineError::Parse(e)
+    }
+}
+
+impl From<io::Error> for EngineError {
+    fn from(e: io::Error) -> Self {
+        EngineError::Io(e)
+    }
+}
@@ -0,0 +1,208 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::error::EngineError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExecutionStrategy {
+    Sequential,
+    Parallel,
+}
+
+impl ExecutionStrategy {
+    pub fn is_parallel(&self) -> bool {
+        *self == ExecutionStrategy::Parallel
+    }
+}
+
+impl fmt::Display for ExecutionStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+// What an adaptive operator did and why: the strategy it picked for an input of `rows_in`
+// rows given its `threshold`, and what the run produced and cost. File parsers count their
+// input, threshold and chunks in bytes instead of rows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutionReport {
+    pub strategy: ExecutionStrategy,
+    pub rows_in: usize,
+    pub rows_out: usize,
+    pub elapsed: Duration,
+    // 1 for a sequential run, otherwise the size of the thread pool the run used
+    pub threads: usize,
+    // Rows (or bytes, for file parsers) per task when the operator splits its input into fixed
+    // chunks; None when it runs sequentially or leaves the splitting to rayon
+    pub chunk_size: Option<usize>,
+    // Input size above which the operator runs in parallel
+    pub threshold: usize,
+}
+
+impl ExecutionReport {
+    pub fn new(strategy: ExecutionStrategy, rows_in: usize, threshold: usize) -> Self {
+        let threads = match strategy {
+            ExecutionStrategy::Sequential => 1,
+            ExecutionStrategy::Parallel => rayon::current_num_threads(),
+        };
+        ExecutionReport {
+            strategy,
+            rows_in,
+            rows_out: 0,
+            elapsed: Duration::ZERO,
+            threads,
+            chunk_size: None,
+            threshold,
+        }
+    }
+
+    // Parallel when the input is larger than the threshold
+    pub fn for_input(rows_in: usize, threshold: usize) -> Self {
+        let strategy = if rows_in > threshold {
+            ExecutionStrategy::Parallel
+        } else {
+            ExecutionStrategy::Sequential
+        };
+        ExecutionReport::new(strategy, rows_in, threshold)
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = self.strategy.is_parallel().then_some(chunk_size);
+        self
+    }
+}
+
+impl fmt::Display for ExecutionReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} rows in, {} rows out, {} thread{}",
+            self.strategy,
+            self.rows_in,
+            self.rows_out,
+            self.threads,
+            if self.threads == 1 { "" } else { "s" }
+        )?;
+        if let Some(chunk_size) = self.chunk_size {
+            write!(f, ", chunks of {}", chunk_size)?;
+        }
+        write!(
+            f,
+            ", threshold {}, {:.3} ms",
+            self.threshold,
+            self.elapsed.as_secs_f64() * 1000.0
+        )
+    }
+}
+
+// The thread pool parallel operators run on. The default context uses rayon's global pool; a
+// built one owns its own pool, so an embedding application can bound the cores the engine uses
+// and keep it apart from its own rayon work. Clones share the pool.
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionContext {
+    pool: Option<Arc<ThreadPool>>,
+}
+
+impl ExecutionContext {
+    pub fn builder() -> ExecutionContextBuilder {
+        ExecutionContextBuilder::default()
+    }
+
+    pub fn num_threads(&self) -> usize {
+        match &self.pool {
+            Some(pool) => pool.current_num_threads(),
+            None => rayon::current_num_threads(),
+        }
+    }
+
+    // Runs `op` with every parallel operator it calls scheduled on this context's pool
+    pub fn install<R: Send>(&self, op: impl FnOnce() -> R + Send) -> R {
+        match &self.pool {
+            Some(pool) => pool.install(op),
+            None => op(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionContextBuilder {
+    num_threads: Option<usize>,
+    stack_size: Option<usize>,
+    thread_name: Option<String>,
+}
+
+impl ExecutionContextBuilder {
+    // Defaults to one thread per CPU
+    pub fn num_threads(mut self, num_threads: usize) -> Self {
+        self.num_threads = Some(num_threads);
+        self
+    }
+
+    // Stack size of every worker thread in bytes
+    pub fn stack_size(mut self, stack_size: usize) -> Self {
+        self.stack_size = Some(stack_size);
+        self
+    }
+
+    // Workers are named `{prefix}-{index}`
+    pub fn thread_name(mut self, prefix: &str) -> Self {
+        self.thread_name = Some(prefix.to_string());
+        self
+    }
+
+    pub fn build(self) -> Result<ExecutionContext, EngineError> {
+        if self.num_threads == Some(0) {
+            return Err(EngineError::Plan("an execution context needs at least one thread".to_string()));
+        }
+        let mut builder = ThreadPoolBuilder::new();
+        if let Some(num_threads) = self.num_threads {
+            builder = builder.num_threads(num_threads);
+        }
+        if let Some(stack_size) = self.stack_size {
+            builder = builder.stack_size(stack_size);
+        }
+        if let Some(prefix) = self.thread_name {
+            builder = builder.thread_name(move |index| format!("{}-{}", prefix, index));
+        }
+        let pool = builder
+            .build()
+            .map_err(|e| EngineError::Execution(format!("cannot start thread pool: {}", e)))?;
+        Ok(ExecutionContext {
+            pool: Some(Arc::new(pool)),
+        })
+    }
+}
+
+// Runs the variant the report's strategy names, see `measure`
+pub(crate) fn run_adaptive<T>(
+    report: ExecutionReport,
+    sequential: impl FnOnce() -> T,
+    parallel: impl FnOnce() -> T,
+    rows_out: impl FnOnce(&T) -> usize,
+) -> (T, ExecutionReport) {
+    let strategy = report.strategy;
+    measure(
+        report,
+        || match strategy {
+            ExecutionStrategy::Sequential => sequential(),
+            ExecutionStrategy::Parallel => parallel(),
+        },
+        rows_out,
+    )
+}
+
+// Times `run` and fills in the elapsed time and output size of the report
+pub(crate) fn measure<T>(
+    mut report: ExecutionReport,
+    run: impl FnOnce() -> T,
+    rows_out: impl FnOnce(&T) -> usize,
+) -> (T, ExecutionReport) {
+    let start = Instant::now();
+    let output = run();
+    report.elapsed = start.elapsed();
+    report.rows_out = rows_out(&output);
+    (output, report)
+}
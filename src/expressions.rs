@@ -0,0 +1,1010 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use rayon::prelude::*;
+use sqlparser::ast::{BinaryOperator, CastKind, DataType as SqlDataType, Expr, UnaryOperator};
+
+use crate::batch::{Bitmap, Column, ColumnData, RecordBatch};
+use crate::calibration::thresholds;
+use crate::catalog::Schema;
+use crate::error::EngineError;
+use crate::execution::{self, ExecutionReport};
+use crate::plan::{field_index, literal, parse_aggregate, parse_grouping, PlanField};
+use crate::types::{parse_date, DataType, Value};
+
+type Result<T> = std::result::Result<T, EngineError>;
+
+pub(crate) const PARALLELIZATION_THRESHOLD: usize = 100000; // Default rows per batch before evaluation is split across threads
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    And,
+    Or,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    Plus,
+    Minus,
+    Multiply,
+    Divide,
+    Modulo,
+    Concat,
+}
+
+impl BinaryOp {
+    fn from_sql(op: &BinaryOperator) -> Result<Self> {
+        Ok(match op {
+            BinaryOperator::And => BinaryOp::And,
+            BinaryOperator::Or => BinaryOp::Or,
+            BinaryOperator::Eq => BinaryOp::Eq,
+            BinaryOperator::NotEq => BinaryOp::NotEq,
+            BinaryOperator::Lt => BinaryOp::Lt,
+            BinaryOperator::LtEq => BinaryOp::LtEq,
+            BinaryOperator::Gt => BinaryOp::Gt,
+            BinaryOperator::GtEq => BinaryOp::GtEq,
+            BinaryOperator::Plus => BinaryOp::Plus,
+            BinaryOperator::Minus => BinaryOp::Minus,
+            BinaryOperator::Multiply => BinaryOp::Multiply,
+            BinaryOperator::Divide => BinaryOp::Divide,
+            BinaryOperator::Modulo => BinaryOp::Modulo,
+            BinaryOperator::StringConcat => BinaryOp::Concat,
+            other => return Err(EngineError::Unsupported(format!("operator: {}", other))),
+        })
+    }
+
+    fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            BinaryOp::Eq | BinaryOp::NotEq | BinaryOp::Lt | BinaryOp::LtEq | BinaryOp::Gt | BinaryOp::GtEq
+        )
+    }
+
+    // Whether the comparison holds for two operands ordered as given. Operands without an order,
+    // a NaN and a number, only differ, as in IEEE 754.
+    fn holds(&self, ordering: Option<Ordering>) -> bool {
+        let Some(ordering) = ordering else {
+            return *self == BinaryOp::NotEq;
+        };
+        match self {
+            BinaryOp::Eq => ordering == Ordering::Equal,
+            BinaryOp::NotEq => ordering != Ordering::Equal,
+            BinaryOp::Lt => ordering == Ordering::Less,
+            BinaryOp::LtEq => ordering != Ordering::Greater,
+            BinaryOp::Gt => ordering == Ordering::Greater,
+            BinaryOp::GtEq => ordering != Ordering::Less,
+            _ => unreachable!("{} is not a comparison", self),
+        }
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            BinaryOp::And => "AND",
+            BinaryOp::Or => "OR",
+            BinaryOp::Eq => "=",
+            BinaryOp::NotEq => "<>",
+            BinaryOp::Lt => "<",
+            BinaryOp::LtEq => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::GtEq => ">=",
+            BinaryOp::Plus => "+",
+            BinaryOp::Minus => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::Modulo => "%",
+            BinaryOp::Concat => "||",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+// A scalar expression bound to column positions and type-checked against its input.
+//
+// Built once per operator from a sqlparser `Expr` and then evaluated a whole batch at a time:
+// every node turns its child columns into one output column.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScalarExpr {
+    Column {
+        index: usize,
+        data_type: DataType,
+        nullable: bool,
+    },
+    Literal(Value),
+    Binary {
+        left: Box<ScalarExpr>,
+        op: BinaryOp,
+        right: Box<ScalarExpr>,
+        data_type: DataType,
+    },
+    Not(Box<ScalarExpr>),
+    Negate(Box<ScalarExpr>),
+    IsNull {
+        expr: Box<ScalarExpr>,
+        negated: bool,
+    },
+    InList {
+        expr: Box<ScalarExpr>,
+        list: Vec<ScalarExpr>,
+        negated: bool,
+    },
+    Between {
+        expr: Box<ScalarExpr>,
+        low: Box<ScalarExpr>,
+        high: Box<ScalarExpr>,
+        negated: bool,
+    },
+    Like {
+        expr: Box<ScalarExpr>,
+        pattern: Box<ScalarExpr>,
+        escape: Option<char>,
+        case_insensitive: bool,
+        negated: bool,
+    },
+    // `CASE operand WHEN value THEN ...` compares the operand with each `when`, otherwise every
+    // `when` is a condition
+    Case {
+        operand: Option<Box<ScalarExpr>>,
+        branches: Vec<(ScalarExpr, ScalarExpr)>,
+        else_expr: Option<Box<ScalarExpr>>,
+        data_type: DataType,
+    },
+    // `safe` casts (TRY_CAST) turn values that do not convert into NULL instead of failing
+    Cast {
+        expr: Box<ScalarExpr>,
+        data_type: DataType,
+        safe: bool,
+    },
+}
+
+impl ScalarExpr {
+    // Resolves column references against the output columns of a plan node
+    pub fn bind(expr: &Expr, fields: &[PlanField]) -> Result<Self> {
+        if let Some(index) = field_index(fields, expr) {
+            let index = index?;
+            return Ok(ScalarExpr::Column {
+                index,
+                data_type: fields[index].data_type,
+                nullable: fields[index].nullable,
+            });
+        }
+
+        let bind = |expr: &Expr| Self::bind(expr, fields).map(Box::new);
+        match expr {
+            Expr::Value(value) => Ok(ScalarExpr::Literal(literal(value)?)),
+            Expr::TypedString { data_type, value } => {
+                let value = cast_value(&Value::Utf8(value.clone()), sql_type(data_type)?)?;
+                Ok(ScalarExpr::Literal(value))
+            }
+            Expr::Nested(inner) => Self::bind(inner, fields),
+            Expr::BinaryOp { left, op, right } => {
+                let (left, right) = (bind(left)?, bind(right)?);
+                let op = BinaryOp::from_sql(op)?;
+                let data_type = binary_type(left.data_type(), op, right.data_type())?;
+                Ok(ScalarExpr::Binary {
+                    left,
+                    op,
+                    right,
+                    data_type,
+                })
+            }
+            Expr::UnaryOp { op, expr: inner } => {
+                let inner = bind(inner)?;
+                match (op, inner.data_type()) {
+                    (UnaryOperator::Not, DataType::Boolean | DataType::Null) => Ok(ScalarExpr::Not(inner)),
+                    (UnaryOperator::Minus, t) if t.is_numeric() || t == DataType::Null => Ok(ScalarExpr::Negate(inner)),
+                    (UnaryOperator::Plus, t) if t.is_numeric() || t == DataType::Null => Ok(*inner),
+                    (op, t) => Err(EngineError::Plan(format!("cannot apply {} to {}", op, t))),
+                }
+            }
+            Expr::IsNull(inner) | Expr::IsNotNull(inner) => Ok(ScalarExpr::IsNull {
+                expr: bind(inner)?,
+                negated: matches!(expr, Expr::IsNotNull(_)),
+            }),
+            Expr::InList { expr, list, negated } => {
+                let expr = bind(expr)?;
+                let list = list
+                    .iter()
+                    .map(|item| Self::bind(item, fields))
+                    .collect::<Result<Vec<_>>>()?;
+                for item in &list {
+                    expect_comparable(expr.data_type(), "IN", item.data_type())?;
+                }
+                Ok(ScalarExpr::InList {
+                    expr,
+                    list,
+                    negated: *negated,
+                })
+            }
+            Expr::Between {
+                expr,
+                negated,
+                low,
+                high,
+            } => {
+                let (expr, low, high) = (bind(expr)?, bind(low)?, bind(high)?);
+                expect_comparable(expr.data_type(), "BETWEEN", low.data_type())?;
+                expect_comparable(expr.data_type(), "BETWEEN", high.data_type())?;
+                Ok(ScalarExpr::Between {
+                    expr,
+                    low,
+                    high,
+                    negated: *negated,
+                })
+            }
+            Expr::Like {
+                negated,
+                expr: value,
+                pattern,
+                escape_char,
+            }
+            | Expr::ILike {
+                negated,
+                expr: value,
+                pattern,
+                escape_char,
+            } => {
+                let (value, pattern) = (bind(value)?, bind(pattern)?);
+                for operand in [&value, &pattern] {
+                    if !matches!(operand.data_type(), DataType::Utf8 | DataType::Null) {
+                        return Err(EngineError::Plan(format!("LIKE expects Utf8, got {}", operand.data_type())));
+                    }
+                }
+                let mut escape = escape_char.as_deref().map(str::chars);
+                let escape = match escape.as_mut().map(|chars| (chars.next(), chars.next())) {
+                    None => None,
+                    Some((Some(c), None)) => Some(c),
+                    Some(_) => return Err(EngineError::Plan("LIKE escape must be a single character".to_string())),
+                };
+                Ok(ScalarExpr::Like {
+                    expr: value,
+                    pattern,
+                    escape,
+                    case_insensitive: matches!(expr, Expr::ILike { .. }),
+                    negated: *negated,
+                })
+            }
+            Expr::Case {
+                operand,
+                conditions,
+                results,
+                else_result,
+            } => {
+                let operand = operand.as_deref().map(bind).transpose()?;
+                let mut data_type = DataType::Null;
+                let mut branches = Vec::new();
+                for (when, then) in conditions.iter().zip(results) {
+                    let (when, then) = (Self::bind(when, fields)?, Self::bind(then, fields)?);
+                    match &operand {
+                        Some(operand) => expect_comparable(operand.data_type(), "CASE", when.data_type())?,
+                        None if matches!(when.data_type(), DataType::Boolean | DataType::Null) => {}
+                        None => {
+                            return Err(EngineError::Plan(format!(
+                                "CASE condition must be Boolean, got {}",
+                                when.data_type()
+                            )))
+                        }
+                    }
+                    data_type = result_type(data_type, then.data_type())?;
+                    branches.push((when, then));
+                }
+                let else_expr = else_result.as_deref().map(bind).transpose()?;
+                if let Some(else_expr) = &else_expr {
+                    data_type = result_type(data_type, else_expr.data_type())?;
+                }
+                Ok(ScalarExpr::Case {
+                    operand,
+                    branches,
+                    else_expr,
+                    data_type,
+                })
+            }
+            Expr::Cast {
+                kind,
+                expr: value,
+                data_type,
+                ..
+            } => {
+                let value = bind(value)?;
+                let data_type = sql_type(data_type)?;
+                if !can_cast(value.data_type(), data_type) {
+                    return Err(EngineError::Plan(format!(
+                        "cannot cast {} to {}",
+                        value.data_type(),
+                        data_type
+                    )));
+                }
+                Ok(ScalarExpr::Cast {
+                    expr: value,
+                    data_type,
+                    safe: matches!(kind, CastKind::TryCast | CastKind::SafeCast),
+                })
+            }
+            Expr::Function(_) if parse_aggregate(expr).is_some() || parse_grouping(expr).is_some() => Err(EngineError::Plan(format!(
+                "aggregate {} is not allowed here",
+                expr
+            ))),
+            other => Err(EngineError::Unsupported(format!("expression: {}", other))),
+        }
+    }
+
+    // Resolves column references by name against the schema of a batch
+    pub fn bind_schema(expr: &Expr, schema: &Schema) -> Result<Self> {
+        let fields: Vec<PlanField> = schema
+            .fields
+            .iter()
+            .map(|field| PlanField {
+                relation: None,
+                name: field.name.clone(),
+                data_type: field.data_type,
+                nullable: field.nullable,
+                expr: None,
+            })
+            .collect();
+        Self::bind(expr, &fields)
+    }
+
+    pub fn data_type(&self) -> DataType {
+        match self {
+            ScalarExpr::Column { data_type, .. }
+            | ScalarExpr::Binary { data_type, .. }
+            | ScalarExpr::Case { data_type, .. }
+            | ScalarExpr::Cast { data_type, .. } => *data_type,
+            ScalarExpr::Literal(value) => value.data_type(),
+            ScalarExpr::Negate(expr) => expr.data_type(),
+            ScalarExpr::Not(_)
+            | ScalarExpr::IsNull { .. }
+            | ScalarExpr::InList { .. }
+            | ScalarExpr::Between { .. }
+            | ScalarExpr::Like { .. } => DataType::Boolean,
+        }
+    }
+
+    pub fn nullable(&self) -> bool {
+        match self {
+            ScalarExpr::Column { nullable, .. } => *nullable,
+            ScalarExpr::Literal(value) => value.is_null(),
+            ScalarExpr::Binary { left, right, .. } => left.nullable() || right.nullable(),
+            ScalarExpr::Not(expr) | ScalarExpr::Negate(expr) => expr.nullable(),
+            ScalarExpr::IsNull { .. } => false,
+            ScalarExpr::InList { expr, list, .. } => expr.nullable() || list.iter().any(ScalarExpr::nullable),
+            ScalarExpr::Between { expr, low, high, .. } => expr.nullable() || low.nullable() || high.nullable(),
+            ScalarExpr::Like { expr, pattern, .. } => expr.nullable() || pattern.nullable(),
+            ScalarExpr::Case {
+                branches, else_expr, ..
+            } => branches.iter().any(|(_, then)| then.nullable()) || else_expr.as_ref().is_none_or(|e| e.nullable()),
+            ScalarExpr::Cast { expr, safe, .. } => *safe || expr.nullable(),
+        }
+    }
+
+    // Vectorized evaluation over every row of the batch
+    pub fn evaluate(&self, batch: &RecordBatch) -> Result<Column> {
+        let num_rows = batch.num_rows();
+        match self {
+            ScalarExpr::Column { index, .. } => Ok(batch.column(*index).clone()),
+            ScalarExpr::Literal(value) => Column::from_values(value.data_type(), &vec![value.clone(); num_rows]),
+            ScalarExpr::Binary {
+                left,
+                op,
+                right,
+                data_type,
+            } => {
+                let (left, right) = (left.evaluate(batch)?, right.evaluate(batch)?);
+                if let Some(column) = binary_kernel(&left, *op, &right) {
+                    return column;
+                }
+                collect(num_rows, *data_type, |i| binary(&left.value(i), *op, &right.value(i)))
+            }
+            ScalarExpr::Not(expr) => {
+                let column = expr.evaluate(batch)?;
+                collect(num_rows, DataType::Boolean, |i| not(&column.value(i)))
+            }
+            ScalarExpr::Negate(expr) => {
+                let column = expr.evaluate(batch)?;
+                collect(num_rows, column.data_type(), |i| negate(&column.value(i)))
+            }
+            ScalarExpr::IsNull { expr, negated } => {
+                let column = expr.evaluate(batch)?;
+                let values = (0..num_rows).map(|i| column.is_null(i) != *negated).collect();
+                Ok(ColumnData::Boolean(values).into())
+            }
+            ScalarExpr::InList { expr, list, negated } => {
+                let column = expr.evaluate(batch)?;
+                let list = list.iter().map(|item| item.evaluate(batch)).collect::<Result<Vec<_>>>()?;
+                collect(num_rows, DataType::Boolean, |i| {
+                    let value = column.value(i);
+                    let mut result = Value::Boolean(false);
+                    for item in &list {
+                        result = binary(&result, BinaryOp::Or, &binary(&value, BinaryOp::Eq, &item.value(i))?)?;
+                    }
+                    if *negated {
+                        not(&result)
+                    } else {
+                        Ok(result)
+                    }
+                })
+            }
+            ScalarExpr::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => {
+                let (column, low, high) = (expr.evaluate(batch)?, low.evaluate(batch)?, high.evaluate(batch)?);
+                collect(num_rows, DataType::Boolean, |i| {
+                    let value = column.value(i);
+                    let result = binary(
+                        &binary(&value, BinaryOp::GtEq, &low.value(i))?,
+                        BinaryOp::And,
+                        &binary(&value, BinaryOp::LtEq, &high.value(i))?,
+                    )?;
+                    if *negated {
+                        not(&result)
+                    } else {
+                        Ok(result)
+                    }
+                })
+            }
+            ScalarExpr::Like {
+                expr,
+                pattern,
+                escape,
+                case_insensitive,
+                negated,
+            } => {
+                let column = expr.evaluate(batch)?;
+                // A literal pattern is compiled once for the whole batch
+                let compiled = match pattern.as_ref() {
+                    ScalarExpr::Literal(Value::Utf8(p)) => Some(LikePattern::new(p, *escape, *case_insensitive)),
+                    _ => None,
+                };
+                let patterns = pattern.evaluate(batch)?;
+                collect(num_rows, DataType::Boolean, |i| match (column.value(i), patterns.value(i)) {
+                    (Value::Utf8(text), Value::Utf8(p)) => {
+                        let matched = match &compiled {
+                            Some(compiled) => compiled.matches(&text),
+                            None => LikePattern::new(&p, *escape, *case_insensitive).matches(&text),
+                        };
+                        Ok(Value::Boolean(matched != *negated))
+                    }
+                    _ => Ok(Value::Null),
+                })
+            }
+            ScalarExpr::Case {
+                operand,
+                branches,
+                else_expr,
+                data_type,
+            } => evaluate_case(batch, operand.as_deref(), branches, else_expr.as_deref(), *data_type),
+            ScalarExpr::Cast {
+                expr,
+                data_type,
+                safe,
+            } => {
+                let column = expr.evaluate(batch)?;
+                collect(num_rows, *data_type, |i| match cast_value(&column.value(i), *data_type) {
+                    Err(_) if *safe => Ok(Value::Null),
+                    result => result,
+                })
+            }
+        }
+    }
+
+    // Value of a single row, for callers that work row at a time
+    pub fn evaluate_row(&self, batch: &RecordBatch, row: usize) -> Result<Value> {
+        Ok(self.evaluate(&batch.slice(row, 1))?.value(0))
+    }
+}
+
+pub fn adaptive_evaluate(expr: &ScalarExpr, batch: &RecordBatch) -> Result<(Column, ExecutionReport)> {
+    let report = ExecutionReport::for_input(batch.num_rows(), thresholds().expression)
+        .with_chunk_size(chunk_size(batch.num_rows()));
+    let (output, report) = execution::run_adaptive(
+        report,
+        || sequential_evaluate(expr, batch),
+        || parallel_evaluate(expr, batch),
+        |output| output.as_ref().map_or(0, Column::len),
+    );
+    Ok((output?, report))
+}
+
+fn chunk_size(num_rows: usize) -> usize {
+    num_rows.div_ceil(rayon::current_num_threads()).max(1)
+}
+
+pub fn sequential_evaluate(expr: &ScalarExpr, batch: &RecordBatch) -> Result<Column> {
+    expr.evaluate(batch)
+}
+
+// Splits the batch into one slice of rows per thread and evaluates the slices independently
+pub fn parallel_evaluate(expr: &ScalarExpr, batch: &RecordBatch) -> Result<Column> {
+    let chunk_size = chunk_size(batch.num_rows());
+    let chunks = (0..batch.num_rows())
+        .step_by(chunk_size)
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|offset| expr.evaluate(&batch.slice(offset, chunk_size)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut result = Column::empty(expr.data_type());
+    for chunk in &chunks {
+        result.append(chunk)?;
+    }
+    Ok(result)
+}
+
+// Evaluates each branch only on the rows that reach it, so a branch that would fail on other
+// rows (e.g. a division guarded by the condition) does not
+fn evaluate_case(
+    batch: &RecordBatch,
+    operand: Option<&ScalarExpr>,
+    branches: &[(ScalarExpr, ScalarExpr)],
+    else_expr: Option<&ScalarExpr>,
+    data_type: DataType,
+) -> Result<Column> {
+    let mut results = vec![Value::Null; batch.num_rows()];
+    let mut remaining: Vec<usize> = (0..batch.num_rows()).collect();
+
+    let mut assign = |rows: &[usize], expr: &ScalarExpr| -> Result<()> {
+        let values = expr.evaluate(&batch.take(rows))?;
+        for (i, &row) in rows.iter().enumerate() {
+            results[row] = cast_value(&values.value(i), data_type)?;
+        }
+        Ok(())
+    };
+
+    for (when, then) in branches {
+        if remaining.is_empty() {
+            break;
+        }
+        let rows = batch.take(&remaining);
+        let conditions = when.evaluate(&rows)?;
+        let operands = operand.map(|operand| operand.evaluate(&rows)).transpose()?;
+        let mut matched = Vec::new();
+        let mut unmatched = Vec::new();
+        for (i, &row) in remaining.iter().enumerate() {
+            let condition = match &operands {
+                Some(operands) => binary(&operands.value(i), BinaryOp::Eq, &conditions.value(i))?,
+                None => conditions.value(i),
+            };
+            if condition == Value::Boolean(true) {
+                matched.push(row);
+            } else {
+                unmatched.push(row);
+            }
+        }
+        assign(&matched, then)?;
+        remaining = unmatched;
+    }
+    if let Some(else_expr) = else_expr {
+        assign(&remaining, else_expr)?;
+    }
+    Column::from_values(data_type, &results)
+}
+
+fn collect<F: Fn(usize) -> Result<Value>>(num_rows: usize, data_type: DataType, value: F) -> Result<Column> {
+    let mut column = Column::with_capacity(data_type, num_rows);
+    for i in 0..num_rows {
+        column.push(value(i)?)?;
+    }
+    Ok(column)
+}
+
+// Typing
+
+fn binary_type(left: DataType, op: BinaryOp, right: DataType) -> Result<DataType> {
+    let mismatch = || EngineError::Plan(format!("cannot apply {} to {} and {}", op, left, right));
+    // An untyped NULL takes the type of the other operand
+    let (left, right) = match (left, right) {
+        (DataType::Null, other) | (other, DataType::Null) => (other, other),
+        types => types,
+    };
+    match op {
+        BinaryOp::And | BinaryOp::Or => match (left, right) {
+            (DataType::Boolean | DataType::Null, DataType::Boolean | DataType::Null) => Ok(DataType::Boolean),
+            _ => Err(mismatch()),
+        },
+        op if op.is_comparison() => {
+            expect_comparable(left, &op.to_string(), right).map_err(|_| mismatch())?;
+            Ok(DataType::Boolean)
+        }
+        BinaryOp::Concat => Ok(DataType::Utf8),
+        _ => match (left, right) {
+            (DataType::Int32, DataType::Int32) => Ok(DataType::Int32),
+            (DataType::Null, DataType::Null) => Ok(DataType::Null),
+            (l, r) if l.is_integer() && r.is_integer() => Ok(DataType::Int64),
+            (l, r) if l.is_numeric() && r.is_numeric() => Ok(DataType::Float64),
+            _ => Err(mismatch()),
+        },
+    }
+}
+
+fn expect_comparable(left: DataType, context: &str, right: DataType) -> Result<()> {
+    let comparable = left == right
+        || left == DataType::Null
+        || right == DataType::Null
+        || (left.is_numeric() && right.is_numeric());
+    if comparable {
+        Ok(())
+    } else {
+        Err(EngineError::Plan(format!("cannot compare {} and {} in {}", left, right, context)))
+    }
+}
+
+// Common type of two CASE results: numbers widen, NULL fits anything
+fn result_type(a: DataType, b: DataType) -> Result<DataType> {
+    match (a, b) {
+        (a, b) if a == b => Ok(a),
+        (DataType::Null, other) | (other, DataType::Null) => Ok(other),
+        (DataType::Float64, b) if b.is_numeric() => Ok(DataType::Float64),
+        (a, DataType::Float64) if a.is_numeric() => Ok(DataType::Float64),
+        (a, b) if a.is_integer() && b.is_integer() => Ok(DataType::Int64),
+        (a, b) => Err(EngineError::Plan(format!("CASE results of types {} and {} do not match", a, b))),
+    }
+}
+
+fn sql_type(data_type: &SqlDataType) -> Result<DataType> {
+    Ok(match data_type {
+        SqlDataType::TinyInt(_)
+        | SqlDataType::SmallInt(_)
+        | SqlDataType::Int2(_)
+        | SqlDataType::Int(_)
+        | SqlDataType::Int4(_)
+        | SqlDataType::Integer(_)
+        | SqlDataType::Int32 => DataType::Int32,
+        SqlDataType::BigInt(_) | SqlDataType::Int8(_) | SqlDataType::Int64 => DataType::Int64,
+        SqlDataType::Float(_)
+        | SqlDataType::Float4
+        | SqlDataType::Float8
+        | SqlDataType::Float32
+        | SqlDataType::Float64
+        | SqlDataType::Real
+        | SqlDataType::Double
+        | SqlDataType::DoublePrecision
+        | SqlDataType::Numeric(_)
+        | SqlDataType::Decimal(_)
+        | SqlDataType::Dec(_) => DataType::Float64,
+        SqlDataType::Char(_)
+        | SqlDataType::Character(_)
+        | SqlDataType::Varchar(_)
+        | SqlDataType::CharVarying(_)
+        | SqlDataType::CharacterVarying(_)
+        | SqlDataType::Nvarchar(_)
+        | SqlDataType::Text
+        | SqlDataType::String(_) => DataType::Utf8,
+        SqlDataType::Bool | SqlDataType::Boolean => DataType::Boolean,
+        SqlDataType::Date => DataType::Date,
+        other => return Err(EngineError::Unsupported(format!("data type: {}", other))),
+    })
+}
+
+fn can_cast(from: DataType, to: DataType) -> bool {
+    match (from, to) {
+        (from, to) if from == to => true,
+        (DataType::Null, _) | (DataType::Utf8, _) | (_, DataType::Utf8) => true,
+        (from, to) if from.is_numeric() && to.is_numeric() => true,
+        (DataType::Boolean, to) => to.is_integer(),
+        (from, DataType::Boolean) => from.is_integer(),
+        _ => false,
+    }
+}
+
+// Scalar semantics shared by the generic path of every node
+
+pub(crate) fn cast_value(value: &Value, to: DataType) -> Result<Value> {
+    let failed = || EngineError::Execution(format!("cannot cast {} '{}' to {}", value.data_type(), value, to));
+    let integer = |i: i64| match to {
+        DataType::Int32 => i32::try_from(i).map(Value::Int32).map_err(|_| failed()),
+        _ => Ok(Value::Int64(i)),
+    };
+    match (value, to) {
+        (Value::Null, _) => Ok(Value::Null),
+        (value, to) if value.data_type() == to => Ok(value.clone()),
+        (value, DataType::Utf8) => Ok(Value::Utf8(value.to_string())),
+        (Value::Int32(_) | Value::Int64(_), DataType::Float64) => Ok(Value::Float64(value.as_f64().unwrap())),
+        (Value::Int32(_) | Value::Int64(_), DataType::Int32 | DataType::Int64) => integer(value.as_i64().unwrap()),
+        (Value::Int32(_) | Value::Int64(_), DataType::Boolean) => Ok(Value::Boolean(value.as_i64() != Some(0))),
+        (Value::Float64(f), DataType::Int32 | DataType::Int64) => {
+            // Truncates toward zero like a C cast, but rejects values out of range
+            if f.is_finite() && f.trunc() >= i64::MIN as f64 && f.trunc() < i64::MAX as f64 {
+                integer(f.trunc() as i64)
+            } else {
+                Err(failed())
+            }
+        }
+        (Value::Boolean(b), DataType::Int32 | DataType::Int64) => integer(*b as i64),
+        (Value::Utf8(s), DataType::Int32 | DataType::Int64) => integer(s.trim().parse().map_err(|_| failed())?),
+        (Value::Utf8(s), DataType::Float64) => s.trim().parse().map(Value::Float64).map_err(|_| failed()),
+        (Value::Utf8(s), DataType::Boolean) => match s.trim().to_lowercase().as_str() {
+            "true" | "t" | "yes" | "1" => Ok(Value::Boolean(true)),
+            "false" | "f" | "no" | "0" => Ok(Value::Boolean(false)),
+            _ => Err(failed()),
+        },
+        (Value::Utf8(s), DataType::Date) => parse_date(s).map(Value::Date).ok_or_else(failed),
+        _ => Err(failed()),
+    }
+}
+
+fn type_error(op: &str, left: &Value, right: Option<&Value>) -> EngineError {
+    match right {
+        Some(right) => EngineError::Execution(format!(
+            "cannot apply {} to {} and {}",
+            op,
+            left.data_type(),
+            right.data_type()
+        )),
+        None => EngineError::Execution(format!("cannot apply {} to {}", op, left.data_type())),
+    }
+}
+
+fn overflow() -> EngineError {
+    EngineError::Execution("integer overflow".to_string())
+}
+
+fn division_by_zero() -> EngineError {
+    EngineError::Execution("division by zero".to_string())
+}
+
+fn not(value: &Value) -> Result<Value> {
+    match value {
+        Value::Boolean(b) => Ok(Value::Boolean(!b)),
+        Value::Null => Ok(Value::Null),
+        other => Err(type_error("NOT", other, None)),
+    }
+}
+
+fn negate(value: &Value) -> Result<Value> {
+    match value {
+        Value::Int32(v) => v.checked_neg().map(Value::Int32).ok_or_else(overflow),
+        Value::Int64(v) => v.checked_neg().map(Value::Int64).ok_or_else(overflow),
+        Value::Float64(v) => Ok(Value::Float64(-v)),
+        Value::Null => Ok(Value::Null),
+        other => Err(type_error("-", other, None)),
+    }
+}
+
+// Boolean operand of AND/OR, None when it is NULL
+fn truth(value: &Value, op: BinaryOp, other: &Value) -> Result<Option<bool>> {
+    match value {
+        Value::Boolean(b) => Ok(Some(*b)),
+        Value::Null => Ok(None),
+        _ => Err(type_error(&op.to_string(), value, Some(other))),
+    }
+}
+
+// Three-valued logic: NULL propagates through every operator except where AND/OR can decide
+// the result from the other operand alone
+fn binary(left: &Value, op: BinaryOp, right: &Value) -> Result<Value> {
+    match op {
+        BinaryOp::And | BinaryOp::Or => {
+            let (a, b) = (truth(left, op, right)?, truth(right, op, left)?);
+            let decisive = op == BinaryOp::Or;
+            Ok(match (a, b) {
+                (Some(a), _) if a == decisive => Value::Boolean(decisive),
+                (_, Some(b)) if b == decisive => Value::Boolean(decisive),
+                (Some(_), Some(_)) => Value::Boolean(!decisive),
+                _ => Value::Null,
+            })
+        }
+        _ if left.is_null() || right.is_null() => Ok(Value::Null),
+        op if op.is_comparison() => match compare(left, right) {
+            Some(ordering) => Ok(Value::Boolean(op.holds(Some(ordering)))),
+            None if left.as_f64().is_some() && right.as_f64().is_some() => Ok(Value::Boolean(op.holds(None))),
+            None => Err(type_error(&op.to_string(), left, Some(right))),
+        },
+        BinaryOp::Concat => Ok(Value::Utf8(format!("{}{}", left, right))),
+        _ => arithmetic(left, op, right),
+    }
+}
+
+// None for types that cannot be compared, and for NaN against any number, as floats compare by
+// IEEE 754: -0.0 equals 0.0
+pub(crate) fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Utf8(a), Value::Utf8(b)) => Some(a.cmp(b)),
+        (Value::Boolean(a), Value::Boolean(b)) => Some(a.cmp(b)),
+        (Value::Date(a), Value::Date(b)) => Some(a.cmp(b)),
+        _ => match (left.as_i64(), right.as_i64()) {
+            (Some(a), Some(b)) => Some(a.cmp(&b)),
+            _ => left.as_f64()?.partial_cmp(&right.as_f64()?),
+        },
+    }
+}
+
+fn arithmetic(left: &Value, op: BinaryOp, right: &Value) -> Result<Value> {
+    if let (Some(a), Some(b)) = (left.as_i64(), right.as_i64()) {
+        if matches!(op, BinaryOp::Divide | BinaryOp::Modulo) && b == 0 {
+            return Err(division_by_zero());
+        }
+        let result = match op {
+            BinaryOp::Plus => a.checked_add(b),
+            BinaryOp::Minus => a.checked_sub(b),
+            BinaryOp::Multiply => a.checked_mul(b),
+            BinaryOp::Divide => a.checked_div(b),
+            _ => a.checked_rem(b),
+        }
+        .ok_or_else(overflow)?;
+        // Int32 op Int32 stays Int32, anything involving Int64 widens
+        return match (left, right) {
+            (Value::Int32(_), Value::Int32(_)) => i32::try_from(result).map(Value::Int32).map_err(|_| overflow()),
+            _ => Ok(Value::Int64(result)),
+        };
+    }
+
+    match (left.as_f64(), right.as_f64()) {
+        (Some(a), Some(b)) => Ok(Value::Float64(match op {
+            BinaryOp::Plus => a + b,
+            BinaryOp::Minus => a - b,
+            BinaryOp::Multiply => a * b,
+            BinaryOp::Divide => a / b,
+            _ => a % b,
+        })),
+        _ => Err(type_error(&op.to_string(), left, Some(right))),
+    }
+}
+
+// Kernels working directly on primitive vectors of the same type, for the common comparisons
+// and arithmetic. Anything they do not cover returns None and goes through `Value`.
+
+fn binary_kernel(left: &Column, op: BinaryOp, right: &Column) -> Option<Result<Column>> {
+    let validity = combined_validity(left, right);
+    let valid = |i: usize| validity.as_ref().is_none_or(|v| v.get(i));
+    let data = match (left.data(), right.data(), op) {
+        (ColumnData::Int32(a), ColumnData::Int32(b), op) if op.is_comparison() => compare_slices(a, b, op),
+        (ColumnData::Int64(a), ColumnData::Int64(b), op) if op.is_comparison() => compare_slices(a, b, op),
+        (ColumnData::Float64(a), ColumnData::Float64(b), op) if op.is_comparison() => compare_slices(a, b, op),
+        (ColumnData::Date(a), ColumnData::Date(b), op) if op.is_comparison() => compare_slices(a, b, op),
+        (ColumnData::Utf8(a), ColumnData::Utf8(b), op) if op.is_comparison() => compare_slices(a, b, op),
+        (ColumnData::Int32(a), ColumnData::Int32(b), BinaryOp::Plus | BinaryOp::Minus | BinaryOp::Multiply) => {
+            let f = match op {
+                BinaryOp::Plus => i32::checked_add,
+                BinaryOp::Minus => i32::checked_sub,
+                _ => i32::checked_mul,
+            };
+            match checked_slices(a, b, valid, f) {
+                Ok(values) => ColumnData::Int32(values),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        (ColumnData::Int64(a), ColumnData::Int64(b), BinaryOp::Plus | BinaryOp::Minus | BinaryOp::Multiply) => {
+            let f = match op {
+                BinaryOp::Plus => i64::checked_add,
+                BinaryOp::Minus => i64::checked_sub,
+                _ => i64::checked_mul,
+            };
+            match checked_slices(a, b, valid, f) {
+                Ok(values) => ColumnData::Int64(values),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        (ColumnData::Float64(a), ColumnData::Float64(b), BinaryOp::Plus | BinaryOp::Minus | BinaryOp::Multiply) => {
+            let f = match op {
+                BinaryOp::Plus => |x: f64, y: f64| x + y,
+                BinaryOp::Minus => |x: f64, y: f64| x - y,
+                _ => |x: f64, y: f64| x * y,
+            };
+            ColumnData::Float64(a.iter().zip(b).map(|(&x, &y)| f(x, y)).collect())
+        }
+        // NULLs take part in AND/OR, so only NULL-free inputs are handled here
+        (ColumnData::Boolean(a), ColumnData::Boolean(b), BinaryOp::And | BinaryOp::Or) if validity.is_none() => {
+            let values = match op {
+                BinaryOp::And => a.iter().zip(b).map(|(&x, &y)| x && y).collect(),
+                _ => a.iter().zip(b).map(|(&x, &y)| x || y).collect(),
+            };
+            return Some(Ok(ColumnData::Boolean(values).into()));
+        }
+        _ => return None,
+    };
+    Some(Ok(match validity {
+        Some(validity) => Column::with_validity(data, validity),
+        None => data.into(),
+    }))
+}
+
+fn combined_validity(left: &Column, right: &Column) -> Option<Bitmap> {
+    match (left.validity(), right.validity()) {
+        (None, None) => None,
+        (Some(v), None) | (None, Some(v)) => Some(v.clone()),
+        (Some(l), Some(r)) => Some((0..l.len()).map(|i| l.get(i) && r.get(i)).collect()),
+    }
+}
+
+// Through `partial_cmp`, so floats compare as in `compare`
+fn compare_slices<T: PartialOrd>(a: &[T], b: &[T], op: BinaryOp) -> ColumnData {
+    ColumnData::Boolean(a.iter().zip(b).map(|(x, y)| op.holds(x.partial_cmp(y))).collect())
+}
+
+// Slots of NULL rows hold arbitrary values, so overflow there is not an error
+fn checked_slices<T: Copy + Default>(
+    a: &[T],
+    b: &[T],
+    valid: impl Fn(usize) -> bool,
+    f: impl Fn(T, T) -> Option<T>,
+) -> Result<Vec<T>> {
+    a.iter()
+        .zip(b)
+        .enumerate()
+        .map(|(i, (&x, &y))| match f(x, y) {
+            Some(v) => Ok(v),
+            None if valid(i) => Err(overflow()),
+            None => Ok(T::default()),
+        })
+        .collect()
+}
+
+// LIKE patterns: `%` matches any run of characters, `_` exactly one
+
+#[derive(Debug, Clone, PartialEq)]
+enum LikeToken {
+    Char(char),
+    One,
+    Any,
+}
+
+struct LikePattern {
+    tokens: Vec<LikeToken>,
+    case_insensitive: bool,
+}
+
+impl LikePattern {
+    fn new(pattern: &str, escape: Option<char>, case_insensitive: bool) -> Self {
+        let pattern = if case_insensitive {
+            pattern.to_lowercase()
+        } else {
+            pattern.to_string()
+        };
+        let mut tokens = Vec::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            tokens.push(match c {
+                c if Some(c) == escape => LikeToken::Char(chars.next().unwrap_or(c)),
+                '%' => LikeToken::Any,
+                '_' => LikeToken::One,
+                c => LikeToken::Char(c),
+            });
+        }
+        LikePattern {
+            tokens,
+            case_insensitive,
+        }
+    }
+
+    // Greedy matching that backtracks to the most recent `%`
+    fn matches(&self, text: &str) -> bool {
+        let text: Vec<char> = if self.case_insensitive {
+            text.to_lowercase().chars().collect()
+        } else {
+            text.chars().collect()
+        };
+        let (mut t, mut p) = (0, 0);
+        let mut backtrack: Option<(usize, usize)> = None;
+        while t < text.len() {
+            match self.tokens.get(p) {
+                Some(LikeToken::Any) => {
+                    backtrack = Some((p, t));
+                    p += 1;
+                }
+                Some(LikeToken::One) => {
+                    t += 1;
+                    p += 1;
+                }
+                Some(LikeToken::Char(c)) if *c == text[t] => {
+                    t += 1;
+                    p += 1;
+                }
+                _ => match backtrack {
+                    Some((star, matched)) => {
+                        backtrack = Some((star, matched + 1));
+                        p = star + 1;
+                        t = matched + 1;
+                    }
+                    None => return false,
+                },
+            }
+        }
+        self.tokens[p..].iter().all(|token| *token == LikeToken::Any)
+    }
+}
@@ -1,90 +1,538 @@
 use std::hash::Hash;
 use std::collections::HashMap;
+use std::iter;
 use rayon::prelude::*;
 
-const PARALLELIZATION_THRESHOLD: usize = 100000; // Adjust this based on your benchmark results
+use crate::aggregations::{Accumulator, AggregateFunction};
+use crate::batch::{Column, RecordBatch};
+use crate::catalog::{Field, Schema};
+use crate::error::EngineError;
+use crate::calibration::thresholds;
+use crate::execution::{self, ExecutionReport};
+use crate::types::{DataType, Row, Value};
+
+pub(crate) const PARALLELIZATION_THRESHOLD: usize = 100000; // Default, see calibration::Thresholds
+
+fn report(data_size: usize) -> ExecutionReport {
+    ExecutionReport::for_input(data_size, thresholds().group_by)
+}
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct GroupByResult<K, V> {
+pub struct GroupByResult<K> {
     pub key: K,
-    pub avg_value: V,
+    // One value per aggregate, in the order they were asked for
+    pub values: Row,
 }
 
-pub fn adaptive_group_by<T, K, F>(data: &[T], key_func: F) -> (Vec<GroupByResult<K, f64>>, String)
+type Groups<K> = HashMap<K, Vec<Box<dyn Accumulator>>>;
+
+// The index of a grouping set and a group key with NULL for the keys the set leaves out
+pub type SetKey = (usize, Row);
+
+// Groups `data` by the key `key_func` returns and computes every aggregate per group.
+// `key_func` also returns the arguments of every aggregate for the item, one after the other,
+// `arity()` values per aggregate; COUNT(*) should be given any non-NULL value.
+pub fn adaptive_group_by<T, K, F>(
+    data: &[T],
+    key_func: F,
+    aggregates: &[AggregateFunction],
+) -> Result<(Vec<GroupByResult<K>>, ExecutionReport), EngineError>
 where
     T: Clone + Send + Sync,
     K: Eq + Hash + Clone + Send,
-    F: Fn(&T) -> (K, f64) + Sync + Send + Clone,
+    F: Fn(&T) -> (K, Row) + Sync + Send + Clone,
+{
+    let (output, report) = execution::run_adaptive(
+        report(data.len()),
+        || sequential_group_by(data, key_func.clone(), aggregates),
+        || parallel_group_by(data, key_func.clone(), aggregates),
+        |output| output.as_ref().map_or(0, Vec::len),
+    );
+    Ok((output?, report))
+}
+
+pub fn sequential_group_by<T, K, F>(
+    data: &[T],
+    key_func: F,
+    aggregates: &[AggregateFunction],
+) -> Result<Vec<GroupByResult<K>>, EngineError>
+where
+    T: Clone,
+    K: Eq + Hash + Clone,
+    F: Fn(&T) -> (K, Row),
+{
+    let groups = sequential_groups(data, |item| {
+        let (key, args) = key_func(item);
+        (iter::once(key), args)
+    }, aggregates)?;
+    finalize_groups(groups)
+}
+
+pub fn parallel_group_by<T, K, F>(
+    data: &[T],
+    key_func: F,
+    aggregates: &[AggregateFunction],
+) -> Result<Vec<GroupByResult<K>>, EngineError>
+where
+    T: Clone + Send + Sync,
+    K: Eq + Hash + Clone + Send,
+    F: Fn(&T) -> (K, Row) + Sync + Send,
+{
+    let groups = parallel_groups(data, |item| {
+        let (key, args) = key_func(item);
+        (iter::once(key), args)
+    }, aggregates)?;
+    finalize_groups(groups)
+}
+
+// Groups `data` by every set of `sets` in one pass. `key_func` returns all the key values of an
+// item and its aggregate arguments, as for `adaptive_group_by`; each set lists the positions of
+// the key values it groups by, and the other values are NULL in its groups. Results are keyed
+// by the index of their set and the key with those NULLs.
+pub fn adaptive_group_by_sets<T, F>(
+    data: &[T],
+    key_func: F,
+    sets: &[Vec<usize>],
+    aggregates: &[AggregateFunction],
+) -> Result<(Vec<GroupByResult<SetKey>>, ExecutionReport), EngineError>
+where
+    T: Clone + Send + Sync,
+    F: Fn(&T) -> (Row, Row) + Sync + Send + Clone,
+{
+    let (output, report) = execution::run_adaptive(
+        report(data.len()),
+        || sequential_group_by_sets(data, key_func.clone(), sets, aggregates),
+        || parallel_group_by_sets(data, key_func.clone(), sets, aggregates),
+        |output| output.as_ref().map_or(0, Vec::len),
+    );
+    Ok((output?, report))
+}
+
+pub fn sequential_group_by_sets<T, F>(
+    data: &[T],
+    key_func: F,
+    sets: &[Vec<usize>],
+    aggregates: &[AggregateFunction],
+) -> Result<Vec<GroupByResult<SetKey>>, EngineError>
+where
+    T: Clone,
+    F: Fn(&T) -> (Row, Row),
+{
+    let groups = sequential_groups(data, |item| {
+        let (key, args) = key_func(item);
+        (set_keys(key, sets), args)
+    }, aggregates)?;
+    finalize_groups(groups)
+}
+
+pub fn parallel_group_by_sets<T, F>(
+    data: &[T],
+    key_func: F,
+    sets: &[Vec<usize>],
+    aggregates: &[AggregateFunction],
+) -> Result<Vec<GroupByResult<SetKey>>, EngineError>
+where
+    T: Clone + Send + Sync,
+    F: Fn(&T) -> (Row, Row) + Sync + Send,
 {
-    if data.len() < PARALLELIZATION_THRESHOLD {
-        (sequential_group_by(data, key_func), "Sequential".to_string())
+    let groups = parallel_groups(data, |item| {
+        let (key, args) = key_func(item);
+        (set_keys(key, sets), args)
+    }, aggregates)?;
+    finalize_groups(groups)
+}
+
+// The key of an item in each grouping set, with the values the set leaves out set to NULL
+fn set_keys(key: Row, sets: &[Vec<usize>]) -> impl Iterator<Item = SetKey> + '_ {
+    sets.iter().enumerate().map(move |(i, set)| {
+        let masked = key
+            .iter()
+            .enumerate()
+            .map(|(j, value)| if set.contains(&j) { value.clone() } else { Value::Null })
+            .collect();
+        (i, masked)
+    })
+}
+
+// Adds every item to the group of each key `key_func` returns for it
+fn sequential_groups<T, K, I, F>(data: &[T], key_func: F, aggregates: &[AggregateFunction]) -> Result<Groups<K>, EngineError>
+where
+    K: Eq + Hash,
+    I: IntoIterator<Item = K>,
+    F: Fn(&T) -> (I, Row),
+{
+    let mut groups: Groups<K> = HashMap::new();
+
+    for item in data {
+        let (keys, args) = key_func(item);
+        for key in keys {
+            update_group(&mut groups, key, &args, aggregates)?;
+        }
+    }
+
+    Ok(groups)
+}
+
+fn parallel_groups<T, K, I, F>(data: &[T], key_func: F, aggregates: &[AggregateFunction]) -> Result<Groups<K>, EngineError>
+where
+    T: Sync,
+    K: Eq + Hash + Send,
+    I: IntoIterator<Item = K>,
+    F: Fn(&T) -> (I, Row) + Sync + Send,
+{
+    // Every task aggregates its part of the input into partial states, which are then merged
+    data.par_iter()
+        .try_fold(
+            HashMap::new,
+            |mut acc: Groups<K>, item| {
+                let (keys, args) = key_func(item);
+                for key in keys {
+                    update_group(&mut acc, key, &args, aggregates)?;
+                }
+                Ok::<_, EngineError>(acc)
+            },
+        )
+        .try_reduce(
+            HashMap::new,
+            |mut a, b| {
+                for (k, partials) in b {
+                    match a.get_mut(&k) {
+                        Some(accumulators) => {
+                            for (accumulator, partial) in accumulators.iter_mut().zip(&partials) {
+                                accumulator.merge(partial.as_ref())?;
+                            }
+                        }
+                        None => {
+                            a.insert(k, partials);
+                        }
+                    }
+                }
+                Ok(a)
+            },
+        )
+}
+
+fn new_accumulators(aggregates: &[AggregateFunction]) -> Vec<Box<dyn Accumulator>> {
+    aggregates.iter().map(AggregateFunction::accumulator).collect()
+}
+
+fn update_group<K: Eq + Hash>(
+    groups: &mut Groups<K>,
+    key: K,
+    args: &[Value],
+    aggregates: &[AggregateFunction],
+) -> Result<(), EngineError> {
+    let arity: usize = aggregates.iter().map(AggregateFunction::arity).sum();
+    if args.len() != arity {
+        return Err(EngineError::Execution(format!(
+            "aggregates take {} arguments but {} were given",
+            arity,
+            args.len()
+        )));
+    }
+    let accumulators = groups.entry(key).or_insert_with(|| new_accumulators(aggregates));
+    let mut args = args;
+    for (accumulator, func) in accumulators.iter_mut().zip(aggregates) {
+        let (own, rest) = args.split_at(func.arity());
+        accumulator.update(own)?;
+        args = rest;
+    }
+    Ok(())
+}
+
+fn finalize_groups<K>(groups: Groups<K>) -> Result<Vec<GroupByResult<K>>, EngineError> {
+    groups.into_iter().map(finalize_group).collect()
+}
+
+fn finalize_group<K>((key, accumulators): (K, Vec<Box<dyn Accumulator>>)) -> Result<GroupByResult<K>, EngineError> {
+    let values = accumulators.iter().map(|a| a.finalize()).collect::<Result<Row, _>>()?;
+    Ok(GroupByResult { key, values })
+}
+
+// Partitions whole rows by key, leaving it to the caller to aggregate each group
+pub fn adaptive_group_rows<T, K, F>(data: &[T], key_func: F) -> (Vec<(K, Vec<T>)>, ExecutionReport)
+where
+    T: Clone + Send + Sync,
+    K: Eq + Hash + Clone + Send,
+    F: Fn(&T) -> K + Sync + Send,
+{
+    let report = report(data.len());
+    if report.strategy.is_parallel() {
+        execution::measure(report, || parallel_group_rows(data, key_func), Vec::len)
     } else {
-        (parallel_group_by(data, key_func), "Parallel".to_string())
+        execution::measure(report, || sequential_group_rows(data, key_func), Vec::len)
     }
 }
 
-pub fn sequential_group_by<T, K, F>(data: &[T], key_func: F) -> Vec<GroupByResult<K, f64>>
+pub fn sequential_group_rows<T, K, F>(data: &[T], key_func: F) -> Vec<(K, Vec<T>)>
 where
     T: Clone,
     K: Eq + Hash + Clone,
-    F: Fn(&T) -> (K, f64),
+    F: Fn(&T) -> K,
 {
-    let mut groups: HashMap<K, (f64, usize)> = HashMap::new();
+    let mut groups: HashMap<K, Vec<T>> = HashMap::new();
 
     for item in data {
-        let (key, value) = key_func(item);
-        let entry = groups.entry(key).or_insert((0.0, 0));
-        entry.0 += value;
-        entry.1 += 1;
+        groups.entry(key_func(item)).or_default().push(item.clone());
     }
 
-    groups
-        .into_iter()
-        .map(|(key, (sum, count))| GroupByResult { 
-            key, 
-            avg_value: sum / count as f64 
-        })
-        .collect()
+    groups.into_iter().collect()
 }
 
-pub fn parallel_group_by<T, K, F>(data: &[T], key_func: F) -> Vec<GroupByResult<K, f64>>
+pub fn parallel_group_rows<T, K, F>(data: &[T], key_func: F) -> Vec<(K, Vec<T>)>
 where
     T: Clone + Send + Sync,
     K: Eq + Hash + Clone + Send,
-    F: Fn(&T) -> (K, f64) + Sync + Send,
+    F: Fn(&T) -> K + Sync + Send,
 {
-    let grouped: HashMap<K, (f64, usize)> = data
+    let grouped: HashMap<K, Vec<T>> = data
         .par_iter()
-        .map(|item| {
-            let (key, value) = key_func(item);
-            (key, (value, 1))
-        })
         .fold(
-            || HashMap::new(),
-            |mut acc, (key, (value, count))| {
-                let entry = acc.entry(key).or_insert((0.0, 0));
-                entry.0 += value;
-                entry.1 += count;
+            HashMap::new,
+            |mut acc: HashMap<K, Vec<T>>, item| {
+                acc.entry(key_func(item)).or_default().push(item.clone());
                 acc
             },
         )
         .reduce(
-            || HashMap::new(),
+            HashMap::new,
             |mut a, b| {
-                for (k, (sum, count)) in b {
-                    let entry = a.entry(k).or_insert((0.0, 0));
-                    entry.0 += sum;
-                    entry.1 += count;
+                for (k, rows) in b {
+                    a.entry(k).or_default().extend(rows);
                 }
                 a
             },
         );
 
-    grouped
-        .into_par_iter()
-        .map(|(key, (sum, count))| GroupByResult { 
-            key, 
-            avg_value: sum / count as f64 
+    grouped.into_iter().collect()
+}
+
+// Groups a record batch by the key columns and computes each `(function, argument columns)`
+// aggregate per group; COUNT(*) has no argument columns. The output holds the key columns
+// followed by one column per aggregate. Without key columns the whole batch is a single group,
+// even when it is empty.
+pub fn adaptive_group_by_batch(
+    batch: &RecordBatch,
+    keys: &[usize],
+    aggregates: &[(AggregateFunction, Vec<usize>)],
+) -> Result<(RecordBatch, ExecutionReport), EngineError> {
+    let (output, report) = execution::run_adaptive(
+        report(batch.num_rows()),
+        || sequential_group_by_batch(batch, keys, aggregates),
+        || parallel_group_by_batch(batch, keys, aggregates),
+        |output| output.as_ref().map_or(0, RecordBatch::num_rows),
+    );
+    Ok((output?, report))
+}
+
+pub fn sequential_group_by_batch(
+    batch: &RecordBatch,
+    keys: &[usize],
+    aggregates: &[(AggregateFunction, Vec<usize>)],
+) -> Result<RecordBatch, EngineError> {
+    let fields = output_fields(batch, keys, aggregates)?;
+    let rows: Vec<usize> = (0..batch.num_rows()).collect();
+    let funcs: Vec<AggregateFunction> = aggregates.iter().map(|(func, _)| *func).collect();
+    let groups = sequential_group_by(&rows, |&i| (group_key(batch, keys, i), group_args(batch, aggregates, i)), &funcs)?;
+    group_output(fields, keys, &funcs, groups)
+}
+
+pub fn parallel_group_by_batch(
+    batch: &RecordBatch,
+    keys: &[usize],
+    aggregates: &[(AggregateFunction, Vec<usize>)],
+) -> Result<RecordBatch, EngineError> {
+    let fields = output_fields(batch, keys, aggregates)?;
+    let rows: Vec<usize> = (0..batch.num_rows()).collect();
+    let funcs: Vec<AggregateFunction> = aggregates.iter().map(|(func, _)| *func).collect();
+    let groups = parallel_group_by(&rows, |&i| (group_key(batch, keys, i), group_args(batch, aggregates, i)), &funcs)?;
+    group_output(fields, keys, &funcs, groups)
+}
+
+fn group_key(batch: &RecordBatch, keys: &[usize], row: usize) -> Row {
+    keys.iter().map(|&c| batch.column(c).value(row)).collect()
+}
+
+// COUNT(*) has no argument column and counts a placeholder for every row
+fn group_args(batch: &RecordBatch, aggregates: &[(AggregateFunction, Vec<usize>)], row: usize) -> Row {
+    let mut args = Vec::new();
+    for (_, columns) in aggregates {
+        if columns.is_empty() {
+            args.push(Value::Boolean(true));
+        }
+        args.extend(columns.iter().map(|&c| batch.column(c).value(row)));
+    }
+    args
+}
+
+// The key fields followed by one field per aggregate; fails for aggregates over unsupported types
+fn output_fields(
+    batch: &RecordBatch,
+    keys: &[usize],
+    aggregates: &[(AggregateFunction, Vec<usize>)],
+) -> Result<Vec<Field>, EngineError> {
+    let mut fields: Vec<Field> = keys.iter().map(|&c| batch.schema().fields[c].clone()).collect();
+    for (func, args) in aggregates {
+        let arg_fields: Vec<&Field> = args.iter().map(|&c| &batch.schema().fields[c]).collect();
+        let arg_types: Vec<DataType> = arg_fields.iter().map(|f| f.data_type).collect();
+        let data_type = func.output_type(&arg_types)?;
+        let arg_names: Vec<&str> = arg_fields.iter().map(|f| f.name.as_str()).collect();
+        let arg_names = if args.is_empty() { "*".to_string() } else { arg_names.join(", ") };
+        let name = format!("{:?}({})", func, arg_names).to_lowercase();
+        fields.push(Field::new(&name, data_type, func.nullable()));
+    }
+    Ok(fields)
+}
+
+fn group_output(
+    fields: Vec<Field>,
+    keys: &[usize],
+    aggregates: &[AggregateFunction],
+    mut groups: Vec<GroupByResult<Row>>,
+) -> Result<RecordBatch, EngineError> {
+    if groups.is_empty() && keys.is_empty() {
+        groups.push(GroupByResult {
+            key: Vec::new(),
+            values: empty_group(aggregates)?,
+        });
+    }
+
+    let rows = groups
+        .into_iter()
+        .map(|group| group.key.into_iter().chain(group.values).collect())
+        .collect();
+    rows_to_batch(fields, rows)
+}
+
+// Aggregates over no rows
+fn empty_group(aggregates: &[AggregateFunction]) -> Result<Row, EngineError> {
+    new_accumulators(aggregates).iter().map(|a| a.finalize()).collect()
+}
+
+fn rows_to_batch(fields: Vec<Field>, rows: Vec<Row>) -> Result<RecordBatch, EngineError> {
+    let mut columns = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        let values: Vec<Value> = rows.iter().map(|row| row[i].clone()).collect();
+        columns.push(Column::from_values(field.data_type, &values)?);
+    }
+    Ok(RecordBatch::from_parts(Schema::new(fields), columns, rows.len()))
+}
+
+// Groups a record batch by each grouping set in one pass; a set lists positions into `keys`.
+// The output holds the key columns, NULL where the set of a row leaves them out, one column
+// per aggregate and a `grouping_id` column whose bits mark the keys left out, the first key
+// being the most significant bit. An empty set always has a row, even for an empty batch.
+pub fn adaptive_group_by_sets_batch(
+    batch: &RecordBatch,
+    keys: &[usize],
+    sets: &[Vec<usize>],
+    aggregates: &[(AggregateFunction, Vec<usize>)],
+) -> Result<(RecordBatch, ExecutionReport), EngineError> {
+    let (output, report) = execution::run_adaptive(
+        report(batch.num_rows()),
+        || sequential_group_by_sets_batch(batch, keys, sets, aggregates),
+        || parallel_group_by_sets_batch(batch, keys, sets, aggregates),
+        |output| output.as_ref().map_or(0, RecordBatch::num_rows),
+    );
+    Ok((output?, report))
+}
+
+pub fn sequential_group_by_sets_batch(
+    batch: &RecordBatch,
+    keys: &[usize],
+    sets: &[Vec<usize>],
+    aggregates: &[(AggregateFunction, Vec<usize>)],
+) -> Result<RecordBatch, EngineError> {
+    let fields = sets_output_fields(batch, keys, sets, aggregates)?;
+    let rows: Vec<usize> = (0..batch.num_rows()).collect();
+    let funcs: Vec<AggregateFunction> = aggregates.iter().map(|(func, _)| *func).collect();
+    let groups = sequential_group_by_sets(
+        &rows,
+        |&i| (group_key(batch, keys, i), group_args(batch, aggregates, i)),
+        sets,
+        &funcs,
+    )?;
+    sets_output(fields, keys, sets, &funcs, groups)
+}
+
+pub fn parallel_group_by_sets_batch(
+    batch: &RecordBatch,
+    keys: &[usize],
+    sets: &[Vec<usize>],
+    aggregates: &[(AggregateFunction, Vec<usize>)],
+) -> Result<RecordBatch, EngineError> {
+    let fields = sets_output_fields(batch, keys, sets, aggregates)?;
+    let rows: Vec<usize> = (0..batch.num_rows()).collect();
+    let funcs: Vec<AggregateFunction> = aggregates.iter().map(|(func, _)| *func).collect();
+    let groups = parallel_group_by_sets(
+        &rows,
+        |&i| (group_key(batch, keys, i), group_args(batch, aggregates, i)),
+        sets,
+        &funcs,
+    )?;
+    sets_output(fields, keys, sets, &funcs, groups)
+}
+
+// Keys a set leaves out become nullable, and the grouping id is added last
+fn sets_output_fields(
+    batch: &RecordBatch,
+    keys: &[usize],
+    sets: &[Vec<usize>],
+    aggregates: &[(AggregateFunction, Vec<usize>)],
+) -> Result<Vec<Field>, EngineError> {
+    if keys.len() >= i64::BITS as usize {
+        return Err(EngineError::Plan(format!(
+            "grouping sets support at most {} keys",
+            i64::BITS - 1
+        )));
+    }
+    if let Some(&position) = sets.iter().flatten().find(|&&position| position >= keys.len()) {
+        return Err(EngineError::Execution(format!(
+            "grouping set refers to key {} but there are {} keys",
+            position,
+            keys.len()
+        )));
+    }
+    let mut fields = output_fields(batch, keys, aggregates)?;
+    for (i, field) in fields.iter_mut().take(keys.len()).enumerate() {
+        if sets.iter().any(|set| !set.contains(&i)) {
+            field.nullable = true;
+        }
+    }
+    fields.push(Field::new("grouping_id", DataType::Int64, false));
+    Ok(fields)
+}
+
+fn grouping_id(keys: usize, set: &[usize]) -> i64 {
+    (0..keys)
+        .filter(|i| !set.contains(i))
+        .fold(0, |id, i| id | 1 << (keys - 1 - i))
+}
+
+fn sets_output(
+    fields: Vec<Field>,
+    keys: &[usize],
+    sets: &[Vec<usize>],
+    aggregates: &[AggregateFunction],
+    mut groups: Vec<GroupByResult<SetKey>>,
+) -> Result<RecordBatch, EngineError> {
+    // Grand totals exist even when no row reached them
+    for (i, set) in sets.iter().enumerate() {
+        if set.is_empty() && !groups.iter().any(|group| group.key.0 == i) {
+            groups.push(GroupByResult {
+                key: (i, vec![Value::Null; keys.len()]),
+                values: empty_group(aggregates)?,
+            });
+        }
+    }
+
+    let rows = groups
+        .into_iter()
+        .map(|group| {
+            let (set, key) = group.key;
+            let id = Value::Int64(grouping_id(keys.len(), &sets[set]));
+            key.into_iter().chain(group.values).chain(iter::once(id)).collect()
         })
-        .collect()
-}
\ No newline at end of file
+        .collect();
+    rows_to_batch(fields, rows)
+}
@@ -1,24 +1,124 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::cmp::Ordering as KeyOrdering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::Hash;
+use std::ops::{Bound, Range};
+use std::sync::atomic::{AtomicBool, Ordering};
 use rayon::prelude::*;
 
+use crate::batch::{Column, ColumnData, RecordBatch};
+use crate::calibration::thresholds;
+use crate::error::EngineError;
+use crate::execution::{self, ExecutionReport};
+use crate::expressions::{self, cast_value, ScalarExpr};
+use crate::scans::predicate_mask;
+use crate::sorting;
+use crate::types::{DataType, Row, Value};
+
+pub(crate) const PARALLELIZATION_THRESHOLD: usize = 100000; // Default for the combined input size, see calibration::Thresholds
+const BLOCK_PAIRS: usize = 8192; // Candidate pairs a nested-loop join checks at a time
+
+type Result<T> = std::result::Result<T, EngineError>;
+
+fn report(left_rows: usize, right_rows: usize) -> ExecutionReport {
+    ExecutionReport::for_input(left_rows + right_rows, thresholds().join)
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Record {
     pub id: i32,
     pub value: String,
 }
 
+// Which rows a join keeps besides the matching pairs: outer joins also emit the unmatched rows
+// of their preserved side(s), paired with None (NULLs in a record batch)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JoinType {
+    Inner,
+    Left,
+    Right,
+    Full,
+}
+
+impl JoinType {
+    pub fn keeps_left(&self) -> bool {
+        matches!(self, JoinType::Left | JoinType::Full)
+    }
+
+    pub fn keeps_right(&self) -> bool {
+        matches!(self, JoinType::Right | JoinType::Full)
+    }
+}
+
+impl fmt::Display for JoinType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            JoinType::Inner => "INNER",
+            JoinType::Left => "LEFT",
+            JoinType::Right => "RIGHT",
+            JoinType::Full => "FULL",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// A pair of an outer join; None stands for the NULL-padded side of an unmatched row
+pub type OuterPair<L, R> = (Option<L>, Option<R>);
+
 pub fn sequential_hash_join(left: &[Record], right: &[Record]) -> Vec<(Record, Record)> {
-    let mut hash_map: HashMap<i32, Vec<&Record>> = HashMap::new();
+    sequential_hash_join_by(left, right, |r| r.id, |r| r.id)
+}
+
+pub fn parallel_hash_join(left: &[Record], right: &[Record]) -> Vec<(Record, Record)> {
+    parallel_hash_join_by(left, right, |r| r.id, |r| r.id)
+}
+
+pub fn adaptive_hash_join_by<L, R, K, FL, FR>(
+    left: &[L],
+    right: &[R],
+    left_key: FL,
+    right_key: FR,
+) -> (Vec<(L, R)>, ExecutionReport)
+where
+    L: Clone + Send + Sync,
+    R: Clone + Send + Sync,
+    K: Eq + Hash + Send + Sync,
+    FL: Fn(&L) -> K + Sync + Send,
+    FR: Fn(&R) -> K + Sync + Send,
+{
+    execution::run_adaptive(
+        report(left.len(), right.len()),
+        || sequential_hash_join_by(left, right, &left_key, &right_key),
+        || parallel_hash_join_by(left, right, &left_key, &right_key),
+        Vec::len,
+    )
+}
+
+pub fn sequential_hash_join_by<L, R, K, FL, FR>(
+    left: &[L],
+    right: &[R],
+    left_key: FL,
+    right_key: FR,
+) -> Vec<(L, R)>
+where
+    L: Clone,
+    R: Clone,
+    K: Eq + Hash,
+    FL: Fn(&L) -> K,
+    FR: Fn(&R) -> K,
+{
+    let mut hash_map: HashMap<K, Vec<&L>> = HashMap::new();
 
     // Build phase
     for record in left {
-        hash_map.entry(record.id).or_default().push(record);
+        hash_map.entry(left_key(record)).or_default().push(record);
     }
 
     // Probe phase
     let mut result = Vec::new();
     for r_record in right {
-        if let Some(l_records) = hash_map.get(&r_record.id) {
+        if let Some(l_records) = hash_map.get(&right_key(r_record)) {
             for l_record in l_records {
                 result.push(((*l_record).clone(), r_record.clone()));
             }
@@ -28,19 +128,31 @@ pub fn sequential_hash_join(left: &[Record], right: &[Record]) -> Vec<(Record, R
     result
 }
 
-pub fn parallel_hash_join(left: &[Record], right: &[Record]) -> Vec<(Record, Record)> {
+pub fn parallel_hash_join_by<L, R, K, FL, FR>(
+    left: &[L],
+    right: &[R],
+    left_key: FL,
+    right_key: FR,
+) -> Vec<(L, R)>
+where
+    L: Clone + Send + Sync,
+    R: Clone + Send + Sync,
+    K: Eq + Hash + Send + Sync,
+    FL: Fn(&L) -> K + Sync + Send,
+    FR: Fn(&R) -> K + Sync + Send,
+{
     // Build phase
-    let hash_map: HashMap<i32, Vec<&Record>> = left
+    let hash_map: HashMap<K, Vec<&L>> = left
         .par_iter()
         .fold(
-            || HashMap::new(),
-            |mut acc: HashMap<i32, Vec<&Record>>, record| {
-                acc.entry(record.id).or_default().push(record);
+            HashMap::new,
+            |mut acc: HashMap<K, Vec<&L>>, record| {
+                acc.entry(left_key(record)).or_default().push(record);
                 acc
             },
         )
         .reduce(
-            || HashMap::new(),
+            HashMap::new,
             |mut a, b| {
                 for (k, v) in b {
                     a.entry(k).or_default().extend(v);
@@ -54,7 +166,7 @@ pub fn parallel_hash_join(left: &[Record], right: &[Record]) -> Vec<(Record, Rec
         .par_iter()
         .flat_map(|r_record| {
             hash_map
-                .get(&r_record.id)
+                .get(&right_key(r_record))
                 .map(|l_records| {
                     l_records
                         .iter()
@@ -66,48 +178,1248 @@ pub fn parallel_hash_join(left: &[Record], right: &[Record]) -> Vec<(Record, Rec
         .collect()
 }
 
+pub fn sequential_outer_hash_join(left: &[Record], right: &[Record], join_type: JoinType) -> Vec<OuterPair<Record, Record>> {
+    sequential_outer_hash_join_by(left, right, |r| r.id, |r| r.id, join_type)
+}
+
+pub fn parallel_outer_hash_join(left: &[Record], right: &[Record], join_type: JoinType) -> Vec<OuterPair<Record, Record>> {
+    parallel_outer_hash_join_by(left, right, |r| r.id, |r| r.id, join_type)
+}
+
+// Hash join that also emits the unmatched rows `join_type` keeps. The left input is the build
+// side: unmatched right rows are known while probing, unmatched left rows once probing ends.
+pub fn adaptive_outer_hash_join_by<L, R, K, FL, FR>(
+    left: &[L],
+    right: &[R],
+    left_key: FL,
+    right_key: FR,
+    join_type: JoinType,
+) -> (Vec<OuterPair<L, R>>, ExecutionReport)
+where
+    L: Clone + Send + Sync,
+    R: Clone + Send + Sync,
+    K: Eq + Hash + Send + Sync,
+    FL: Fn(&L) -> K + Sync + Send,
+    FR: Fn(&R) -> K + Sync + Send,
+{
+    execution::run_adaptive(
+        report(left.len(), right.len()),
+        || sequential_outer_hash_join_by(left, right, &left_key, &right_key, join_type),
+        || parallel_outer_hash_join_by(left, right, &left_key, &right_key, join_type),
+        Vec::len,
+    )
+}
+
+pub fn sequential_outer_hash_join_by<L, R, K, FL, FR>(
+    left: &[L],
+    right: &[R],
+    left_key: FL,
+    right_key: FR,
+    join_type: JoinType,
+) -> Vec<OuterPair<L, R>>
+where
+    L: Clone,
+    R: Clone,
+    K: Eq + Hash,
+    FL: Fn(&L) -> K,
+    FR: Fn(&R) -> K,
+{
+    // Build phase, on positions so matched build rows can be marked
+    let mut hash_map: HashMap<K, Vec<usize>> = HashMap::new();
+    for (i, record) in left.iter().enumerate() {
+        hash_map.entry(left_key(record)).or_default().push(i);
+    }
+
+    // Probe phase
+    let mut matched = vec![false; left.len()];
+    let mut result = Vec::new();
+    for r_record in right {
+        match hash_map.get(&right_key(r_record)) {
+            Some(l_indices) => {
+                for &i in l_indices {
+                    matched[i] = true;
+                    result.push((Some(left[i].clone()), Some(r_record.clone())));
+                }
+            }
+            None if join_type.keeps_right() => result.push((None, Some(r_record.clone()))),
+            None => {}
+        }
+    }
+
+    if join_type.keeps_left() {
+        result.extend(
+            left.iter()
+                .zip(&matched)
+                .filter(|(_, &matched)| !matched)
+                .map(|(l_record, _)| (Some(l_record.clone()), None)),
+        );
+    }
+    result
+}
+
+pub fn parallel_outer_hash_join_by<L, R, K, FL, FR>(
+    left: &[L],
+    right: &[R],
+    left_key: FL,
+    right_key: FR,
+    join_type: JoinType,
+) -> Vec<OuterPair<L, R>>
+where
+    L: Clone + Send + Sync,
+    R: Clone + Send + Sync,
+    K: Eq + Hash + Send + Sync,
+    FL: Fn(&L) -> K + Sync + Send,
+    FR: Fn(&R) -> K + Sync + Send,
+{
+    // Build phase, on positions so matched build rows can be marked
+    let hash_map: HashMap<K, Vec<usize>> = left
+        .par_iter()
+        .enumerate()
+        .fold(
+            HashMap::new,
+            |mut acc: HashMap<K, Vec<usize>>, (i, record)| {
+                acc.entry(left_key(record)).or_default().push(i);
+                acc
+            },
+        )
+        .reduce(
+            HashMap::new,
+            |mut a, b| {
+                for (k, v) in b {
+                    a.entry(k).or_default().extend(v);
+                }
+                a
+            },
+        );
+
+    // Probe phase. Tasks flag the build rows they match; a flag is only written while unset,
+    // so rows with many matches do not keep bouncing their cache line between threads.
+    let matched: Vec<AtomicBool> = (0..left.len()).map(|_| AtomicBool::new(false)).collect();
+    let mut result: Vec<OuterPair<L, R>> = right
+        .par_iter()
+        .flat_map_iter(|r_record| {
+            let pairs: Vec<OuterPair<L, R>> = match hash_map.get(&right_key(r_record)) {
+                Some(l_indices) => l_indices
+                    .iter()
+                    .map(|&i| {
+                        if !matched[i].load(Ordering::Relaxed) {
+                            matched[i].store(true, Ordering::Relaxed);
+                        }
+                        (Some(left[i].clone()), Some(r_record.clone()))
+                    })
+                    .collect(),
+                None if join_type.keeps_right() => vec![(None, Some(r_record.clone()))],
+                None => Vec::new(),
+            };
+            pairs
+        })
+        .collect();
+
+    // Every probe task has finished, so the flags are final
+    if join_type.keeps_left() {
+        let unmatched: Vec<OuterPair<L, R>> = left
+            .par_iter()
+            .zip(&matched)
+            .filter(|(_, matched)| !matched.load(Ordering::Relaxed))
+            .map(|(l_record, _)| (Some(l_record.clone()), None))
+            .collect();
+        result.extend(unmatched);
+    }
+    result
+}
+
+// Semi and anti joins keep the left rows that have, or do not have, a match on the right, each
+// left row once, as IN and EXISTS subqueries do. The right side only needs a set of its keys, so
+// every left row is decided by a single lookup that stops at the first match.
+pub fn adaptive_semi_hash_join_by<L, R, K, FL, FR>(left: &[L], right: &[R], left_key: FL, right_key: FR) -> (Vec<L>, ExecutionReport)
+where
+    L: Clone + Send + Sync,
+    R: Sync,
+    K: Eq + Hash + Send + Sync,
+    FL: Fn(&L) -> K + Sync + Send,
+    FR: Fn(&R) -> K + Sync + Send,
+{
+    execution::run_adaptive(
+        report(left.len(), right.len()),
+        || sequential_semi_hash_join_by(left, right, &left_key, &right_key),
+        || parallel_semi_hash_join_by(left, right, &left_key, &right_key),
+        Vec::len,
+    )
+}
+
+pub fn sequential_semi_hash_join_by<L, R, K, FL, FR>(left: &[L], right: &[R], left_key: FL, right_key: FR) -> Vec<L>
+where
+    L: Clone,
+    K: Eq + Hash,
+    FL: Fn(&L) -> K,
+    FR: Fn(&R) -> K,
+{
+    sequential_filter_join_by(left, right, left_key, right_key, true)
+}
+
+pub fn parallel_semi_hash_join_by<L, R, K, FL, FR>(left: &[L], right: &[R], left_key: FL, right_key: FR) -> Vec<L>
+where
+    L: Clone + Send + Sync,
+    R: Sync,
+    K: Eq + Hash + Send + Sync,
+    FL: Fn(&L) -> K + Sync + Send,
+    FR: Fn(&R) -> K + Sync + Send,
+{
+    parallel_filter_join_by(left, right, left_key, right_key, true)
+}
+
+pub fn adaptive_anti_hash_join_by<L, R, K, FL, FR>(left: &[L], right: &[R], left_key: FL, right_key: FR) -> (Vec<L>, ExecutionReport)
+where
+    L: Clone + Send + Sync,
+    R: Sync,
+    K: Eq + Hash + Send + Sync,
+    FL: Fn(&L) -> K + Sync + Send,
+    FR: Fn(&R) -> K + Sync + Send,
+{
+    execution::run_adaptive(
+        report(left.len(), right.len()),
+        || sequential_anti_hash_join_by(left, right, &left_key, &right_key),
+        || parallel_anti_hash_join_by(left, right, &left_key, &right_key),
+        Vec::len,
+    )
+}
+
+pub fn sequential_anti_hash_join_by<L, R, K, FL, FR>(left: &[L], right: &[R], left_key: FL, right_key: FR) -> Vec<L>
+where
+    L: Clone,
+    K: Eq + Hash,
+    FL: Fn(&L) -> K,
+    FR: Fn(&R) -> K,
+{
+    sequential_filter_join_by(left, right, left_key, right_key, false)
+}
+
+pub fn parallel_anti_hash_join_by<L, R, K, FL, FR>(left: &[L], right: &[R], left_key: FL, right_key: FR) -> Vec<L>
+where
+    L: Clone + Send + Sync,
+    R: Sync,
+    K: Eq + Hash + Send + Sync,
+    FL: Fn(&L) -> K + Sync + Send,
+    FR: Fn(&R) -> K + Sync + Send,
+{
+    parallel_filter_join_by(left, right, left_key, right_key, false)
+}
+
+// Left rows whose key is (`matched`) or is not among the right keys, in input order
+fn sequential_filter_join_by<L, R, K, FL, FR>(left: &[L], right: &[R], left_key: FL, right_key: FR, matched: bool) -> Vec<L>
+where
+    L: Clone,
+    K: Eq + Hash,
+    FL: Fn(&L) -> K,
+    FR: Fn(&R) -> K,
+{
+    let keys: HashSet<K> = right.iter().map(right_key).collect();
+    left.iter()
+        .filter(|record| keys.contains(&left_key(record)) == matched)
+        .cloned()
+        .collect()
+}
+
+fn parallel_filter_join_by<L, R, K, FL, FR>(left: &[L], right: &[R], left_key: FL, right_key: FR, matched: bool) -> Vec<L>
+where
+    L: Clone + Send + Sync,
+    R: Sync,
+    K: Eq + Hash + Send + Sync,
+    FL: Fn(&L) -> K + Sync + Send,
+    FR: Fn(&R) -> K + Sync + Send,
+{
+    let keys: HashSet<K> = right
+        .par_iter()
+        .fold(HashSet::new, |mut acc: HashSet<K>, record| {
+            acc.insert(right_key(record));
+            acc
+        })
+        .reduce(HashSet::new, |mut a, b| {
+            a.extend(b);
+            a
+        });
+    left.par_iter()
+        .filter(|record| keys.contains(&left_key(record)) == matched)
+        .cloned()
+        .collect()
+}
+
+// Equi-join of two record batches on the given key columns. Output rows hold the left
+// columns followed by the right columns; no key columns means a cross join. Keys of different
+// numeric types are compared in their common type, see `join_key_type`. A NULL key never
+// equals anything, so rows with a NULL in any key column take no part in the join.
+pub fn adaptive_hash_join_batches(
+    left: &RecordBatch,
+    right: &RecordBatch,
+    left_on: &[usize],
+    right_on: &[usize],
+) -> (RecordBatch, ExecutionReport) {
+    execution::run_adaptive(
+        report(left.num_rows(), right.num_rows()),
+        || sequential_hash_join_batches(left, right, left_on, right_on),
+        || parallel_hash_join_batches(left, right, left_on, right_on),
+        RecordBatch::num_rows,
+    )
+}
+
+pub fn sequential_hash_join_batches(
+    left: &RecordBatch,
+    right: &RecordBatch,
+    left_on: &[usize],
+    right_on: &[usize],
+) -> RecordBatch {
+    let (left_keys, right_keys) = key_columns(left, right, left_on, right_on);
+    let left_rows = joinable_rows(left, left_on);
+    let right_rows = joinable_rows(right, right_on);
+    let pairs = sequential_hash_join_by(
+        &left_rows,
+        &right_rows,
+        |&i| batch_key(&left_keys, i),
+        |&i| batch_key(&right_keys, i),
+    );
+    take_pairs(left, right, &pairs)
+}
+
+pub fn parallel_hash_join_batches(
+    left: &RecordBatch,
+    right: &RecordBatch,
+    left_on: &[usize],
+    right_on: &[usize],
+) -> RecordBatch {
+    let (left_keys, right_keys) = key_columns(left, right, left_on, right_on);
+    let left_rows = joinable_rows(left, left_on);
+    let right_rows = joinable_rows(right, right_on);
+    let pairs = parallel_hash_join_by(
+        &left_rows,
+        &right_rows,
+        |&i| batch_key(&left_keys, i),
+        |&i| batch_key(&right_keys, i),
+    );
+    take_pairs(left, right, &pairs)
+}
+
+// Outer equi-join of two record batches, see `adaptive_hash_join_batches`. Rows of a preserved
+// side that find no match, including those with a NULL key, are padded with NULLs for the
+// columns of the other side.
+pub fn adaptive_outer_hash_join_batches(
+    left: &RecordBatch,
+    right: &RecordBatch,
+    left_on: &[usize],
+    right_on: &[usize],
+    join_type: JoinType,
+) -> (RecordBatch, ExecutionReport) {
+    execution::run_adaptive(
+        report(left.num_rows(), right.num_rows()),
+        || sequential_outer_hash_join_batches(left, right, left_on, right_on, join_type),
+        || parallel_outer_hash_join_batches(left, right, left_on, right_on, join_type),
+        RecordBatch::num_rows,
+    )
+}
+
+pub fn sequential_outer_hash_join_batches(
+    left: &RecordBatch,
+    right: &RecordBatch,
+    left_on: &[usize],
+    right_on: &[usize],
+    join_type: JoinType,
+) -> RecordBatch {
+    let (left_keys, right_keys) = key_columns(left, right, left_on, right_on);
+    let left_rows = joinable_rows(left, left_on);
+    let right_rows = joinable_rows(right, right_on);
+    let pairs = sequential_outer_hash_join_by(
+        &left_rows,
+        &right_rows,
+        |&i| batch_key(&left_keys, i),
+        |&i| batch_key(&right_keys, i),
+        join_type,
+    );
+    take_outer_pairs(left, right, with_null_keys(pairs, left, left_on, right, right_on, join_type))
+}
+
+pub fn parallel_outer_hash_join_batches(
+    left: &RecordBatch,
+    right: &RecordBatch,
+    left_on: &[usize],
+    right_on: &[usize],
+    join_type: JoinType,
+) -> RecordBatch {
+    let (left_keys, right_keys) = key_columns(left, right, left_on, right_on);
+    let left_rows = joinable_rows(left, left_on);
+    let right_rows = joinable_rows(right, right_on);
+    let pairs = parallel_outer_hash_join_by(
+        &left_rows,
+        &right_rows,
+        |&i| batch_key(&left_keys, i),
+        |&i| batch_key(&right_keys, i),
+        join_type,
+    );
+    take_outer_pairs(left, right, with_null_keys(pairs, left, left_on, right, right_on, join_type))
+}
+
+// Left rows with a match on the key columns; a NULL key matches nothing
+pub fn adaptive_semi_join_batches(
+    left: &RecordBatch,
+    right: &RecordBatch,
+    left_on: &[usize],
+    right_on: &[usize],
+) -> (RecordBatch, ExecutionReport) {
+    execution::run_adaptive(
+        report(left.num_rows(), right.num_rows()),
+        || sequential_semi_join_batches(left, right, left_on, right_on),
+        || parallel_semi_join_batches(left, right, left_on, right_on),
+        RecordBatch::num_rows,
+    )
+}
+
+pub fn sequential_semi_join_batches(left: &RecordBatch, right: &RecordBatch, left_on: &[usize], right_on: &[usize]) -> RecordBatch {
+    let (left_keys, right_keys) = key_columns(left, right, left_on, right_on);
+    let rows = sequential_semi_hash_join_by(
+        &joinable_rows(left, left_on),
+        &joinable_rows(right, right_on),
+        |&i| batch_key(&left_keys, i),
+        |&i| batch_key(&right_keys, i),
+    );
+    left.take(&rows)
+}
+
+pub fn parallel_semi_join_batches(left: &RecordBatch, right: &RecordBatch, left_on: &[usize], right_on: &[usize]) -> RecordBatch {
+    let (left_keys, right_keys) = key_columns(left, right, left_on, right_on);
+    let rows = parallel_semi_hash_join_by(
+        &joinable_rows(left, left_on),
+        &joinable_rows(right, right_on),
+        |&i| batch_key(&left_keys, i),
+        |&i| batch_key(&right_keys, i),
+    );
+    left.take(&rows)
+}
+
+// Left rows without a match on the key columns. A NULL key matches nothing, so those rows are
+// kept as NOT EXISTS does, unless `null_aware` asks for the semantics of NOT IN: a NULL
+// among the right keys leaves no row, and left rows with a NULL key are dropped, both unless
+// the right side is empty. A key with a NULL in any column counts as NULL.
+pub fn adaptive_anti_join_batches(
+    left: &RecordBatch,
+    right: &RecordBatch,
+    left_on: &[usize],
+    right_on: &[usize],
+    null_aware: bool,
+) -> (RecordBatch, ExecutionReport) {
+    execution::run_adaptive(
+        report(left.num_rows(), right.num_rows()),
+        || sequential_anti_join_batches(left, right, left_on, right_on, null_aware),
+        || parallel_anti_join_batches(left, right, left_on, right_on, null_aware),
+        RecordBatch::num_rows,
+    )
+}
+
+pub fn sequential_anti_join_batches(
+    left: &RecordBatch,
+    right: &RecordBatch,
+    left_on: &[usize],
+    right_on: &[usize],
+    null_aware: bool,
+) -> RecordBatch {
+    let (left_keys, right_keys) = key_columns(left, right, left_on, right_on);
+    anti_join_batches(left, right, left_on, right_on, null_aware, |left_rows, right_rows| {
+        sequential_anti_hash_join_by(
+            left_rows,
+            right_rows,
+            |&i| batch_key(&left_keys, i),
+            |&i| batch_key(&right_keys, i),
+        )
+    })
+}
+
+pub fn parallel_anti_join_batches(
+    left: &RecordBatch,
+    right: &RecordBatch,
+    left_on: &[usize],
+    right_on: &[usize],
+    null_aware: bool,
+) -> RecordBatch {
+    let (left_keys, right_keys) = key_columns(left, right, left_on, right_on);
+    anti_join_batches(left, right, left_on, right_on, null_aware, |left_rows, right_rows| {
+        parallel_anti_hash_join_by(
+            left_rows,
+            right_rows,
+            |&i| batch_key(&left_keys, i),
+            |&i| batch_key(&right_keys, i),
+        )
+    })
+}
+
+// Handles NULL keys around `anti_join`, which sees only the joinable rows of both sides
+fn anti_join_batches(
+    left: &RecordBatch,
+    right: &RecordBatch,
+    left_on: &[usize],
+    right_on: &[usize],
+    null_aware: bool,
+    anti_join: impl FnOnce(&[usize], &[usize]) -> Vec<usize>,
+) -> RecordBatch {
+    if null_aware && right.num_rows() == 0 {
+        return left.clone();
+    }
+    if null_aware && right_on.iter().any(|&c| right.column(c).null_count() > 0) {
+        return left.take(&[]);
+    }
+    let right_rows = joinable_rows(right, right_on);
+    let left_rows = joinable_rows(left, left_on);
+    let mut rows = anti_join(&left_rows, &right_rows);
+    if left_rows.len() < left.num_rows() {
+        // A NaN key differs from every key, while NOT IN leaves a NULL one unknown
+        let has_null = |i: usize| left_on.iter().any(|&c| left.column(c).is_null(i));
+        rows.extend(
+            (0..left.num_rows())
+                .filter(|&i| !is_joinable(left, left_on, i))
+                .filter(|&i| !null_aware || !has_null(i)),
+        );
+        rows.sort_unstable();
+    }
+    left.take(&rows)
+}
+
+// Adds the rows of preserved sides that never took part in the join because of a NULL key
+fn with_null_keys(
+    mut pairs: Vec<OuterPair<usize, usize>>,
+    left: &RecordBatch,
+    left_on: &[usize],
+    right: &RecordBatch,
+    right_on: &[usize],
+    join_type: JoinType,
+) -> Vec<OuterPair<usize, usize>> {
+    let null_keys = |batch: &RecordBatch, on: &[usize]| -> Vec<usize> {
+        (0..batch.num_rows()).filter(|&i| !is_joinable(batch, on, i)).collect()
+    };
+    if join_type.keeps_left() {
+        pairs.extend(null_keys(left, left_on).into_iter().map(|i| (Some(i), None)));
+    }
+    if join_type.keeps_right() {
+        pairs.extend(null_keys(right, right_on).into_iter().map(|i| (None, Some(i))));
+    }
+    pairs
+}
+
+// Completes the matching `pairs` of rows of an outer join with the unmatched rows `join_type`
+// keeps, for callers that decide matches themselves, e.g. with conditions beyond the join keys
+pub fn pad_unmatched(
+    pairs: &[(usize, usize)],
+    left_rows: usize,
+    right_rows: usize,
+    join_type: JoinType,
+) -> Vec<OuterPair<usize, usize>> {
+    let mut left_matched = vec![false; left_rows];
+    let mut right_matched = vec![false; right_rows];
+    for &(l, r) in pairs {
+        left_matched[l] = true;
+        right_matched[r] = true;
+    }
+    let mut padded: Vec<OuterPair<usize, usize>> = pairs.iter().map(|&(l, r)| (Some(l), Some(r))).collect();
+    if join_type.keeps_left() {
+        padded.extend((0..left_rows).filter(|&i| !left_matched[i]).map(|i| (Some(i), None)));
+    }
+    if join_type.keeps_right() {
+        padded.extend((0..right_rows).filter(|&i| !right_matched[i]).map(|i| (None, Some(i))));
+    }
+    padded
+}
+
+pub fn take_outer_pairs(left: &RecordBatch, right: &RecordBatch, pairs: Vec<OuterPair<usize, usize>>) -> RecordBatch {
+    let (left_indices, right_indices): (Vec<Option<usize>>, Vec<Option<usize>>) = pairs.into_iter().unzip();
+    left.take_nullable(&left_indices)
+        .merge_columns(&right.take_nullable(&right_indices))
+}
+
+fn joinable_rows(batch: &RecordBatch, on: &[usize]) -> Vec<usize> {
+    (0..batch.num_rows()).filter(|&i| is_joinable(batch, on, i)).collect()
+}
+
+// A NULL key matches nothing, and neither does NaN, which SQL `=` finds equal to no float
+fn is_joinable(batch: &RecordBatch, on: &[usize], row: usize) -> bool {
+    on.iter().all(|&c| {
+        let column = batch.column(c);
+        !column.is_null(row) && !matches!(column.data(), ColumnData::Float64(values) if values[row].is_nan())
+    })
+}
+
+// Values hash and compare floats through `types::float_key`, so -0.0 and 0.0 are one key
+fn batch_key(keys: &[Cow<Column>], row: usize) -> Row {
+    keys.iter().map(|column| column.value(row)).collect()
+}
+
+// The type two join keys are compared in: integers widen to Int64 and meet floats as Float64,
+// as in comparisons. None when the types cannot be compared at all. Float keys compare as
+// numbers: -0.0 matches 0.0, and NaN matches nothing, like NULL.
+pub fn join_key_type(left: DataType, right: DataType) -> Option<DataType> {
+    match (left, right) {
+        (left, right) if left == right => Some(left),
+        (DataType::Null, other) | (other, DataType::Null) => Some(other),
+        (left, right) if left.is_integer() && right.is_integer() => Some(DataType::Int64),
+        (left, right) if left.is_numeric() && right.is_numeric() => Some(DataType::Float64),
+        _ => None,
+    }
+}
+
+// The key columns of both sides, each pair converted to its common type when their types
+// differ, so that an Int32 key finds the equal Int64 one. Keys that cannot be compared are left
+// as they are and never match.
+fn key_columns<'a>(
+    left: &'a RecordBatch,
+    right: &'a RecordBatch,
+    left_on: &[usize],
+    right_on: &[usize],
+) -> (Vec<Cow<'a, Column>>, Vec<Cow<'a, Column>>) {
+    left_on
+        .iter()
+        .zip(right_on)
+        .map(|(&l, &r)| {
+            let (l, r) = (left.column(l), right.column(r));
+            match join_key_type(l.data_type(), r.data_type()) {
+                Some(key_type) => (cast_key(l, key_type), cast_key(r, key_type)),
+                None => (Cow::Borrowed(l), Cow::Borrowed(r)),
+            }
+        })
+        .unzip()
+}
+
+fn cast_key(column: &Column, to: DataType) -> Cow<'_, Column> {
+    if column.data_type() == to {
+        return Cow::Borrowed(column);
+    }
+    let values: Vec<Value> = (0..column.len())
+        .map(|i| cast_value(&column.value(i), to).expect("join keys only widen"))
+        .collect();
+    Cow::Owned(Column::from_values(to, &values).expect("values of the key type"))
+}
+
+fn take_pairs(left: &RecordBatch, right: &RecordBatch, pairs: &[(usize, usize)]) -> RecordBatch {
+    let (left_indices, right_indices): (Vec<usize>, Vec<usize>) = pairs.iter().copied().unzip();
+    left.take(&left_indices).merge_columns(&right.take(&right_indices))
+}
+
 pub fn sequential_merge_join(left: &[Record], right: &[Record]) -> Vec<(Record, Record)> {
+    sequential_merge_join_by(left, right, |r| r.id, |r| r.id)
+}
+
+pub fn parallel_merge_join(left: &[Record], right: &[Record]) -> Vec<(Record, Record)> {
+    parallel_merge_join_by(left, right, |r| r.id, |r| r.id)
+}
+
+// Merge joins expect both inputs sorted by their keys
+pub fn sequential_merge_join_by<L, R, K, FL, FR>(
+    left: &[L],
+    right: &[R],
+    left_key: FL,
+    right_key: FR,
+) -> Vec<(L, R)>
+where
+    L: Clone,
+    R: Clone,
+    K: Ord,
+    FL: Fn(&L) -> K,
+    FR: Fn(&R) -> K,
+{
     let mut result = Vec::new();
     let mut i = 0;
     let mut j = 0;
 
     while i < left.len() && j < right.len() {
-        if left[i].id < right[j].id {
-            i += 1;
-        } else if left[i].id > right[j].id {
-            j += 1;
-        } else {
-            let mut k = j;
-            while k < right.len() && right[k].id == left[i].id {
-                result.push((left[i].clone(), right[k].clone()));
-                k += 1;
+        let key = left_key(&left[i]);
+        match key.cmp(&right_key(&right[j])) {
+            KeyOrdering::Less => i += 1,
+            KeyOrdering::Greater => j += 1,
+            KeyOrdering::Equal => {
+                let mut k = j;
+                while k < right.len() && right_key(&right[k]) == key {
+                    result.push((left[i].clone(), right[k].clone()));
+                    k += 1;
+                }
+                i += 1;
             }
-            i += 1;
         }
     }
 
     result
 }
 
-pub fn parallel_merge_join(left: &[Record], right: &[Record]) -> Vec<(Record, Record)> {
-    let chunk_size = (left.len() / rayon::current_num_threads()).max(1);
-    
-    left.par_chunks(chunk_size)
-        .flat_map(|left_chunk| {
-            let mut result = Vec::new();
-            let mut j = 0;
-
-            for l_record in left_chunk {
-                while j < right.len() && right[j].id < l_record.id {
-                    j += 1;
+pub fn parallel_merge_join_by<L, R, K, FL, FR>(
+    left: &[L],
+    right: &[R],
+    left_key: FL,
+    right_key: FR,
+) -> Vec<(L, R)>
+where
+    L: Clone + Send + Sync,
+    R: Clone + Send + Sync,
+    K: Ord,
+    FL: Fn(&L) -> K + Sync + Send,
+    FR: Fn(&R) -> K + Sync + Send,
+{
+    merge_partitions(left, right, &left_key, &right_key)
+        .into_par_iter()
+        .flat_map_iter(|(left_part, right_part)| sequential_merge_join_by(left_part, right_part, &left_key, &right_key))
+        .collect()
+}
+
+pub fn sequential_outer_merge_join(left: &[Record], right: &[Record], join_type: JoinType) -> Vec<OuterPair<Record, Record>> {
+    sequential_outer_merge_join_by(left, right, |r| r.id, |r| r.id, join_type)
+}
+
+pub fn parallel_outer_merge_join(left: &[Record], right: &[Record], join_type: JoinType) -> Vec<OuterPair<Record, Record>> {
+    parallel_outer_merge_join_by(left, right, |r| r.id, |r| r.id, join_type)
+}
+
+// Merge join that also emits the unmatched rows `join_type` keeps, in key order
+pub fn sequential_outer_merge_join_by<L, R, K, FL, FR>(
+    left: &[L],
+    right: &[R],
+    left_key: FL,
+    right_key: FR,
+    join_type: JoinType,
+) -> Vec<OuterPair<L, R>>
+where
+    L: Clone,
+    R: Clone,
+    K: Ord,
+    FL: Fn(&L) -> K,
+    FR: Fn(&R) -> K,
+{
+    let mut result = Vec::new();
+    let mut i = 0;
+    let mut j = 0;
+
+    while i < left.len() && j < right.len() {
+        let key = left_key(&left[i]);
+        match key.cmp(&right_key(&right[j])) {
+            KeyOrdering::Less => {
+                if join_type.keeps_left() {
+                    result.push((Some(left[i].clone()), None));
                 }
-                while j < right.len() && right[j].id == l_record.id {
-                    result.push((l_record.clone(), right[j].clone()));
-                    j += 1;
+                i += 1;
+            }
+            KeyOrdering::Greater => {
+                if join_type.keeps_right() {
+                    result.push((None, Some(right[j].clone())));
                 }
+                j += 1;
             }
+            KeyOrdering::Equal => {
+                // Every record of the left run pairs with every record of the right run
+                let left_end = i + left[i..].partition_point(|l| left_key(l) == key);
+                let right_end = j + right[j..].partition_point(|r| right_key(r) == key);
+                for l_record in &left[i..left_end] {
+                    for r_record in &right[j..right_end] {
+                        result.push((Some(l_record.clone()), Some(r_record.clone())));
+                    }
+                }
+                i = left_end;
+                j = right_end;
+            }
+        }
+    }
+
+    if join_type.keeps_left() {
+        result.extend(left[i..].iter().map(|l_record| (Some(l_record.clone()), None)));
+    }
+    if join_type.keeps_right() {
+        result.extend(right[j..].iter().map(|r_record| (None, Some(r_record.clone()))));
+    }
+    result
+}
 
-            result
+pub fn parallel_outer_merge_join_by<L, R, K, FL, FR>(
+    left: &[L],
+    right: &[R],
+    left_key: FL,
+    right_key: FR,
+    join_type: JoinType,
+) -> Vec<OuterPair<L, R>>
+where
+    L: Clone + Send + Sync,
+    R: Clone + Send + Sync,
+    K: Ord,
+    FL: Fn(&L) -> K + Sync + Send,
+    FR: Fn(&R) -> K + Sync + Send,
+{
+    merge_partitions(left, right, &left_key, &right_key)
+        .into_par_iter()
+        .flat_map_iter(|(left_part, right_part)| {
+            sequential_outer_merge_join_by(left_part, right_part, &left_key, &right_key, join_type)
         })
         .collect()
-}
\ No newline at end of file
+}
+
+// Semi and anti merge joins of inputs sorted by id; every left record is kept at most once
+pub fn sequential_semi_merge_join(left: &[Record], right: &[Record]) -> Vec<Record> {
+    sequential_semi_merge_join_by(left, right, |r| r.id, |r| r.id)
+}
+
+pub fn parallel_semi_merge_join(left: &[Record], right: &[Record]) -> Vec<Record> {
+    parallel_semi_merge_join_by(left, right, |r| r.id, |r| r.id)
+}
+
+pub fn sequential_anti_merge_join(left: &[Record], right: &[Record]) -> Vec<Record> {
+    sequential_anti_merge_join_by(left, right, |r| r.id, |r| r.id)
+}
+
+pub fn parallel_anti_merge_join(left: &[Record], right: &[Record]) -> Vec<Record> {
+    parallel_anti_merge_join_by(left, right, |r| r.id, |r| r.id)
+}
+
+pub fn sequential_semi_merge_join_by<L, R, K, FL, FR>(left: &[L], right: &[R], left_key: FL, right_key: FR) -> Vec<L>
+where
+    L: Clone,
+    K: Ord,
+    FL: Fn(&L) -> K,
+    FR: Fn(&R) -> K,
+{
+    sequential_filter_merge_join(left, right, left_key, right_key, true)
+}
+
+pub fn parallel_semi_merge_join_by<L, R, K, FL, FR>(left: &[L], right: &[R], left_key: FL, right_key: FR) -> Vec<L>
+where
+    L: Clone + Send + Sync,
+    R: Sync,
+    K: Ord,
+    FL: Fn(&L) -> K + Sync + Send,
+    FR: Fn(&R) -> K + Sync + Send,
+{
+    parallel_filter_merge_join(left, right, left_key, right_key, true)
+}
+
+pub fn sequential_anti_merge_join_by<L, R, K, FL, FR>(left: &[L], right: &[R], left_key: FL, right_key: FR) -> Vec<L>
+where
+    L: Clone,
+    K: Ord,
+    FL: Fn(&L) -> K,
+    FR: Fn(&R) -> K,
+{
+    sequential_filter_merge_join(left, right, left_key, right_key, false)
+}
+
+pub fn parallel_anti_merge_join_by<L, R, K, FL, FR>(left: &[L], right: &[R], left_key: FL, right_key: FR) -> Vec<L>
+where
+    L: Clone + Send + Sync,
+    R: Sync,
+    K: Ord,
+    FL: Fn(&L) -> K + Sync + Send,
+    FR: Fn(&R) -> K + Sync + Send,
+{
+    parallel_filter_merge_join(left, right, left_key, right_key, false)
+}
+
+fn sequential_filter_merge_join<L, R, K, FL, FR>(left: &[L], right: &[R], left_key: FL, right_key: FR, matched: bool) -> Vec<L>
+where
+    L: Clone,
+    K: Ord,
+    FL: Fn(&L) -> K,
+    FR: Fn(&R) -> K,
+{
+    let mut result = Vec::new();
+    let mut j = 0;
+
+    for l_record in left {
+        let key = left_key(l_record);
+        while j < right.len() && right_key(&right[j]) < key {
+            j += 1;
+        }
+        // The first equal key decides; `j` stays on it for the next left record of the run
+        let found = j < right.len() && right_key(&right[j]) == key;
+        if found == matched {
+            result.push(l_record.clone());
+        }
+    }
+
+    result
+}
+
+fn parallel_filter_merge_join<L, R, K, FL, FR>(left: &[L], right: &[R], left_key: FL, right_key: FR, matched: bool) -> Vec<L>
+where
+    L: Clone + Send + Sync,
+    R: Sync,
+    K: Ord,
+    FL: Fn(&L) -> K + Sync + Send,
+    FR: Fn(&R) -> K + Sync + Send,
+{
+    merge_partitions(left, right, &left_key, &right_key)
+        .into_par_iter()
+        .flat_map_iter(|(left_part, right_part)| {
+            sequential_filter_merge_join(left_part, right_part, &left_key, &right_key, matched)
+        })
+        .collect()
+}
+
+// Splits two inputs sorted by key into about one pair of parts per thread, such that every key
+// falls into a single pair. Each right record goes to the part that covers its key, including
+// keys before the first or after the last left record, so unmatched rows are not lost.
+fn merge_partitions<'a, L, R, K: Ord>(
+    left: &'a [L],
+    right: &'a [R],
+    left_key: impl Fn(&L) -> K,
+    right_key: impl Fn(&R) -> K,
+) -> Vec<(&'a [L], &'a [R])> {
+    let chunk_size = left.len().div_ceil(rayon::current_num_threads()).max(1);
+
+    // Left parts start at multiples of the chunk size, moved forward past runs of one key
+    let mut starts = vec![0];
+    let mut next = chunk_size;
+    while next < left.len() {
+        let previous = left_key(&left[next - 1]);
+        next += left[next..].partition_point(|l| left_key(l) == previous);
+        if next < left.len() {
+            starts.push(next);
+        }
+        next += chunk_size;
+    }
+
+    let mut parts = Vec::with_capacity(starts.len());
+    let mut right_start = 0;
+    for (k, &start) in starts.iter().enumerate() {
+        let (end, right_end) = match starts.get(k + 1) {
+            Some(&end) => {
+                let key = left_key(&left[end]);
+                (end, right.partition_point(|r| right_key(r) < key))
+            }
+            None => (left.len(), right.len()),
+        };
+        parts.push((&left[start..end], &right[right_start..right_end]));
+        right_start = right_end;
+    }
+    parts
+}
+
+// Joins every pair of rows for which `predicate` holds, for conditions that neither hashing nor
+// sorting can answer. A block of left rows is held against all right rows at a time, so it stays
+// in cache while the right side streams past; the parallel join runs the blocks as tasks. Both
+// emit the same pairs in the same order.
+pub fn adaptive_nested_loop_join_by<L, R, P>(left: &[L], right: &[R], predicate: P) -> (Vec<(L, R)>, ExecutionReport)
+where
+    L: Clone + Send + Sync,
+    R: Clone + Send + Sync,
+    P: Fn(&L, &R) -> bool + Sync + Send,
+{
+    execution::run_adaptive(
+        report(left.len(), right.len()),
+        || sequential_nested_loop_join_by(left, right, &predicate),
+        || parallel_nested_loop_join_by(left, right, &predicate),
+        Vec::len,
+    )
+}
+
+pub fn sequential_nested_loop_join_by<L, R, P>(left: &[L], right: &[R], predicate: P) -> Vec<(L, R)>
+where
+    L: Clone,
+    R: Clone,
+    P: Fn(&L, &R) -> bool,
+{
+    left.chunks(block_rows(right.len()))
+        .flat_map(|block| block_nested_loop(block, right, &predicate))
+        .collect()
+}
+
+pub fn parallel_nested_loop_join_by<L, R, P>(left: &[L], right: &[R], predicate: P) -> Vec<(L, R)>
+where
+    L: Clone + Send + Sync,
+    R: Clone + Send + Sync,
+    P: Fn(&L, &R) -> bool + Sync + Send,
+{
+    left.par_chunks(block_rows(right.len()))
+        .flat_map_iter(|block| block_nested_loop(block, right, &predicate))
+        .collect()
+}
+
+fn block_nested_loop<L: Clone, R: Clone>(block: &[L], right: &[R], predicate: impl Fn(&L, &R) -> bool) -> Vec<(L, R)> {
+    let mut result = Vec::new();
+    for r_record in right {
+        for l_record in block {
+            if predicate(l_record, r_record) {
+                result.push((l_record.clone(), r_record.clone()));
+            }
+        }
+    }
+    result
+}
+
+// Left rows per block, so that a block paired with every right row makes about BLOCK_PAIRS pairs
+fn block_rows(right_rows: usize) -> usize {
+    (BLOCK_PAIRS / right_rows.max(1)).max(1)
+}
+
+// Row numbers of the left and right rows for which `predicate` is true; it is bound over the
+// left columns followed by the right columns. Each block of left rows is paired with every
+// right row in one batch, so the predicate is evaluated a batch at a time. Pairs come in order
+// of left, then right row.
+pub fn adaptive_nested_loop_join_pairs(
+    left: &RecordBatch,
+    right: &RecordBatch,
+    predicate: &ScalarExpr,
+) -> Result<(Vec<(usize, usize)>, ExecutionReport)> {
+    let (pairs, report) = execution::run_adaptive(
+        report(left.num_rows(), right.num_rows()),
+        || sequential_nested_loop_join_pairs(left, right, predicate),
+        || parallel_nested_loop_join_pairs(left, right, predicate),
+        |pairs| pairs.as_ref().map_or(0, Vec::len),
+    );
+    Ok((pairs?, report))
+}
+
+pub fn sequential_nested_loop_join_pairs(left: &RecordBatch, right: &RecordBatch, predicate: &ScalarExpr) -> Result<Vec<(usize, usize)>> {
+    let mut pairs = Vec::new();
+    for block in row_blocks(left.num_rows(), right.num_rows()) {
+        pairs.extend(filter_block(left, right, &block_pairs(block, right.num_rows()), predicate)?);
+    }
+    Ok(pairs)
+}
+
+pub fn parallel_nested_loop_join_pairs(left: &RecordBatch, right: &RecordBatch, predicate: &ScalarExpr) -> Result<Vec<(usize, usize)>> {
+    let blocks: Vec<Vec<(usize, usize)>> = row_blocks(left.num_rows(), right.num_rows())
+        .into_par_iter()
+        .map(|block| filter_block(left, right, &block_pairs(block, right.num_rows()), predicate))
+        .collect::<Result<_>>()?;
+    Ok(blocks.concat())
+}
+
+fn row_blocks(left_rows: usize, right_rows: usize) -> Vec<Range<usize>> {
+    let size = block_rows(right_rows);
+    (0..left_rows).step_by(size).map(|start| start..(start + size).min(left_rows)).collect()
+}
+
+fn block_pairs(block: Range<usize>, right_rows: usize) -> Vec<(usize, usize)> {
+    block.flat_map(|l| (0..right_rows).map(move |r| (l, r))).collect()
+}
+
+// Keeps the pairs of left and right row numbers for which `predicate`, bound over the left
+// columns followed by the right ones, is true; NULL counts as false. Pairs keep their order.
+pub fn adaptive_filter_pairs(
+    left: &RecordBatch,
+    right: &RecordBatch,
+    pairs: &[(usize, usize)],
+    predicate: &ScalarExpr,
+) -> Result<(Vec<(usize, usize)>, ExecutionReport)> {
+    let (pairs, report) = execution::run_adaptive(
+        ExecutionReport::for_input(pairs.len(), thresholds().join).with_chunk_size(BLOCK_PAIRS),
+        || sequential_filter_pairs(left, right, pairs, predicate),
+        || parallel_filter_pairs(left, right, pairs, predicate),
+        |pairs| pairs.as_ref().map_or(0, Vec::len),
+    );
+    Ok((pairs?, report))
+}
+
+pub fn sequential_filter_pairs(
+    left: &RecordBatch,
+    right: &RecordBatch,
+    pairs: &[(usize, usize)],
+    predicate: &ScalarExpr,
+) -> Result<Vec<(usize, usize)>> {
+    let mut kept = Vec::new();
+    for chunk in pairs.chunks(BLOCK_PAIRS) {
+        kept.extend(filter_block(left, right, chunk, predicate)?);
+    }
+    Ok(kept)
+}
+
+pub fn parallel_filter_pairs(
+    left: &RecordBatch,
+    right: &RecordBatch,
+    pairs: &[(usize, usize)],
+    predicate: &ScalarExpr,
+) -> Result<Vec<(usize, usize)>> {
+    let chunks: Vec<Vec<(usize, usize)>> = pairs
+        .par_chunks(BLOCK_PAIRS)
+        .map(|chunk| filter_block(left, right, chunk, predicate))
+        .collect::<Result<_>>()?;
+    Ok(chunks.concat())
+}
+
+fn filter_block(
+    left: &RecordBatch,
+    right: &RecordBatch,
+    pairs: &[(usize, usize)],
+    predicate: &ScalarExpr,
+) -> Result<Vec<(usize, usize)>> {
+    let joined = take_pairs(left, right, pairs);
+    let mask = predicate_mask(&expressions::sequential_evaluate(predicate, &joined)?)?;
+    Ok(pairs.iter().zip(mask).filter(|(_, keep)| *keep).map(|(&pair, _)| pair).collect())
+}
+
+// Joins each right row with the left rows whose key lies in the range the right row gives, as
+// the events that fall into each time window. The left keys are sorted once with a merge sort,
+// after which every right row finds its matches with two binary searches instead of comparing
+// against each left row. Pairs come in right order, each right row's matches in key order.
+pub fn adaptive_band_join_by<L, R, K, FL, FR>(
+    left: &[L],
+    right: &[R],
+    left_key: FL,
+    right_range: FR,
+) -> (Vec<(L, R)>, ExecutionReport)
+where
+    L: Clone + Send + Sync,
+    R: Clone + Send + Sync,
+    K: Ord + Clone + Send + Sync,
+    FL: Fn(&L) -> K + Sync + Send,
+    FR: Fn(&R) -> (Bound<K>, Bound<K>) + Sync + Send,
+{
+    execution::run_adaptive(
+        report(left.len(), right.len()),
+        || sequential_band_join_by(left, right, &left_key, &right_range),
+        || parallel_band_join_by(left, right, &left_key, &right_range),
+        Vec::len,
+    )
+}
+
+pub fn sequential_band_join_by<L, R, K, FL, FR>(left: &[L], right: &[R], left_key: FL, right_range: FR) -> Vec<(L, R)>
+where
+    L: Clone,
+    R: Clone,
+    K: Ord + Clone,
+    FL: Fn(&L) -> K,
+    FR: Fn(&R) -> (Bound<K>, Bound<K>),
+{
+    let keys: Vec<(K, usize)> = left.iter().enumerate().map(|(i, record)| (left_key(record), i)).collect();
+    let sorted = sorting::sequential_merge_sort(&keys);
+
+    let mut result = Vec::new();
+    for r_record in right {
+        for &(_, i) in &sorted[band_range(&sorted, right_range(r_record))] {
+            result.push((left[i].clone(), r_record.clone()));
+        }
+    }
+    result
+}
+
+pub fn parallel_band_join_by<L, R, K, FL, FR>(left: &[L], right: &[R], left_key: FL, right_range: FR) -> Vec<(L, R)>
+where
+    L: Clone + Send + Sync,
+    R: Clone + Send + Sync,
+    K: Ord + Clone + Send + Sync,
+    FL: Fn(&L) -> K + Sync + Send,
+    FR: Fn(&R) -> (Bound<K>, Bound<K>) + Sync + Send,
+{
+    let keys: Vec<(K, usize)> = left.par_iter().enumerate().map(|(i, record)| (left_key(record), i)).collect();
+    let sorted = sorting::parallel_merge_sort(&keys);
+
+    right
+        .par_iter()
+        .flat_map_iter(|r_record| {
+            sorted[band_range(&sorted, right_range(r_record))]
+                .iter()
+                .map(|&(_, i)| (left[i].clone(), r_record.clone()))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+// Positions of the sorted keys within the bounds; empty when the bounds cross
+fn band_range<K: Ord>(sorted: &[(K, usize)], (lower, upper): (Bound<K>, Bound<K>)) -> Range<usize> {
+    let start = match &lower {
+        Bound::Included(bound) => sorted.partition_point(|(key, _)| key < bound),
+        Bound::Excluded(bound) => sorted.partition_point(|(key, _)| key <= bound),
+        Bound::Unbounded => 0,
+    };
+    let end = match &upper {
+        Bound::Included(bound) => sorted.partition_point(|(key, _)| key <= bound),
+        Bound::Excluded(bound) => sorted.partition_point(|(key, _)| key < bound),
+        Bound::Unbounded => sorted.len(),
+    };
+    start..end.max(start)
+}
+
+// One end of the range of a band join: a column of the right batch and whether the bound
+// itself is in the range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BandBound {
+    pub column: usize,
+    pub inclusive: bool,
+}
+
+// Row numbers of the left and right rows where the left `point` column lies between the
+// `lower` and `upper` bound columns of the right row, see `adaptive_band_join_by`. A missing
+// bound leaves that end of the range open. Columns of different numeric types are compared in
+// their common type; a NULL point or bound is in no range.
+pub fn adaptive_band_join_pairs(
+    left: &RecordBatch,
+    right: &RecordBatch,
+    point: usize,
+    lower: Option<BandBound>,
+    upper: Option<BandBound>,
+) -> (Vec<(usize, usize)>, ExecutionReport) {
+    execution::run_adaptive(
+        report(left.num_rows(), right.num_rows()),
+        || sequential_band_join_pairs(left, right, point, lower, upper),
+        || parallel_band_join_pairs(left, right, point, lower, upper),
+        Vec::len,
+    )
+}
+
+pub fn sequential_band_join_pairs(
+    left: &RecordBatch,
+    right: &RecordBatch,
+    point: usize,
+    lower: Option<BandBound>,
+    upper: Option<BandBound>,
+) -> Vec<(usize, usize)> {
+    let band = BandColumns::new(left, right, point, lower, upper);
+    sequential_band_join_by(&band.left_rows, &band.right_rows, |&i| band.point.value(i), |&j| band.range(j))
+}
+
+pub fn parallel_band_join_pairs(
+    left: &RecordBatch,
+    right: &RecordBatch,
+    point: usize,
+    lower: Option<BandBound>,
+    upper: Option<BandBound>,
+) -> Vec<(usize, usize)> {
+    let band = BandColumns::new(left, right, point, lower, upper);
+    parallel_band_join_by(&band.left_rows, &band.right_rows, |&i| band.point.value(i), |&j| band.range(j))
+}
+
+// The point and bound columns of a band join in their common type, and the rows without NULL or
+// NaN in them
+struct BandColumns<'a> {
+    point: Cow<'a, Column>,
+    lower: Option<(Cow<'a, Column>, bool)>,
+    upper: Option<(Cow<'a, Column>, bool)>,
+    left_rows: Vec<usize>,
+    right_rows: Vec<usize>,
+}
+
+impl<'a> BandColumns<'a> {
+    fn new(
+        left: &'a RecordBatch,
+        right: &'a RecordBatch,
+        point: usize,
+        lower: Option<BandBound>,
+        upper: Option<BandBound>,
+    ) -> Self {
+        let bounds: Vec<usize> = lower.iter().chain(&upper).map(|bound| bound.column).collect();
+        let key_type = bounds.iter().try_fold(left.column(point).data_type(), |key_type, &c| {
+            join_key_type(key_type, right.column(c).data_type())
+        });
+        let cast = |column: &'a Column| match key_type {
+            Some(key_type) => cast_key(column, key_type),
+            None => Cow::Borrowed(column),
+        };
+        // Values that cannot be compared are in no range
+        let (left_rows, right_rows) = match key_type {
+            Some(_) => (joinable_rows(left, &[point]), joinable_rows(right, &bounds)),
+            None => (Vec::new(), Vec::new()),
+        };
+        let bound = |bound: BandBound| (cast(right.column(bound.column)), bound.inclusive);
+        BandColumns {
+            point: cast(left.column(point)),
+            lower: lower.map(bound),
+            upper: upper.map(bound),
+            left_rows,
+            right_rows,
+        }
+    }
+
+    fn range(&self, row: usize) -> (Bound<Value>, Bound<Value>) {
+        let bound = |end: &Option<(Cow<Column>, bool)>| match end {
+            Some((column, true)) => Bound::Included(column.value(row)),
+            Some((column, false)) => Bound::Excluded(column.value(row)),
+            None => Bound::Unbounded,
+        };
+        (bound(&self.lower), bound(&self.upper))
+    }
+}
@@ -0,0 +1,632 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use rayon::prelude::*;
+
+use crate::batch::{Column, RecordBatch};
+use crate::calibration::thresholds;
+use crate::catalog::{Field, Schema};
+use crate::csv::widen;
+use crate::error::EngineError;
+use crate::execution::{self, ExecutionReport};
+use crate::expressions::cast_value;
+use crate::types::{DataType, Value};
+
+type Result<T> = std::result::Result<T, EngineError>;
+
+pub(crate) const PARALLELIZATION_THRESHOLD: usize = 1 << 20; // Default bytes of input before parsing is split across threads
+const MAX_DEPTH: usize = 128; // Nesting allowed in a single document
+
+#[derive(Debug, Clone)]
+pub struct JsonOptions {
+    // Records sampled to infer column types when no schema is given
+    pub infer_rows: usize,
+    pub schema: Option<Schema>,
+}
+
+impl Default for JsonOptions {
+    fn default() -> Self {
+        JsonOptions {
+            infer_rows: 1000,
+            schema: None,
+        }
+    }
+}
+
+// Reads a file with one JSON object per line (NDJSON), or a single array of objects.
+//
+// Nested objects are flattened into columns named by their dotted path, so `{"a": {"b": 1}}`
+// has a column `a.b`. Arrays are kept as JSON text. Keys missing from a record are NULL.
+pub fn read_json(path: impl AsRef<Path>, options: &JsonOptions) -> Result<RecordBatch> {
+    let text = fs::read_to_string(path)?;
+    Ok(adaptive_parse_json(&text, options)?.0)
+}
+
+pub fn adaptive_parse_json(text: &str, options: &JsonOptions) -> Result<(RecordBatch, ExecutionReport)> {
+    let report = ExecutionReport::for_input(text.len(), thresholds().json)
+        .with_chunk_size(text.len().div_ceil(rayon::current_num_threads()));
+    let (output, report) = execution::run_adaptive(
+        report,
+        || sequential_parse_json(text, options),
+        || parallel_parse_json(text, options),
+        |output| output.as_ref().map_or(0, RecordBatch::num_rows),
+    );
+    Ok((output?, report))
+}
+
+pub fn sequential_parse_json(text: &str, options: &JsonOptions) -> Result<RecordBatch> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let records = if is_array(text) {
+        parse_array(text)?
+    } else {
+        parse_lines(text, 1)?
+    };
+    let schema = resolve_schema(&records, options);
+    let columns = build_columns(&records, &schema)?;
+    RecordBatch::try_new(schema, columns)
+}
+
+// NDJSON is split into one chunk of lines per thread and every chunk is parsed and converted
+// independently. A top-level array is a single document and is parsed sequentially.
+pub fn parallel_parse_json(text: &str, options: &JsonOptions) -> Result<RecordBatch> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    if is_array(text) {
+        return sequential_parse_json(text, options);
+    }
+    let starts = chunk_starts(text, rayon::current_num_threads());
+    let chunks = starts
+        .par_iter()
+        .enumerate()
+        .map(|(i, &(start, line))| {
+            let end = starts.get(i + 1).map_or(text.len(), |&(end, _)| end);
+            parse_lines(&text[start..end], line)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let schema = resolve_schema(chunks.iter().flatten(), options);
+    let parts = chunks
+        .par_iter()
+        .map(|records| build_columns(records, &schema))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut columns: Vec<Column> = schema.fields.iter().map(|f| Column::empty(f.data_type)).collect();
+    for part in &parts {
+        for (column, part) in columns.iter_mut().zip(part) {
+            column.append(part)?;
+        }
+    }
+    RecordBatch::try_new(schema, columns)
+}
+
+fn is_array(text: &str) -> bool {
+    text.trim_start().starts_with('[')
+}
+
+// Byte offsets and line numbers of roughly equal chunks of the text, each starting a line.
+// JSON strings cannot contain raw newlines, so every newline ends a record.
+fn chunk_starts(text: &str, chunks: usize) -> Vec<(usize, usize)> {
+    let bytes = text.as_bytes();
+    let target = bytes.len().div_ceil(chunks.max(1)).max(1);
+    let mut starts = vec![(0, 1)];
+    let mut line = 1;
+    for (i, &b) in bytes.iter().enumerate() {
+        if b == b'\n' {
+            line += 1;
+            let start = i + 1;
+            if start < bytes.len() && start >= starts.last().unwrap().0 + target {
+                starts.push((start, line));
+            }
+        }
+    }
+    starts
+}
+
+// One record: the leaf values of an object keyed by their dotted path
+struct JsonRecord<'a> {
+    line: usize,
+    fields: Vec<(String, JsonValue<'a>)>,
+}
+
+fn parse_lines(text: &str, first_line: usize) -> Result<Vec<JsonRecord<'_>>> {
+    let mut records = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut parser = JsonParser::new(line, first_line + i);
+        let value = parser.parse_document()?;
+        records.push(JsonRecord::new(value, first_line + i)?);
+    }
+    Ok(records)
+}
+
+fn parse_array(text: &str) -> Result<Vec<JsonRecord<'_>>> {
+    let mut parser = JsonParser::new(text, 1);
+    let mut records = Vec::new();
+    parser.skip_whitespace();
+    parser.expect(b'[')?;
+    parser.skip_whitespace();
+    if parser.peek() == Some(b']') {
+        parser.pos += 1;
+    } else {
+        loop {
+            parser.skip_whitespace();
+            let line = parser.line();
+            let value = parser.parse_value(0)?;
+            records.push(JsonRecord::new(value, line)?);
+            parser.skip_whitespace();
+            match parser.next() {
+                Some(b',') => {}
+                Some(b']') => break,
+                _ => return Err(parser.error("expected ',' or ']' in array")),
+            }
+        }
+    }
+    parser.skip_whitespace();
+    if parser.pos < text.len() {
+        return Err(parser.error("unexpected text after the array"));
+    }
+    Ok(records)
+}
+
+impl<'a> JsonRecord<'a> {
+    fn new(value: JsonValue<'a>, line: usize) -> Result<Self> {
+        let JsonValue::Object(members) = value else {
+            return Err(EngineError::Execution(format!("line {}: expected a JSON object", line)));
+        };
+        let mut fields = Vec::new();
+        flatten("", members, &mut fields);
+        Ok(JsonRecord { line, fields })
+    }
+}
+
+fn flatten<'a>(prefix: &str, members: Vec<(Cow<'a, str>, JsonValue<'a>)>, out: &mut Vec<(String, JsonValue<'a>)>) {
+    for (key, value) in members {
+        let name = if prefix.is_empty() {
+            key.into_owned()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            JsonValue::Object(members) => flatten(&name, members, out),
+            value => out.push((name, value)),
+        }
+    }
+}
+
+fn resolve_schema<'r, 'a: 'r>(records: impl IntoIterator<Item = &'r JsonRecord<'a>>, options: &JsonOptions) -> Schema {
+    match &options.schema {
+        Some(schema) => schema.clone(),
+        None => infer_schema(records.into_iter().take(options.infer_rows)),
+    }
+}
+
+// Columns appear in the order their keys are first seen. Types are widened like CSV columns;
+// columns that are NULL in the whole sample are read as text.
+fn infer_schema<'r, 'a: 'r>(records: impl IntoIterator<Item = &'r JsonRecord<'a>>) -> Schema {
+    let mut names: Vec<&str> = Vec::new();
+    let mut types: Vec<Option<DataType>> = Vec::new();
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    for record in records {
+        for (name, value) in &record.fields {
+            let i = *index.entry(name).or_insert_with(|| {
+                names.push(name);
+                types.push(None);
+                names.len() - 1
+            });
+            if let Some(seen) = value.data_type() {
+                types[i] = Some(types[i].map_or(seen, |current| widen(current, seen)));
+            }
+        }
+    }
+    let fields = names
+        .iter()
+        .zip(types)
+        .map(|(name, data_type)| Field::new(name, data_type.unwrap_or(DataType::Utf8), true))
+        .collect();
+    Schema::new(fields)
+}
+
+fn build_columns(records: &[JsonRecord], schema: &Schema) -> Result<Vec<Column>> {
+    let index: HashMap<&str, usize> = schema
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| (field.name.as_str(), i))
+        .collect();
+    let mut columns: Vec<Column> = schema
+        .fields
+        .iter()
+        .map(|f| Column::with_capacity(f.data_type, records.len()))
+        .collect();
+    let mut row = vec![Value::Null; columns.len()];
+
+    for record in records {
+        // Keys outside the schema are dropped; a repeated key keeps its last value
+        for (name, value) in &record.fields {
+            if let Some(&i) = index.get(name.as_str()) {
+                let field = &schema.fields[i];
+                row[i] = value.to_value(field.data_type).ok_or_else(|| {
+                    EngineError::Execution(format!(
+                        "line {}: cannot read {} as {} for column {}",
+                        record.line,
+                        value.to_json(),
+                        field.data_type,
+                        field.name
+                    ))
+                })?;
+            }
+        }
+        for (column, value) in columns.iter_mut().zip(row.iter_mut()) {
+            column.push(std::mem::replace(value, Value::Null))?;
+        }
+    }
+    Ok(columns)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum JsonValue<'a> {
+    Null,
+    Bool(bool),
+    // Kept as text until the column type is known
+    Number(&'a str),
+    String(Cow<'a, str>),
+    Array(Vec<JsonValue<'a>>),
+    Object(Vec<(Cow<'a, str>, JsonValue<'a>)>),
+}
+
+impl JsonValue<'_> {
+    fn data_type(&self) -> Option<DataType> {
+        match self {
+            JsonValue::Null => None,
+            JsonValue::Bool(_) => Some(DataType::Boolean),
+            JsonValue::Number(text) => Some(match text.parse::<i64>() {
+                Ok(v) if i32::try_from(v).is_ok() => DataType::Int32,
+                Ok(_) => DataType::Int64,
+                Err(_) => DataType::Float64,
+            }),
+            JsonValue::String(_) | JsonValue::Array(_) | JsonValue::Object(_) => Some(DataType::Utf8),
+        }
+    }
+
+    // None when the value cannot be stored in a column of the type
+    fn to_value(&self, data_type: DataType) -> Option<Value> {
+        match (self, data_type) {
+            (JsonValue::Null, _) => Some(Value::Null),
+            (JsonValue::Bool(v), DataType::Boolean) => Some(Value::Boolean(*v)),
+            (JsonValue::String(text), DataType::Utf8) => Some(Value::Utf8(text.to_string())),
+            (JsonValue::String(text), _) => cast_value(&Value::Utf8(text.to_string()), data_type).ok(),
+            (JsonValue::Number(text), DataType::Int32 | DataType::Int64 | DataType::Float64) => {
+                cast_value(&Value::Utf8(text.to_string()), data_type).ok()
+            }
+            (_, DataType::Utf8) => Some(Value::Utf8(self.to_json())),
+            _ => None,
+        }
+    }
+
+    fn to_json(&self) -> String {
+        let mut output = String::new();
+        self.write_json(&mut output);
+        output
+    }
+
+    fn write_json(&self, output: &mut String) {
+        match self {
+            JsonValue::Null => output.push_str("null"),
+            JsonValue::Bool(v) => write!(output, "{}", v).unwrap(),
+            JsonValue::Number(text) => output.push_str(text),
+            JsonValue::String(text) => output.push_str(&json_string(text)),
+            JsonValue::Array(values) => {
+                output.push('[');
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        output.push(',');
+                    }
+                    value.write_json(output);
+                }
+                output.push(']');
+            }
+            JsonValue::Object(members) => {
+                output.push('{');
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        output.push(',');
+                    }
+                    output.push_str(&json_string(key));
+                    output.push(':');
+                    value.write_json(output);
+                }
+                output.push('}');
+            }
+        }
+    }
+}
+
+// Recursive descent parser over one document. Strings without escapes borrow from the input.
+struct JsonParser<'a> {
+    text: &'a str,
+    pos: usize,
+    // Line of `line_pos`, advanced lazily for error messages and record lines
+    line: usize,
+    line_pos: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn new(text: &'a str, first_line: usize) -> Self {
+        JsonParser {
+            text,
+            pos: 0,
+            line: first_line,
+            line_pos: 0,
+        }
+    }
+
+    fn parse_document(&mut self) -> Result<JsonValue<'a>> {
+        self.skip_whitespace();
+        let value = self.parse_value(0)?;
+        self.skip_whitespace();
+        if self.pos < self.text.len() {
+            return Err(self.error("unexpected text after the value"));
+        }
+        Ok(value)
+    }
+
+    fn line(&mut self) -> usize {
+        let end = self.pos.min(self.text.len());
+        if end > self.line_pos {
+            self.line += self.text.as_bytes()[self.line_pos..end].iter().filter(|&&b| b == b'\n').count();
+            self.line_pos = end;
+        }
+        self.line
+    }
+
+    fn error(&mut self, message: &str) -> EngineError {
+        EngineError::Execution(format!("line {}: invalid JSON, {}", self.line(), message))
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let b = self.peek()?;
+        self.pos += 1;
+        Some(b)
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<()> {
+        match self.next() {
+            Some(b) if b == expected => Ok(()),
+            _ => Err(self.error(&format!("expected '{}'", expected as char))),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn parse_value(&mut self, depth: usize) -> Result<JsonValue<'a>> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nested too deeply"));
+        }
+        match self.peek() {
+            Some(b'{') => {
+                self.pos += 1;
+                let mut members = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(b'}') {
+                    self.pos += 1;
+                    return Ok(JsonValue::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    if self.peek() != Some(b'"') {
+                        return Err(self.error("expected a string key"));
+                    }
+                    let key = self.parse_string()?;
+                    self.skip_whitespace();
+                    self.expect(b':')?;
+                    self.skip_whitespace();
+                    members.push((key, self.parse_value(depth + 1)?));
+                    self.skip_whitespace();
+                    match self.next() {
+                        Some(b',') => {}
+                        Some(b'}') => return Ok(JsonValue::Object(members)),
+                        _ => return Err(self.error("expected ',' or '}' in object")),
+                    }
+                }
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                let mut values = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(b']') {
+                    self.pos += 1;
+                    return Ok(JsonValue::Array(values));
+                }
+                loop {
+                    self.skip_whitespace();
+                    values.push(self.parse_value(depth + 1)?);
+                    self.skip_whitespace();
+                    match self.next() {
+                        Some(b',') => {}
+                        Some(b']') => return Ok(JsonValue::Array(values)),
+                        _ => return Err(self.error("expected ',' or ']' in array")),
+                    }
+                }
+            }
+            Some(b'"') => Ok(JsonValue::String(self.parse_string()?)),
+            Some(b'-' | b'0'..=b'9') => self.parse_number(),
+            Some(b't') => self.parse_literal("true", JsonValue::Bool(true)),
+            Some(b'f') => self.parse_literal("false", JsonValue::Bool(false)),
+            Some(b'n') => self.parse_literal("null", JsonValue::Null),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn parse_literal(&mut self, word: &str, value: JsonValue<'a>) -> Result<JsonValue<'a>> {
+        if self.text[self.pos..].starts_with(word) {
+            self.pos += word.len();
+            Ok(value)
+        } else {
+            Err(self.error("unexpected character"))
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<JsonValue<'a>> {
+        let start = self.pos;
+        while matches!(self.peek(), Some(b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E')) {
+            self.pos += 1;
+        }
+        let text = &self.text[start..self.pos];
+        let digits = text.strip_prefix('-').unwrap_or(text);
+        let leading_zero = digits.len() > 1 && digits.starts_with('0') && digits.as_bytes()[1].is_ascii_digit();
+        if !digits.starts_with(|c: char| c.is_ascii_digit()) || leading_zero || text.parse::<f64>().is_err() {
+            return Err(self.error(&format!("bad number {}", text)));
+        }
+        Ok(JsonValue::Number(text))
+    }
+
+    fn parse_string(&mut self) -> Result<Cow<'a, str>> {
+        self.expect(b'"')?;
+        let mut owned: Option<String> = None;
+        let mut run = self.pos;
+        loop {
+            match self.peek() {
+                None => return Err(self.error("unterminated string")),
+                Some(b'"') => {
+                    let text = &self.text[run..self.pos];
+                    self.pos += 1;
+                    return Ok(match owned {
+                        Some(mut value) => {
+                            value.push_str(text);
+                            Cow::Owned(value)
+                        }
+                        None => Cow::Borrowed(text),
+                    });
+                }
+                Some(b'\\') => {
+                    let value = owned.get_or_insert_with(String::new);
+                    value.push_str(&self.text[run..self.pos]);
+                    self.pos += 1;
+                    let escaped = match self.next() {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => self.parse_unicode_escape()?,
+                        _ => return Err(self.error("bad escape in string")),
+                    };
+                    owned.as_mut().unwrap().push(escaped);
+                    run = self.pos;
+                }
+                Some(b) if b < 0x20 => return Err(self.error("control character in string")),
+                Some(_) => self.pos += 1,
+            }
+        }
+    }
+
+    // The four hex digits after `\u`, combining a surrogate pair into one character
+    fn parse_unicode_escape(&mut self) -> Result<char> {
+        let high = self.parse_hex4()?;
+        let code = if (0xD800..0xDC00).contains(&high) {
+            if !self.text[self.pos..].starts_with("\\u") {
+                return Err(self.error("unpaired surrogate in string"));
+            }
+            self.pos += 2;
+            let low = self.parse_hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error("unpaired surrogate in string"));
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| self.error("bad unicode escape in string"))
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32> {
+        let digits = self
+            .text
+            .get(self.pos..self.pos + 4)
+            .filter(|digits| digits.bytes().all(|b| b.is_ascii_hexdigit()));
+        match digits {
+            Some(digits) => {
+                self.pos += 4;
+                Ok(u32::from_str_radix(digits, 16).unwrap())
+            }
+            None => Err(self.error("bad unicode escape in string")),
+        }
+    }
+}
+
+// Result output
+
+// An array with one object per row
+pub fn format_json(batch: &RecordBatch) -> String {
+    let rows = json_rows(batch);
+    if rows.is_empty() {
+        "[]\n".to_string()
+    } else {
+        format!("[\n{}\n]\n", rows.join(",\n"))
+    }
+}
+
+// One object per line, readable by `read_json`
+pub fn format_ndjson(batch: &RecordBatch) -> String {
+    json_rows(batch).iter().map(|row| format!("{}\n", row)).collect()
+}
+
+fn json_rows(batch: &RecordBatch) -> Vec<String> {
+    let names: Vec<String> = batch.schema().fields.iter().map(|f| json_string(&f.name)).collect();
+    batch
+        .rows()
+        .iter()
+        .map(|row| {
+            let members: Vec<String> = names
+                .iter()
+                .zip(row)
+                .map(|(name, value)| format!("{}:{}", name, json_value(value)))
+                .collect();
+            format!("{{{}}}", members.join(","))
+        })
+        .collect()
+}
+
+fn json_value(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Float64(v) if !v.is_finite() => "null".to_string(),
+        Value::Int32(_) | Value::Int64(_) | Value::Float64(_) | Value::Boolean(_) => value.to_string(),
+        Value::Utf8(v) => json_string(v),
+        Value::Date(_) => json_string(&value.to_string()),
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut output = String::with_capacity(text.len() + 2);
+    output.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(output, "\\u{:04x}", c as u32).unwrap(),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
@@ -1,6 +1,20 @@
 pub mod parser;
+pub mod plan;
+pub mod optimizer;
 pub mod scans;
 pub mod aggregations;
 pub mod sorting;
 pub mod joins;
-pub mod group_by;
\ No newline at end of file
+pub mod group_by;
+pub mod types;
+pub mod batch;
+pub mod expressions;
+pub mod catalog;
+pub mod csv;
+pub mod json;
+pub mod parquet;
+pub mod error;
+pub mod execution;
+pub mod calibration;
+pub mod engine;
+pub mod shell;
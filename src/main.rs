@@ -1,2 +1,149 @@
-fn main() {
+use std::fs;
+use std::io::{self, BufRead, IsTerminal};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+use project::calibration::{default_config_path, load_config, recalibrate};
+use project::engine::Engine;
+use project::error::EngineError;
+use project::shell::Shell;
+
+const PROMPT: &str = "sql> ";
+const CONTINUATION_PROMPT: &str = "...> ";
+
+// Usage: `project [SCRIPT]`. With a script file, or with SQL piped into stdin, every statement is
+// run in order and the shell exits with a failure status on the first error.
+// `project --calibrate` measures the parallelization thresholds of this machine and saves them
+// to `~/.project_thresholds`, which every later run loads at startup.
+fn main() -> ExitCode {
+    let config = default_config_path();
+    if let Some(path) = &config {
+        if let Err(e) = load_config(path) {
+            eprintln!("ignoring {}: {}", path.display(), e);
+        }
+    }
+
+    let mut shell = Shell::new(Engine::new());
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.as_slice() {
+        [flag] if flag == "--calibrate" => match config.map(recalibrate) {
+            Some(Ok(thresholds)) => {
+                print!("{}", thresholds);
+                ExitCode::SUCCESS
+            }
+            Some(Err(e)) => {
+                eprintln!("calibration failed: {}", e);
+                ExitCode::FAILURE
+            }
+            None => {
+                eprintln!("HOME is not set, nowhere to save thresholds");
+                ExitCode::FAILURE
+            }
+        },
+        [] if io::stdin().is_terminal() => interactive(&mut shell),
+        [] => run_script(&mut shell, io::stdin().lock()),
+        [path] => match fs::File::open(path) {
+            Ok(file) => run_script(&mut shell, io::BufReader::new(file)),
+            Err(e) => {
+                eprintln!("cannot open {}: {}", path, e);
+                ExitCode::FAILURE
+            }
+        },
+        _ => {
+            eprintln!("usage: project [--calibrate | SCRIPT]");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run_script(shell: &mut Shell, input: impl BufRead) -> ExitCode {
+    for line in input.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("read error: {}", e);
+                return ExitCode::FAILURE;
+            }
+        };
+        if !print_outputs(shell.feed_line(&line)) {
+            return ExitCode::FAILURE;
+        }
+        if shell.should_quit() {
+            return ExitCode::SUCCESS;
+        }
+    }
+    match print_outputs(shell.finish()) {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
+
+fn interactive(shell: &mut Shell) -> ExitCode {
+    let mut editor = match DefaultEditor::new() {
+        Ok(editor) => editor,
+        Err(e) => {
+            eprintln!("cannot start line editor: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let history = history_path();
+    if let Some(path) = &history {
+        // A missing history file just means a first run
+        let _ = editor.load_history(path);
+    }
+    println!("Enter SQL terminated by ';', or .help for commands");
+
+    loop {
+        let prompt = if shell.is_pending() { CONTINUATION_PROMPT } else { PROMPT };
+        match editor.readline(prompt) {
+            Ok(line) => {
+                if !line.trim().is_empty() {
+                    let _ = editor.add_history_entry(line.as_str());
+                }
+                // Errors are reported and the session goes on
+                print_outputs(shell.feed_line(&line));
+                if shell.should_quit() {
+                    break;
+                }
+            }
+            Err(ReadlineError::Interrupted) => shell.cancel(),
+            Err(ReadlineError::Eof) => {
+                print_outputs(shell.finish());
+                break;
+            }
+            Err(e) => {
+                eprintln!("read error: {}", e);
+                break;
+            }
+        }
+    }
+
+    if let Some(path) = &history {
+        if let Err(e) = editor.save_history(path) {
+            eprintln!("cannot save history to {}: {}", path.display(), e);
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+// Prints results to stdout and errors to stderr; false if there was an error
+fn print_outputs(outputs: impl IntoIterator<Item = Result<String, EngineError>>) -> bool {
+    let mut ok = true;
+    for output in outputs {
+        match output {
+            Ok(text) => print!("{}", text),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                ok = false;
+            }
+        }
+    }
+    ok
+}
+
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".project_history"))
 }
@@ -0,0 +1,198 @@
+use std::collections::HashSet;
+
+use sqlparser::ast::Expr;
+use sqlparser::dialect::GenericDialect;
+use sqlparser::tokenizer::{Token, Tokenizer};
+
+use crate::catalog::Catalog;
+use crate::error::EngineError;
+use crate::expressions::ScalarExpr;
+use crate::joins::JoinType;
+use crate::plan::{split_conjunction, table_fields, LogicalPlan, ProjectionItem};
+
+type Result<T> = std::result::Result<T, EngineError>;
+
+// Rewrites a resolved plan so scans read less:
+//
+// - Filter conjuncts above a scan, directly or through joins that never pad its side with
+//   NULLs, are copied into the scan when they only refer to its columns. The filters stay in
+//   place; the scan only uses its copies to skip data. The ON conditions of an outer join
+//   reach the side it pads the same way.
+// - Scans read only the columns whose names appear somewhere in the query. A wildcard
+//   anywhere keeps every column.
+pub fn optimize(plan: &LogicalPlan, catalog: &Catalog) -> Result<LogicalPlan> {
+    let names = referenced_names(plan);
+    push_down(plan, &[], names.as_ref(), catalog)
+}
+
+fn push_down(
+    plan: &LogicalPlan,
+    filters: &[Expr],
+    names: Option<&HashSet<String>>,
+    catalog: &Catalog,
+) -> Result<LogicalPlan> {
+    let input = |input: &LogicalPlan| push_down(input, &[], names, catalog).map(Box::new);
+    Ok(match plan {
+        LogicalPlan::Scan { table, alias, .. } => {
+            let fields = table_fields(catalog, table, alias)?;
+            let filters = filters
+                .iter()
+                .filter(|filter| ScalarExpr::bind(filter, &fields).is_ok())
+                .cloned()
+                .collect();
+            let projection = names.and_then(|names| {
+                let mut projection: Vec<usize> = (0..fields.len())
+                    .filter(|&i| names.contains(&fields[i].name.to_lowercase()))
+                    .collect();
+                // Keep one column so the scan still produces rows, e.g. for COUNT(*)
+                if projection.is_empty() && !fields.is_empty() {
+                    projection.push(0);
+                }
+                (projection.len() < fields.len()).then_some(projection)
+            });
+            LogicalPlan::Scan {
+                table: table.clone(),
+                alias: alias.clone(),
+                projection,
+                filters,
+            }
+        }
+        LogicalPlan::Filter { input, predicate } => {
+            let mut pushed = filters.to_vec();
+            pushed.extend(split_conjunction(predicate).into_iter().cloned());
+            LogicalPlan::Filter {
+                input: Box::new(push_down(input, &pushed, names, catalog)?),
+                predicate: predicate.clone(),
+            }
+        }
+        // Conditions of an inner join filter its output like a WHERE clause does. An outer join
+        // keeps the unmatched rows of its preserved sides, so filters from above only reach
+        // those sides and its own conditions only the sides it pads.
+        LogicalPlan::Join {
+            left,
+            right,
+            join_type,
+            on,
+        } => {
+            let conditions: Vec<Expr> = on.iter().flat_map(split_conjunction).cloned().collect();
+            let (left_filters, right_filters) = match join_type {
+                JoinType::Inner => {
+                    let mut pushed = filters.to_vec();
+                    pushed.extend(conditions);
+                    (pushed.clone(), pushed)
+                }
+                JoinType::Left => (filters.to_vec(), conditions),
+                JoinType::Right => (conditions, filters.to_vec()),
+                JoinType::Full => (Vec::new(), Vec::new()),
+            };
+            LogicalPlan::Join {
+                left: Box::new(push_down(left, &left_filters, names, catalog)?),
+                right: Box::new(push_down(right, &right_filters, names, catalog)?),
+                join_type: *join_type,
+                on: on.clone(),
+            }
+        }
+        // A semi or anti join only removes left rows, so filters from above reach the left
+        // side; its own conditions reach the subquery side
+        LogicalPlan::SemiJoin {
+            left,
+            right,
+            left_keys,
+            right_keys,
+            on,
+            anti,
+            null_aware,
+        } => {
+            let conditions: Vec<Expr> = on.iter().flat_map(split_conjunction).cloned().collect();
+            LogicalPlan::SemiJoin {
+                left: Box::new(push_down(left, filters, names, catalog)?),
+                right: Box::new(push_down(right, &conditions, names, catalog)?),
+                left_keys: left_keys.clone(),
+                right_keys: right_keys.clone(),
+                on: on.clone(),
+                anti: *anti,
+                null_aware: *null_aware,
+            }
+        }
+        LogicalPlan::Projection { input: i, items } => LogicalPlan::Projection {
+            input: input(i)?,
+            items: items.clone(),
+        },
+        LogicalPlan::Aggregate {
+            input: i,
+            group_by,
+            grouping_sets,
+            aggregates,
+        } => LogicalPlan::Aggregate {
+            input: input(i)?,
+            group_by: group_by.clone(),
+            grouping_sets: grouping_sets.clone(),
+            aggregates: aggregates.clone(),
+        },
+        LogicalPlan::Sort { input: i, keys } => LogicalPlan::Sort {
+            input: input(i)?,
+            keys: keys.clone(),
+        },
+        LogicalPlan::Limit {
+            input: i,
+            limit,
+            offset,
+        } => LogicalPlan::Limit {
+            input: input(i)?,
+            limit: *limit,
+            offset: *offset,
+        },
+        LogicalPlan::Distinct { input: i } => LogicalPlan::Distinct { input: input(i)? },
+        LogicalPlan::Union { left, right, all } => LogicalPlan::Union {
+            left: input(left)?,
+            right: input(right)?,
+            all: *all,
+        },
+    })
+}
+
+// Lowercased words of every expression in the plan, a superset of the column names it uses.
+// None when a wildcard needs all columns.
+fn referenced_names(plan: &LogicalPlan) -> Option<HashSet<String>> {
+    let mut exprs: Vec<&Expr> = Vec::new();
+    let mut pending = vec![plan];
+    while let Some(plan) = pending.pop() {
+        match plan {
+            LogicalPlan::Scan { filters, .. } => exprs.extend(filters),
+            LogicalPlan::Filter { predicate, .. } => exprs.push(predicate),
+            LogicalPlan::Projection { items, .. } => {
+                for item in items {
+                    match item {
+                        ProjectionItem::Expr(expr, _) => exprs.push(expr),
+                        ProjectionItem::Wildcard | ProjectionItem::QualifiedWildcard(_) => return None,
+                    }
+                }
+            }
+            LogicalPlan::Join { on, .. } => exprs.extend(on),
+            LogicalPlan::SemiJoin {
+                left_keys,
+                right_keys,
+                on,
+                ..
+            } => exprs.extend(left_keys.iter().chain(right_keys).chain(on)),
+            LogicalPlan::Aggregate {
+                group_by, aggregates, ..
+            } => exprs.extend(group_by.iter().chain(aggregates)),
+            LogicalPlan::Sort { keys, .. } => exprs.extend(keys.iter().map(|key| &key.expr)),
+            LogicalPlan::Limit { .. } | LogicalPlan::Distinct { .. } | LogicalPlan::Union { .. } => {}
+        }
+        pending.extend(plan.inputs());
+    }
+
+    // Identifiers are found in the rendered SQL so no expression kind can be missed
+    let mut names = HashSet::new();
+    for expr in exprs {
+        let tokens = Tokenizer::new(&GenericDialect {}, &expr.to_string()).tokenize().ok()?;
+        for token in tokens {
+            if let Token::Word(word) = token {
+                names.insert(word.value.to_lowercase());
+            }
+        }
+    }
+    Some(names)
+}
@@ -0,0 +1,504 @@
+use std::cmp::Ordering;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use ::parquet::basic::{ConvertedType, LogicalType, Repetition, Type as PhysicalType};
+use ::parquet::column::reader::{ColumnReader, ColumnReaderImpl};
+use ::parquet::data_type::{BoolType, ByteArray, ByteArrayType, DataType as ParquetDataType, DoubleType, Int32Type, Int64Type};
+use ::parquet::errors::ParquetError;
+use ::parquet::file::properties::WriterProperties;
+use ::parquet::file::reader::{FileReader, RowGroupReader};
+use ::parquet::file::serialized_reader::SerializedFileReader;
+use ::parquet::file::statistics::Statistics;
+use ::parquet::file::writer::SerializedFileWriter;
+use ::parquet::schema::types::{ColumnDescriptor, Type as SchemaType};
+use rayon::prelude::*;
+
+use crate::batch::{Bitmap, Column, ColumnData, RecordBatch};
+use crate::calibration::thresholds;
+use crate::catalog::{Field, Schema, TableProvider};
+use crate::error::EngineError;
+use crate::execution::{self, ExecutionReport, ExecutionStrategy};
+use crate::expressions::{compare, BinaryOp, ScalarExpr};
+use crate::types::{DataType, Value};
+
+type Result<T> = std::result::Result<T, EngineError>;
+
+pub(crate) const PARALLELIZATION_THRESHOLD: usize = 100000; // Default rows in the selected row groups before they are decoded in parallel
+
+fn parquet_error(e: ParquetError) -> EngineError {
+    EngineError::Execution(format!("parquet: {}", e))
+}
+
+// Min/max statistics of one column chunk, as values of the column type
+#[derive(Debug, Clone)]
+struct ColumnStatistics {
+    min: Option<Value>,
+    max: Option<Value>,
+    null_count: Option<u64>,
+    num_rows: u64,
+}
+
+// A Parquet file registered as a table. Only the footer is read up front; scans decode the
+// projected columns of the row groups that the filters do not rule out.
+#[derive(Debug)]
+pub struct ParquetTable {
+    path: PathBuf,
+    schema: Schema,
+    row_group_rows: Vec<usize>,
+    // Per row group, per column
+    statistics: Vec<Vec<Option<ColumnStatistics>>>,
+}
+
+impl ParquetTable {
+    pub fn try_new(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let reader = SerializedFileReader::new(File::open(&path)?).map_err(parquet_error)?;
+        let metadata = reader.metadata();
+        let descriptor = metadata.file_metadata().schema_descr();
+
+        let fields = (0..descriptor.num_columns())
+            .map(|i| column_field(&descriptor.column(i)))
+            .collect::<Result<Vec<_>>>()?;
+        // Nested columns would not map one to one onto top-level fields
+        if descriptor.root_schema().get_fields().len() != fields.len() {
+            return Err(EngineError::Unsupported("nested Parquet columns".to_string()));
+        }
+
+        let mut row_group_rows = Vec::new();
+        let mut statistics = Vec::new();
+        for row_group in metadata.row_groups() {
+            let num_rows = row_group.num_rows() as u64;
+            row_group_rows.push(num_rows as usize);
+            statistics.push(
+                fields
+                    .iter()
+                    .enumerate()
+                    .map(|(i, field)| {
+                        row_group
+                            .column(i)
+                            .statistics()
+                            .map(|stats| column_statistics(stats, field.data_type, num_rows))
+                    })
+                    .collect(),
+            );
+        }
+
+        Ok(ParquetTable {
+            path,
+            schema: Schema::new(fields),
+            row_group_rows,
+            statistics,
+        })
+    }
+
+    pub fn num_row_groups(&self) -> usize {
+        self.row_group_rows.len()
+    }
+
+    // Row groups that may hold rows satisfying every filter
+    pub fn prune_row_groups(&self, filters: &[ScalarExpr]) -> Vec<usize> {
+        (0..self.num_row_groups())
+            .filter(|&group| !filters.iter().any(|filter| can_skip(filter, &self.statistics[group])))
+            .collect()
+    }
+
+    // Parallel tasks read one row group each
+    pub fn adaptive_read(&self, projection: &[usize], row_groups: &[usize]) -> Result<(RecordBatch, ExecutionReport)> {
+        self.adaptive_read_with_threshold(projection, row_groups, thresholds().parquet)
+    }
+
+    // Like `adaptive_read`, but parallel above the given number of rows instead of the
+    // configured threshold
+    pub fn adaptive_read_with_threshold(
+        &self,
+        projection: &[usize],
+        row_groups: &[usize],
+        threshold: usize,
+    ) -> Result<(RecordBatch, ExecutionReport)> {
+        let rows: usize = row_groups.iter().map(|&group| self.row_group_rows[group]).sum();
+        let strategy = if row_groups.len() > 1 && rows > threshold {
+            ExecutionStrategy::Parallel
+        } else {
+            ExecutionStrategy::Sequential
+        };
+        let (output, report) = execution::run_adaptive(
+            ExecutionReport::new(strategy, rows, threshold),
+            || self.sequential_read(projection, row_groups),
+            || self.parallel_read(projection, row_groups),
+            |output| output.as_ref().map_or(0, RecordBatch::num_rows),
+        );
+        Ok((output?, report))
+    }
+
+    pub fn sequential_read(&self, projection: &[usize], row_groups: &[usize]) -> Result<RecordBatch> {
+        let reader = self.open()?;
+        let batches = row_groups
+            .iter()
+            .map(|&group| self.read_row_group(&reader, group, projection))
+            .collect::<Result<Vec<_>>>()?;
+        RecordBatch::concat(self.projected_schema(projection), &batches)
+    }
+
+    // Every task opens its own reader: readers over one file handle share its position
+    pub fn parallel_read(&self, projection: &[usize], row_groups: &[usize]) -> Result<RecordBatch> {
+        let batches = row_groups
+            .par_iter()
+            .map(|&group| self.read_row_group(&self.open()?, group, projection))
+            .collect::<Result<Vec<_>>>()?;
+        RecordBatch::concat(self.projected_schema(projection), &batches)
+    }
+
+    fn open(&self) -> Result<SerializedFileReader<File>> {
+        SerializedFileReader::new(File::open(&self.path)?).map_err(parquet_error)
+    }
+
+    fn projected_schema(&self, projection: &[usize]) -> Schema {
+        Schema::new(projection.iter().map(|&i| self.schema.fields[i].clone()).collect())
+    }
+
+    fn read_row_group(&self, reader: &SerializedFileReader<File>, group: usize, projection: &[usize]) -> Result<RecordBatch> {
+        let row_group = reader.get_row_group(group).map_err(parquet_error)?;
+        let num_rows = self.row_group_rows[group];
+        let columns = projection
+            .iter()
+            .map(|&i| read_column(row_group.as_ref(), i, self.schema.fields[i].data_type, num_rows))
+            .collect::<Result<Vec<_>>>()?;
+        RecordBatch::try_new(self.projected_schema(projection), columns)
+    }
+}
+
+impl TableProvider for ParquetTable {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn scan(&self, projection: Option<&[usize]>, filters: &[ScalarExpr]) -> Result<RecordBatch> {
+        let all: Vec<usize> = (0..self.schema.fields.len()).collect();
+        let row_groups = self.prune_row_groups(filters);
+        Ok(self.adaptive_read(projection.unwrap_or(&all), &row_groups)?.0)
+    }
+}
+
+fn column_field(column: &ColumnDescriptor) -> Result<Field> {
+    let unsupported = || {
+        EngineError::Unsupported(format!(
+            "Parquet column {} of type {} ({:?})",
+            column.name(),
+            column.physical_type(),
+            column.logical_type_ref()
+        ))
+    };
+    if column.max_rep_level() > 0 {
+        return Err(unsupported());
+    }
+    let logical = column.logical_type_ref();
+    let data_type = match (column.physical_type(), column.converted_type()) {
+        (PhysicalType::BOOLEAN, _) => DataType::Boolean,
+        (PhysicalType::INT32, ConvertedType::DATE) => DataType::Date,
+        (PhysicalType::INT32, ConvertedType::NONE | ConvertedType::INT_8 | ConvertedType::INT_16 | ConvertedType::INT_32)
+            if logical.is_none_or(|l| matches!(l, LogicalType::Integer(int) if int.is_signed)) =>
+        {
+            DataType::Int32
+        }
+        (PhysicalType::INT64, ConvertedType::NONE | ConvertedType::INT_64)
+            if logical.is_none_or(|l| matches!(l, LogicalType::Integer(int) if int.is_signed)) =>
+        {
+            DataType::Int64
+        }
+        (PhysicalType::FLOAT | PhysicalType::DOUBLE, _) => DataType::Float64,
+        (PhysicalType::BYTE_ARRAY, ConvertedType::NONE | ConvertedType::UTF8 | ConvertedType::ENUM | ConvertedType::JSON) => {
+            DataType::Utf8
+        }
+        _ => return Err(unsupported()),
+    };
+    let nullable = column.self_type().get_basic_info().repetition() == Repetition::OPTIONAL;
+    Ok(Field::new(column.name(), data_type, nullable))
+}
+
+fn column_statistics(stats: &Statistics, data_type: DataType, num_rows: u64) -> ColumnStatistics {
+    let (min, max) = match stats {
+        Statistics::Boolean(s) => (s.min_opt().map(|v| Value::Boolean(*v)), s.max_opt().map(|v| Value::Boolean(*v))),
+        Statistics::Int32(s) if data_type == DataType::Date => {
+            (s.min_opt().map(|v| Value::Date(*v)), s.max_opt().map(|v| Value::Date(*v)))
+        }
+        Statistics::Int32(s) => (s.min_opt().map(|v| Value::Int32(*v)), s.max_opt().map(|v| Value::Int32(*v))),
+        Statistics::Int64(s) => (s.min_opt().map(|v| Value::Int64(*v)), s.max_opt().map(|v| Value::Int64(*v))),
+        // NaN is left out of float statistics but sorts above every number here, so the max
+        // is not an upper bound
+        Statistics::Float(s) => (s.min_opt().map(|v| Value::Float64(*v as f64)), None),
+        Statistics::Double(s) => (s.min_opt().map(|v| Value::Float64(*v)), None),
+        Statistics::ByteArray(s) => {
+            let text = |v: &ByteArray| std::str::from_utf8(v.data()).ok().map(|s| Value::Utf8(s.to_string()));
+            (s.min_opt().and_then(text), s.max_opt().and_then(text))
+        }
+        _ => (None, None),
+    };
+    ColumnStatistics {
+        min,
+        max,
+        null_count: stats.null_count_opt(),
+        num_rows,
+    }
+}
+
+// Whether the statistics prove that no row of a row group satisfies the predicate. Anything
+// not understood here keeps the row group.
+fn can_skip(expr: &ScalarExpr, statistics: &[Option<ColumnStatistics>]) -> bool {
+    let column = |expr: &ScalarExpr| match expr {
+        ScalarExpr::Column { index, .. } => statistics[*index].as_ref(),
+        _ => None,
+    };
+    match expr {
+        ScalarExpr::Binary {
+            left,
+            op: BinaryOp::And,
+            right,
+            ..
+        } => can_skip(left, statistics) || can_skip(right, statistics),
+        ScalarExpr::Binary {
+            left,
+            op: BinaryOp::Or,
+            right,
+            ..
+        } => can_skip(left, statistics) && can_skip(right, statistics),
+        ScalarExpr::Binary { left, op, right, .. } => match (left.as_ref(), right.as_ref()) {
+            (_, ScalarExpr::Literal(value)) => column(left).is_some_and(|s| skip_comparison(s, *op, value)),
+            (ScalarExpr::Literal(value), _) => column(right).is_some_and(|s| skip_comparison(s, flip(*op), value)),
+            _ => false,
+        },
+        ScalarExpr::Between {
+            expr,
+            low,
+            high,
+            negated: false,
+        } => match (column(expr), low.as_ref(), high.as_ref()) {
+            (Some(s), ScalarExpr::Literal(low), ScalarExpr::Literal(high)) => {
+                skip_comparison(s, BinaryOp::GtEq, low) || skip_comparison(s, BinaryOp::LtEq, high)
+            }
+            _ => false,
+        },
+        ScalarExpr::InList {
+            expr,
+            list,
+            negated: false,
+        } => column(expr).is_some_and(|s| {
+            list.iter().all(|item| match item {
+                ScalarExpr::Literal(value) => skip_comparison(s, BinaryOp::Eq, value),
+                _ => false,
+            })
+        }),
+        ScalarExpr::IsNull { expr, negated } => column(expr).is_some_and(|s| match negated {
+            false => s.null_count == Some(0),
+            true => s.null_count == Some(s.num_rows),
+        }),
+        _ => false,
+    }
+}
+
+// `column op value` for every row in the statistics' range
+fn skip_comparison(statistics: &ColumnStatistics, op: BinaryOp, value: &Value) -> bool {
+    // Comparisons with NULL, or on a column that is all NULL, are never true
+    if value.is_null() || statistics.null_count == Some(statistics.num_rows) {
+        return true;
+    }
+    let versus = |bound: &Option<Value>| bound.as_ref().and_then(|bound| compare(bound, value));
+    let (min, max) = (versus(&statistics.min), versus(&statistics.max));
+    match op {
+        BinaryOp::Eq => min == Some(Ordering::Greater) || max == Some(Ordering::Less),
+        BinaryOp::NotEq => min == Some(Ordering::Equal) && max == Some(Ordering::Equal),
+        BinaryOp::Lt => matches!(min, Some(Ordering::Greater | Ordering::Equal)),
+        BinaryOp::LtEq => min == Some(Ordering::Greater),
+        BinaryOp::Gt => matches!(max, Some(Ordering::Less | Ordering::Equal)),
+        BinaryOp::GtEq => max == Some(Ordering::Less),
+        _ => false,
+    }
+}
+
+// The same comparison with the operands swapped
+fn flip(op: BinaryOp) -> BinaryOp {
+    match op {
+        BinaryOp::Lt => BinaryOp::Gt,
+        BinaryOp::LtEq => BinaryOp::GtEq,
+        BinaryOp::Gt => BinaryOp::Lt,
+        BinaryOp::GtEq => BinaryOp::LtEq,
+        op => op,
+    }
+}
+
+// Decoding
+
+fn read_column(row_group: &dyn RowGroupReader, i: usize, data_type: DataType, num_rows: usize) -> Result<Column> {
+    let (data, validity) = match row_group.get_column_reader(i).map_err(parquet_error)? {
+        ColumnReader::BoolColumnReader(mut reader) => {
+            let (values, validity) = read_values(&mut reader, num_rows)?;
+            (ColumnData::Boolean(spread(values, validity.as_ref())), validity)
+        }
+        ColumnReader::Int32ColumnReader(mut reader) => {
+            let (values, validity) = read_values(&mut reader, num_rows)?;
+            let values = spread(values, validity.as_ref());
+            match data_type {
+                DataType::Date => (ColumnData::Date(values), validity),
+                _ => (ColumnData::Int32(values), validity),
+            }
+        }
+        ColumnReader::Int64ColumnReader(mut reader) => {
+            let (values, validity) = read_values(&mut reader, num_rows)?;
+            (ColumnData::Int64(spread(values, validity.as_ref())), validity)
+        }
+        ColumnReader::FloatColumnReader(mut reader) => {
+            let (values, validity) = read_values(&mut reader, num_rows)?;
+            let values = values.into_iter().map(f64::from).collect();
+            (ColumnData::Float64(spread(values, validity.as_ref())), validity)
+        }
+        ColumnReader::DoubleColumnReader(mut reader) => {
+            let (values, validity) = read_values(&mut reader, num_rows)?;
+            (ColumnData::Float64(spread(values, validity.as_ref())), validity)
+        }
+        ColumnReader::ByteArrayColumnReader(mut reader) => {
+            let (values, validity) = read_values(&mut reader, num_rows)?;
+            let values = values
+                .into_iter()
+                .map(|v| {
+                    String::from_utf8(v.data().to_vec())
+                        .map_err(|_| EngineError::Execution("parquet: invalid UTF-8 in string column".to_string()))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            (ColumnData::Utf8(spread(values, validity.as_ref())), validity)
+        }
+        _ => return Err(EngineError::Unsupported(format!("Parquet column {}", i))),
+    };
+    Ok(match validity {
+        Some(validity) => Column::with_validity(data, validity),
+        None => data.into(),
+    })
+}
+
+// The non-NULL values of a column chunk, and for optional columns which rows they belong to
+fn read_values<T: ParquetDataType>(reader: &mut ColumnReaderImpl<T>, num_rows: usize) -> Result<(Vec<T::T>, Option<Bitmap>)> {
+    let mut levels = Vec::with_capacity(num_rows);
+    let mut values = Vec::with_capacity(num_rows);
+    let mut read = 0;
+    while read < num_rows {
+        let (records, _, _) = reader
+            .read_records(num_rows - read, Some(&mut levels), None, &mut values)
+            .map_err(parquet_error)?;
+        if records == 0 {
+            return Err(EngineError::Execution(format!(
+                "parquet: column chunk ended after {} of {} rows",
+                read, num_rows
+            )));
+        }
+        read += records;
+    }
+    // Required columns have no definition levels
+    let validity = (!levels.is_empty()).then(|| levels.iter().map(|&level| level > 0).collect());
+    Ok((values, validity))
+}
+
+// Places the values at the valid rows, with defaults at the NULL rows
+fn spread<T: Default>(values: Vec<T>, validity: Option<&Bitmap>) -> Vec<T> {
+    match validity {
+        None => values,
+        Some(validity) => {
+            let mut values = values.into_iter();
+            (0..validity.len())
+                .map(|row| match validity.get(row) {
+                    true => values.next().unwrap_or_default(),
+                    false => T::default(),
+                })
+                .collect()
+        }
+    }
+}
+
+// Writing
+
+// Writes a batch as a Parquet file with row groups of at most `row_group_rows` rows
+pub fn write_parquet(path: impl AsRef<Path>, batch: &RecordBatch, row_group_rows: usize) -> Result<()> {
+    let fields = batch
+        .schema()
+        .fields
+        .iter()
+        .map(|field| parquet_type(field).map(Arc::new))
+        .collect::<Result<Vec<_>>>()?;
+    let schema = SchemaType::group_type_builder("schema")
+        .with_fields(fields)
+        .build()
+        .map_err(parquet_error)?;
+    let properties = Arc::new(WriterProperties::builder().build());
+    let mut writer = SerializedFileWriter::new(File::create(path)?, Arc::new(schema), properties).map_err(parquet_error)?;
+
+    let row_group_rows = row_group_rows.max(1);
+    for offset in (0..batch.num_rows()).step_by(row_group_rows) {
+        let chunk = batch.slice(offset, row_group_rows);
+        let mut row_group = writer.next_row_group().map_err(parquet_error)?;
+        for column in chunk.columns() {
+            let mut column_writer = row_group
+                .next_column()
+                .map_err(parquet_error)?
+                .expect("a column writer for every field");
+            write_column(&mut column_writer, column)?;
+            column_writer.close().map_err(parquet_error)?;
+        }
+        row_group.close().map_err(parquet_error)?;
+    }
+    writer.close().map_err(parquet_error)?;
+    Ok(())
+}
+
+fn parquet_type(field: &Field) -> Result<SchemaType> {
+    let (physical, logical) = match field.data_type {
+        DataType::Int32 => (PhysicalType::INT32, None),
+        DataType::Int64 => (PhysicalType::INT64, None),
+        DataType::Float64 => (PhysicalType::DOUBLE, None),
+        DataType::Utf8 => (PhysicalType::BYTE_ARRAY, Some(LogicalType::String)),
+        DataType::Boolean => (PhysicalType::BOOLEAN, None),
+        DataType::Date => (PhysicalType::INT32, Some(LogicalType::Date)),
+        DataType::Null => return Err(EngineError::Unsupported(format!("Parquet column {} of type Null", field.name))),
+    };
+    let repetition = if field.nullable {
+        Repetition::OPTIONAL
+    } else {
+        Repetition::REQUIRED
+    };
+    SchemaType::primitive_type_builder(&field.name, physical)
+        .with_repetition(repetition)
+        .with_logical_type(logical)
+        .build()
+        .map_err(parquet_error)
+}
+
+fn write_column(writer: &mut ::parquet::file::writer::SerializedColumnWriter<'_>, column: &Column) -> Result<()> {
+    let levels: Option<Vec<i16>> = column
+        .validity()
+        .map(|validity| (0..column.len()).map(|i| validity.get(i) as i16).collect());
+    let valid = |i: &usize| !column.is_null(*i);
+    let rows = 0..column.len();
+    let written = match column.data() {
+        ColumnData::Int32(values) | ColumnData::Date(values) => {
+            let values: Vec<i32> = rows.filter(valid).map(|i| values[i]).collect();
+            writer.typed::<Int32Type>().write_batch(&values, levels.as_deref(), None)
+        }
+        ColumnData::Int64(values) => {
+            let values: Vec<i64> = rows.filter(valid).map(|i| values[i]).collect();
+            writer.typed::<Int64Type>().write_batch(&values, levels.as_deref(), None)
+        }
+        ColumnData::Float64(values) => {
+            let values: Vec<f64> = rows.filter(valid).map(|i| values[i]).collect();
+            writer.typed::<DoubleType>().write_batch(&values, levels.as_deref(), None)
+        }
+        ColumnData::Utf8(values) => {
+            let values: Vec<ByteArray> = rows.filter(valid).map(|i| ByteArray::from(values[i].as_str())).collect();
+            writer.typed::<ByteArrayType>().write_batch(&values, levels.as_deref(), None)
+        }
+        ColumnData::Boolean(values) => {
+            let values: Vec<bool> = rows.filter(valid).map(|i| values[i]).collect();
+            writer.typed::<BoolType>().write_batch(&values, levels.as_deref(), None)
+        }
+        ColumnData::Null(_) => return Err(EngineError::Unsupported("Parquet column of type Null".to_string())),
+    };
+    written.map_err(parquet_error)?;
+    Ok(())
+}
@@ -1,8 +1,36 @@
 use sqlparser::dialect::GenericDialect;
-use sqlparser::parser::Parser;
+use sqlparser::parser::{Parser, ParserError};
+use sqlparser::tokenizer::{Token, Tokenizer};
 
 pub fn parse_sql(query: &str) -> Result<sqlparser::ast::Statement, sqlparser::parser::ParserError> {
     let dialect = GenericDialect {};
     let ast = Parser::parse_sql(&dialect, query)?;
     Ok(ast[0].clone())
-}
\ No newline at end of file
+}
+
+pub fn parse_expr(expr: &str) -> Result<sqlparser::ast::Expr, sqlparser::parser::ParserError> {
+    let dialect = GenericDialect {};
+    Parser::new(&dialect).try_with_sql(expr)?.parse_expr()
+}
+
+// `CREATE TABLE name FROM 'path'`, which sqlparser does not know. Returns the table name and
+// path, or None for any other statement.
+pub fn parse_create_table_from(sql: &str) -> Result<Option<(String, String)>, ParserError> {
+    let dialect = GenericDialect {};
+    let tokens = Tokenizer::new(&dialect, sql).tokenize()?;
+    let tokens: Vec<&Token> = tokens
+        .iter()
+        .filter(|token| !matches!(token, Token::Whitespace(_) | Token::SemiColon))
+        .collect();
+    let keyword = |token: &Token, expected: &str| {
+        matches!(token, Token::Word(w) if w.quote_style.is_none() && w.value.eq_ignore_ascii_case(expected))
+    };
+    match tokens.as_slice() {
+        [create, table, Token::Word(name), from, Token::SingleQuotedString(path)]
+            if keyword(create, "CREATE") && keyword(table, "TABLE") && keyword(from, "FROM") =>
+        {
+            Ok(Some((name.value.clone(), path.clone())))
+        }
+        _ => Ok(None),
+    }
+}
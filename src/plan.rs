@@ -0,0 +1,1399 @@
+use std::fmt;
+
+use sqlparser::ast::{
+    BinaryOperator, Distinct, DuplicateTreatment, Expr, Function, FunctionArg,
+    FunctionArgExpr, FunctionArguments, GroupByExpr, GroupByWithModifier, JoinConstraint, JoinOperator,
+    OrderByExpr, Query, Select, SelectItem, SetExpr, SetOperator, SetQuantifier, TableFactor, TableWithJoins,
+    UnaryOperator, Value as SqlValue,
+};
+
+use crate::aggregations::{AggregateFunction, DEFAULT_PRECISION, MAX_PRECISION, MIN_PRECISION};
+use crate::catalog::{Catalog, Field};
+use crate::error::EngineError;
+use crate::joins::{join_key_type, JoinType};
+use crate::expressions::ScalarExpr;
+use crate::types::{DataType, Value};
+
+type Result<T> = std::result::Result<T, EngineError>;
+
+// Operator tree between the sqlparser AST and execution.
+//
+// Expressions stay as sqlparser `Expr` nodes; column names are resolved when the plan is
+// executed against concrete inputs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogicalPlan {
+    // `projection` limits the columns read, `filters` are WHERE conjuncts the table may use to
+    // skip data. Both are filled in by the optimizer.
+    Scan {
+        table: String,
+        alias: String,
+        projection: Option<Vec<usize>>,
+        filters: Vec<Expr>,
+    },
+    Filter {
+        input: Box<LogicalPlan>,
+        predicate: Expr,
+    },
+    Projection {
+        input: Box<LogicalPlan>,
+        items: Vec<ProjectionItem>,
+    },
+    // `on: None` is a cross join
+    Join {
+        left: Box<LogicalPlan>,
+        right: Box<LogicalPlan>,
+        join_type: JoinType,
+        on: Option<Expr>,
+    },
+    // Keeps each row of `left` once if some row of `right` has equal keys and satisfies `on`,
+    // or with `anti` if none does, as IN and EXISTS subqueries do. `left_keys` are bound
+    // against `left` and `right_keys` against `right`; `on` may refer to both, and its names
+    // resolve against `right` first. `null_aware` gives an anti join the NULL semantics of
+    // NOT IN.
+    SemiJoin {
+        left: Box<LogicalPlan>,
+        right: Box<LogicalPlan>,
+        left_keys: Vec<Expr>,
+        right_keys: Vec<Expr>,
+        on: Option<Expr>,
+        anti: bool,
+        null_aware: bool,
+    },
+    // With `grouping_sets` the input is grouped by each set of positions into `group_by`
+    // instead of by all of the keys
+    Aggregate {
+        input: Box<LogicalPlan>,
+        group_by: Vec<Expr>,
+        grouping_sets: Option<Vec<Vec<usize>>>,
+        aggregates: Vec<Expr>,
+    },
+    Sort {
+        input: Box<LogicalPlan>,
+        keys: Vec<SortKey>,
+    },
+    Limit {
+        input: Box<LogicalPlan>,
+        limit: Option<usize>,
+        offset: usize,
+    },
+    Distinct {
+        input: Box<LogicalPlan>,
+    },
+    Union {
+        left: Box<LogicalPlan>,
+        right: Box<LogicalPlan>,
+        all: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProjectionItem {
+    // Expression and its output column name
+    Expr(Box<Expr>, String),
+    Wildcard,
+    QualifiedWildcard(String),
+}
+
+impl ProjectionItem {
+    pub fn expr(expr: Expr) -> Self {
+        let name = output_name(&expr);
+        ProjectionItem::Expr(Box::new(expr), name)
+    }
+
+    pub fn aliased(expr: Expr, alias: &str) -> Self {
+        ProjectionItem::Expr(Box::new(expr), alias.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKey {
+    pub expr: Expr,
+    pub descending: bool,
+    pub nulls_first: bool,
+}
+
+impl SortKey {
+    // NULLs sort as if larger than every value unless NULLS FIRST/LAST says otherwise, so they
+    // come last in ascending and first in descending order
+    pub fn asc(expr: Expr) -> Self {
+        SortKey {
+            expr,
+            descending: false,
+            nulls_first: false,
+        }
+    }
+
+    pub fn desc(expr: Expr) -> Self {
+        SortKey {
+            expr,
+            descending: true,
+            nulls_first: true,
+        }
+    }
+
+    pub fn with_nulls_first(self, nulls_first: bool) -> Self {
+        SortKey { nulls_first, ..self }
+    }
+
+    fn from_order(expr: Expr, order: &OrderByExpr) -> Self {
+        let key = if order.asc == Some(false) {
+            SortKey::desc(expr)
+        } else {
+            SortKey::asc(expr)
+        };
+        match order.nulls_first {
+            Some(nulls_first) => key.with_nulls_first(nulls_first),
+            None => key,
+        }
+    }
+}
+
+// A column in the output of a plan node
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanField {
+    pub relation: Option<String>,
+    pub name: String,
+    pub data_type: DataType,
+    pub nullable: bool,
+    // Set for GROUP BY expressions and aggregates, which later nodes match structurally
+    pub expr: Option<Box<Expr>>,
+}
+
+impl PlanField {
+    fn column(relation: Option<&str>, field: &Field) -> Self {
+        PlanField {
+            relation: relation.map(str::to_string),
+            name: field.name.clone(),
+            data_type: field.data_type,
+            nullable: field.nullable,
+            expr: None,
+        }
+    }
+
+    fn derived(expr: &Expr, data_type: DataType, nullable: bool) -> Self {
+        PlanField {
+            relation: None,
+            name: output_name(expr),
+            data_type,
+            nullable,
+            expr: Some(Box::new(expr.clone())),
+        }
+    }
+}
+
+impl LogicalPlan {
+    // Resolves every table and column the plan refers to against the catalog and returns the
+    // output columns of this node
+    pub fn schema(&self, catalog: &Catalog) -> Result<Vec<PlanField>> {
+        match self {
+            LogicalPlan::Scan {
+                table,
+                alias,
+                projection,
+                ..
+            } => {
+                let fields = table_fields(catalog, table, alias)?;
+                match projection {
+                    Some(projection) => Ok(projection.iter().map(|&i| fields[i].clone()).collect()),
+                    None => Ok(fields),
+                }
+            }
+            LogicalPlan::Filter { input, predicate } => {
+                let fields = input.schema(catalog)?;
+                expect_boolean(predicate, &fields, "WHERE")?;
+                Ok(fields)
+            }
+            LogicalPlan::Projection { input, items } => {
+                let fields = input.schema(catalog)?;
+                let mut output = Vec::new();
+                for item in items {
+                    match item {
+                        ProjectionItem::Expr(expr, name) => {
+                            let (data_type, nullable) = expr_type(expr, &fields)?;
+                            output.push(PlanField {
+                                relation: None,
+                                name: name.clone(),
+                                data_type,
+                                nullable,
+                                expr: None,
+                            });
+                        }
+                        ProjectionItem::Wildcard => {
+                            output.extend(fields.iter().filter(|f| f.expr.is_none()).cloned())
+                        }
+                        ProjectionItem::QualifiedWildcard(qualifier) => {
+                            let matching: Vec<PlanField> = fields
+                                .iter()
+                                .filter(|f| f.expr.is_none() && has_qualifier(f, qualifier))
+                                .cloned()
+                                .collect();
+                            if matching.is_empty() {
+                                return Err(EngineError::TableNotFound(qualifier.clone()));
+                            }
+                            output.extend(matching);
+                        }
+                    }
+                }
+                Ok(output)
+            }
+            LogicalPlan::Join {
+                left,
+                right,
+                join_type,
+                on,
+            } => {
+                // The columns of the side an outer join pads are NULL in its unmatched rows
+                let nullable = |mut fields: Vec<PlanField>, padded: bool| {
+                    fields.iter_mut().for_each(|field| field.nullable |= padded);
+                    fields
+                };
+                let mut fields = nullable(left.schema(catalog)?, join_type.keeps_right());
+                fields.extend(nullable(right.schema(catalog)?, join_type.keeps_left()));
+                if let Some(on) = on {
+                    expect_boolean(on, &fields, "JOIN ON")?;
+                }
+                Ok(fields)
+            }
+            LogicalPlan::SemiJoin {
+                left,
+                right,
+                left_keys,
+                right_keys,
+                ..
+            } => {
+                let fields = left.schema(catalog)?;
+                let right_fields = right.schema(catalog)?;
+                if left_keys.len() != right_keys.len() {
+                    return Err(EngineError::Plan(format!(
+                        "{} expressions are compared with {} subquery columns",
+                        left_keys.len(),
+                        right_keys.len()
+                    )));
+                }
+                for (left_key, right_key) in left_keys.iter().zip(right_keys) {
+                    let (left_type, _) = expr_type(left_key, &fields)?;
+                    let (right_type, _) = expr_type(right_key, &right_fields)?;
+                    if join_key_type(left_type, right_type).is_none() {
+                        return Err(EngineError::Plan(format!(
+                            "cannot compare {} and {} in IN subquery",
+                            left_type, right_type
+                        )));
+                    }
+                }
+                Ok(fields)
+            }
+            LogicalPlan::Aggregate {
+                input,
+                group_by,
+                grouping_sets,
+                aggregates,
+            } => {
+                let fields = input.schema(catalog)?;
+                let mut output = Vec::new();
+                for (i, expr) in group_by.iter().enumerate() {
+                    // Plain column keys keep their column identity so both `city` and `u.city`
+                    // still resolve above the aggregate
+                    let mut field = match field_index(&fields, expr) {
+                        Some(Ok(i)) if fields[i].expr.is_none() => fields[i].clone(),
+                        _ => {
+                            let (data_type, nullable) = expr_type(expr, &fields)?;
+                            PlanField::derived(expr, data_type, nullable)
+                        }
+                    };
+                    // A key is NULL in the rows of the grouping sets that leave it out
+                    if let Some(sets) = grouping_sets {
+                        field.nullable |= sets.iter().any(|set| !set.contains(&i));
+                    }
+                    output.push(field);
+                }
+                for expr in aggregates {
+                    if let Some(args) = parse_grouping(expr) {
+                        let positions = grouping_positions(&args?, group_by, &fields)?;
+                        if positions.len() >= i32::BITS as usize {
+                            return Err(EngineError::Plan(format!("{} takes at most 31 arguments", expr)));
+                        }
+                        output.push(PlanField::derived(expr, DataType::Int32, false));
+                        continue;
+                    }
+                    let call = parse_aggregate(expr)
+                        .ok_or_else(|| EngineError::Plan(format!("{} is not an aggregate", expr)))??;
+                    let arg_types = call
+                        .args
+                        .iter()
+                        .map(|arg| expr_type(arg, &fields).map(|(data_type, _)| data_type))
+                        .collect::<Result<Vec<_>>>()?;
+                    output.push(PlanField::derived(expr, call.output_type(&arg_types)?, call.func.nullable()));
+                }
+                Ok(output)
+            }
+            LogicalPlan::Sort { input, keys } => {
+                let fields = input.schema(catalog)?;
+                for key in keys {
+                    expr_type(&key.expr, &fields)?;
+                }
+                Ok(fields)
+            }
+            LogicalPlan::Limit { input, .. } | LogicalPlan::Distinct { input } => input.schema(catalog),
+            LogicalPlan::Union { left, right, .. } => {
+                let left = left.schema(catalog)?;
+                let right = right.schema(catalog)?;
+                if left.len() != right.len() {
+                    return Err(EngineError::Plan(format!(
+                        "UNION inputs have {} and {} columns",
+                        left.len(),
+                        right.len()
+                    )));
+                }
+                left.into_iter()
+                    .zip(right)
+                    .map(|(l, r)| {
+                        if l.data_type != r.data_type {
+                            return Err(EngineError::Plan(format!(
+                                "UNION column {} is {} on the left and {} on the right",
+                                l.name, l.data_type, r.data_type
+                            )));
+                        }
+                        Ok(PlanField {
+                            nullable: l.nullable || r.nullable,
+                            ..l
+                        })
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    pub fn inputs(&self) -> Vec<&LogicalPlan> {
+        match self {
+            LogicalPlan::Scan { .. } => vec![],
+            LogicalPlan::Filter { input, .. }
+            | LogicalPlan::Projection { input, .. }
+            | LogicalPlan::Aggregate { input, .. }
+            | LogicalPlan::Sort { input, .. }
+            | LogicalPlan::Limit { input, .. }
+            | LogicalPlan::Distinct { input } => vec![input],
+            LogicalPlan::Join { left, right, .. }
+            | LogicalPlan::SemiJoin { left, right, .. }
+            | LogicalPlan::Union { left, right, .. } => vec![left, right],
+        }
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        write!(f, "{:indent$}", "", indent = depth * 2)?;
+        match self {
+            LogicalPlan::Scan {
+                table,
+                alias,
+                projection,
+                filters,
+            } => {
+                write!(f, "Scan: {}", table)?;
+                if table != alias {
+                    write!(f, " AS {}", alias)?;
+                }
+                if let Some(projection) = projection {
+                    let indices: Vec<String> = projection.iter().map(|i| i.to_string()).collect();
+                    write!(f, " projection=[{}]", indices.join(", "))?;
+                }
+                if !filters.is_empty() {
+                    write!(f, " filters=[{}]", join_exprs(filters))?;
+                }
+                writeln!(f)?
+            }
+            LogicalPlan::Filter { predicate, .. } => writeln!(f, "Filter: {}", predicate)?,
+            LogicalPlan::Projection { items, .. } => {
+                let items: Vec<String> = items
+                    .iter()
+                    .map(|item| match item {
+                        ProjectionItem::Expr(expr, name) if output_name(expr) == *name => expr.to_string(),
+                        ProjectionItem::Expr(expr, name) => format!("{} AS {}", expr, name),
+                        ProjectionItem::Wildcard => "*".to_string(),
+                        ProjectionItem::QualifiedWildcard(qualifier) => format!("{}.*", qualifier),
+                    })
+                    .collect();
+                writeln!(f, "Projection: {}", items.join(", "))?
+            }
+            LogicalPlan::Join {
+                join_type: JoinType::Inner,
+                on: Some(on),
+                ..
+            } => writeln!(f, "Join: {}", on)?,
+            LogicalPlan::Join {
+                join_type: JoinType::Inner,
+                on: None,
+                ..
+            } => writeln!(f, "CrossJoin")?,
+            LogicalPlan::Join { join_type, on, .. } => {
+                let on = on.as_ref().map_or("TRUE".to_string(), Expr::to_string);
+                writeln!(f, "{} Join: {}", join_type, on)?
+            }
+            LogicalPlan::SemiJoin {
+                left_keys,
+                right_keys,
+                on,
+                anti,
+                null_aware,
+                ..
+            } => {
+                let name = match (anti, null_aware) {
+                    (false, _) => "SemiJoin",
+                    (true, false) => "AntiJoin",
+                    (true, true) => "NullAwareAntiJoin",
+                };
+                let mut conditions: Vec<String> = left_keys
+                    .iter()
+                    .zip(right_keys)
+                    .map(|(l, r)| format!("{} = {}", l, r))
+                    .collect();
+                conditions.extend(on.iter().map(Expr::to_string));
+                match conditions.is_empty() {
+                    true => writeln!(f, "{}", name)?,
+                    false => writeln!(f, "{}: {}", name, conditions.join(" AND "))?,
+                }
+            }
+            LogicalPlan::Aggregate {
+                group_by,
+                grouping_sets: None,
+                aggregates,
+                ..
+            } => writeln!(
+                f,
+                "Aggregate: group_by=[{}] aggregates=[{}]",
+                join_exprs(group_by),
+                join_exprs(aggregates)
+            )?,
+            LogicalPlan::Aggregate {
+                group_by,
+                grouping_sets: Some(sets),
+                aggregates,
+                ..
+            } => {
+                let sets: Vec<String> = sets
+                    .iter()
+                    .map(|set| {
+                        let keys: Vec<String> = set.iter().map(|&i| group_by[i].to_string()).collect();
+                        format!("({})", keys.join(", "))
+                    })
+                    .collect();
+                writeln!(
+                    f,
+                    "Aggregate: group_by=[{}] grouping_sets=[{}] aggregates=[{}]",
+                    join_exprs(group_by),
+                    sets.join(", "),
+                    join_exprs(aggregates)
+                )?
+            }
+            LogicalPlan::Sort { keys, .. } => {
+                let keys: Vec<String> = keys
+                    .iter()
+                    .map(|key| {
+                        format!(
+                            "{} {} NULLS {}",
+                            key.expr,
+                            if key.descending { "DESC" } else { "ASC" },
+                            if key.nulls_first { "FIRST" } else { "LAST" }
+                        )
+                    })
+                    .collect();
+                writeln!(f, "Sort: {}", keys.join(", "))?
+            }
+            LogicalPlan::Limit { limit, offset, .. } => match limit {
+                Some(limit) => writeln!(f, "Limit: {} offset {}", limit, offset)?,
+                None => writeln!(f, "Limit: all offset {}", offset)?,
+            },
+            LogicalPlan::Distinct { .. } => writeln!(f, "Distinct")?,
+            LogicalPlan::Union { all: true, .. } => writeln!(f, "Union: all")?,
+            LogicalPlan::Union { all: false, .. } => writeln!(f, "Union")?,
+        }
+        for input in self.inputs() {
+            input.fmt_indented(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+fn join_exprs(exprs: &[Expr]) -> String {
+    exprs.iter().map(|e| e.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+impl fmt::Display for LogicalPlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+// Builder
+
+// Builds a plan bottom-up, each call wrapping the plan built so far.
+#[derive(Debug, Clone)]
+pub struct LogicalPlanBuilder {
+    plan: LogicalPlan,
+}
+
+impl LogicalPlanBuilder {
+    pub fn scan(table: &str) -> Self {
+        Self::scan_with_alias(table, table)
+    }
+
+    pub fn scan_with_alias(table: &str, alias: &str) -> Self {
+        LogicalPlanBuilder {
+            plan: LogicalPlan::Scan {
+                table: table.to_string(),
+                alias: alias.to_string(),
+                projection: None,
+                filters: Vec::new(),
+            },
+        }
+    }
+
+    pub fn from(plan: LogicalPlan) -> Self {
+        LogicalPlanBuilder { plan }
+    }
+
+    pub fn filter(self, predicate: Expr) -> Self {
+        self.wrap(|input| LogicalPlan::Filter { input, predicate })
+    }
+
+    pub fn project(self, items: Vec<ProjectionItem>) -> Self {
+        self.wrap(|input| LogicalPlan::Projection { input, items })
+    }
+
+    pub fn join(self, right: LogicalPlan, on: Option<Expr>) -> Self {
+        self.join_with(right, JoinType::Inner, on)
+    }
+
+    pub fn join_with(self, right: LogicalPlan, join_type: JoinType, on: Option<Expr>) -> Self {
+        self.wrap(|left| LogicalPlan::Join {
+            left,
+            right: Box::new(right),
+            join_type,
+            on,
+        })
+    }
+
+    // See `LogicalPlan::SemiJoin`
+    pub fn semi_join(
+        self,
+        right: LogicalPlan,
+        keys: Vec<(Expr, Expr)>,
+        on: Option<Expr>,
+        anti: bool,
+        null_aware: bool,
+    ) -> Self {
+        let (left_keys, right_keys) = keys.into_iter().unzip();
+        self.wrap(|left| LogicalPlan::SemiJoin {
+            left,
+            right: Box::new(right),
+            left_keys,
+            right_keys,
+            on,
+            anti,
+            null_aware,
+        })
+    }
+
+    pub fn aggregate(self, group_by: Vec<Expr>, aggregates: Vec<Expr>) -> Self {
+        self.wrap(|input| LogicalPlan::Aggregate {
+            input,
+            group_by,
+            grouping_sets: None,
+            aggregates,
+        })
+    }
+
+    // Groups by each of `sets`, positions into `group_by`, as GROUPING SETS does
+    pub fn aggregate_grouping_sets(self, group_by: Vec<Expr>, sets: Vec<Vec<usize>>, aggregates: Vec<Expr>) -> Self {
+        self.wrap(|input| LogicalPlan::Aggregate {
+            input,
+            group_by,
+            grouping_sets: Some(sets),
+            aggregates,
+        })
+    }
+
+    pub fn sort(self, keys: Vec<SortKey>) -> Self {
+        self.wrap(|input| LogicalPlan::Sort { input, keys })
+    }
+
+    pub fn limit(self, limit: Option<usize>, offset: usize) -> Self {
+        self.wrap(|input| LogicalPlan::Limit { input, limit, offset })
+    }
+
+    pub fn distinct(self) -> Self {
+        self.wrap(|input| LogicalPlan::Distinct { input })
+    }
+
+    pub fn union(self, right: LogicalPlan, all: bool) -> Self {
+        self.wrap(|left| LogicalPlan::Union {
+            left,
+            right: Box::new(right),
+            all,
+        })
+    }
+
+    pub fn build(self) -> LogicalPlan {
+        self.plan
+    }
+
+    fn wrap<F: FnOnce(Box<LogicalPlan>) -> LogicalPlan>(self, f: F) -> Self {
+        LogicalPlanBuilder {
+            plan: f(Box::new(self.plan)),
+        }
+    }
+}
+
+// Conversion from the AST
+
+impl TryFrom<&Query> for LogicalPlan {
+    type Error = EngineError;
+
+    fn try_from(query: &Query) -> Result<Self> {
+        if query.with.is_some() {
+            return Err(EngineError::Unsupported("WITH".to_string()));
+        }
+
+        let mut builder = match query.body.as_ref() {
+            // A plain SELECT sorts before its projection so ORDER BY can use any input column
+            SetExpr::Select(select) => plan_select(select, &query.order_by)?,
+            body => {
+                let keys = query
+                    .order_by
+                    .iter()
+                    .map(|order| match &order.expr {
+                        Expr::Value(SqlValue::Number(..)) => Err(EngineError::Unsupported(
+                            "ORDER BY position on a set operation".to_string(),
+                        )),
+                        expr => Ok(SortKey::from_order(expr.clone(), order)),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let builder = LogicalPlanBuilder::from(plan_set_expr(body)?);
+                if keys.is_empty() {
+                    builder
+                } else {
+                    builder.sort(keys)
+                }
+            }
+        };
+
+        let limit = query.limit.as_ref().map(literal_usize).transpose()?;
+        let offset = query
+            .offset
+            .as_ref()
+            .map(|offset| literal_usize(&offset.value))
+            .transpose()?
+            .unwrap_or(0);
+        if limit.is_some() || offset > 0 {
+            builder = builder.limit(limit, offset);
+        }
+
+        Ok(builder.build())
+    }
+}
+
+fn plan_set_expr(body: &SetExpr) -> Result<LogicalPlan> {
+    match body {
+        SetExpr::Select(select) => Ok(plan_select(select, &[])?.build()),
+        SetExpr::Query(query) => LogicalPlan::try_from(query.as_ref()),
+        SetExpr::SetOperation {
+            op: SetOperator::Union,
+            set_quantifier,
+            left,
+            right,
+        } => {
+            let all = match set_quantifier {
+                SetQuantifier::All => true,
+                SetQuantifier::Distinct | SetQuantifier::None => false,
+                other => return Err(EngineError::Unsupported(format!("UNION {}", other))),
+            };
+            Ok(LogicalPlanBuilder::from(plan_set_expr(left)?)
+                .union(plan_set_expr(right)?, all)
+                .build())
+        }
+        other => Err(EngineError::Unsupported(format!("query body: {}", other))),
+    }
+}
+
+fn plan_select(select: &Select, order_by: &[OrderByExpr]) -> Result<LogicalPlanBuilder> {
+    let distinct = match &select.distinct {
+        None => false,
+        Some(Distinct::Distinct) => true,
+        Some(other) => return Err(EngineError::Unsupported(format!("{}", other))),
+    };
+
+    let mut builder = plan_from(&select.from)?;
+
+    // IN and EXISTS subqueries among the WHERE conjuncts become semi and anti joins after the
+    // other conjuncts have filtered the input
+    if let Some(predicate) = &select.selection {
+        let mut filters = Vec::new();
+        let mut subqueries = Vec::new();
+        for conjunct in split_conjunction(predicate) {
+            match conjunct {
+                Expr::InSubquery { .. } | Expr::Exists { .. } => subqueries.push(conjunct),
+                Expr::UnaryOp {
+                    op: UnaryOperator::Not,
+                    expr,
+                } if matches!(expr.as_ref(), Expr::InSubquery { .. } | Expr::Exists { .. }) => subqueries.push(conjunct),
+                _ => filters.push(conjunct.clone()),
+            }
+        }
+        if let Some(filter) = filters.into_iter().reduce(|left, right| Expr::BinaryOp {
+            left: Box::new(left),
+            op: BinaryOperator::And,
+            right: Box::new(right),
+        }) {
+            builder = builder.filter(filter);
+        }
+        for subquery in subqueries {
+            builder = plan_subquery_predicate(builder, subquery)?;
+        }
+    }
+
+    let (group_by, grouping_sets) = plan_group_by(&select.group_by)?;
+
+    let items: Vec<ProjectionItem> = select
+        .projection
+        .iter()
+        .map(|item| match item {
+            SelectItem::UnnamedExpr(expr) => ProjectionItem::expr(expr.clone()),
+            SelectItem::ExprWithAlias { expr, alias } => ProjectionItem::aliased(expr.clone(), &alias.value),
+            SelectItem::Wildcard(_) => ProjectionItem::Wildcard,
+            SelectItem::QualifiedWildcard(name, _) => ProjectionItem::QualifiedWildcard(name.to_string()),
+        })
+        .collect();
+
+    let sort_keys = order_by
+        .iter()
+        .map(|order| Ok(SortKey::from_order(resolve_order_expr(&order.expr, &items)?, order)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut aggregates = Vec::new();
+    for item in &items {
+        if let ProjectionItem::Expr(expr, _) = item {
+            collect_aggregates(expr, &mut aggregates);
+        }
+    }
+    for key in &sort_keys {
+        collect_aggregates(&key.expr, &mut aggregates);
+    }
+    // Aggregates only HAVING uses are computed too and dropped by the projection
+    if let Some(having) = &select.having {
+        collect_aggregates(having, &mut aggregates);
+    }
+
+    if !aggregates.is_empty() || !group_by.is_empty() || select.having.is_some() {
+        if items.iter().any(|item| !matches!(item, ProjectionItem::Expr(..))) {
+            return Err(EngineError::Plan(
+                "wildcards cannot be combined with GROUP BY or aggregates".to_string(),
+            ));
+        }
+        builder = match grouping_sets {
+            Some(sets) => builder.aggregate_grouping_sets(group_by, sets, aggregates),
+            None => builder.aggregate(group_by, aggregates),
+        };
+    }
+
+    // HAVING filters the groups, so it is bound against the output of the aggregate
+    if let Some(having) = &select.having {
+        builder = builder.filter(having.clone());
+    }
+
+    if !sort_keys.is_empty() {
+        builder = builder.sort(sort_keys);
+    }
+
+    builder = builder.project(items);
+
+    // Distinct keeps the first occurrence of each row, so the sort order survives it
+    if distinct {
+        builder = builder.distinct();
+    }
+
+    Ok(builder)
+}
+
+const MAX_GROUPING_SETS: usize = 4096;
+
+// Positions into the GROUP BY keys, one list per set
+type GroupingSets = Vec<Vec<usize>>;
+
+// The GROUP BY keys and, when the clause uses ROLLUP, CUBE or GROUPING SETS, the sets to group
+// by as positions into the keys. Items combine as a cross product, so `a, ROLLUP(b, c)` groups
+// by (a, b, c), (a, b) and (a).
+fn plan_group_by(group_by: &GroupByExpr) -> Result<(Vec<Expr>, Option<GroupingSets>)> {
+    let (exprs, modifiers) = match group_by {
+        GroupByExpr::Expressions(exprs, modifiers) => (exprs, modifiers),
+        other => return Err(EngineError::Unsupported(format!("{}", other))),
+    };
+    let single: Vec<Vec<Expr>> = exprs.iter().map(|expr| vec![expr.clone()]).collect();
+    let items = match modifiers.as_slice() {
+        [] if !exprs
+            .iter()
+            .any(|expr| matches!(expr, Expr::Rollup(_) | Expr::Cube(_) | Expr::GroupingSets(_))) =>
+        {
+            return Ok((exprs.clone(), None))
+        }
+        [] => exprs
+            .iter()
+            .map(|expr| match expr {
+                Expr::Rollup(groups) => Ok(rollup(groups)),
+                Expr::Cube(groups) => cube(groups),
+                Expr::GroupingSets(sets) => Ok(sets.clone()),
+                expr => Ok(vec![vec![expr.clone()]]),
+            })
+            .collect::<Result<Vec<_>>>()?,
+        [GroupByWithModifier::Rollup] => vec![rollup(&single)],
+        [GroupByWithModifier::Cube] => vec![cube(&single)?],
+        _ => return Err(EngineError::Unsupported(format!("{}", group_by))),
+    };
+
+    let mut sets: Vec<Vec<Expr>> = vec![Vec::new()];
+    for item in items {
+        if sets.len() * item.len() > MAX_GROUPING_SETS {
+            return Err(EngineError::Plan(format!("GROUP BY expands to more than {} grouping sets", MAX_GROUPING_SETS)));
+        }
+        sets = sets
+            .iter()
+            .flat_map(|set| item.iter().map(move |other| set.iter().chain(other).cloned().collect()))
+            .collect();
+    }
+
+    let mut keys: Vec<Expr> = Vec::new();
+    let sets = sets
+        .into_iter()
+        .map(|set| {
+            let mut positions = Vec::new();
+            for expr in set {
+                let position = keys.iter().position(|key| *key == expr).unwrap_or_else(|| {
+                    keys.push(expr);
+                    keys.len() - 1
+                });
+                if !positions.contains(&position) {
+                    positions.push(position);
+                }
+            }
+            positions.sort_unstable();
+            positions
+        })
+        .collect();
+    Ok((keys, Some(sets)))
+}
+
+// ROLLUP(a, b) is (a, b), (a) and ()
+fn rollup(groups: &[Vec<Expr>]) -> Vec<Vec<Expr>> {
+    (0..=groups.len()).rev().map(|n| groups[..n].concat()).collect()
+}
+
+// CUBE(a, b) is (a, b), (a), (b) and ()
+fn cube(groups: &[Vec<Expr>]) -> Result<Vec<Vec<Expr>>> {
+    if groups.len() > MAX_GROUPING_SETS.ilog2() as usize {
+        return Err(EngineError::Plan(format!("GROUP BY expands to more than {} grouping sets", MAX_GROUPING_SETS)));
+    }
+    let n = groups.len();
+    Ok((0..1usize << n)
+        .rev()
+        .map(|mask| {
+            (0..n)
+                .filter(|i| mask & 1 << (n - 1 - i) != 0)
+                .flat_map(|i| groups[i].iter().cloned())
+                .collect()
+        })
+        .collect())
+}
+
+// `[NOT] EXISTS (subquery)` or `expr [NOT] IN (subquery)` as a semi or anti join. The subquery
+// must be a plain SELECT over its FROM clause: its WHERE becomes the join condition, so it may
+// refer to columns of the outer query, and the select list of IN gives the right key.
+fn plan_subquery_predicate(builder: LogicalPlanBuilder, predicate: &Expr) -> Result<LogicalPlanBuilder> {
+    let (predicate, negated_outside) = match predicate {
+        Expr::UnaryOp {
+            op: UnaryOperator::Not,
+            expr,
+        } => (expr.as_ref(), true),
+        predicate => (predicate, false),
+    };
+    let (subquery, negated, in_expr) = match predicate {
+        Expr::Exists { subquery, negated } => (subquery, *negated, None),
+        Expr::InSubquery {
+            expr,
+            subquery,
+            negated,
+        } => (subquery, *negated, Some(expr.as_ref())),
+        other => return Err(EngineError::Unsupported(format!("subquery predicate: {}", other))),
+    };
+    let anti = negated != negated_outside;
+
+    let unsupported = || EngineError::Unsupported(format!("subquery {}: only a plain SELECT ... FROM ... [WHERE ...] is supported", subquery));
+    let select = match subquery.body.as_ref() {
+        SetExpr::Select(select)
+            if subquery.with.is_none()
+                && subquery.order_by.is_empty()
+                && subquery.limit.is_none()
+                && subquery.offset.is_none() =>
+        {
+            select
+        }
+        _ => return Err(unsupported()),
+    };
+    let plain = select.distinct.is_none()
+        && select.having.is_none()
+        && matches!(&select.group_by, GroupByExpr::Expressions(exprs, modifiers) if exprs.is_empty() && modifiers.is_empty());
+    let mut aggregates = Vec::new();
+    for item in &select.projection {
+        if let SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } = item {
+            collect_aggregates(expr, &mut aggregates);
+        }
+    }
+    if !plain || !aggregates.is_empty() {
+        return Err(unsupported());
+    }
+
+    let keys = match in_expr {
+        None => Vec::new(),
+        Some(expr) => match select.projection.as_slice() {
+            [SelectItem::UnnamedExpr(column) | SelectItem::ExprWithAlias { expr: column, .. }] => {
+                vec![(expr.clone(), column.clone())]
+            }
+            _ => return Err(EngineError::Plan(format!("subquery {} must return one column", subquery))),
+        },
+    };
+    // Only NOT IN needs NULL-aware matching; NOT EXISTS treats NULL like any non-match
+    let null_aware = anti && in_expr.is_some();
+    let right = plan_from(&select.from)?.build();
+    Ok(builder.semi_join(right, keys, select.selection.clone(), anti, null_aware))
+}
+
+fn plan_from(from: &[TableWithJoins]) -> Result<LogicalPlanBuilder> {
+    let mut tables = from.iter().map(plan_table_with_joins);
+    let mut builder = tables
+        .next()
+        .ok_or_else(|| EngineError::Unsupported("SELECT without FROM".to_string()))??;
+    for table in tables {
+        builder = builder.join(table?.build(), None);
+    }
+    Ok(builder)
+}
+
+fn plan_table_with_joins(table: &TableWithJoins) -> Result<LogicalPlanBuilder> {
+    let mut builder = plan_table_factor(&table.relation)?;
+    for join in &table.joins {
+        let (join_type, on) = match &join.join_operator {
+            JoinOperator::Inner(JoinConstraint::On(expr)) => (JoinType::Inner, Some(expr.clone())),
+            JoinOperator::Inner(JoinConstraint::None) | JoinOperator::CrossJoin => (JoinType::Inner, None),
+            JoinOperator::LeftOuter(JoinConstraint::On(expr)) => (JoinType::Left, Some(expr.clone())),
+            JoinOperator::RightOuter(JoinConstraint::On(expr)) => (JoinType::Right, Some(expr.clone())),
+            JoinOperator::FullOuter(JoinConstraint::On(expr)) => (JoinType::Full, Some(expr.clone())),
+            other => return Err(EngineError::Unsupported(format!("join: {:?}", other))),
+        };
+        builder = builder.join_with(plan_table_factor(&join.relation)?.build(), join_type, on);
+    }
+    Ok(builder)
+}
+
+fn plan_table_factor(factor: &TableFactor) -> Result<LogicalPlanBuilder> {
+    match factor {
+        TableFactor::Table { name, alias, .. } => {
+            let table = name.to_string();
+            Ok(match alias {
+                Some(alias) => LogicalPlanBuilder::scan_with_alias(&table, &alias.name.value),
+                None => LogicalPlanBuilder::scan(&table),
+            })
+        }
+        other => Err(EngineError::Unsupported(format!("table factor: {}", other))),
+    }
+}
+
+// ORDER BY may name a select-list alias or position; those are replaced by the aliased
+// expression so that sorting can happen before the projection.
+fn resolve_order_expr(expr: &Expr, items: &[ProjectionItem]) -> Result<Expr> {
+    match expr {
+        Expr::Identifier(ident) => {
+            let aliased = items.iter().find_map(|item| match item {
+                ProjectionItem::Expr(aliased, name)
+                    if name.eq_ignore_ascii_case(&ident.value) && **aliased != *expr =>
+                {
+                    Some(aliased.as_ref().clone())
+                }
+                _ => None,
+            });
+            Ok(aliased.unwrap_or_else(|| expr.clone()))
+        }
+        Expr::Value(SqlValue::Number(_, _)) => {
+            let position = literal_usize(expr)?;
+            match position.checked_sub(1).and_then(|i| items.get(i)) {
+                Some(ProjectionItem::Expr(expr, _)) => Ok(expr.as_ref().clone()),
+                _ => Err(EngineError::Plan(format!("ORDER BY position {} is not in select list", position))),
+            }
+        }
+        _ => Ok(expr.clone()),
+    }
+}
+
+fn literal_usize(expr: &Expr) -> Result<usize> {
+    match expr {
+        Expr::Value(SqlValue::Number(n, _)) => n
+            .parse()
+            .map_err(|_| EngineError::Plan(format!("expected a non-negative integer, got {}", n))),
+        other => Err(EngineError::Unsupported(format!("non-literal LIMIT/OFFSET: {}", other))),
+    }
+}
+
+pub(crate) fn output_name(expr: &Expr) -> String {
+    match expr {
+        Expr::Identifier(ident) => ident.value.clone(),
+        Expr::CompoundIdentifier(parts) => parts.last().map(|p| p.value.clone()).unwrap_or_default(),
+        other => other.to_string(),
+    }
+}
+
+pub(crate) fn split_conjunction(expr: &Expr) -> Vec<&Expr> {
+    match expr {
+        Expr::BinaryOp {
+            left,
+            op: BinaryOperator::And,
+            right,
+        } => {
+            let mut conjuncts = split_conjunction(left);
+            conjuncts.extend(split_conjunction(right));
+            conjuncts
+        }
+        Expr::Nested(inner) => split_conjunction(inner),
+        other => vec![other],
+    }
+}
+
+// Name resolution and typing
+
+fn has_qualifier(field: &PlanField, qualifier: &str) -> bool {
+    field
+        .relation
+        .as_deref()
+        .is_some_and(|relation| relation.eq_ignore_ascii_case(qualifier))
+}
+
+// Every column of a table, qualified by the alias it is scanned under
+pub(crate) fn table_fields(catalog: &Catalog, table: &str, alias: &str) -> Result<Vec<PlanField>> {
+    Ok(catalog
+        .schema(table)?
+        .fields
+        .iter()
+        .map(|field| PlanField::column(Some(alias), field))
+        .collect())
+}
+
+// Position of the field an expression refers to directly: a derived field with the same
+// expression, or the column named by an identifier. `None` if the expression is neither.
+pub(crate) fn field_index(fields: &[PlanField], expr: &Expr) -> Option<Result<usize>> {
+    if let Some(i) = fields.iter().position(|f| f.expr.as_deref() == Some(expr)) {
+        return Some(Ok(i));
+    }
+    match expr {
+        Expr::Identifier(ident) => Some(resolve_column(fields, None, &ident.value)),
+        Expr::CompoundIdentifier(parts) if parts.len() == 2 => {
+            Some(resolve_column(fields, Some(&parts[0].value), &parts[1].value))
+        }
+        Expr::CompoundIdentifier(_) => Some(Err(EngineError::Unsupported(format!("identifier: {}", expr)))),
+        _ => None,
+    }
+}
+
+fn resolve_column(fields: &[PlanField], qualifier: Option<&str>, name: &str) -> Result<usize> {
+    let matches: Vec<usize> = fields
+        .iter()
+        .enumerate()
+        .filter(|(_, field)| {
+            field.expr.is_none()
+                && field.name.eq_ignore_ascii_case(name)
+                && qualifier.is_none_or(|qualifier| has_qualifier(field, qualifier))
+        })
+        .map(|(i, _)| i)
+        .collect();
+
+    let display = match qualifier {
+        Some(qualifier) => format!("{}.{}", qualifier, name),
+        None => name.to_string(),
+    };
+    match matches.as_slice() {
+        [i] => Ok(*i),
+        [] if fields.iter().any(|field| field.expr.is_some()) => Err(EngineError::Plan(format!(
+            "column {} must appear in GROUP BY or be used in an aggregate",
+            display
+        ))),
+        [] => Err(EngineError::ColumnNotFound(display)),
+        _ => Err(EngineError::AmbiguousColumn(display)),
+    }
+}
+
+pub(crate) fn literal(value: &SqlValue) -> Result<Value> {
+    match value {
+        SqlValue::Number(n, _) => {
+            if let Ok(i) = n.parse::<i64>() {
+                Ok(i32::try_from(i).map(Value::Int32).unwrap_or(Value::Int64(i)))
+            } else {
+                n.parse::<f64>()
+                    .map(Value::Float64)
+                    .map_err(|_| EngineError::Plan(format!("invalid number: {}", n)))
+            }
+        }
+        SqlValue::SingleQuotedString(s) => Ok(Value::Utf8(s.clone())),
+        SqlValue::Boolean(b) => Ok(Value::Boolean(*b)),
+        SqlValue::Null => Ok(Value::Null),
+        other => Err(EngineError::Unsupported(format!("literal: {}", other))),
+    }
+}
+
+fn expect_boolean(expr: &Expr, fields: &[PlanField], clause: &str) -> Result<()> {
+    match expr_type(expr, fields)? {
+        (DataType::Boolean | DataType::Null, _) => Ok(()),
+        (other, _) => Err(EngineError::Plan(format!("{} condition must be Boolean, got {}", clause, other))),
+    }
+}
+
+// Output type and nullability of an expression over the given input columns
+pub(crate) fn expr_type(expr: &Expr, fields: &[PlanField]) -> Result<(DataType, bool)> {
+    let bound = ScalarExpr::bind(expr, fields)?;
+    Ok((bound.data_type(), bound.nullable()))
+}
+
+// Aggregate calls
+
+#[derive(Debug, Clone)]
+pub(crate) struct AggregateCall {
+    pub func: AggregateFunction,
+    // Empty for `COUNT(*)`
+    pub args: Vec<Expr>,
+}
+
+impl AggregateCall {
+    pub fn output_type(&self, args: &[DataType]) -> Result<DataType> {
+        self.func.output_type(args)
+    }
+}
+
+pub(crate) fn parse_aggregate(expr: &Expr) -> Option<Result<AggregateCall>> {
+    let function = match expr {
+        Expr::Function(function) => function,
+        _ => return None,
+    };
+    let name = function.name.to_string().to_uppercase();
+    let func = match name.as_str() {
+        "COUNT" => AggregateFunction::Count,
+        "SUM" => AggregateFunction::Sum,
+        "AVG" => AggregateFunction::Avg,
+        "MIN" => AggregateFunction::Min,
+        "MAX" => AggregateFunction::Max,
+        "VAR_POP" => AggregateFunction::VarPop,
+        "VAR_SAMP" | "VARIANCE" => AggregateFunction::VarSamp,
+        "STDDEV_POP" => AggregateFunction::StddevPop,
+        "STDDEV_SAMP" | "STDDEV" => AggregateFunction::StddevSamp,
+        "COVAR_POP" => AggregateFunction::CovarPop,
+        "CORR" => AggregateFunction::Corr,
+        "REGR_SLOPE" => AggregateFunction::RegrSlope,
+        "MEDIAN" => AggregateFunction::PercentileCont(0.5),
+        "PERCENTILE_CONT" | "PERCENTILE_DISC" | "APPROX_PERCENTILE" => return Some(parse_percentile(expr, &name)),
+        "APPROX_COUNT_DISTINCT" => return Some(parse_approx_count_distinct(expr)),
+        _ => return None,
+    };
+    let list = match &function.args {
+        FunctionArguments::List(list) => list,
+        _ => return Some(Err(EngineError::Plan(format!("{} expects one argument", expr)))),
+    };
+    if !function.within_group.is_empty() {
+        return Some(Err(EngineError::Unsupported(format!("WITHIN GROUP in {}", expr))));
+    }
+    let distinct = matches!(list.duplicate_treatment, Some(DuplicateTreatment::Distinct));
+    let args = match list.args.as_slice() {
+        [FunctionArg::Unnamed(FunctionArgExpr::Wildcard)] if func == AggregateFunction::Count && !distinct => Vec::new(),
+        args => {
+            match plain_args(args) {
+                Some(args) if args.len() == func.arity() => args,
+                Some(_) => {
+                    return Some(Err(EngineError::Plan(format!(
+                        "{} takes {} argument(s)",
+                        function.name,
+                        func.arity()
+                    ))))
+                }
+                None => return Some(Err(EngineError::Unsupported(format!("aggregate call: {}", expr)))),
+            }
+        }
+    };
+    let func = match (func, distinct) {
+        (func, false) => func,
+        (AggregateFunction::Count, true) => AggregateFunction::CountDistinct,
+        (_, true) => return Some(Err(EngineError::Unsupported(format!("DISTINCT in {}", expr)))),
+    };
+    Some(Ok(AggregateCall { func, args }))
+}
+
+// Expressions of a call without wildcards or named arguments
+fn plain_args(args: &[FunctionArg]) -> Option<Vec<Expr>> {
+    args.iter()
+        .map(|arg| match arg {
+            FunctionArg::Unnamed(FunctionArgExpr::Expr(arg)) => Some(arg.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+// `PERCENTILE_CONT(fraction) WITHIN GROUP (ORDER BY x)`, the same for PERCENTILE_DISC, and
+// `APPROX_PERCENTILE(x, fraction)`
+fn parse_percentile(expr: &Expr, name: &str) -> Result<AggregateCall> {
+    let usage = || match name {
+        "APPROX_PERCENTILE" => EngineError::Plan(format!("{} expects APPROX_PERCENTILE(expr, fraction)", expr)),
+        _ => EngineError::Plan(format!("{} expects {}(fraction) WITHIN GROUP (ORDER BY expr)", expr, name)),
+    };
+    let function = match expr {
+        Expr::Function(function) => function,
+        _ => return Err(usage()),
+    };
+    let args = match &function.args {
+        FunctionArguments::List(list) if list.duplicate_treatment.is_none() => plain_args(&list.args).ok_or_else(usage)?,
+        _ => return Err(usage()),
+    };
+    let (arg, fraction) = match (name, args.as_slice(), function.within_group.as_slice()) {
+        ("APPROX_PERCENTILE", [arg, fraction], []) => (arg.clone(), fraction),
+        ("PERCENTILE_CONT" | "PERCENTILE_DISC", [fraction], [order]) => {
+            if order.asc == Some(false) {
+                return Err(EngineError::Unsupported(format!("descending order in {}", expr)));
+            }
+            (order.expr.clone(), fraction)
+        }
+        _ => return Err(usage()),
+    };
+    let fraction = match fraction {
+        Expr::Value(value) => literal(value)?.as_f64(),
+        _ => None,
+    };
+    let fraction = match fraction {
+        Some(fraction) if (0.0..=1.0).contains(&fraction) => fraction,
+        _ => return Err(EngineError::Plan(format!("the fraction in {} must be a number between 0 and 1", expr))),
+    };
+    let func = match name {
+        "PERCENTILE_CONT" => AggregateFunction::PercentileCont(fraction),
+        "PERCENTILE_DISC" => AggregateFunction::PercentileDisc(fraction),
+        _ => AggregateFunction::ApproxPercentile(fraction),
+    };
+    Ok(AggregateCall { func, args: vec![arg] })
+}
+
+// `APPROX_COUNT_DISTINCT(x)`, or `APPROX_COUNT_DISTINCT(x, precision)` to pick the size of the sketch
+fn parse_approx_count_distinct(expr: &Expr) -> Result<AggregateCall> {
+    let usage = || EngineError::Plan(format!("{} expects APPROX_COUNT_DISTINCT(expr [, precision])", expr));
+    let args = match expr {
+        Expr::Function(Function {
+            args: FunctionArguments::List(list),
+            within_group,
+            ..
+        }) if list.duplicate_treatment.is_none() && within_group.is_empty() => plain_args(&list.args).ok_or_else(usage)?,
+        _ => return Err(usage()),
+    };
+    let (arg, precision) = match args.as_slice() {
+        [arg] => (arg.clone(), DEFAULT_PRECISION),
+        [arg, Expr::Value(value)] => {
+            let precision = literal(value)?
+                .as_i64()
+                .filter(|p| (MIN_PRECISION as i64..=MAX_PRECISION as i64).contains(p))
+                .ok_or_else(|| {
+                    EngineError::Plan(format!(
+                        "the precision in {} must be an integer between {} and {}",
+                        expr, MIN_PRECISION, MAX_PRECISION
+                    ))
+                })?;
+            (arg.clone(), precision as u8)
+        }
+        _ => return Err(usage()),
+    };
+    Ok(AggregateCall {
+        func: AggregateFunction::ApproxCountDistinct(precision),
+        args: vec![arg],
+    })
+}
+
+// The arguments of `GROUPING(key, ...)`, which tells which of the keys the grouping set of a
+// row leaves out
+pub(crate) fn parse_grouping(expr: &Expr) -> Option<Result<Vec<Expr>>> {
+    let function = match expr {
+        Expr::Function(function) if function.name.to_string().eq_ignore_ascii_case("GROUPING") => function,
+        _ => return None,
+    };
+    let args = match &function.args {
+        FunctionArguments::List(list) if list.duplicate_treatment.is_none() && function.within_group.is_empty() => {
+            plain_args(&list.args)
+        }
+        _ => None,
+    };
+    Some(match args {
+        Some(args) if !args.is_empty() => Ok(args),
+        _ => Err(EngineError::Plan(format!("{} expects GROUPING(key, ...)", expr))),
+    })
+}
+
+// The position among the GROUP BY keys of every argument of GROUPING
+pub(crate) fn grouping_positions(args: &[Expr], group_by: &[Expr], fields: &[PlanField]) -> Result<Vec<usize>> {
+    let column = |expr: &Expr| match field_index(fields, expr) {
+        Some(Ok(i)) => Some(i),
+        _ => None,
+    };
+    args.iter()
+        .map(|arg| {
+            group_by
+                .iter()
+                .position(|key| key == arg || column(key).is_some() && column(key) == column(arg))
+                .ok_or_else(|| EngineError::Plan(format!("GROUPING argument {} is not a GROUP BY key", arg)))
+        })
+        .collect()
+}
+
+fn collect_aggregates(expr: &Expr, out: &mut Vec<Expr>) {
+    if parse_aggregate(expr).is_some() || parse_grouping(expr).is_some() {
+        if !out.contains(expr) {
+            out.push(expr.clone());
+        }
+        return;
+    }
+    match expr {
+        Expr::BinaryOp { left, right, .. } => {
+            collect_aggregates(left, out);
+            collect_aggregates(right, out);
+        }
+        Expr::UnaryOp { expr, .. }
+        | Expr::Nested(expr)
+        | Expr::IsNull(expr)
+        | Expr::IsNotNull(expr)
+        | Expr::Cast { expr, .. } => collect_aggregates(expr, out),
+        Expr::InList { expr, list, .. } => {
+            collect_aggregates(expr, out);
+            list.iter().for_each(|item| collect_aggregates(item, out));
+        }
+        Expr::Between { expr, low, high, .. } => {
+            collect_aggregates(expr, out);
+            collect_aggregates(low, out);
+            collect_aggregates(high, out);
+        }
+        Expr::Like { expr, pattern, .. } | Expr::ILike { expr, pattern, .. } => {
+            collect_aggregates(expr, out);
+            collect_aggregates(pattern, out);
+        }
+        Expr::Case {
+            operand,
+            conditions,
+            results,
+            else_result,
+        } => {
+            operand.iter().chain(else_result).for_each(|expr| collect_aggregates(expr, out));
+            conditions.iter().chain(results).for_each(|expr| collect_aggregates(expr, out));
+        }
+        Expr::Function(Function {
+            args: FunctionArguments::List(list),
+            ..
+        }) => {
+            for arg in &list.args {
+                if let FunctionArg::Unnamed(FunctionArgExpr::Expr(arg)) | FunctionArg::Named { arg: FunctionArgExpr::Expr(arg), .. } = arg {
+                    collect_aggregates(arg, out);
+                }
+            }
+        }
+        _ => {}
+    }
+}
@@ -1,5 +1,12 @@
 use rayon::prelude::*;
 
+use crate::batch::{Column, ColumnData, RecordBatch};
+use crate::calibration::thresholds;
+use crate::catalog::Schema;
+use crate::error::EngineError;
+use crate::execution::{self, ExecutionReport};
+use crate::expressions::{self, ScalarExpr};
+
 // Adaptive chunk size determination
 fn determine_chunk_size(data_size: usize) -> usize {
     let available_threads = rayon::current_num_threads();
@@ -9,65 +16,68 @@ fn determine_chunk_size(data_size: usize) -> usize {
     std::cmp::max(base_chunk_size, data_size / ideal_chunks)
 }
 
+pub(crate) const PARALLELIZATION_THRESHOLD: usize = 500000; // Default, see calibration::Thresholds
+
 // Adaptive parallelism decision
-fn should_parallelize(data_size: usize) -> bool {
-    let threshold = 500000; // Adjusted based on the new benchmark results
-    data_size > threshold
+fn report(data_size: usize) -> ExecutionReport {
+    ExecutionReport::for_input(data_size, thresholds().scan)
 }
 
 #[allow(dead_code)]
-pub fn adaptive_scan<T, F>(data: &[T], process: F) -> (Vec<T>, String)
+pub fn adaptive_scan<T, U, F>(data: &[T], process: F) -> (Vec<U>, ExecutionReport)
 where
     T: Send + Sync + Clone,
-    F: Fn(&T) -> T + Sync + Send + Clone,
+    U: Send,
+    F: Fn(&T) -> U + Sync + Send + Clone,
 {
-    if should_parallelize(data.len()) {
-        (parallel_scan(data, process), "Parallel".to_string())
-    } else {
-        (normal_scan(data, process), "Sequential".to_string())
-    }
+    execution::run_adaptive(
+        report(data.len()),
+        || normal_scan(data, process.clone()),
+        || parallel_scan(data, process.clone()),
+        Vec::len,
+    )
 }
 
 #[allow(dead_code)]
-pub fn adaptive_scan_with_filter<T, F, P>(data: &[T], process: F, predicate: P) -> (Vec<T>, String)
+pub fn adaptive_scan_with_filter<T, U, F, P>(data: &[T], process: F, predicate: P) -> (Vec<U>, ExecutionReport)
 where
     T: Send + Sync + Clone,
-    F: Fn(&T) -> T + Sync + Send + Clone,
+    U: Send,
+    F: Fn(&T) -> U + Sync + Send + Clone,
     P: Fn(&T) -> bool + Sync + Send + Clone,
 {
-    let sequential_result = normal_scan_with_filter(data, process.clone(), predicate.clone());
-    let parallel_result = parallel_scan_with_filter(data, process.clone(), predicate.clone());
-    
-    if should_parallelize(data.len()) {
-        (parallel_result, String::from("Parallel"))
-    } else {
-        (sequential_result, String::from("Sequential"))
-    }
+    execution::run_adaptive(
+        report(data.len()).with_chunk_size(determine_chunk_size(data.len())),
+        || normal_scan_with_filter(data, process.clone(), predicate.clone()),
+        || parallel_scan_with_filter(data, process.clone(), predicate.clone()),
+        Vec::len,
+    )
 }
 
 #[allow(dead_code)]
-pub fn normal_scan<T, F>(data: &[T], process: F) -> Vec<T>
+pub fn normal_scan<T, U, F>(data: &[T], process: F) -> Vec<U>
 where
     T: Clone,
-    F: Fn(&T) -> T,
+    F: Fn(&T) -> U,
 {
     data.iter().map(process).collect()
 }
 
 #[allow(dead_code)]
-pub fn parallel_scan<T, F>(data: &[T], process: F) -> Vec<T>
+pub fn parallel_scan<T, U, F>(data: &[T], process: F) -> Vec<U>
 where
     T: Send + Sync + Clone,
-    F: Fn(&T) -> T + Sync + Send,
+    U: Send,
+    F: Fn(&T) -> U + Sync + Send,
 {
     data.par_iter().map(process).collect()
 }
 
 #[allow(dead_code)]
-pub fn normal_scan_with_filter<T, F, P>(data: &[T], process: F, predicate: P) -> Vec<T>
+pub fn normal_scan_with_filter<T, U, F, P>(data: &[T], process: F, predicate: P) -> Vec<U>
 where
     T: Clone,
-    F: Fn(&T) -> T,
+    F: Fn(&T) -> U,
     P: Fn(&T) -> bool,
 {
     data.iter()
@@ -77,10 +87,11 @@ where
 }
 
 #[allow(dead_code)]
-pub fn parallel_scan_with_filter<T, F, P>(data: &[T], process: F, predicate: P) -> Vec<T>
+pub fn parallel_scan_with_filter<T, U, F, P>(data: &[T], process: F, predicate: P) -> Vec<U>
 where
     T: Send + Sync + Clone,
-    F: Fn(&T) -> T + Sync + Send,
+    U: Send,
+    F: Fn(&T) -> U + Sync + Send,
     P: Fn(&T) -> bool + Sync + Send,
 {
     let chunk_size = determine_chunk_size(data.len());
@@ -92,4 +103,86 @@ where
                 .map(&process)
         })
         .collect()
-}
\ No newline at end of file
+}
+
+// Record batch scans: a projection of the batch columns, and a filter by a row mask
+
+pub fn adaptive_scan_batch(batch: &RecordBatch, projection: &[usize]) -> (RecordBatch, ExecutionReport) {
+    execution::run_adaptive(
+        report(batch.num_rows()),
+        || normal_scan_batch(batch, projection),
+        || parallel_scan_batch(batch, projection),
+        RecordBatch::num_rows,
+    )
+}
+
+pub fn normal_scan_batch(batch: &RecordBatch, projection: &[usize]) -> RecordBatch {
+    batch.project(projection)
+}
+
+pub fn parallel_scan_batch(batch: &RecordBatch, projection: &[usize]) -> RecordBatch {
+    let columns = projection.par_iter().map(|&i| batch.column(i).clone()).collect();
+    let fields = projection.iter().map(|&i| batch.schema().fields[i].clone()).collect();
+    RecordBatch::from_parts(Schema::new(fields), columns, batch.num_rows())
+}
+
+pub fn adaptive_filter_batch(batch: &RecordBatch, mask: &[bool]) -> (RecordBatch, ExecutionReport) {
+    execution::run_adaptive(
+        report(batch.num_rows()),
+        || normal_filter_batch(batch, mask),
+        || parallel_filter_batch(batch, mask),
+        RecordBatch::num_rows,
+    )
+}
+
+pub fn normal_filter_batch(batch: &RecordBatch, mask: &[bool]) -> RecordBatch {
+    let num_rows = mask.iter().filter(|keep| **keep).count();
+    let columns = batch.columns().iter().map(|c| c.filter(mask)).collect();
+    RecordBatch::from_parts(batch.schema().clone(), columns, num_rows)
+}
+
+pub fn parallel_filter_batch(batch: &RecordBatch, mask: &[bool]) -> RecordBatch {
+    let num_rows = mask.par_iter().filter(|keep| **keep).count();
+    let columns = batch.columns().par_iter().map(|c| c.filter(mask)).collect();
+    RecordBatch::from_parts(batch.schema().clone(), columns, num_rows)
+}
+
+// Scans that keep the rows where a bound predicate is true; NULL counts as false
+
+pub fn adaptive_scan_with_predicate(
+    batch: &RecordBatch,
+    predicate: &ScalarExpr,
+) -> Result<(RecordBatch, ExecutionReport), EngineError> {
+    let (output, report) = execution::run_adaptive(
+        report(batch.num_rows()),
+        || normal_scan_with_predicate(batch, predicate),
+        || parallel_scan_with_predicate(batch, predicate),
+        |output| output.as_ref().map_or(0, RecordBatch::num_rows),
+    );
+    Ok((output?, report))
+}
+
+pub fn normal_scan_with_predicate(batch: &RecordBatch, predicate: &ScalarExpr) -> Result<RecordBatch, EngineError> {
+    let mask = predicate_mask(&expressions::sequential_evaluate(predicate, batch)?)?;
+    Ok(normal_filter_batch(batch, &mask))
+}
+
+pub fn parallel_scan_with_predicate(batch: &RecordBatch, predicate: &ScalarExpr) -> Result<RecordBatch, EngineError> {
+    let mask = predicate_mask(&expressions::parallel_evaluate(predicate, batch)?)?;
+    Ok(parallel_filter_batch(batch, &mask))
+}
+
+pub(crate) fn predicate_mask(column: &Column) -> Result<Vec<bool>, EngineError> {
+    match column.data() {
+        ColumnData::Boolean(values) => Ok(values
+            .iter()
+            .enumerate()
+            .map(|(i, &keep)| keep && !column.is_null(i))
+            .collect()),
+        ColumnData::Null(values) => Ok(vec![false; values.len()]),
+        _ => Err(EngineError::Execution(format!(
+            "predicate must be Boolean, got {}",
+            column.data_type()
+        ))),
+    }
+}
@@ -0,0 +1,342 @@
+use std::fmt::Write;
+use std::time::Instant;
+
+use crate::batch::RecordBatch;
+use crate::calibration::{default_config_path, recalibrate, thresholds};
+use crate::engine::Engine;
+use crate::error::EngineError;
+use crate::json::{format_json, format_ndjson};
+use crate::types::{DataType, Value};
+
+type Result<T> = std::result::Result<T, EngineError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    Table,
+    Csv,
+    Json,
+    Ndjson,
+}
+
+impl OutputMode {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "table" => Some(OutputMode::Table),
+            "csv" => Some(OutputMode::Csv),
+            "json" => Some(OutputMode::Json),
+            "ndjson" | "jsonl" => Some(OutputMode::Ndjson),
+            _ => None,
+        }
+    }
+}
+
+const HELP: &str = "\
+.help                  Show this message
+.tables                List the registered tables
+.schema TABLE          Show the columns of a table
+.timer on|off          Print the run time of every statement
+.thresholds            Show the input sizes above which operators run in parallel
+.calibrate             Measure those thresholds on this machine and save them
+.mode MODE             Print results as table, csv, json or ndjson
+.quit                  Exit the shell";
+
+// Line oriented front end of the engine, shared by the interactive shell and script mode.
+//
+// Lines are fed one at a time; SQL is buffered until a `;` outside of quotes completes a
+// statement, so statements can span lines and a line can hold several of them. Lines starting
+// with `.` are meta-commands when no statement is pending.
+#[derive(Debug)]
+pub struct Shell {
+    engine: Engine,
+    mode: OutputMode,
+    timer: bool,
+    buffer: String,
+    quit: bool,
+}
+
+impl Shell {
+    pub fn new(engine: Engine) -> Self {
+        Shell {
+            engine,
+            mode: OutputMode::Table,
+            timer: false,
+            buffer: String::new(),
+            quit: false,
+        }
+    }
+
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    pub fn engine_mut(&mut self) -> &mut Engine {
+        &mut self.engine
+    }
+
+    pub fn mode(&self) -> OutputMode {
+        self.mode
+    }
+
+    // Whether a statement is waiting for more lines
+    pub fn is_pending(&self) -> bool {
+        !self.buffer.trim().is_empty()
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.quit
+    }
+
+    // Drops a partially entered statement
+    pub fn cancel(&mut self) {
+        self.buffer.clear();
+    }
+
+    // Runs everything the line completes; each entry is the output of one statement or command
+    pub fn feed_line(&mut self, line: &str) -> Vec<Result<String>> {
+        if !self.is_pending() && line.trim_start().starts_with('.') {
+            self.buffer.clear();
+            return vec![self.meta_command(line.trim())];
+        }
+        self.buffer.push_str(line);
+        self.buffer.push('\n');
+
+        let (statements, rest) = split_statements(&self.buffer);
+        self.buffer = rest;
+        statements.iter().map(|sql| self.run_statement(sql)).collect()
+    }
+
+    // Runs a trailing statement that was never terminated by `;`
+    pub fn finish(&mut self) -> Option<Result<String>> {
+        let sql = std::mem::take(&mut self.buffer);
+        (!sql.trim().is_empty()).then(|| self.run_statement(sql.trim()))
+    }
+
+    fn run_statement(&mut self, sql: &str) -> Result<String> {
+        let start = Instant::now();
+        let result = self.engine.execute_statement(sql)?;
+        let elapsed = start.elapsed();
+
+        // Statements without a result, like CREATE TABLE, print nothing
+        let mut output = match result.batch().num_columns() {
+            0 => String::new(),
+            _ => format_batch(result.batch(), self.mode),
+        };
+        if self.timer {
+            writeln!(output, "Run Time: {:.3} ms", elapsed.as_secs_f64() * 1000.0).unwrap();
+        }
+        Ok(output)
+    }
+
+    fn meta_command(&mut self, line: &str) -> Result<String> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default();
+        let args: Vec<&str> = words.collect();
+        let usage = |text: &str| Err(EngineError::Plan(format!("usage: {}", text)));
+
+        match (command, args.as_slice()) {
+            (".help", []) => Ok(format!("{}\n", HELP)),
+            (".quit" | ".exit", []) => {
+                self.quit = true;
+                Ok(String::new())
+            }
+            (".tables", []) => {
+                let mut names = self.engine.catalog().table_names();
+                names.sort();
+                Ok(names.iter().map(|name| format!("{}\n", name)).collect())
+            }
+            (".schema", [table]) => {
+                let schema = self.engine.catalog().schema(table)?;
+                let columns: Vec<String> = schema
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        let null = if field.nullable { "" } else { " NOT NULL" };
+                        format!("  {} {}{}", field.name, sql_type_name(field.data_type), null)
+                    })
+                    .collect();
+                Ok(format!("CREATE TABLE {} (\n{}\n);\n", table, columns.join(",\n")))
+            }
+            (".schema", _) => usage(".schema TABLE"),
+            (".timer", [setting]) => {
+                self.timer = match setting.to_ascii_lowercase().as_str() {
+                    "on" => true,
+                    "off" => false,
+                    _ => return usage(".timer on|off"),
+                };
+                Ok(String::new())
+            }
+            (".timer", _) => usage(".timer on|off"),
+            (".mode", [mode]) => match OutputMode::parse(mode) {
+                Some(mode) => {
+                    self.mode = mode;
+                    Ok(String::new())
+                }
+                None => usage(".mode table|csv|json|ndjson"),
+            },
+            (".thresholds", []) => Ok(thresholds().to_string()),
+            (".calibrate", []) => {
+                let path = default_config_path()
+                    .ok_or_else(|| EngineError::Plan("HOME is not set, nowhere to save thresholds".to_string()))?;
+                let thresholds = recalibrate(&path)?;
+                Ok(format!("{}Saved to {}\n", thresholds, path.display()))
+            }
+            (".mode", _) => usage(".mode table|csv|json|ndjson"),
+            _ => Err(EngineError::Unsupported(format!(
+                "meta-command {} (see .help)",
+                line
+            ))),
+        }
+    }
+}
+
+// Splits off every statement terminated by `;`, ignoring semicolons inside quotes and
+// comments, and returns the unterminated remainder. Text made only of comments is dropped,
+// unless a block comment is still open.
+fn split_statements(text: &str) -> (Vec<String>, String) {
+    let mut statements = Vec::new();
+    let mut start = 0;
+    let mut quote: Option<char> = None;
+    let mut has_code = false;
+    let mut open_comment = false;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '-') if chars.peek().is_some_and(|&(_, next)| next == '-') => {
+                // Skip to the end of the line comment
+                for (_, c) in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            (None, '/') if chars.peek().is_some_and(|&(_, next)| next == '*') => {
+                // Skip past the `*/` that closes the block comment, if it has arrived yet
+                chars.next();
+                open_comment = true;
+                let mut previous = None;
+                for (_, c) in chars.by_ref() {
+                    if previous == Some('*') && c == '/' {
+                        open_comment = false;
+                        break;
+                    }
+                    previous = Some(c);
+                }
+            }
+            (None, ';') => {
+                let statement = text[start..i].trim();
+                if has_code {
+                    statements.push(statement.to_string());
+                }
+                start = i + 1;
+                has_code = false;
+            }
+            (None, c) => {
+                if c == '\'' || c == '"' {
+                    quote = Some(c);
+                }
+                has_code |= !c.is_whitespace();
+            }
+        }
+    }
+    let rest = &text[start..];
+    let rest = if has_code || open_comment { rest } else { "" };
+    (statements, rest.to_string())
+}
+
+fn sql_type_name(data_type: DataType) -> &'static str {
+    match data_type {
+        DataType::Int32 => "INT",
+        DataType::Int64 => "BIGINT",
+        DataType::Float64 => "DOUBLE",
+        DataType::Utf8 => "VARCHAR",
+        DataType::Boolean => "BOOLEAN",
+        DataType::Date => "DATE",
+        DataType::Null => "NULL",
+    }
+}
+
+// Result formatting
+
+pub fn format_batch(batch: &RecordBatch, mode: OutputMode) -> String {
+    match mode {
+        OutputMode::Table => format_table(batch),
+        OutputMode::Csv => format_csv(batch),
+        OutputMode::Json => format_json(batch),
+        OutputMode::Ndjson => format_ndjson(batch),
+    }
+}
+
+// Aligned columns with numbers right-aligned, followed by the row count
+pub fn format_table(batch: &RecordBatch) -> String {
+    let names: Vec<&str> = batch.schema().fields.iter().map(|f| f.name.as_str()).collect();
+    let cells: Vec<Vec<String>> = batch
+        .rows()
+        .iter()
+        .map(|row| row.iter().map(Value::to_string).collect())
+        .collect();
+    let widths: Vec<usize> = names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            cells
+                .iter()
+                .map(|row| row[i].chars().count())
+                .fold(name.chars().count(), usize::max)
+        })
+        .collect();
+    let numeric: Vec<bool> = batch.schema().fields.iter().map(|f| f.data_type.is_numeric()).collect();
+
+    let border: String = widths.iter().map(|w| format!("+{}", "-".repeat(w + 2))).collect::<String>() + "+\n";
+    let line = |values: &[String], align_numbers: bool| -> String {
+        let mut line = String::new();
+        for (i, value) in values.iter().enumerate() {
+            let pad = " ".repeat(widths[i] - value.chars().count());
+            if align_numbers && numeric[i] {
+                write!(line, "| {}{} ", pad, value).unwrap();
+            } else {
+                write!(line, "| {}{} ", value, pad).unwrap();
+            }
+        }
+        line + "|\n"
+    };
+
+    let mut output = border.clone();
+    output += &line(&names.iter().map(|n| n.to_string()).collect::<Vec<_>>(), false);
+    output += &border;
+    for row in &cells {
+        output += &line(row, true);
+    }
+    if !cells.is_empty() {
+        output += &border;
+    }
+    let plural = if cells.len() == 1 { "" } else { "s" };
+    writeln!(output, "({} row{})", cells.len(), plural).unwrap();
+    output
+}
+
+// RFC 4180 style: fields containing separators or quotes are quoted, NULL is an empty field
+pub fn format_csv(batch: &RecordBatch) -> String {
+    let escape = |text: &str| -> String {
+        if text.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", text.replace('"', "\"\""))
+        } else {
+            text.to_string()
+        }
+    };
+    let mut output = String::new();
+    let header: Vec<String> = batch.schema().fields.iter().map(|f| escape(&f.name)).collect();
+    writeln!(output, "{}", header.join(",")).unwrap();
+    for row in batch.rows() {
+        let fields: Vec<String> = row
+            .iter()
+            .map(|value| match value {
+                Value::Null => String::new(),
+                value => escape(&value.to_string()),
+            })
+            .collect();
+        writeln!(output, "{}", fields.join(",")).unwrap();
+    }
+    output
+}
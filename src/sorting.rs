@@ -1,3 +1,25 @@
+use std::cmp::Ordering;
+
+use crate::batch::RecordBatch;
+use crate::calibration::thresholds;
+use crate::execution::{self, ExecutionReport};
+use crate::types::Row;
+
+pub(crate) const PARALLELIZATION_THRESHOLD: usize = 10000; // Default, below it the rayon::join overhead dominates
+
+fn report(data_size: usize) -> ExecutionReport {
+    ExecutionReport::for_input(data_size, thresholds().sort)
+}
+
+pub fn adaptive_merge_sort<T: Ord + Clone + Send + Sync>(arr: &[T]) -> (Vec<T>, ExecutionReport) {
+    execution::run_adaptive(
+        report(arr.len()),
+        || sequential_merge_sort(arr),
+        || parallel_merge_sort(arr),
+        Vec::len,
+    )
+}
+
 pub fn sequential_merge_sort<T: Ord + Clone>(arr: &[T]) -> Vec<T> {
     if arr.len() <= 1 {
         return arr.to_vec();
@@ -44,6 +66,85 @@ fn merge<T: Ord + Clone>(left: &[T], right: &[T]) -> Vec<T> {
     result
 }
 
+// Sorts a record batch by key columns with a stable merge sort
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortColumn {
+    pub index: usize,
+    pub descending: bool,
+    // Whether NULLs come before all values, regardless of the direction
+    pub nulls_first: bool,
+}
+
+pub fn adaptive_sort_batch(batch: &RecordBatch, keys: &[SortColumn]) -> (RecordBatch, ExecutionReport) {
+    execution::run_adaptive(
+        report(batch.num_rows()),
+        || sequential_sort_batch(batch, keys),
+        || parallel_sort_batch(batch, keys),
+        RecordBatch::num_rows,
+    )
+}
+
+pub fn sequential_sort_batch(batch: &RecordBatch, keys: &[SortColumn]) -> RecordBatch {
+    let sorted = sequential_merge_sort(&sort_rows(batch, keys));
+    batch.take(&sorted.into_iter().map(|row| row.index).collect::<Vec<_>>())
+}
+
+pub fn parallel_sort_batch(batch: &RecordBatch, keys: &[SortColumn]) -> RecordBatch {
+    let sorted = parallel_merge_sort(&sort_rows(batch, keys));
+    batch.take(&sorted.into_iter().map(|row| row.index).collect::<Vec<_>>())
+}
+
+#[derive(Debug, Clone)]
+struct SortRow<'a> {
+    keys: Row,
+    columns: &'a [SortColumn],
+    index: usize,
+}
+
+fn sort_rows<'a>(batch: &RecordBatch, keys: &'a [SortColumn]) -> Vec<SortRow<'a>> {
+    (0..batch.num_rows())
+        .map(|index| SortRow {
+            keys: keys.iter().map(|key| batch.column(key.index).value(index)).collect(),
+            columns: keys,
+            index,
+        })
+        .collect()
+}
+
+impl PartialEq for SortRow<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for SortRow<'_> {}
+
+impl PartialOrd for SortRow<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SortRow<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        for ((a, b), column) in self.keys.iter().zip(&other.keys).zip(self.columns) {
+            let ordering = match (a.is_null(), b.is_null()) {
+                (true, true) => Ordering::Equal,
+                (true, false) if column.nulls_first => Ordering::Less,
+                (true, false) => Ordering::Greater,
+                (false, true) if column.nulls_first => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                _ if column.descending => b.cmp(a),
+                _ => a.cmp(b),
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    }
+}
+
 pub fn sequential_quicksort<T: Ord + Clone>(arr: &mut [T]) {
     if arr.len() <= 1 {
         return;
@@ -1,5 +1,57 @@
-use project::parser::parse_sql;
-use project::scans::{normal_scan, parallel_scan};
+use project::parser::{parse_expr, parse_sql};
+use project::plan::{LogicalPlan, LogicalPlanBuilder, ProjectionItem, SortKey};
+use project::scans::{adaptive_scan_with_filter, normal_scan, parallel_scan};
+use project::aggregations::{
+    adaptive_aggregate, adaptive_avg, adaptive_sum, normal_avg, normal_co_moments, normal_max, normal_min, normal_moments,
+    normal_sum, parallel_avg, parallel_co_moments, parallel_max, parallel_min, parallel_moments, parallel_sum, AggregateFunction,
+};
+use project::aggregations::{
+    normal_approx_percentile, normal_percentile_cont, normal_percentile_disc, parallel_approx_percentile,
+    parallel_percentile_cont, parallel_percentile_disc, TDigest,
+};
+use project::aggregations::{
+    adaptive_distinct_count, normal_approx_distinct_count, normal_distinct_count, parallel_approx_distinct_count,
+    parallel_distinct_count, HyperLogLog,
+};
+use project::batch::{Bitmap, Column, ColumnData, RecordBatch};
+use project::catalog::{Catalog, Field, Schema, TableProvider};
+use project::group_by::{
+    adaptive_group_by_batch, parallel_group_by, parallel_group_by_sets_batch, sequential_group_by, sequential_group_by_sets,
+    sequential_group_by_sets_batch, GroupByResult,
+};
+use project::joins::{
+    adaptive_hash_join_batches, parallel_merge_join, parallel_outer_hash_join, parallel_outer_hash_join_batches,
+    parallel_outer_merge_join, sequential_merge_join, sequential_outer_hash_join, sequential_outer_hash_join_batches,
+    sequential_outer_merge_join, JoinType, OuterPair, Record,
+};
+use project::joins::{
+    parallel_anti_hash_join_by, parallel_anti_join_batches, parallel_anti_merge_join, parallel_semi_hash_join_by,
+    parallel_semi_merge_join, sequential_anti_hash_join_by, sequential_anti_join_batches, sequential_anti_merge_join,
+    sequential_semi_hash_join_by, sequential_semi_join_batches, sequential_semi_merge_join,
+};
+use project::joins::{
+    join_key_type, parallel_anti_merge_join_by, parallel_hash_join_by, parallel_merge_join_by, parallel_outer_merge_join_by,
+    sequential_anti_merge_join_by, sequential_hash_join_batches, sequential_hash_join_by, sequential_merge_join_by,
+    sequential_outer_merge_join_by, sequential_semi_merge_join_by,
+};
+use project::joins::{
+    adaptive_band_join_pairs, parallel_band_join_by, parallel_band_join_pairs, parallel_filter_pairs,
+    parallel_nested_loop_join_by, parallel_nested_loop_join_pairs, sequential_band_join_by, sequential_band_join_pairs,
+    sequential_filter_pairs, sequential_nested_loop_join_by, sequential_nested_loop_join_pairs, BandBound,
+};
+use project::scans::adaptive_filter_batch;
+use project::sorting::{adaptive_sort_batch, SortColumn};
+use project::csv::{parallel_parse_csv, sequential_parse_csv, CsvOptions};
+use project::engine::Engine;
+use project::parquet::{write_parquet, ParquetTable};
+use project::json::{format_json, format_ndjson, parallel_parse_json, sequential_parse_json, JsonOptions};
+use project::shell::{format_csv, OutputMode, Shell};
+use project::expressions::{parallel_evaluate, sequential_evaluate, ScalarExpr};
+use project::calibration::{calibrate, thresholds, Thresholds};
+use project::error::EngineError;
+use project::execution::{ExecutionContext, ExecutionStrategy};
+use project::types::{parse_date, DataType, Decimal, Value};
+use std::ops::{Bound, RangeBounds};
 
 #[cfg(test)]
 mod tests {
@@ -54,5 +106,2608 @@ mod tests {
         // Add more test cases for different parallel operations
     }
 
-    // Add test modules for other components when I implement them (planner, executor)
-}
\ No newline at end of file
+    mod plan_tests {
+        use super::*;
+
+        #[test]
+        fn test_plan_from_query_matches_builder() {
+            let query = match parse_sql("SELECT name FROM users u WHERE age > 30 ORDER BY name LIMIT 5").unwrap() {
+                sqlparser::ast::Statement::Query(query) => query,
+                _ => unreachable!(),
+            };
+            let plan = LogicalPlan::try_from(query.as_ref()).unwrap();
+
+            let expected = LogicalPlanBuilder::scan_with_alias("users", "u")
+                .filter(parse_expr("age > 30").unwrap())
+                .sort(vec![SortKey::asc(parse_expr("name").unwrap())])
+                .project(vec![ProjectionItem::expr(parse_expr("name").unwrap())])
+                .limit(Some(5), 0)
+                .build();
+            assert_eq!(plan, expected);
+        }
+
+        #[test]
+        fn test_execute_built_plan() {
+            let mut engine = Engine::new();
+            let schema = Schema::new(vec![Field::new("x", DataType::Int32, false)]);
+            let rows = (1..=5).map(|x| vec![Value::Int32(x)]).collect();
+            engine.register_table("t", RecordBatch::from_rows(schema, rows).unwrap());
+
+            let plan = LogicalPlanBuilder::scan("t")
+                .filter(parse_expr("x % 2 = 1").unwrap())
+                .sort(vec![SortKey::desc(parse_expr("x").unwrap())])
+                .project(vec![ProjectionItem::aliased(parse_expr("x * 10").unwrap(), "y")])
+                .build();
+            let result = engine.execute_plan(&plan).unwrap();
+            assert_eq!(result.columns(), vec!["y"]);
+            assert_eq!(result.rows(), vec![vec![Value::Int32(50)], vec![Value::Int32(30)], vec![Value::Int32(10)]]);
+        }
+    }
+
+    mod catalog_tests {
+        use super::*;
+
+        #[test]
+        fn test_table_rows_checked_against_schema() {
+            let schema = Schema::new(vec![
+                Field::new("id", DataType::Int32, false),
+                Field::new("name", DataType::Utf8, true),
+            ]);
+            assert!(RecordBatch::from_rows(schema.clone(), vec![vec![Value::Int32(1), Value::Utf8("a".into())]]).is_ok());
+            assert!(matches!(
+                RecordBatch::from_rows(schema.clone(), vec![vec![Value::Utf8("a".into()), Value::Int32(1)]]),
+                Err(EngineError::SchemaMismatch(_))
+            ));
+            assert!(matches!(
+                RecordBatch::from_rows(schema, vec![vec![Value::Int32(1)]]),
+                Err(EngineError::SchemaMismatch(_))
+            ));
+        }
+
+        #[test]
+        fn test_plan_resolves_against_catalog() {
+            let mut catalog = Catalog::new();
+            let schema = Schema::new(vec![
+                Field::new("id", DataType::Int32, false),
+                Field::new("score", DataType::Float64, true),
+            ]);
+            catalog.register_table("Scores", RecordBatch::from_rows(schema, vec![]).unwrap());
+            assert_eq!(catalog.table_names(), vec!["scores"]);
+            assert_eq!(catalog.schema("SCORES").unwrap().field("score").unwrap().data_type, DataType::Float64);
+
+            let engine = Engine::with_catalog(catalog);
+            let plan = engine.create_logical_plan("SELECT s.id, score * 2 AS doubled FROM scores s").unwrap();
+            let fields = plan.schema(engine.catalog()).unwrap();
+            assert_eq!(fields[1].name, "doubled");
+            assert_eq!(fields[1].data_type, DataType::Float64);
+            assert!(fields[1].nullable);
+
+            assert!(matches!(
+                engine.create_logical_plan("SELECT x.id FROM scores s"),
+                Err(EngineError::ColumnNotFound(_))
+            ));
+            assert!(matches!(
+                engine.create_logical_plan("SELECT a.id FROM scores a JOIN scores b ON a.id = b.id WHERE id > 1"),
+                Err(EngineError::AmbiguousColumn(_))
+            ));
+            assert!(matches!(
+                engine.create_logical_plan("SELECT id FROM scores WHERE score"),
+                Err(EngineError::Plan(_))
+            ));
+        }
+    }
+
+    mod engine_tests {
+        use super::*;
+
+        fn int(v: i32) -> Value {
+            Value::Int32(v)
+        }
+
+        fn text(v: &str) -> Value {
+            Value::Utf8(v.to_string())
+        }
+
+        fn sample_engine() -> Engine {
+            let mut engine = Engine::new();
+            let users = Schema::new(vec![
+                Field::new("id", DataType::Int32, false),
+                Field::new("name", DataType::Utf8, false),
+                Field::new("age", DataType::Int32, false),
+                Field::new("city_id", DataType::Int32, false),
+            ]);
+            let cities = Schema::new(vec![
+                Field::new("id", DataType::Int32, false),
+                Field::new("city", DataType::Utf8, false),
+            ]);
+            engine.register_table(
+                "users",
+                RecordBatch::from_rows(
+                    users,
+                    vec![
+                        vec![int(1), text("Alice"), int(34), int(1)],
+                        vec![int(2), text("Bob"), int(28), int(2)],
+                        vec![int(3), text("Carol"), int(45), int(1)],
+                        vec![int(4), text("Dave"), int(31), int(3)],
+                    ],
+                )
+                .unwrap(),
+            );
+            engine.register_table(
+                "cities",
+                RecordBatch::from_rows(
+                    cities,
+                    vec![
+                        vec![int(1), text("Sydney")],
+                        vec![int(2), text("Melbourne")],
+                        vec![int(3), text("Perth")],
+                    ],
+                )
+                .unwrap(),
+            );
+            engine
+        }
+
+        #[test]
+        fn test_select_where_order_by() {
+            let result = sample_engine()
+                .execute("SELECT name, age + 1 AS next_age FROM users WHERE age > 30 ORDER BY age DESC LIMIT 2")
+                .unwrap();
+            assert_eq!(result.columns(), vec!["name", "next_age"]);
+            assert_eq!(result.rows(), vec![vec![text("Carol"), int(46)], vec![text("Alice"), int(35)]]);
+        }
+
+        #[test]
+        fn test_join_with_filter() {
+            let result = sample_engine()
+                .execute("SELECT u.name, c.city FROM users u JOIN cities c ON u.city_id = c.id WHERE u.age > 30 ORDER BY u.name")
+                .unwrap();
+            assert_eq!(
+                result.rows(),
+                vec![
+                    vec![text("Alice"), text("Sydney")],
+                    vec![text("Carol"), text("Sydney")],
+                    vec![text("Dave"), text("Perth")],
+                ]
+            );
+        }
+
+        #[test]
+        fn test_group_by_aggregates() {
+            let result = sample_engine()
+                .execute("SELECT city_id, COUNT(*) AS n, SUM(age), MAX(age) FROM users GROUP BY city_id ORDER BY n DESC, city_id")
+                .unwrap();
+            assert_eq!(
+                result.rows(),
+                vec![
+                    vec![int(1), Value::Int64(2), Value::Int64(79), int(45)],
+                    vec![int(2), Value::Int64(1), Value::Int64(28), int(28)],
+                    vec![int(3), Value::Int64(1), Value::Int64(31), int(31)],
+                ]
+            );
+        }
+
+        #[test]
+        fn test_distinct_and_union() {
+            let engine = sample_engine();
+            let result = engine
+                .execute("SELECT DISTINCT city_id FROM users ORDER BY city_id")
+                .unwrap();
+            assert_eq!(result.rows(), vec![vec![int(1)], vec![int(2)], vec![int(3)]]);
+
+            let result = engine
+                .execute("SELECT id FROM users WHERE id < 3 UNION SELECT id FROM cities ORDER BY id DESC")
+                .unwrap();
+            assert_eq!(result.rows(), vec![vec![int(3)], vec![int(2)], vec![int(1)]]);
+
+            let result = engine
+                .execute("SELECT id FROM users UNION ALL SELECT id FROM cities")
+                .unwrap();
+            assert_eq!(result.rows().len(), 7);
+        }
+
+        #[test]
+        fn test_negative_zero_and_nan_keys() {
+            let mut engine = Engine::new();
+            let floats = RecordBatch::try_new(
+                Schema::new(vec![Field::new("x", DataType::Float64, false)]),
+                vec![ColumnData::Float64(vec![-0.0, 1.0, 0.0, f64::NAN, 2.0, -f64::NAN]).into()],
+            )
+            .unwrap();
+            engine.register_table("floats", floats);
+
+            // -0.0 and 0.0 are one group, and so are all NaNs
+            let result = engine.execute("SELECT x, COUNT(*) FROM floats GROUP BY x ORDER BY x").unwrap();
+            let counts: Vec<Value> = result.rows().into_iter().map(|row| row[1].clone()).collect();
+            assert_eq!(counts, vec![Value::Int64(2), Value::Int64(1), Value::Int64(1), Value::Int64(2)]);
+            assert_eq!(result.rows()[0][0], Value::Float64(0.0));
+
+            assert_eq!(engine.execute("SELECT DISTINCT x FROM floats").unwrap().num_rows(), 4);
+            let result = engine.execute("SELECT COUNT(DISTINCT x) FROM floats WHERE x BETWEEN -1 AND 2").unwrap();
+            assert_eq!(result.rows(), vec![vec![Value::Int64(3)]]);
+        }
+
+        #[test]
+        fn test_unknown_table_and_column() {
+            let engine = sample_engine();
+            assert!(matches!(engine.execute("SELECT * FROM orders"), Err(EngineError::TableNotFound(_))));
+            assert!(matches!(engine.execute("SELECT salary FROM users"), Err(EngineError::ColumnNotFound(_))));
+        }
+    }
+
+    mod batch_tests {
+        use super::*;
+
+        fn orders() -> RecordBatch {
+            let schema = Schema::new(vec![
+                Field::new("customer_id", DataType::Int32, false),
+                Field::new("amount", DataType::Int32, false),
+                Field::new("day", DataType::Date, false),
+            ]);
+            let days = ["2024-01-15", "2024-02-29", "2024-03-01", "2024-03-02"]
+                .iter()
+                .map(|d| parse_date(d).unwrap())
+                .collect();
+            RecordBatch::try_new(
+                schema,
+                vec![
+                    ColumnData::Int32(vec![1, 2, 1, 3]).into(),
+                    ColumnData::Int32(vec![10, 20, 30, 40]).into(),
+                    ColumnData::Date(days).into(),
+                ],
+            )
+            .unwrap()
+        }
+
+        #[test]
+        fn test_batch_rejects_mismatched_columns() {
+            let schema = Schema::new(vec![
+                Field::new("a", DataType::Int32, false),
+                Field::new("b", DataType::Utf8, false),
+            ]);
+            assert!(matches!(
+                RecordBatch::try_new(schema.clone(), vec![ColumnData::Int32(vec![1]).into(), ColumnData::Int64(vec![1]).into()]),
+                Err(EngineError::SchemaMismatch(_))
+            ));
+            assert!(matches!(
+                RecordBatch::try_new(schema, vec![ColumnData::Int32(vec![1, 2]).into(), ColumnData::Utf8(vec!["x".into()]).into()]),
+                Err(EngineError::SchemaMismatch(_))
+            ));
+        }
+
+        #[test]
+        fn test_operators_compose_over_batches() {
+            let customers = RecordBatch::try_new(
+                Schema::new(vec![
+                    Field::new("id", DataType::Int32, false),
+                    Field::new("name", DataType::Utf8, false),
+                ]),
+                vec![ColumnData::Int32(vec![1, 2]).into(), ColumnData::Utf8(vec!["Ann".into(), "Ben".into()]).into()],
+            )
+            .unwrap();
+
+            let (joined, _) = adaptive_hash_join_batches(&orders(), &customers, &[0], &[0]);
+            assert_eq!(joined.num_rows(), 3);
+            assert_eq!(joined.num_columns(), 5);
+
+            let mask: Vec<bool> = (0..joined.num_rows())
+                .map(|i| joined.column(2).value(i) >= Value::Date(parse_date("2024-02-01").unwrap()))
+                .collect();
+            let (recent, _) = adaptive_filter_batch(&joined, &mask);
+            assert_eq!(recent.num_rows(), 2);
+
+            let (totals, _) =
+                adaptive_group_by_batch(&joined, &[4], &[(AggregateFunction::Sum, vec![1]), (AggregateFunction::Count, vec![])])
+                    .unwrap();
+            let (sorted, _) = adaptive_sort_batch(&totals, &[SortColumn { index: 0, descending: true, nulls_first: false }]);
+            assert_eq!(
+                sorted.rows(),
+                vec![
+                    vec![Value::Utf8("Ben".into()), Value::Int64(20), Value::Int64(1)],
+                    vec![Value::Utf8("Ann".into()), Value::Int64(40), Value::Int64(2)],
+                ]
+            );
+        }
+
+        #[test]
+        fn test_query_on_date_column() {
+            let mut engine = Engine::new();
+            engine.register_table("orders", orders());
+            let result = engine
+                .execute("SELECT day, amount FROM orders WHERE day >= DATE '2024-02-29' ORDER BY day DESC")
+                .unwrap();
+            assert_eq!(result.batch().column(0).data_type(), DataType::Date);
+            assert_eq!(
+                result.rows().iter().map(|row| row[0].to_string()).collect::<Vec<_>>(),
+                vec!["2024-03-02", "2024-03-01", "2024-02-29"]
+            );
+        }
+    }
+
+    mod null_tests {
+        use super::*;
+
+        // scores(id, team, score) with NULL teams and scores
+        fn null_engine() -> Engine {
+            let schema = Schema::new(vec![
+                Field::new("id", DataType::Int32, false),
+                Field::new("team", DataType::Int32, true),
+                Field::new("score", DataType::Int32, true),
+            ]);
+            let team: Bitmap = [true, true, false, true].into_iter().collect();
+            let score: Bitmap = [true, false, true, true].into_iter().collect();
+            let scores = RecordBatch::try_new(
+                schema,
+                vec![
+                    ColumnData::Int32(vec![1, 2, 3, 4]).into(),
+                    Column::with_validity(ColumnData::Int32(vec![1, 1, 0, 2]), team),
+                    Column::with_validity(ColumnData::Int32(vec![10, 0, 30, 40]), score),
+                ],
+            )
+            .unwrap();
+            let mut engine = Engine::new();
+            engine.register_table("scores", scores);
+            engine
+        }
+
+        #[test]
+        fn test_empty_input_aggregates() {
+            assert_eq!(normal_min::<i32>(&[]), None);
+            assert_eq!(normal_avg::<i32>(&[]).unwrap(), None);
+            assert_eq!(parallel_max(&[3, 7]), Some(7));
+        }
+
+        #[test]
+        fn test_aggregates_skip_nulls() {
+            let engine = null_engine();
+            let result = engine
+                .execute("SELECT COUNT(*), COUNT(score), SUM(score), AVG(score), MIN(team) FROM scores")
+                .unwrap();
+            assert_eq!(
+                result.rows(),
+                vec![vec![Value::Int64(4), Value::Int64(3), Value::Int64(80), Value::Float64(80.0 / 3.0), Value::Int32(1)]]
+            );
+
+            let result = engine
+                .execute("SELECT COUNT(score), SUM(score), MAX(score) FROM scores WHERE id = 2")
+                .unwrap();
+            assert_eq!(result.rows(), vec![vec![Value::Int64(0), Value::Null, Value::Null]]);
+        }
+
+        #[test]
+        fn test_three_valued_logic() {
+            let engine = null_engine();
+            let ids = |sql: &str| -> Vec<Value> { engine.execute(sql).unwrap().rows().into_iter().map(|r| r[0].clone()).collect() };
+            // NOT (NULL > 15) is NULL, so row 2 is filtered out either way
+            assert_eq!(ids("SELECT id FROM scores WHERE score > 15 ORDER BY id"), vec![Value::Int32(3), Value::Int32(4)]);
+            assert_eq!(ids("SELECT id FROM scores WHERE NOT score > 15 ORDER BY id"), vec![Value::Int32(1)]);
+            assert_eq!(ids("SELECT id FROM scores WHERE score > 15 OR team = 1 ORDER BY id"), vec![Value::Int32(1), Value::Int32(2), Value::Int32(3), Value::Int32(4)]);
+            assert_eq!(ids("SELECT id FROM scores WHERE score IS NULL OR team IS NULL ORDER BY id"), vec![Value::Int32(2), Value::Int32(3)]);
+            assert_eq!(engine.execute("SELECT score + 1 FROM scores WHERE id = 2").unwrap().rows(), vec![vec![Value::Null]]);
+        }
+
+        #[test]
+        fn test_null_keys_never_join_but_group_together() {
+            let engine = null_engine();
+            let result = engine
+                .execute("SELECT a.id, b.id FROM scores a JOIN scores b ON a.team = b.team WHERE a.id < b.id")
+                .unwrap();
+            assert_eq!(result.rows(), vec![vec![Value::Int32(1), Value::Int32(2)]]);
+
+            let result = engine
+                .execute("SELECT team, COUNT(*) FROM scores GROUP BY team ORDER BY team")
+                .unwrap();
+            assert_eq!(
+                result.rows(),
+                vec![
+                    vec![Value::Int32(1), Value::Int64(2)],
+                    vec![Value::Int32(2), Value::Int64(1)],
+                    vec![Value::Null, Value::Int64(1)],
+                ]
+            );
+        }
+
+        #[test]
+        fn test_nulls_first_and_last() {
+            let engine = null_engine();
+            let ids = |sql: &str| -> Vec<Value> { engine.execute(sql).unwrap().rows().into_iter().map(|r| r[0].clone()).collect() };
+            let expected = |order: [i32; 4]| order.iter().map(|&i| Value::Int32(i)).collect::<Vec<_>>();
+            assert_eq!(ids("SELECT id FROM scores ORDER BY score"), expected([1, 3, 4, 2]));
+            assert_eq!(ids("SELECT id FROM scores ORDER BY score DESC"), expected([2, 4, 3, 1]));
+            assert_eq!(ids("SELECT id FROM scores ORDER BY score NULLS FIRST"), expected([2, 1, 3, 4]));
+            assert_eq!(ids("SELECT id FROM scores ORDER BY score DESC NULLS LAST"), expected([4, 3, 1, 2]));
+        }
+    }
+
+    mod expression_tests {
+        use super::*;
+
+        // people(id, name, age) with one NULL name and one NULL age
+        fn people() -> RecordBatch {
+            let schema = Schema::new(vec![
+                Field::new("id", DataType::Int32, false),
+                Field::new("name", DataType::Utf8, true),
+                Field::new("age", DataType::Int32, true),
+            ]);
+            let names = ["Alice", "bob", "Carol_1", "dave", ""].iter().map(|s| s.to_string()).collect();
+            RecordBatch::try_new(
+                schema,
+                vec![
+                    ColumnData::Int32(vec![1, 2, 3, 4, 5]).into(),
+                    Column::with_validity(ColumnData::Utf8(names), [true, true, true, true, false].into_iter().collect()),
+                    Column::with_validity(ColumnData::Int32(vec![30, 0, 45, 17, 60]), [true, false, true, true, true].into_iter().collect()),
+                ],
+            )
+            .unwrap()
+        }
+
+        fn eval(sql: &str) -> Vec<Value> {
+            let batch = people();
+            let expr = ScalarExpr::bind_schema(&parse_expr(sql).unwrap(), batch.schema()).unwrap();
+            let column = sequential_evaluate(&expr, &batch).unwrap();
+            (0..column.len()).map(|i| column.value(i)).collect()
+        }
+
+        fn bools(values: &[Option<bool>]) -> Vec<Value> {
+            values.iter().map(|v| v.map(Value::Boolean).unwrap_or(Value::Null)).collect()
+        }
+
+        #[test]
+        fn test_like_and_ilike() {
+            let (t, f) = (Some(true), Some(false));
+            assert_eq!(eval("name LIKE '%o%'"), bools(&[f, t, t, f, None]));
+            assert_eq!(eval("name ILIKE 'a%'"), bools(&[t, f, f, f, None]));
+            assert_eq!(eval("name LIKE '_ave'"), bools(&[f, f, f, t, None]));
+            assert_eq!(eval("name LIKE '%!_1' ESCAPE '!'"), bools(&[f, f, t, f, None]));
+            assert_eq!(eval("name NOT LIKE '%a%'"), bools(&[t, t, f, f, None]));
+        }
+
+        #[test]
+        fn test_float_comparisons_follow_ieee() {
+            let schema = Schema::new(vec![Field::new("x", DataType::Float64, false), Field::new("y", DataType::Float64, false)]);
+            let batch = RecordBatch::try_new(
+                schema,
+                vec![ColumnData::Float64(vec![-0.0, f64::NAN, f64::NAN]).into(), ColumnData::Float64(vec![0.0, f64::NAN, 1.0]).into()],
+            )
+            .unwrap();
+            let (t, f) = (Some(true), Some(false));
+            // -0.0 equals 0.0, and NaN is neither equal to, less than nor greater than anything
+            for (sql, expected) in [
+                ("x = 0", bools(&[t, f, f])),
+                ("x = y", bools(&[t, f, f])),
+                ("x < y", bools(&[f, f, f])),
+                ("x >= y", bools(&[t, f, f])),
+                ("x <> y", bools(&[f, t, t])),
+                ("x < 1", bools(&[t, f, f])),
+            ] {
+                let expr = ScalarExpr::bind_schema(&parse_expr(sql).unwrap(), batch.schema()).unwrap();
+                // The vectorized kernels and the row at a time path agree
+                let column = sequential_evaluate(&expr, &batch).unwrap();
+                let rows: Vec<Value> = (0..3).map(|i| expr.evaluate_row(&batch, i).unwrap()).collect();
+                assert_eq!((0..3).map(|i| column.value(i)).collect::<Vec<_>>(), expected, "{}", sql);
+                assert_eq!(rows, expected, "{}", sql);
+            }
+        }
+
+        #[test]
+        fn test_in_and_between_with_nulls() {
+            let (t, f) = (Some(true), Some(false));
+            assert_eq!(eval("age IN (17, 30)"), bools(&[t, None, f, t, f]));
+            // x NOT IN (.., NULL) is never true
+            assert_eq!(eval("age NOT IN (17, NULL)"), bools(&[None, None, None, f, None]));
+            assert_eq!(eval("age BETWEEN 18 AND 45"), bools(&[t, None, t, f, f]));
+            assert_eq!(eval("age NOT BETWEEN 18 AND 45"), bools(&[f, None, f, t, t]));
+        }
+
+        #[test]
+        fn test_case_and_cast() {
+            let text = |v: &str| Value::Utf8(v.to_string());
+            assert_eq!(
+                eval("CASE WHEN age < 18 THEN 'minor' WHEN age >= 18 THEN 'adult' ELSE 'unknown' END"),
+                vec![text("adult"), text("unknown"), text("adult"), text("minor"), text("adult")]
+            );
+            // The division only runs on the rows that reach its branch
+            assert_eq!(
+                eval("CASE id WHEN 2 THEN 0 ELSE 60 / (id - 2) END"),
+                vec![Value::Int32(-60), Value::Int32(0), Value::Int32(60), Value::Int32(30), Value::Int32(20)]
+            );
+            assert_eq!(eval("CAST(id AS VARCHAR) || '!'")[0], text("1!"));
+            assert_eq!(eval("CAST('2024-02-29' AS DATE)")[0], Value::Date(parse_date("2024-02-29").unwrap()));
+            assert_eq!(eval("TRY_CAST(name AS INT)"), vec![Value::Null; 5]);
+
+            let batch = people();
+            let expr = ScalarExpr::bind_schema(&parse_expr("CAST(name AS INT)").unwrap(), batch.schema()).unwrap();
+            assert!(matches!(sequential_evaluate(&expr, &batch), Err(EngineError::Execution(_))));
+            let mismatch = ScalarExpr::bind_schema(&parse_expr("name + 1").unwrap(), batch.schema());
+            assert!(matches!(mismatch, Err(EngineError::Plan(_))));
+        }
+
+        #[test]
+        fn test_parallel_evaluation_matches_sequential() {
+            let n = 250_000;
+            let schema = Schema::new(vec![Field::new("x", DataType::Int64, true), Field::new("y", DataType::Float64, false)]);
+            let validity: Bitmap = (0..n).map(|i| i % 7 != 0).collect();
+            let batch = RecordBatch::try_new(
+                schema,
+                vec![
+                    Column::with_validity(ColumnData::Int64((0..n as i64).collect()), validity),
+                    ColumnData::Float64((0..n).map(|i| i as f64 / 3.0).collect()).into(),
+                ],
+            )
+            .unwrap();
+            for sql in ["x * 2 + 1 > y OR x IS NULL", "CASE WHEN x % 2 = 0 THEN y ELSE -x END", "x IN (1, 2, 3) AND y < 10"] {
+                let expr = ScalarExpr::bind_schema(&parse_expr(sql).unwrap(), batch.schema()).unwrap();
+                let sequential = sequential_evaluate(&expr, &batch).unwrap();
+                let parallel = parallel_evaluate(&expr, &batch).unwrap();
+                assert_eq!(parallel.len(), n);
+                assert!((0..n).all(|i| sequential.value(i) == parallel.value(i)), "{}", sql);
+            }
+        }
+
+        #[test]
+        fn test_expressions_in_queries() {
+            let mut engine = Engine::new();
+            engine.register_table("people", people());
+            let result = engine
+                .execute("SELECT id FROM people WHERE name LIKE '%a%' OR age BETWEEN 40 AND 50 ORDER BY id")
+                .unwrap();
+            assert_eq!(result.rows(), vec![vec![Value::Int32(3)], vec![Value::Int32(4)]]);
+            let result = engine
+                .execute("SELECT CASE WHEN age IS NULL THEN 'n/a' ELSE 'known' END, COUNT(*) FROM people GROUP BY CASE WHEN age IS NULL THEN 'n/a' ELSE 'known' END ORDER BY 1")
+                .unwrap();
+            assert_eq!(
+                result.rows(),
+                vec![
+                    vec![Value::Utf8("known".to_string()), Value::Int64(4)],
+                    vec![Value::Utf8("n/a".to_string()), Value::Int64(1)],
+                ]
+            );
+        }
+    }
+
+    mod shell_tests {
+        use super::*;
+
+        fn shell() -> Shell {
+            let schema = Schema::new(vec![Field::new("id", DataType::Int32, false), Field::new("name", DataType::Utf8, true)]);
+            let users = RecordBatch::try_new(
+                schema,
+                vec![
+                    ColumnData::Int32(vec![1, 2]).into(),
+                    Column::with_validity(ColumnData::Utf8(vec!["a, \"b\"".to_string(), String::new()]), [true, false].into_iter().collect()),
+                ],
+            )
+            .unwrap();
+            let mut engine = Engine::new();
+            engine.register_table("users", users);
+            Shell::new(engine)
+        }
+
+        fn outputs(shell: &mut Shell, line: &str) -> Vec<String> {
+            shell.feed_line(line).into_iter().map(|output| output.unwrap()).collect()
+        }
+
+        #[test]
+        fn test_multi_line_and_multiple_statements() {
+            let mut shell = shell();
+            assert!(outputs(&mut shell, "SELECT id, name").is_empty());
+            assert!(shell.is_pending());
+            let table = outputs(&mut shell, "FROM users WHERE name <> ';' ORDER BY id;");
+            assert_eq!(table, vec!["+----+--------+\n| id | name   |\n+----+--------+\n|  1 | a, \"b\" |\n+----+--------+\n(1 row)\n"]);
+            assert!(!shell.is_pending());
+
+            assert_eq!(outputs(&mut shell, "SELECT 1 FROM users; SELECT 2 FROM users; -- trailing comment").len(), 2);
+            assert!(!shell.is_pending());
+            assert!(outputs(&mut shell, "SELECT COUNT(*) FROM users").is_empty());
+            assert!(shell.finish().unwrap().unwrap().contains(" 2 |"));
+        }
+
+        #[test]
+        fn test_block_comments() {
+            let mut shell = shell();
+            // A semicolon inside a block comment ends no statement, even across lines
+            assert!(outputs(&mut shell, "SELECT id /* first; then").is_empty());
+            assert!(shell.is_pending());
+            assert_eq!(outputs(&mut shell, "more; */ FROM users WHERE id = 1;").len(), 1);
+            assert!(!shell.is_pending());
+
+            // Comments alone are no statement, and an open one keeps the input pending
+            assert!(outputs(&mut shell, "/* note; */ ; /* a").is_empty());
+            assert!(shell.is_pending());
+            assert!(outputs(&mut shell, "*/").is_empty());
+            assert!(!shell.is_pending());
+            assert_eq!(outputs(&mut shell, "/**/ SELECT 1 FROM users /* ; */;").len(), 1);
+        }
+
+        #[test]
+        fn test_meta_commands() {
+            let mut shell = shell();
+            assert_eq!(outputs(&mut shell, ".tables"), vec!["users\n"]);
+            assert_eq!(outputs(&mut shell, ".schema users"), vec!["CREATE TABLE users (\n  id INT NOT NULL,\n  name VARCHAR\n);\n"]);
+            outputs(&mut shell, ".mode csv");
+            assert_eq!(shell.mode(), OutputMode::Csv);
+            outputs(&mut shell, ".timer on");
+            let output = outputs(&mut shell, "SELECT id FROM users;");
+            assert!(output[0].starts_with("id\n1\n2\nRun Time: "));
+
+            assert!(matches!(shell.feed_line(".schema missing")[0], Err(EngineError::TableNotFound(_))));
+            assert!(shell.feed_line(".mode xml")[0].is_err());
+            assert!(shell.feed_line(".frobnicate")[0].is_err());
+            assert!(!shell.should_quit());
+            outputs(&mut shell, ".quit");
+            assert!(shell.should_quit());
+        }
+
+        #[test]
+        fn test_csv_and_json_output() {
+            let mut shell = shell();
+            let result = shell.engine().execute("SELECT id, name FROM users ORDER BY id").unwrap();
+            assert_eq!(format_csv(result.batch()), "id,name\n1,\"a, \"\"b\"\"\"\n2,\n");
+            assert_eq!(format_json(result.batch()), "[\n{\"id\":1,\"name\":\"a, \\\"b\\\"\"},\n{\"id\":2,\"name\":null}\n]\n");
+            assert!(matches!(shell.feed_line("SELECT nope FROM users;")[0], Err(EngineError::ColumnNotFound(_))));
+        }
+    }
+
+    mod csv_tests {
+        use super::*;
+
+        #[test]
+        fn test_infers_types_and_handles_quotes_and_nulls() {
+            let text = "id,name,score,joined,active\n1,\"Smith, Ann\",1.5,2024-01-31,true\n2,\"say \"\"hi\"\"\nthere\",,2024-02-01,false\r\n\n3,NULL,7,,TRUE\n4,\"\",3000000000,2024-03-01,false\n";
+            let batch = sequential_parse_csv(text, &CsvOptions::default()).unwrap();
+            let types: Vec<DataType> = batch.schema().fields.iter().map(|f| f.data_type).collect();
+            assert_eq!(types, vec![DataType::Int32, DataType::Utf8, DataType::Float64, DataType::Date, DataType::Boolean]);
+            assert_eq!(batch.num_rows(), 4);
+            assert_eq!(batch.row(0)[1], Value::Utf8("Smith, Ann".to_string()));
+            assert_eq!(batch.row(1)[1], Value::Utf8("say \"hi\"\nthere".to_string()));
+            assert_eq!(batch.row(1)[2], Value::Null);
+            assert_eq!(batch.row(2)[1], Value::Null);
+            assert_eq!(batch.row(2)[3], Value::Null);
+            // A quoted empty string is a value, not a NULL marker
+            assert_eq!(batch.row(3)[1], Value::Utf8(String::new()));
+            assert_eq!(batch.row(3)[2], Value::Float64(3e9));
+
+            let options = CsvOptions { has_header: false, delimiter: ';', ..CsvOptions::default() };
+            let batch = sequential_parse_csv("1;x\n2;y\n", &options).unwrap();
+            assert_eq!(batch.schema().fields[1].name, "column_2");
+            assert_eq!(batch.num_rows(), 2);
+        }
+
+        #[test]
+        fn test_empty_lines_of_one_column_files_are_null() {
+            let batch = sequential_parse_csv("v\n1\n\n3\n", &CsvOptions::default()).unwrap();
+            assert_eq!(batch.schema().fields[0].data_type, DataType::Int32);
+            assert_eq!(batch.rows(), vec![vec![Value::Int32(1)], vec![Value::Null], vec![Value::Int32(3)]]);
+            let context = ExecutionContext::builder().num_threads(4).build().unwrap();
+            let parallel = context.install(|| parallel_parse_csv("v\n1\n\n3\n", &CsvOptions::default())).unwrap();
+            assert_eq!(parallel.rows(), batch.rows());
+
+            let options = CsvOptions { has_header: false, ..CsvOptions::default() };
+            let batch = sequential_parse_csv("\nx\n\n", &options).unwrap();
+            assert_eq!(batch.rows(), vec![vec![Value::Null], vec![Value::Utf8("x".into())], vec![Value::Null]]);
+            // Files of several columns skip empty lines
+            assert_eq!(sequential_parse_csv("a,b\n1,2\n\n3,4\n", &CsvOptions::default()).unwrap().num_rows(), 2);
+        }
+
+        #[test]
+        fn test_parse_errors_report_line() {
+            let options = CsvOptions { infer_rows: 1, ..CsvOptions::default() };
+            let err = sequential_parse_csv("a,b\n1,x\n\"2\nstill 2\",y\nz,w\n", &options).unwrap_err();
+            assert!(err.to_string().contains("line 3"), "{}", err);
+            let err = sequential_parse_csv("a,b\n1,2\n3\n", &CsvOptions::default()).unwrap_err();
+            assert!(matches!(err, EngineError::SchemaMismatch(ref msg) if msg.starts_with("line 3")), "{}", err);
+            assert!(sequential_parse_csv("a\n\"open\n", &CsvOptions::default()).is_err());
+        }
+
+        #[test]
+        fn test_parallel_parse_matches_sequential() {
+            let mut text = String::from("id,label,value\n");
+            for i in 0..50_000 {
+                if i % 11 == 0 {
+                    text.push_str(&format!("{},\"multi\nline, {}\",\n", i, i));
+                } else {
+                    text.push_str(&format!("{},plain {},{}\n", i, i, i as f64 / 4.0));
+                }
+            }
+            let sequential = sequential_parse_csv(&text, &CsvOptions::default()).unwrap();
+            let parallel = parallel_parse_csv(&text, &CsvOptions::default()).unwrap();
+            assert_eq!(parallel.num_rows(), 50_000);
+            assert_eq!(parallel, sequential);
+        }
+
+        #[test]
+        fn test_parallel_parse_ignores_quotes_inside_unquoted_fields() {
+            // The stray quote in the first row does not open a quoted field
+            let mut text = String::from("name,note\na,5\" screen\n");
+            for i in 0..200 {
+                text.push_str(&format!("{},\"two\nlines\"\n", i));
+            }
+            let sequential = sequential_parse_csv(&text, &CsvOptions::default()).unwrap();
+            assert_eq!(sequential.num_rows(), 201);
+            assert_eq!(sequential.row(0)[1], Value::Utf8("5\" screen".to_string()));
+            let context = ExecutionContext::builder().num_threads(4).build().unwrap();
+            let parallel = context.install(|| parallel_parse_csv(&text, &CsvOptions::default())).unwrap();
+            assert_eq!(parallel, sequential);
+        }
+
+        #[test]
+        fn test_create_table_from_csv() {
+            let path = std::env::temp_dir().join(format!("project_csv_test_{}.csv", std::process::id()));
+            std::fs::write(&path, "city,population\nOslo,709000\nBergen,291000\nParis,\n").unwrap();
+
+            let mut engine = Engine::new();
+            let sql = format!("CREATE TABLE cities FROM '{}';", path.display());
+            assert_eq!(engine.execute_statement(&sql).unwrap().num_rows(), 0);
+            assert!(matches!(engine.execute_statement(&sql), Err(EngineError::Plan(_))));
+            let result = engine
+                .execute_statement("SELECT city FROM cities WHERE population > 500000 OR population IS NULL ORDER BY city")
+                .unwrap();
+            assert_eq!(result.rows(), vec![vec![Value::Utf8("Oslo".to_string())], vec![Value::Utf8("Paris".to_string())]]);
+
+            let mut catalog = Catalog::new();
+            catalog.register_csv("c", &path).unwrap();
+            assert_eq!(catalog.table("c").unwrap().scan(None, &[]).unwrap().num_rows(), 3);
+            std::fs::remove_file(&path).unwrap();
+            assert!(matches!(catalog.register_csv("missing", &path), Err(EngineError::Io(_))));
+        }
+    }
+
+    mod parquet_tests {
+        use super::*;
+
+        // events(id, kind, amount, day): 10 row groups of 100 ids each, NULL kind on every 7th
+        fn write_events(name: &str) -> std::path::PathBuf {
+            let n = 1000;
+            let schema = Schema::new(vec![
+                Field::new("id", DataType::Int64, false),
+                Field::new("kind", DataType::Utf8, true),
+                Field::new("amount", DataType::Float64, false),
+                Field::new("day", DataType::Date, false),
+            ]);
+            let kinds = (0..n).map(|i| ["click", "view", "buy"][i % 3].to_string()).collect();
+            let start = parse_date("2024-01-01").unwrap();
+            let batch = RecordBatch::try_new(
+                schema,
+                vec![
+                    ColumnData::Int64((0..n as i64).collect()).into(),
+                    Column::with_validity(ColumnData::Utf8(kinds), (0..n).map(|i| i % 7 != 0).collect()),
+                    ColumnData::Float64((0..n).map(|i| i as f64 * 0.5).collect()).into(),
+                    ColumnData::Date((0..n as i32).map(|i| start + i / 100).collect()).into(),
+                ],
+            )
+            .unwrap();
+            let path = std::env::temp_dir().join(format!("project_{}_{}.parquet", name, std::process::id()));
+            write_parquet(&path, &batch, 100).unwrap();
+            path
+        }
+
+        #[test]
+        fn test_round_trip_and_projection() {
+            let path = write_events("round_trip");
+            let table = ParquetTable::try_new(&path).unwrap();
+            assert_eq!(table.num_row_groups(), 10);
+            let types: Vec<DataType> = table.schema().fields.iter().map(|f| f.data_type).collect();
+            assert_eq!(types, vec![DataType::Int64, DataType::Utf8, DataType::Float64, DataType::Date]);
+            assert!(table.schema().fields[1].nullable && !table.schema().fields[0].nullable);
+
+            let batch = table.scan(Some(&[3, 1]), &[]).unwrap();
+            assert_eq!(batch.num_columns(), 2);
+            assert_eq!(batch.num_rows(), 1000);
+            assert_eq!(batch.row(0), vec![Value::Date(parse_date("2024-01-01").unwrap()), Value::Null]);
+            assert_eq!(batch.row(999), vec![Value::Date(parse_date("2024-01-10").unwrap()), Value::Utf8("click".to_string())]);
+
+            let all: Vec<usize> = (0..table.num_row_groups()).collect();
+            assert_eq!(table.sequential_read(&[0, 1, 2, 3], &all).unwrap(), table.parallel_read(&[0, 1, 2, 3], &all).unwrap());
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        #[test]
+        fn test_row_groups_pruned_by_statistics() {
+            let path = write_events("pruning");
+            let table = ParquetTable::try_new(&path).unwrap();
+            let prune = |sql: &str| {
+                let expr = ScalarExpr::bind_schema(&parse_expr(sql).unwrap(), table.schema()).unwrap();
+                table.prune_row_groups(&[expr])
+            };
+            assert_eq!(prune("id >= 950"), vec![9]);
+            assert_eq!(prune("id < 100 OR id BETWEEN 420 AND 430"), vec![0, 4]);
+            assert_eq!(prune("250 > id AND id > 120"), vec![1, 2]);
+            assert_eq!(prune("id IN (5, 705)"), vec![0, 7]);
+            assert_eq!(prune("day = DATE '2024-01-03'"), vec![2]);
+            assert_eq!(prune("id = NULL"), Vec::<usize>::new());
+            // Float maxima are not trusted, minima are
+            assert_eq!(prune("amount < 100").len(), 2);
+            assert_eq!(prune("amount > 400").len(), 10);
+            assert_eq!(prune("kind IS NULL").len(), 10);
+            assert_eq!(prune("id + 1 > 950").len(), 10);
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        #[test]
+        fn test_queries_over_parquet() {
+            let path = write_events("queries");
+            let mut engine = Engine::new();
+            engine.execute_statement(&format!("CREATE TABLE events FROM '{}'", path.display())).unwrap();
+
+            let plan = engine.create_logical_plan("SELECT kind, COUNT(*) FROM events WHERE id >= 900 GROUP BY kind").unwrap();
+            let optimized = project::optimizer::optimize(&plan, engine.catalog()).unwrap();
+            assert!(optimized.to_string().contains("Scan: events projection=[0, 1] filters=[id >= 900]"), "{}", optimized);
+
+            let result = engine
+                .execute("SELECT kind, COUNT(*) FROM events WHERE id >= 900 GROUP BY kind ORDER BY kind")
+                .unwrap();
+            assert_eq!(
+                result.rows(),
+                vec![
+                    vec![Value::Utf8("buy".to_string()), Value::Int64(29)],
+                    vec![Value::Utf8("click".to_string()), Value::Int64(29)],
+                    vec![Value::Utf8("view".to_string()), Value::Int64(28)],
+                    vec![Value::Null, Value::Int64(14)],
+                ]
+            );
+            let result = engine.execute("SELECT COUNT(*) FROM events WHERE day < DATE '2024-01-02'").unwrap();
+            assert_eq!(result.rows(), vec![vec![Value::Int64(100)]]);
+            let result = engine.execute("SELECT * FROM events WHERE id = 3").unwrap();
+            assert_eq!(result.num_rows(), 1);
+            assert_eq!(result.columns(), vec!["id", "kind", "amount", "day"]);
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    mod json_tests {
+        use super::*;
+
+        #[test]
+        fn test_nested_objects_and_missing_keys() {
+            let text = concat!(
+                "{\"id\": 1, \"user\": {\"name\": \"ann\", \"address\": {\"city\": \"Oslo\"}}, \"tags\": [\"a\", \"b\"]}\n",
+                "\n",
+                "{\"id\": 2, \"user\": {\"name\": \"b\\u00e9n \\\"b\\\"\"}, \"score\": 2.5}\n",
+                "{\"id\": 3000000000, \"active\": true, \"score\": 4}\n",
+            );
+            let batch = sequential_parse_json(text, &JsonOptions::default()).unwrap();
+            let fields: Vec<(&str, DataType)> = batch.schema().fields.iter().map(|f| (f.name.as_str(), f.data_type)).collect();
+            assert_eq!(
+                fields,
+                vec![
+                    ("id", DataType::Int64),
+                    ("user.name", DataType::Utf8),
+                    ("user.address.city", DataType::Utf8),
+                    ("tags", DataType::Utf8),
+                    ("score", DataType::Float64),
+                    ("active", DataType::Boolean),
+                ]
+            );
+            assert_eq!(batch.row(0)[3], Value::Utf8("[\"a\",\"b\"]".to_string()));
+            assert_eq!(batch.row(1)[1], Value::Utf8("bén \"b\"".to_string()));
+            assert_eq!(batch.row(1)[2], Value::Null);
+            assert_eq!(
+                batch.row(2),
+                vec![Value::Int64(3000000000), Value::Null, Value::Null, Value::Null, Value::Float64(4.0), Value::Boolean(true)]
+            );
+
+            // A top-level array of objects reads the same way
+            let array = format!("[{}]", text.lines().filter(|l| !l.is_empty()).collect::<Vec<_>>().join(",\n"));
+            assert_eq!(sequential_parse_json(&array, &JsonOptions::default()).unwrap(), batch);
+        }
+
+        #[test]
+        fn test_invalid_json_reports_line() {
+            let options = JsonOptions::default();
+            let err = sequential_parse_json("{\"a\": 1}\n{\"a\": 2,}\n", &options).unwrap_err();
+            assert!(err.to_string().contains("line 2"), "{}", err);
+            let err = sequential_parse_json("{\"a\": 1}\n[1]\n", &options).unwrap_err();
+            assert!(err.to_string().contains("line 2: expected a JSON object"), "{}", err);
+            let schema = Schema::new(vec![Field::new("a", DataType::Int32, true)]);
+            let options = JsonOptions { schema: Some(schema), ..JsonOptions::default() };
+            let err = sequential_parse_json("{\"a\": 1}\n\n{\"a\": \"x\"}\n", &options).unwrap_err();
+            assert!(err.to_string().contains("line 3: cannot read \"x\" as Int32 for column a"), "{}", err);
+        }
+
+        #[test]
+        fn test_parallel_parse_matches_sequential() {
+            let text: String = (0..5000)
+                .map(|i| match i % 3 {
+                    0 => format!("{{\"id\": {}, \"kind\": {{\"name\": \"k{}\"}}}}\n", i, i % 7),
+                    1 => format!("{{\"id\": {}, \"amount\": {}}}\n", i, i as f64 / 4.0),
+                    _ => format!("{{\"amount\": null, \"id\": {}}}\n", i),
+                })
+                .collect();
+            let options = JsonOptions::default();
+            let sequential = sequential_parse_json(&text, &options).unwrap();
+            assert_eq!(sequential.num_rows(), 5000);
+            assert_eq!(parallel_parse_json(&text, &options).unwrap(), sequential);
+        }
+
+        #[test]
+        fn test_query_json_file_and_output() {
+            let path = std::env::temp_dir().join(format!("project_events_{}.ndjson", std::process::id()));
+            std::fs::write(
+                &path,
+                "{\"id\": 1, \"user\": {\"name\": \"ann\"}, \"amount\": 10}\n{\"id\": 2, \"user\": {\"name\": \"ben\"}}\n{\"id\": 3, \"user\": {\"name\": \"ann\"}, \"amount\": 5}\n",
+            )
+            .unwrap();
+            let mut shell = Shell::new(Engine::new());
+            let create = format!("CREATE TABLE events FROM '{}';", path.display());
+            assert_eq!(shell.feed_line(&create).pop().unwrap().unwrap(), "");
+            shell.feed_line(".mode ndjson").pop().unwrap().unwrap();
+            let output = shell
+                .feed_line("SELECT \"user.name\" AS name, SUM(amount) AS total FROM events GROUP BY \"user.name\" ORDER BY name;")
+                .pop()
+                .unwrap()
+                .unwrap();
+            assert_eq!(output, "{\"name\":\"ann\",\"total\":15}\n{\"name\":\"ben\",\"total\":null}\n");
+            std::fs::remove_file(&path).unwrap();
+
+            // NDJSON output reads back into the same table
+            let result = shell.engine().execute("SELECT * FROM events ORDER BY id").unwrap();
+            let ndjson = format_ndjson(result.batch());
+            assert_eq!(sequential_parse_json(&ndjson, &JsonOptions::default()).unwrap().rows(), result.rows());
+            assert!(format_json(result.batch()).starts_with("[\n{\"id\":1,\"user.name\":\"ann\",\"amount\":10},\n"));
+        }
+    }
+
+    mod execution_report_tests {
+        use super::*;
+
+        #[test]
+        fn test_strategy_follows_threshold() {
+            let small: Vec<i32> = (0..1000).collect();
+            let (sum, report) = adaptive_sum(&small).unwrap();
+            assert_eq!(sum, 499500);
+            assert_eq!(report.strategy, ExecutionStrategy::Sequential);
+            assert_eq!((report.rows_in, report.rows_out, report.threads), (1000, 1, 1));
+            assert_eq!(report.threshold, 50000);
+            assert_eq!(report.chunk_size, None);
+
+            let large = vec![1i32; 60000];
+            let (sum, report) = adaptive_sum(&large).unwrap();
+            assert_eq!(sum, 60000);
+            assert_eq!(report.strategy, ExecutionStrategy::Parallel);
+            assert_eq!(report.threads, rayon::current_num_threads());
+            assert_eq!(report.strategy.to_string(), "Parallel");
+        }
+
+        #[test]
+        fn test_report_counts_rows_out() {
+            let data: Vec<i32> = (0..600000).collect();
+            let (evens, report) = adaptive_scan_with_filter(&data, |x| x * 2, |x| x % 2 == 0);
+            assert_eq!(evens.len(), 300000);
+            assert_eq!(report.strategy, ExecutionStrategy::Parallel);
+            assert_eq!((report.rows_in, report.rows_out), (600000, 300000));
+            assert!(report.chunk_size.is_some());
+
+            let batch = RecordBatch::try_new(
+                Schema::new(vec![Field::new("x", DataType::Int32, false)]),
+                vec![ColumnData::Int32(vec![3, 1, 2]).into()],
+            )
+            .unwrap();
+            let mask = [true, false, true];
+            let (filtered, report) = adaptive_filter_batch(&batch, &mask);
+            assert_eq!(filtered.num_rows(), 2);
+            assert_eq!((report.strategy, report.rows_in, report.rows_out), (ExecutionStrategy::Sequential, 3, 2));
+            let (_, report) = adaptive_sort_batch(&batch, &[SortColumn { index: 0, descending: false, nulls_first: false }]);
+            assert_eq!(report.rows_out, 3);
+            assert!(report.to_string().starts_with("Sequential: 3 rows in, 3 rows out, 1 thread, threshold 10000, "));
+        }
+
+        #[test]
+        fn test_aggregate_shortcuts_report_sequential() {
+            let column: Column = ColumnData::Int32(vec![1, 2, 3]).into();
+            let (count, report) = adaptive_aggregate(AggregateFunction::Count, None, 5).unwrap();
+            assert_eq!(count, Value::Int64(5));
+            assert_eq!((report.strategy, report.rows_in, report.rows_out), (ExecutionStrategy::Sequential, 5, 1));
+            let (max, report) = adaptive_aggregate(AggregateFunction::Max, Some(&column), 3).unwrap();
+            assert_eq!(max, Value::Int32(3));
+            assert_eq!(report.threshold, 50000);
+        }
+    }
+
+    mod calibration_tests {
+        use super::*;
+
+        #[test]
+        fn test_config_round_trip() {
+            let config = Thresholds {
+                sort: 0,
+                join: usize::MAX,
+                ..Thresholds::default()
+            };
+            let path = std::env::temp_dir().join(format!("project_thresholds_{}", std::process::id()));
+            config.save(&path).unwrap();
+            let text = std::fs::read_to_string(&path).unwrap();
+            assert!(text.contains("join = never\n") && text.contains("sort = 0\n"), "{}", text);
+            assert_eq!(Thresholds::load(&path).unwrap(), config);
+            std::fs::remove_file(&path).unwrap();
+
+            let parsed = Thresholds::parse("# tuned by hand\nscan = 1_000  # rows\n\naggregation=never\n").unwrap();
+            assert_eq!(parsed.scan, 1000);
+            assert_eq!(parsed.aggregation, usize::MAX);
+            assert_eq!(parsed.group_by, Thresholds::DEFAULT.group_by);
+            let err = Thresholds::parse("scan = 10\nsorting = 5\n").unwrap_err();
+            assert!(err.to_string().contains("line 2: unknown operator 'sorting'"), "{}", err);
+            assert!(Thresholds::parse("scan = lots").is_err());
+        }
+
+        #[test]
+        fn test_calibration_picks_a_tried_size() {
+            let sizes = [200, 2000];
+            let learned = calibrate(&sizes).unwrap();
+            let values = [learned.scan, learned.aggregation, learned.group_by, learned.join, learned.sort, learned.expression, learned.parquet];
+            for value in values {
+                // Never below the smallest size tried
+                assert!([200, usize::MAX].contains(&value), "{:?}", learned);
+            }
+            assert!(calibrate(&[]).is_err());
+        }
+
+        #[test]
+        fn test_decisions_read_the_configured_thresholds() {
+            let batch = RecordBatch::try_new(
+                Schema::new(vec![Field::new("x", DataType::Int32, false)]),
+                vec![ColumnData::Int32((0..100).collect()).into()],
+            )
+            .unwrap();
+            let path = std::env::temp_dir().join(format!("project_calibrated_{}.parquet", std::process::id()));
+            write_parquet(&path, &batch, 10).unwrap();
+            let table = ParquetTable::try_new(&path).unwrap();
+            let all: Vec<usize> = (0..table.num_row_groups()).collect();
+
+            let (_, report) = table.adaptive_read(&[0], &all).unwrap();
+            assert_eq!((report.strategy, report.threshold), (ExecutionStrategy::Sequential, thresholds().parquet));
+
+            // A config file that lowers the threshold flips the same read to parallel
+            let config = std::env::temp_dir().join(format!("project_thresholds_parquet_{}", std::process::id()));
+            std::fs::write(&config, "parquet = 0\n").unwrap();
+            let configured = Thresholds::load(&config).unwrap();
+            std::fs::remove_file(&config).unwrap();
+            let (read, report) = table.adaptive_read_with_threshold(&[0], &all, configured.parquet).unwrap();
+            assert_eq!((report.strategy, report.threshold), (ExecutionStrategy::Parallel, 0));
+            assert_eq!(read, batch);
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    mod execution_context_tests {
+        use super::*;
+
+        #[test]
+        fn test_operators_run_on_the_context_pool() {
+            let context = ExecutionContext::builder()
+                .num_threads(2)
+                .stack_size(4 << 20)
+                .thread_name("query-worker")
+                .build()
+                .unwrap();
+            assert_eq!(context.num_threads(), 2);
+
+            let data = vec![1i32; 100000];
+            let (sum, report) = context.install(|| adaptive_sum(&data)).unwrap();
+            assert_eq!(sum, 100000);
+            assert_eq!((report.strategy, report.threads), (ExecutionStrategy::Parallel, 2));
+
+            let names = context.install(|| {
+                let values: Vec<i32> = (0..1000).collect();
+                parallel_scan(&values, |_| std::thread::current().name().unwrap_or_default().to_string())
+            });
+            assert!(names.iter().all(|name| name.starts_with("query-worker-")), "{:?}", &names[..3]);
+        }
+
+        #[test]
+        fn test_engine_runs_queries_in_its_context() {
+            let schema = Schema::new(vec![Field::new("k", DataType::Int32, false), Field::new("v", DataType::Int32, false)]);
+            let n = 200000;
+            let batch = RecordBatch::try_new(
+                schema,
+                vec![
+                    ColumnData::Int32((0..n).map(|i| i % 10).collect()).into(),
+                    ColumnData::Int32((0..n).map(|i| i % 7).collect()).into(),
+                ],
+            )
+            .unwrap();
+            let sql = "SELECT k, SUM(v) FROM t WHERE v > 2 GROUP BY k ORDER BY k";
+
+            let mut default = Engine::new();
+            default.register_table("t", batch.clone());
+            let mut engine = Engine::with_context(ExecutionContext::builder().num_threads(3).build().unwrap());
+            engine.register_table("t", batch);
+            assert_eq!(engine.context().num_threads(), 3);
+            assert_eq!(engine.execute(sql).unwrap(), default.execute(sql).unwrap());
+            assert!(ExecutionContext::builder().num_threads(0).build().is_err());
+        }
+    }
+
+    mod accumulator_tests {
+        use super::*;
+
+        fn sorted<K: Ord>(mut groups: Vec<GroupByResult<K>>) -> Vec<GroupByResult<K>> {
+            groups.sort_by(|a, b| a.key.cmp(&b.key));
+            groups
+        }
+
+        #[test]
+        fn test_many_aggregates_per_group() {
+            let data: Vec<(i32, i32, &str)> = (0..1000).map(|i| (i % 3, i, ["a", "b", "c", "d"][i as usize % 4])).collect();
+            let aggregates = [
+                AggregateFunction::Sum,
+                AggregateFunction::Count,
+                AggregateFunction::Min,
+                AggregateFunction::Max,
+                AggregateFunction::Avg,
+                AggregateFunction::CountDistinct,
+            ];
+            let key_func = |&(k, v, name): &(i32, i32, &str)| {
+                let name = Value::Utf8(name.to_string());
+                (k, vec![Value::Int32(v), Value::Int32(v), name.clone(), name, Value::Int32(v), Value::Int32(v % 10)])
+            };
+            let sequential = sorted(sequential_group_by(&data, key_func, &aggregates).unwrap());
+            assert_eq!(sequential.len(), 3);
+            assert_eq!(
+                sequential[0].values,
+                vec![
+                    Value::Int64((0..1000).step_by(3).sum()),
+                    Value::Int64(334),
+                    Value::Utf8("a".to_string()),
+                    Value::Utf8("d".to_string()),
+                    Value::Float64(499.5),
+                    Value::Int64(10),
+                ]
+            );
+            assert_eq!(sorted(parallel_group_by(&data, key_func, &aggregates).unwrap()), sequential);
+        }
+
+        #[test]
+        fn test_accumulators_merge_and_skip_nulls() {
+            let mut sum = AggregateFunction::Sum.accumulator();
+            sum.update(&[Value::Int64(5)]).unwrap();
+            sum.update(&[Value::Null]).unwrap();
+            let mut other = AggregateFunction::Sum.accumulator();
+            other.update(&[Value::Int64(7)]).unwrap();
+            sum.merge(other.as_ref()).unwrap();
+            assert_eq!(sum.finalize().unwrap(), Value::Int64(12));
+            assert!(sum.merge(AggregateFunction::Count.accumulator().as_ref()).is_err());
+
+            let mut overflow = AggregateFunction::Sum.accumulator();
+            overflow.update(&[Value::Int64(i64::MAX)]).unwrap();
+            assert!(overflow.update(&[Value::Int64(1)]).is_err());
+
+            assert_eq!(AggregateFunction::Avg.accumulator().finalize().unwrap(), Value::Null);
+            assert_eq!(AggregateFunction::Count.accumulator().finalize().unwrap(), Value::Int64(0));
+        }
+
+        #[test]
+        fn test_sql_group_by_over_any_column_type() {
+            let schema = Schema::new(vec![
+                Field::new("k", DataType::Utf8, false),
+                Field::new("price", DataType::Float64, false),
+                Field::new("qty", DataType::Int64, false),
+            ]);
+            let batch = RecordBatch::try_new(
+                schema,
+                vec![
+                    ColumnData::Utf8(vec!["x".into(), "y".into(), "x".into(), "x".into()]).into(),
+                    ColumnData::Float64(vec![1.5, 2.0, 2.5, 1.5]).into(),
+                    ColumnData::Int64(vec![10, 20, 30, 10]).into(),
+                ],
+            )
+            .unwrap();
+            let mut engine = Engine::new();
+            engine.register_table("sales", batch);
+            let result = engine
+                .execute("SELECT k, SUM(price), COUNT(*), MIN(qty), MAX(k), AVG(qty), COUNT(DISTINCT price) FROM sales GROUP BY k ORDER BY k")
+                .unwrap();
+            assert_eq!(
+                result.rows(),
+                vec![
+                    vec![
+                        Value::Utf8("x".into()),
+                        Value::Float64(5.5),
+                        Value::Int64(3),
+                        Value::Int64(10),
+                        Value::Utf8("x".into()),
+                        Value::Float64(50.0 / 3.0),
+                        Value::Int64(2),
+                    ],
+                    vec![
+                        Value::Utf8("y".into()),
+                        Value::Float64(2.0),
+                        Value::Int64(1),
+                        Value::Int64(20),
+                        Value::Utf8("y".into()),
+                        Value::Float64(20.0),
+                        Value::Int64(1),
+                    ],
+                ]
+            );
+            assert!(engine.execute("SELECT SUM(k) FROM sales").is_err());
+        }
+    }
+
+    mod numeric_aggregation_tests {
+        use super::*;
+
+        #[test]
+        fn test_integer_sums_widen_to_bigint() {
+            let data = vec![i32::MAX; 3];
+            assert_eq!(normal_sum(&data).unwrap(), 3 * i32::MAX as i64);
+            assert_eq!(parallel_sum(&data).unwrap(), 3 * i32::MAX as i64);
+            assert_eq!(normal_sum(&[u8::MAX, 1]).unwrap(), 256);
+            assert_eq!(normal_sum(&[-5i8, 3]).unwrap(), -2);
+
+            // Past BIGINT the sum fails instead of wrapping, in every mode
+            let data = vec![i64::MAX, 1];
+            assert!(normal_sum(&data).is_err());
+            assert!(parallel_sum(&data).is_err());
+            assert!(normal_avg(&data).is_err());
+            assert!(normal_sum(&[u64::MAX]).is_err());
+        }
+
+        #[test]
+        fn test_avg_agrees_between_modes() {
+            let data: Vec<i32> = (0..100000).map(|i| i32::MAX - i).collect();
+            let expected = (0..100000).map(|i| (i32::MAX - i) as f64).sum::<f64>() / 100000.0;
+            assert_eq!(normal_avg(&data).unwrap(), Some(expected));
+            assert_eq!(parallel_avg(&data).unwrap(), Some(expected));
+            assert_eq!(adaptive_avg(&data).unwrap().0, Some(expected));
+        }
+
+        #[test]
+        fn test_floats_and_decimals() {
+            assert_eq!(normal_sum(&[1.5f32, 2.25]).unwrap(), 3.75f64);
+            assert_eq!(parallel_max(&[2.5f32, -1.0]), Some(2.5));
+
+            let prices = [Decimal::new(1250, 2), Decimal::new(5, 1), Decimal::new(3, 0)];
+            let sum = normal_sum(&prices).unwrap();
+            assert_eq!((sum.value(), sum.scale()), (1600, 2));
+            assert_eq!(sum.to_string(), "16.00");
+            assert_eq!(parallel_sum(&prices).unwrap(), sum);
+            assert_eq!(normal_min(&prices), Some(Decimal::new(5, 1)));
+            assert_eq!(normal_avg(&prices).unwrap(), Some(16.0 / 3.0));
+            assert_eq!(Decimal::new(-5, 3).to_string(), "-0.005");
+            assert!(normal_sum(&[Decimal::new(i128::MAX, 0), Decimal::new(1, 0)]).is_err());
+        }
+
+        #[test]
+        fn test_min_and_max_order_nan_like_sql() {
+            // NaN is the largest float wherever it appears and however the input is split
+            let mut data: Vec<f64> = (0..100000).map(|i| i as f64).collect();
+            for position in [0, 50000, 99999] {
+                data[position] = f64::NAN;
+                assert!(normal_max(&data).unwrap().is_nan());
+                assert!(parallel_max(&data).unwrap().is_nan());
+                assert_eq!(normal_min(&data), parallel_min(&data));
+                data[position] = position as f64;
+            }
+            assert_eq!(normal_min(&[f64::NAN, 1.0, 2.0]), Some(1.0));
+
+            let batch = RecordBatch::try_new(
+                Schema::new(vec![Field::new("f", DataType::Float64, false)]),
+                vec![ColumnData::Float64(vec![f64::NAN, 1.0, 99999.0]).into()],
+            )
+            .unwrap();
+            let mut engine = Engine::new();
+            engine.register_table("t", batch);
+            let row = engine.execute("SELECT MIN(f), MAX(f) FROM t").unwrap().rows().remove(0);
+            assert_eq!(row[0], Value::Float64(normal_min(&[f64::NAN, 1.0, 99999.0]).unwrap()));
+            assert_eq!(row[1], Value::Float64(f64::NAN));
+            assert!(normal_max(&[1.0, f64::NAN, 99999.0]).unwrap().is_nan());
+        }
+
+        #[test]
+        fn test_sql_decimal_is_double() {
+            let batch = RecordBatch::try_new(
+                Schema::new(vec![Field::new("a", DataType::Int32, false)]),
+                vec![ColumnData::Int32(vec![1, 2, 4]).into()],
+            )
+            .unwrap();
+            let mut engine = Engine::new();
+            engine.register_table("t", batch);
+            let result = engine
+                .execute("SELECT SUM(CAST(a AS DECIMAL(10, 2))), AVG(CAST(a AS NUMERIC)) FROM t")
+                .unwrap();
+            assert_eq!(result.rows(), vec![vec![Value::Float64(7.0), Value::Float64(7.0 / 3.0)]]);
+        }
+
+        #[test]
+        fn test_sql_sum_is_bigint() {
+            let schema = Schema::new(vec![Field::new("a", DataType::Int32, false), Field::new("b", DataType::Int64, false)]);
+            let batch = RecordBatch::try_new(
+                schema,
+                vec![ColumnData::Int32(vec![i32::MAX, i32::MAX]).into(), ColumnData::Int64(vec![i64::MAX, 1]).into()],
+            )
+            .unwrap();
+            let mut engine = Engine::new();
+            engine.register_table("t", batch);
+            let result = engine.execute("SELECT SUM(a), AVG(a) FROM t").unwrap();
+            assert_eq!(result.batch().schema().fields[0].data_type, DataType::Int64);
+            assert_eq!(result.rows(), vec![vec![Value::Int64(2 * i32::MAX as i64), Value::Float64(i32::MAX as f64)]]);
+            assert!(engine.execute("SELECT SUM(b) FROM t").is_err());
+            assert!(engine.execute("SELECT a, SUM(b) FROM t GROUP BY a").is_err());
+        }
+    }
+
+    mod statistics_tests {
+        use super::*;
+
+        fn close(a: f64, b: f64) -> bool {
+            (a - b).abs() <= 1e-9 * a.abs().max(b.abs()).max(1.0)
+        }
+
+        fn float(value: &Value) -> f64 {
+            value.as_f64().unwrap()
+        }
+
+        #[test]
+        fn test_moments() {
+            let moments = normal_moments(&[2, 4, 4, 4, 5, 5, 7, 9]);
+            assert_eq!(moments.var_pop(), Some(4.0));
+            assert_eq!(moments.stddev_pop(), Some(2.0));
+            assert!(close(moments.var_samp().unwrap(), 32.0 / 7.0));
+            assert_eq!(normal_moments(&[3.5]).var_samp(), None);
+            assert_eq!(normal_moments::<f64>(&[]).var_pop(), None);
+
+            // A large offset would ruin a sum of squares, but not the merged moments
+            let data: Vec<f64> = (0..200000).map(|i| 1e9 + (i % 10) as f64).collect();
+            let (sequential, parallel) = (normal_moments(&data), parallel_moments(&data));
+            assert!(close(sequential.var_pop().unwrap(), 8.25));
+            assert!(close(parallel.var_pop().unwrap(), 8.25));
+            assert_eq!(parallel.count, 200000);
+        }
+
+        #[test]
+        fn test_co_moments() {
+            // y = 2x + 1, passed as REGR_SLOPE(y, x)
+            let pairs: Vec<(i64, i64)> = (0..100000).map(|x| (2 * x + 1, x)).collect();
+            let (sequential, parallel) = (normal_co_moments(&pairs), parallel_co_moments(&pairs));
+            for moments in [sequential, parallel] {
+                assert!(close(moments.regr_slope().unwrap(), 2.0));
+                assert!(close(moments.corr().unwrap(), 1.0));
+            }
+            assert!(close(sequential.covar_pop().unwrap(), parallel.covar_pop().unwrap()));
+
+            let constant = normal_co_moments(&[(1, 5), (2, 5)]);
+            assert_eq!(constant.corr(), None);
+            assert_eq!(constant.regr_slope(), None);
+            assert_eq!(constant.covar_pop(), Some(0.0));
+        }
+
+        #[test]
+        fn test_statistics_in_sql() {
+            let schema = Schema::new(vec![
+                Field::new("g", DataType::Int32, false),
+                Field::new("x", DataType::Int32, false),
+                Field::new("y", DataType::Float64, true),
+            ]);
+            let batch = RecordBatch::try_new(
+                schema,
+                vec![
+                    ColumnData::Int32(vec![1, 1, 1, 2]).into(),
+                    ColumnData::Int32(vec![1, 2, 3, 4]).into(),
+                    Column::from_values(
+                        DataType::Float64,
+                        &[Value::Float64(2.0), Value::Float64(4.0), Value::Float64(6.5), Value::Null],
+                    )
+                    .unwrap(),
+                ],
+            )
+            .unwrap();
+            let mut engine = Engine::new();
+            engine.register_table("t", batch);
+
+            let result = engine
+                .execute("SELECT VAR_POP(x), VAR_SAMP(x), STDDEV_POP(x), STDDEV(x), COVAR_POP(y, x), CORR(x, x), REGR_SLOPE(y, x) FROM t")
+                .unwrap();
+            let row = &result.rows()[0];
+            assert_eq!(row[0], Value::Float64(1.25));
+            assert!(close(float(&row[1]), 5.0 / 3.0));
+            assert!(close(float(&row[2]), 1.25f64.sqrt()));
+            assert!(close(float(&row[3]), (5.0f64 / 3.0).sqrt()));
+            // The row with a NULL y is left out of every pair statistic
+            assert!(close(float(&row[4]), 1.5));
+            assert!(close(float(&row[5]), 1.0));
+            assert!(close(float(&row[6]), 2.25));
+
+            let result = engine.execute("SELECT g, VAR_SAMP(x), REGR_SLOPE(y, x) FROM t GROUP BY g ORDER BY g").unwrap();
+            assert_eq!(result.rows()[1], vec![Value::Int32(2), Value::Null, Value::Null]);
+
+            assert!(engine.execute("SELECT CORR(x) FROM t").is_err());
+            assert!(engine.execute("SELECT VAR_POP(x, y) FROM t").is_err());
+        }
+
+        #[test]
+        fn test_parallel_group_by_agrees() {
+            let data: Vec<(i32, f64)> = (0..100000).map(|i| (i % 4, 1e6 + ((i * 7919) % 1000) as f64)).collect();
+            let aggregates = [AggregateFunction::VarSamp, AggregateFunction::Corr];
+            let key_func = |&(k, v): &(i32, f64)| (k, vec![Value::Float64(v), Value::Float64(v), Value::Float64(v * v)]);
+            let mut sequential = sequential_group_by(&data, key_func, &aggregates).unwrap();
+            let mut parallel = parallel_group_by(&data, key_func, &aggregates).unwrap();
+            sequential.sort_by_key(|group| group.key);
+            parallel.sort_by_key(|group| group.key);
+            for (a, b) in sequential.iter().zip(&parallel) {
+                assert_eq!(a.key, b.key);
+                assert!(close(float(&a.values[0]), float(&b.values[0])));
+                assert!(close(float(&a.values[1]), float(&b.values[1])));
+            }
+        }
+    }
+
+    mod percentile_tests {
+        use super::*;
+
+        // 0..n in a scrambled order
+        fn scrambled(n: i64) -> Vec<i64> {
+            (0..n).map(|i| (i * 7919) % n).collect()
+        }
+
+        #[test]
+        fn test_exact_percentiles() {
+            let data = scrambled(100003);
+            for fraction in [0.0, 0.25, 0.5, 0.95, 0.99, 1.0] {
+                let expected = fraction * 100002.0;
+                assert_eq!(normal_percentile_cont(&data, fraction), Some(expected));
+                assert_eq!(parallel_percentile_cont(&data, fraction), Some(expected));
+                let index = ((fraction * 100003.0).ceil() as i64).max(1) - 1;
+                assert_eq!(normal_percentile_disc(&data, fraction), Some(index));
+                assert_eq!(parallel_percentile_disc(&data, fraction), Some(index));
+            }
+            assert_eq!(normal_percentile_cont(&[1.0, 2.0, 4.0, 8.0], 0.5), Some(3.0));
+            assert_eq!(normal_percentile_disc(&[1.0, 2.0, 4.0, 8.0], 0.5), Some(2.0));
+            assert_eq!(parallel_percentile_cont::<i32>(&[], 0.5), None);
+
+            // Many duplicates around the pivot
+            let data: Vec<i32> = (0..50000).map(|i| i % 3).collect();
+            assert_eq!(parallel_percentile_disc(&data, 0.5), Some(1));
+            assert_eq!(parallel_percentile_cont(&data, 0.99), Some(2.0));
+        }
+
+        #[test]
+        fn test_approximate_percentiles() {
+            let data: Vec<f64> = scrambled(200000).into_iter().map(|v| v as f64).collect();
+            for fraction in [0.01, 0.5, 0.95, 0.99] {
+                let expected = fraction * 199999.0;
+                let sequential = normal_approx_percentile(&data, fraction).unwrap();
+                let parallel = parallel_approx_percentile(&data, fraction).unwrap();
+                assert!((sequential - expected).abs() < 200.0, "p{} = {}", fraction, sequential);
+                assert!((parallel - expected).abs() < 200.0, "p{} = {}", fraction, parallel);
+            }
+
+            let mut low = TDigest::new(100.0);
+            let mut high = TDigest::new(100.0);
+            (0..1000).for_each(|v| low.push(v as f64));
+            (1000..2000).for_each(|v| high.push(v as f64));
+            low.merge(&high);
+            assert_eq!(low.count(), 2000);
+            assert_eq!(low.quantile(0.0), Some(0.0));
+            assert_eq!(low.quantile(1.0), Some(1999.0));
+            assert!((low.quantile(0.5).unwrap() - 999.5).abs() < 10.0);
+            assert_eq!(TDigest::new(100.0).quantile(0.5), None);
+        }
+
+        #[test]
+        fn test_percentiles_in_sql() {
+            let schema = Schema::new(vec![
+                Field::new("endpoint", DataType::Utf8, false),
+                Field::new("latency", DataType::Int32, true),
+            ]);
+            let latencies = [10, 20, 30, 40, 100, 5, 7];
+            let mut values: Vec<Value> = latencies.iter().map(|&v| Value::Int32(v)).collect();
+            values.push(Value::Null);
+            let endpoints = ["a", "a", "a", "a", "a", "b", "b", "b"];
+            let batch = RecordBatch::try_new(
+                schema,
+                vec![
+                    ColumnData::Utf8(endpoints.iter().map(|e| e.to_string()).collect()).into(),
+                    Column::from_values(DataType::Int32, &values).unwrap(),
+                ],
+            )
+            .unwrap();
+            let mut engine = Engine::new();
+            engine.register_table("requests", batch);
+
+            let result = engine
+                .execute(
+                    "SELECT endpoint, MEDIAN(latency), PERCENTILE_CONT(0.9) WITHIN GROUP (ORDER BY latency), \
+                     PERCENTILE_DISC(0.5) WITHIN GROUP (ORDER BY latency), APPROX_PERCENTILE(latency, 1.0) \
+                     FROM requests GROUP BY endpoint ORDER BY endpoint",
+                )
+                .unwrap();
+            assert_eq!(
+                result.rows(),
+                vec![
+                    vec![Value::Utf8("a".into()), Value::Float64(30.0), Value::Float64(76.0), Value::Int32(30), Value::Float64(100.0)],
+                    vec![Value::Utf8("b".into()), Value::Float64(6.0), Value::Float64(6.8), Value::Int32(5), Value::Float64(7.0)],
+                ]
+            );
+
+            let result = engine
+                .execute("SELECT PERCENTILE_DISC(0.5) WITHIN GROUP (ORDER BY endpoint), MEDIAN(latency) FROM requests")
+                .unwrap();
+            assert_eq!(result.rows(), vec![vec![Value::Utf8("a".into()), Value::Float64(20.0)]]);
+
+            assert!(engine.execute("SELECT PERCENTILE_CONT(1.5) WITHIN GROUP (ORDER BY latency) FROM requests").is_err());
+            assert!(engine.execute("SELECT PERCENTILE_CONT(0.5) FROM requests").is_err());
+            assert!(engine.execute("SELECT APPROX_PERCENTILE(endpoint, 0.5) FROM requests").is_err());
+            assert!(engine.execute("SELECT MEDIAN(endpoint) FROM requests").is_err());
+        }
+    }
+
+    mod approx_distinct_tests {
+        use super::*;
+
+        fn within(estimate: u64, exact: u64, tolerance: f64) -> bool {
+            (estimate as f64 - exact as f64).abs() <= tolerance * exact as f64
+        }
+
+        #[test]
+        fn test_hyperloglog_estimates() {
+            for exact in [10u64, 1000, 50000, 300000] {
+                let data: Vec<i32> = (0..exact as i32).chain(0..exact as i32 / 2).collect();
+                let sequential = normal_approx_distinct_count(&data, 14).unwrap();
+                let parallel = parallel_approx_distinct_count(&data, 14).unwrap();
+                assert!(within(sequential, exact, 0.03), "{} for {}", sequential, exact);
+                // Merging sketches takes the register maxima, so the split does not matter
+                assert_eq!(parallel, sequential);
+            }
+            assert_eq!(normal_approx_distinct_count::<i32>(&[], 14).unwrap(), 0);
+            assert!(normal_approx_distinct_count(&[1], 3).is_err());
+        }
+
+        #[test]
+        fn test_precision_and_merge() {
+            let mut coarse = HyperLogLog::new(8).unwrap();
+            let mut other = HyperLogLog::new(8).unwrap();
+            (0..5000).for_each(|i| coarse.insert(&i));
+            (2500..7500).for_each(|i| other.insert(&i));
+            coarse.merge(&other).unwrap();
+            assert!(within(coarse.estimate(), 7500, 0.15));
+            assert!(coarse.merge(&HyperLogLog::new(10).unwrap()).is_err());
+            assert_eq!(coarse.precision(), 8);
+        }
+
+        #[test]
+        fn test_approx_count_distinct_in_sql() {
+            let schema = Schema::new(vec![Field::new("k", DataType::Int32, false), Field::new("visitor", DataType::Utf8, true)]);
+            let n = 20000;
+            let users: Vec<Value> = (0..n)
+                .map(|i| if i % 100 == 0 { Value::Null } else { Value::Utf8(format!("user{}", i % (1000 + (i % 2) * 1000))) })
+                .collect();
+            let batch = RecordBatch::try_new(
+                schema,
+                vec![ColumnData::Int32((0..n).map(|i| i % 2).collect()).into(), Column::from_values(DataType::Utf8, &users).unwrap()],
+            )
+            .unwrap();
+            let mut engine = Engine::new();
+            engine.register_table("visits", batch);
+
+            let result = engine
+                .execute("SELECT k, APPROX_COUNT_DISTINCT(visitor), COUNT(DISTINCT visitor), APPROX_COUNT_DISTINCT(visitor, 10) FROM visits GROUP BY k ORDER BY k")
+                .unwrap();
+            for row in result.rows() {
+                let exact = row[2].as_i64().unwrap() as u64;
+                assert!(within(row[1].as_i64().unwrap() as u64, exact, 0.03));
+                assert!(within(row[3].as_i64().unwrap() as u64, exact, 0.1));
+            }
+            let result = engine.execute("SELECT APPROX_COUNT_DISTINCT(k) FROM visits WHERE k > 5").unwrap();
+            assert_eq!(result.rows(), vec![vec![Value::Int64(0)]]);
+            assert!(engine.execute("SELECT APPROX_COUNT_DISTINCT(visitor, 30) FROM visits").is_err());
+        }
+
+        #[test]
+        fn test_scalar_and_grouped_estimates_agree() {
+            let n = 60000;
+            let a: Vec<Value> = (0..n).map(|i| if i % 7 == 0 { Value::Null } else { Value::Int32(i % 40000) }).collect();
+            let a = Column::from_values(DataType::Int32, &a).unwrap();
+            let b: Column = ColumnData::Int64((0..n as i64).map(|i| i * 1_000_003 % 45000).collect()).into();
+            let schema = Schema::new(vec![
+                Field::new("g", DataType::Int32, false),
+                Field::new("a", DataType::Int32, true),
+                Field::new("b", DataType::Int64, false),
+            ]);
+            let batch =
+                RecordBatch::try_new(schema, vec![ColumnData::Int32(vec![1; n as usize]).into(), a.clone(), b.clone()]).unwrap();
+            let mut engine = Engine::new();
+            engine.register_table("t", batch);
+            let grouped = engine
+                .execute("SELECT APPROX_COUNT_DISTINCT(a), APPROX_COUNT_DISTINCT(b) FROM t GROUP BY g")
+                .unwrap()
+                .rows()
+                .remove(0);
+
+            let context = ExecutionContext::builder().num_threads(4).build().unwrap();
+            context.install(|| {
+                let func = AggregateFunction::ApproxCountDistinct(14);
+                let scalar: Vec<Value> =
+                    [&a, &b].iter().map(|column| adaptive_aggregate(func, Some(column), n as usize).unwrap().0).collect();
+                assert_eq!(scalar, grouped);
+            });
+        }
+    }
+
+    mod distinct_count_tests {
+        use super::*;
+
+        #[test]
+        fn test_inputs_smaller_than_the_pool() {
+            let context = ExecutionContext::builder().num_threads(8).build().unwrap();
+            context.install(|| {
+                assert_eq!(parallel_distinct_count::<i32>(&[]), 0);
+                assert_eq!(parallel_distinct_count(&[7]), 1);
+                assert_eq!(parallel_distinct_count(&[3, 1, 3]), 2);
+                let (count, report) = adaptive_distinct_count(&[1, 1, 2, 2, 2]);
+                assert_eq!(count, 2);
+                assert_eq!(report.chunk_size, None);
+            });
+        }
+
+        #[test]
+        fn test_partitions_agree_with_a_single_set() {
+            let data: Vec<i64> = (0..300000).map(|i| (i * 7919) % 123457).collect();
+            for threads in [1, 3, 8] {
+                let context = ExecutionContext::builder().num_threads(threads).build().unwrap();
+                assert_eq!(context.install(|| parallel_distinct_count(&data)), 123457);
+            }
+            assert_eq!(normal_distinct_count(&data), 123457);
+
+            let words: Vec<String> = (0..1000).map(|i| format!("w{}", i % 37)).collect();
+            assert_eq!(parallel_distinct_count(&words), 37);
+
+            let context = ExecutionContext::builder().num_threads(4).build().unwrap();
+            let (count, report) = context.install(|| adaptive_distinct_count(&data));
+            assert_eq!(count, 123457);
+            assert_eq!(report.chunk_size, Some(75000));
+        }
+    }
+
+    mod grouping_sets_tests {
+        use super::*;
+
+        fn sales_engine() -> Engine {
+            let schema = Schema::new(vec![
+                Field::new("region", DataType::Utf8, false),
+                Field::new("product", DataType::Utf8, false),
+                Field::new("amount", DataType::Int32, false),
+            ]);
+            let text = |values: &[&str]| ColumnData::Utf8(values.iter().map(|v| v.to_string()).collect()).into();
+            let batch = RecordBatch::try_new(
+                schema,
+                vec![
+                    text(&["east", "east", "west", "west"]),
+                    text(&["a", "b", "a", "a"]),
+                    ColumnData::Int32(vec![1, 2, 4, 8]).into(),
+                ],
+            )
+            .unwrap();
+            let mut engine = Engine::new();
+            engine.register_table("sales", batch);
+            engine
+        }
+
+        fn text(value: &str) -> Value {
+            Value::Utf8(value.to_string())
+        }
+
+        #[test]
+        fn test_rollup_subtotals() {
+            let engine = sales_engine();
+            let result = engine
+                .execute(
+                    "SELECT region, product, SUM(amount), GROUPING(region, product) FROM sales \
+                     GROUP BY ROLLUP(region, product) ORDER BY region NULLS LAST, product NULLS LAST",
+                )
+                .unwrap();
+            assert_eq!(
+                result.rows(),
+                vec![
+                    vec![text("east"), text("a"), Value::Int64(1), Value::Int32(0)],
+                    vec![text("east"), text("b"), Value::Int64(2), Value::Int32(0)],
+                    vec![text("east"), Value::Null, Value::Int64(3), Value::Int32(1)],
+                    vec![text("west"), text("a"), Value::Int64(12), Value::Int32(0)],
+                    vec![text("west"), Value::Null, Value::Int64(12), Value::Int32(1)],
+                    vec![Value::Null, Value::Null, Value::Int64(15), Value::Int32(3)],
+                ]
+            );
+            assert!(result.batch().schema().fields[1].nullable);
+        }
+
+        #[test]
+        fn test_cube_and_grouping_sets() {
+            let engine = sales_engine();
+            let result = engine
+                .execute("SELECT region, product, COUNT(*) FROM sales GROUP BY CUBE(region, product)")
+                .unwrap();
+            assert_eq!(result.rows().len(), 8);
+
+            let result = engine
+                .execute(
+                    "SELECT region, product, SUM(amount), GROUPING(product) FROM sales \
+                     GROUP BY GROUPING SETS ((region), (product), ()) ORDER BY 4, 1 NULLS LAST, 2 NULLS LAST",
+                )
+                .unwrap();
+            assert_eq!(
+                result.rows(),
+                vec![
+                    vec![Value::Null, text("a"), Value::Int64(13), Value::Int32(0)],
+                    vec![Value::Null, text("b"), Value::Int64(2), Value::Int32(0)],
+                    vec![text("east"), Value::Null, Value::Int64(3), Value::Int32(1)],
+                    vec![text("west"), Value::Null, Value::Int64(12), Value::Int32(1)],
+                    vec![Value::Null, Value::Null, Value::Int64(15), Value::Int32(1)],
+                ]
+            );
+
+            // Plain keys combine with every set of a ROLLUP
+            let result = engine
+                .execute("SELECT region, product, SUM(amount) FROM sales GROUP BY region, ROLLUP(product) ORDER BY 1, 2 NULLS LAST")
+                .unwrap();
+            assert_eq!(result.rows().len(), 5);
+            assert_eq!(result.rows()[2], vec![text("east"), Value::Null, Value::Int64(3)]);
+
+            let result = engine.execute("SELECT region, SUM(amount) FROM sales GROUP BY region WITH ROLLUP").unwrap();
+            assert_eq!(result.rows().len(), 3);
+        }
+
+        #[test]
+        fn test_grand_total_of_empty_input() {
+            let engine = sales_engine();
+            let result = engine
+                .execute("SELECT region, COUNT(*), SUM(amount) FROM sales WHERE amount > 100 GROUP BY ROLLUP(region)")
+                .unwrap();
+            assert_eq!(result.rows(), vec![vec![Value::Null, Value::Int64(0), Value::Null]]);
+        }
+
+        #[test]
+        fn test_grouping_errors() {
+            let engine = sales_engine();
+            assert!(engine.execute("SELECT GROUPING(amount) FROM sales GROUP BY ROLLUP(region)").is_err());
+            assert!(engine.execute("SELECT region FROM sales WHERE GROUPING(region) = 0 GROUP BY region").is_err());
+            // Without grouping sets every key is grouped
+            let result = engine.execute("SELECT region, GROUPING(region) FROM sales GROUP BY region").unwrap();
+            assert!(result.rows().iter().all(|row| row[1] == Value::Int32(0)));
+        }
+
+        #[test]
+        fn test_parallel_sets_match_sequential() {
+            let n = 50000;
+            let schema = Schema::new(vec![
+                Field::new("a", DataType::Int32, false),
+                Field::new("b", DataType::Int32, false),
+                Field::new("v", DataType::Int64, false),
+            ]);
+            let batch = RecordBatch::try_new(
+                schema,
+                vec![
+                    ColumnData::Int32((0..n).map(|i| i % 7).collect()).into(),
+                    ColumnData::Int32((0..n).map(|i| i % 11).collect()).into(),
+                    ColumnData::Int64((0..n as i64).collect()).into(),
+                ],
+            )
+            .unwrap();
+            let sets = vec![vec![0, 1], vec![0], vec![]];
+            let aggregates = [(AggregateFunction::Sum, vec![2]), (AggregateFunction::Count, vec![])];
+            let sorted = |batch: RecordBatch| {
+                let mut rows: Vec<Vec<Value>> = (0..batch.num_rows()).map(|i| batch.row(i)).collect();
+                rows.sort_by_key(|row| format!("{:?}", row));
+                rows
+            };
+            let sequential = sequential_group_by_sets_batch(&batch, &[0, 1], &sets, &aggregates).unwrap();
+            let parallel = parallel_group_by_sets_batch(&batch, &[0, 1], &sets, &aggregates).unwrap();
+            assert_eq!(sequential.num_rows(), 77 + 7 + 1);
+            assert_eq!(sequential.schema().fields[4].name, "grouping_id");
+            assert_eq!(sorted(sequential), sorted(parallel));
+
+            // The generic form keys each group by its set
+            let data: Vec<(i32, i32)> = vec![(1, 1), (1, 2), (2, 1)];
+            let groups = sequential_group_by_sets(
+                &data,
+                |&(a, b)| (vec![Value::Int32(a), Value::Int32(b)], vec![Value::Int32(b)]),
+                &[vec![0], vec![1]],
+                &[AggregateFunction::Sum],
+            )
+            .unwrap();
+            let mut groups: Vec<_> = groups.into_iter().map(|group| (group.key, group.values)).collect();
+            groups.sort_by_key(|(key, _)| format!("{:?}", key));
+            assert_eq!(groups[0], ((0, vec![Value::Int32(1), Value::Null]), vec![Value::Int64(3)]));
+            assert_eq!(groups.len(), 4);
+        }
+    }
+
+    mod having_tests {
+        use super::*;
+
+        fn readings_engine() -> Engine {
+            let schema = Schema::new(vec![Field::new("sensor", DataType::Int32, false), Field::new("x", DataType::Float64, true)]);
+            // Sensor k has k * 5 readings, all equal to k
+            let (sensors, values): (Vec<i32>, Vec<f64>) = (1..=4).flat_map(|k| (0..k * 5).map(move |_| (k, k as f64))).unzip();
+            let batch = RecordBatch::try_new(schema, vec![ColumnData::Int32(sensors).into(), ColumnData::Float64(values).into()]).unwrap();
+            let mut engine = Engine::new();
+            engine.register_table("readings", batch);
+            engine
+        }
+
+        #[test]
+        fn test_having_filters_groups() {
+            let engine = readings_engine();
+            let result = engine
+                .execute("SELECT sensor, COUNT(*) FROM readings GROUP BY sensor HAVING COUNT(*) > 10 AND AVG(x) < 4 ORDER BY sensor")
+                .unwrap();
+            assert_eq!(result.rows(), vec![vec![Value::Int32(3), Value::Int64(15)]]);
+        }
+
+        #[test]
+        fn test_having_only_aggregates() {
+            let engine = readings_engine();
+            // MAX(x) is only computed for HAVING and does not reach the output
+            let result = engine
+                .execute("SELECT sensor FROM readings GROUP BY sensor HAVING MAX(x) >= 2 AND sensor <> 4 ORDER BY sensor")
+                .unwrap();
+            assert_eq!(result.rows(), vec![vec![Value::Int32(2)], vec![Value::Int32(3)]]);
+            assert_eq!(result.batch().num_columns(), 1);
+
+            // Without GROUP BY the whole table is one group
+            let result = engine.execute("SELECT COUNT(*) FROM readings HAVING SUM(x) > 1000").unwrap();
+            assert!(result.rows().is_empty());
+            let result = engine.execute("SELECT COUNT(*) FROM readings HAVING SUM(x) > 10").unwrap();
+            assert_eq!(result.rows(), vec![vec![Value::Int64(50)]]);
+        }
+
+        #[test]
+        fn test_aggregates_nested_in_other_expressions() {
+            let engine = readings_engine();
+            let sensors = |sql: &str| -> Vec<Value> {
+                engine.execute(sql).unwrap().rows().into_iter().map(|row| row[0].clone()).collect()
+            };
+            let ints = |values: &[i32]| -> Vec<Value> { values.iter().map(|&v| Value::Int32(v)).collect() };
+            assert_eq!(sensors("SELECT sensor FROM readings GROUP BY sensor HAVING COUNT(*) IN (10, 20) ORDER BY sensor"), ints(&[2, 4]));
+            assert_eq!(
+                sensors("SELECT sensor FROM readings GROUP BY sensor HAVING SUM(x) NOT BETWEEN 10 AND 50 ORDER BY sensor"),
+                ints(&[1, 4])
+            );
+            assert_eq!(sensors("SELECT sensor FROM readings GROUP BY sensor HAVING CAST(AVG(x) AS INT) = 3"), ints(&[3]));
+            assert_eq!(
+                sensors("SELECT sensor FROM readings GROUP BY sensor HAVING MAX(x) IS NOT NULL AND COUNT(*) < 10"),
+                ints(&[1])
+            );
+
+            let result = engine
+                .execute("SELECT sensor, CASE WHEN SUM(x) > 30 THEN 1 ELSE 0 END AS big FROM readings GROUP BY sensor ORDER BY sensor")
+                .unwrap();
+            let big: Vec<Value> = result.rows().into_iter().map(|row| row[1].clone()).collect();
+            assert_eq!(big, ints(&[0, 0, 1, 1]));
+        }
+
+        #[test]
+        fn test_having_errors() {
+            let engine = readings_engine();
+            assert!(engine.execute("SELECT sensor FROM readings GROUP BY sensor HAVING x > 1").is_err());
+            assert!(engine.execute("SELECT sensor FROM readings GROUP BY sensor HAVING COUNT(*)").is_err());
+        }
+    }
+
+    mod outer_join_tests {
+        use super::*;
+
+        fn records(ids: &[i32], side: &str) -> Vec<Record> {
+            ids.iter()
+                .enumerate()
+                .map(|(i, &id)| Record { id, value: format!("{}{}", side, i) })
+                .collect()
+        }
+
+        fn sorted(mut pairs: Vec<OuterPair<Record, Record>>) -> Vec<OuterPair<Record, Record>> {
+            pairs.sort_by_key(|(l, r)| (l.as_ref().map(|l| l.value.clone()), r.as_ref().map(|r| r.value.clone())));
+            pairs
+        }
+
+        fn ids(pairs: &[OuterPair<Record, Record>]) -> Vec<(Option<i32>, Option<i32>)> {
+            pairs.iter().map(|(l, r)| (l.as_ref().map(|l| l.id), r.as_ref().map(|r| r.id))).collect()
+        }
+
+        #[test]
+        fn test_outer_hash_joins() {
+            let left = records(&[1, 2, 2, 4], "l");
+            let right = records(&[2, 3, 4, 4], "r");
+            let expected = |join_type: JoinType| -> Vec<(Option<i32>, Option<i32>)> {
+                let mut pairs = vec![(Some(2), Some(2)), (Some(2), Some(2)), (Some(4), Some(4)), (Some(4), Some(4))];
+                if join_type.keeps_left() {
+                    pairs.push((Some(1), None));
+                }
+                if join_type.keeps_right() {
+                    pairs.push((None, Some(3)));
+                }
+                pairs.sort();
+                pairs
+            };
+            for join_type in [JoinType::Inner, JoinType::Left, JoinType::Right, JoinType::Full] {
+                let sequential = sorted(sequential_outer_hash_join(&left, &right, join_type));
+                let parallel = sorted(parallel_outer_hash_join(&left, &right, join_type));
+                let mut found = ids(&sequential);
+                found.sort();
+                assert_eq!(found, expected(join_type), "{}", join_type);
+                assert_eq!(sequential, parallel);
+            }
+        }
+
+        #[test]
+        fn test_parallel_outer_hash_join_tracks_build_rows() {
+            // Most build rows match many probe rows from different tasks, every tenth none
+            let left = records(&(0..20000).collect::<Vec<_>>(), "l");
+            let right = records(&(0..200000).map(|i| i % 20000).filter(|i| i % 10 != 0).collect::<Vec<_>>(), "r");
+            let joined = parallel_outer_hash_join(&left, &right, JoinType::Left);
+            assert_eq!(joined.len(), 180000 + 2000);
+            assert_eq!(joined.iter().filter(|(_, r)| r.is_none()).count(), 2000);
+            assert_eq!(sorted(joined), sorted(sequential_outer_hash_join(&left, &right, JoinType::Left)));
+        }
+
+        #[test]
+        fn test_outer_merge_joins() {
+            let left = records(&[1, 1, 3, 5, 5, 5, 8], "l");
+            let right = records(&[0, 1, 5, 5, 6, 8, 8, 9], "r");
+            for join_type in [JoinType::Inner, JoinType::Left, JoinType::Right, JoinType::Full] {
+                let sequential = sequential_outer_merge_join(&left, &right, join_type);
+                // The merge join emits in id order and agrees with the hash join
+                assert_eq!(sorted(sequential.clone()), sorted(sequential_outer_hash_join(&left, &right, join_type)));
+                assert_eq!(parallel_outer_merge_join(&left, &right, join_type), sequential);
+            }
+            let full = ids(&sequential_outer_merge_join(&left, &right, JoinType::Full));
+            assert_eq!(full.first(), Some(&(None, Some(0))));
+            assert_eq!(full.last(), Some(&(None, Some(9))));
+
+            // Runs of one id are never split between tasks
+            let left = records(&(0..5000).map(|i| i / 100).collect::<Vec<_>>(), "l");
+            let right = records(&(0..50).collect::<Vec<_>>(), "r");
+            assert_eq!(parallel_merge_join(&left, &right).len(), 5000);
+            assert_eq!(parallel_merge_join(&left, &right), sequential_merge_join(&left, &right));
+            assert_eq!(parallel_outer_merge_join(&[], &right, JoinType::Right).len(), 50);
+        }
+
+        #[test]
+        fn test_outer_join_batches_pad_null_keys() {
+            let left = RecordBatch::try_new(
+                Schema::new(vec![Field::new("k", DataType::Int32, true)]),
+                vec![Column::from_values(DataType::Int32, &[Value::Int32(1), Value::Null, Value::Int32(2)]).unwrap()],
+            )
+            .unwrap();
+            let right = RecordBatch::try_new(
+                Schema::new(vec![Field::new("k", DataType::Int32, true), Field::new("v", DataType::Utf8, false)]),
+                vec![
+                    Column::from_values(DataType::Int32, &[Value::Int32(1), Value::Null]).unwrap(),
+                    ColumnData::Utf8(vec!["one".into(), "none".into()]).into(),
+                ],
+            )
+            .unwrap();
+            let sorted_rows = |batch: &RecordBatch| {
+                let mut rows = batch.rows();
+                rows.sort_by_key(|row| format!("{:?}", row));
+                rows
+            };
+            let joined = sequential_outer_hash_join_batches(&left, &right, &[0], &[0], JoinType::Full);
+            // NULL keys match nothing, but their rows are kept like any unmatched row
+            let mut expected = vec![
+                vec![Value::Int32(1), Value::Int32(1), Value::Utf8("one".into())],
+                vec![Value::Null, Value::Null, Value::Null],
+                vec![Value::Int32(2), Value::Null, Value::Null],
+                vec![Value::Null, Value::Null, Value::Utf8("none".into())],
+            ];
+            expected.sort_by_key(|row| format!("{:?}", row));
+            assert_eq!(sorted_rows(&joined), expected);
+            assert!(joined.schema().fields[2].nullable);
+            let parallel = parallel_outer_hash_join_batches(&left, &right, &[0], &[0], JoinType::Full);
+            assert_eq!(sorted_rows(&parallel), expected);
+        }
+
+        fn shop() -> Engine {
+            let mut engine = Engine::new();
+            engine.register_table(
+                "customers",
+                RecordBatch::try_new(
+                    Schema::new(vec![Field::new("id", DataType::Int32, false), Field::new("name", DataType::Utf8, false)]),
+                    vec![
+                        ColumnData::Int32(vec![1, 2, 3]).into(),
+                        ColumnData::Utf8(vec!["Ann".into(), "Ben".into(), "Cy".into()]).into(),
+                    ],
+                )
+                .unwrap(),
+            );
+            engine.register_table(
+                "orders",
+                RecordBatch::try_new(
+                    Schema::new(vec![Field::new("customer_id", DataType::Int32, false), Field::new("amount", DataType::Int32, false)]),
+                    vec![ColumnData::Int32(vec![1, 1, 2, 9]).into(), ColumnData::Int32(vec![10, 20, 5, 7]).into()],
+                )
+                .unwrap(),
+            );
+            engine
+        }
+
+        #[test]
+        fn test_outer_joins_in_sql() {
+            let engine = shop();
+            let name = |name: &str| Value::Utf8(name.to_string());
+            let result = engine
+                .execute("SELECT c.name, o.amount FROM customers c LEFT JOIN orders o ON c.id = o.customer_id ORDER BY c.name, o.amount")
+                .unwrap();
+            assert_eq!(
+                result.rows(),
+                vec![
+                    vec![name("Ann"), Value::Int32(10)],
+                    vec![name("Ann"), Value::Int32(20)],
+                    vec![name("Ben"), Value::Int32(5)],
+                    vec![name("Cy"), Value::Null],
+                ]
+            );
+            assert!(result.batch().schema().fields[1].nullable);
+
+            let result = engine
+                .execute("SELECT c.name, o.amount FROM customers c RIGHT JOIN orders o ON c.id = o.customer_id WHERE c.name IS NULL")
+                .unwrap();
+            assert_eq!(result.rows(), vec![vec![Value::Null, Value::Int32(7)]]);
+
+            let result = engine
+                .execute("SELECT COUNT(*), COUNT(c.id), COUNT(o.customer_id) FROM customers c FULL OUTER JOIN orders o ON c.id = o.customer_id")
+                .unwrap();
+            assert_eq!(result.rows(), vec![vec![Value::Int64(5), Value::Int64(4), Value::Int64(4)]]);
+        }
+
+        #[test]
+        fn test_outer_join_conditions_and_filters() {
+            let engine = shop();
+            // A condition in ON only decides matches; the customers stay
+            let result = engine
+                .execute(
+                    "SELECT c.name, o.amount FROM customers c LEFT JOIN orders o \
+                     ON c.id = o.customer_id AND o.amount > 8 ORDER BY c.name, o.amount",
+                )
+                .unwrap();
+            assert_eq!(
+                result.rows().iter().map(|row| row[1].clone()).collect::<Vec<_>>(),
+                vec![Value::Int32(10), Value::Int32(20), Value::Null, Value::Null]
+            );
+
+            // The same condition in WHERE filters the padded rows out
+            let result = engine
+                .execute("SELECT c.name FROM customers c LEFT JOIN orders o ON c.id = o.customer_id WHERE o.amount > 8")
+                .unwrap();
+            assert_eq!(result.rows().len(), 2);
+
+            // Customers without orders, which pushing the filter below the join would lose
+            let result = engine
+                .execute("SELECT c.name FROM customers c LEFT JOIN orders o ON c.id = o.customer_id WHERE o.customer_id IS NULL")
+                .unwrap();
+            assert_eq!(result.rows(), vec![vec![Value::Utf8("Cy".into())]]);
+
+            let plan = engine.create_logical_plan("SELECT * FROM customers c LEFT JOIN orders o ON c.id = o.customer_id").unwrap().to_string();
+            assert!(plan.contains("LEFT Join: c.id = o.customer_id"), "{}", plan);
+        }
+    }
+
+    mod semi_join_tests {
+        use super::*;
+
+        fn records(ids: &[i32]) -> Vec<Record> {
+            ids.iter().enumerate().map(|(i, &id)| Record { id, value: i.to_string() }).collect()
+        }
+
+        fn ids(records: &[Record]) -> Vec<i32> {
+            records.iter().map(|r| r.id).collect()
+        }
+
+        #[test]
+        fn test_semi_and_anti_joins() {
+            let left = records(&[1, 2, 2, 3, 5, 5, 7]);
+            // Many matches per key still keep each left record once
+            let right = records(&[2, 2, 2, 5, 6]);
+            let key = |r: &Record| r.id;
+            assert_eq!(ids(&sequential_semi_hash_join_by(&left, &right, key, key)), vec![2, 2, 5, 5]);
+            assert_eq!(ids(&sequential_anti_hash_join_by(&left, &right, key, key)), vec![1, 3, 7]);
+            assert_eq!(parallel_semi_hash_join_by(&left, &right, key, key), sequential_semi_hash_join_by(&left, &right, key, key));
+            assert_eq!(parallel_anti_hash_join_by(&left, &right, key, key), sequential_anti_hash_join_by(&left, &right, key, key));
+
+            assert_eq!(sequential_semi_merge_join(&left, &right), sequential_semi_hash_join_by(&left, &right, key, key));
+            assert_eq!(sequential_anti_merge_join(&left, &right), sequential_anti_hash_join_by(&left, &right, key, key));
+
+            let left = records(&(0..20000).map(|i| i / 3).collect::<Vec<_>>());
+            let right = records(&(0..10000).filter(|i| i % 4 == 0).collect::<Vec<_>>());
+            assert_eq!(parallel_semi_merge_join(&left, &right), sequential_semi_merge_join(&left, &right));
+            assert_eq!(parallel_anti_merge_join(&left, &right), sequential_anti_merge_join(&left, &right));
+            assert_eq!(
+                parallel_semi_merge_join(&left, &right).len() + parallel_anti_merge_join(&left, &right).len(),
+                left.len()
+            );
+        }
+
+        #[test]
+        fn test_anti_join_null_semantics() {
+            let column = |values: &[Option<i32>]| {
+                let values: Vec<Value> = values.iter().map(|v| v.map_or(Value::Null, Value::Int32)).collect();
+                RecordBatch::try_new(
+                    Schema::new(vec![Field::new("k", DataType::Int32, true)]),
+                    vec![Column::from_values(DataType::Int32, &values).unwrap()],
+                )
+                .unwrap()
+            };
+            let left = column(&[Some(1), None, Some(2)]);
+            let keys = |batch: RecordBatch| batch.rows().into_iter().map(|row| row[0].clone()).collect::<Vec<_>>();
+
+            assert_eq!(keys(sequential_semi_join_batches(&left, &column(&[Some(2), None]), &[0], &[0])), vec![Value::Int32(2)]);
+            // NOT EXISTS: a NULL key matches nothing and is kept
+            assert_eq!(
+                keys(sequential_anti_join_batches(&left, &column(&[Some(2), None]), &[0], &[0], false)),
+                vec![Value::Int32(1), Value::Null]
+            );
+            // NOT IN: a NULL on the right leaves nothing, a NULL on the left is dropped
+            assert!(keys(sequential_anti_join_batches(&left, &column(&[Some(2), None]), &[0], &[0], true)).is_empty());
+            assert_eq!(keys(parallel_anti_join_batches(&left, &column(&[Some(2)]), &[0], &[0], true)), vec![Value::Int32(1)]);
+            // ... unless the subquery is empty
+            assert_eq!(keys(sequential_anti_join_batches(&left, &column(&[]), &[0], &[0], true)).len(), 3);
+        }
+
+        fn shop() -> Engine {
+            let mut engine = Engine::new();
+            engine.register_table(
+                "customers",
+                RecordBatch::try_new(
+                    Schema::new(vec![
+                        Field::new("id", DataType::Int32, false),
+                        Field::new("name", DataType::Utf8, false),
+                        Field::new("credit", DataType::Int32, false),
+                    ]),
+                    vec![
+                        ColumnData::Int32(vec![1, 2, 3, 4]).into(),
+                        ColumnData::Utf8(vec!["Ann".into(), "Ben".into(), "Cy".into(), "Di".into()]).into(),
+                        ColumnData::Int32(vec![15, 100, 0, 50]).into(),
+                    ],
+                )
+                .unwrap(),
+            );
+            let customer_ids = [Value::Int32(1), Value::Int32(1), Value::Int32(2), Value::Null];
+            engine.register_table(
+                "orders",
+                RecordBatch::try_new(
+                    Schema::new(vec![Field::new("customer_id", DataType::Int32, true), Field::new("amount", DataType::Int32, false)]),
+                    vec![Column::from_values(DataType::Int32, &customer_ids).unwrap(), ColumnData::Int32(vec![10, 20, 5, 7]).into()],
+                )
+                .unwrap(),
+            );
+            engine
+        }
+
+        fn names(engine: &Engine, sql: &str) -> Vec<String> {
+            engine.execute(sql).unwrap().rows().iter().map(|row| row[0].to_string()).collect()
+        }
+
+        #[test]
+        fn test_in_and_exists_subqueries() {
+            let engine = shop();
+            assert_eq!(names(&engine, "SELECT name FROM customers WHERE id IN (SELECT customer_id FROM orders) ORDER BY name"), vec!["Ann", "Ben"]);
+            assert_eq!(
+                names(&engine, "SELECT name FROM customers c WHERE EXISTS (SELECT 1 FROM orders o WHERE o.customer_id = c.id AND o.amount > 8)"),
+                vec!["Ann"]
+            );
+            assert_eq!(
+                names(&engine, "SELECT name FROM customers c WHERE NOT EXISTS (SELECT * FROM orders o WHERE o.customer_id = c.id) ORDER BY name"),
+                vec!["Cy", "Di"]
+            );
+            // A condition that compares both sides without being an equality
+            assert_eq!(
+                names(&engine, "SELECT name FROM customers c WHERE EXISTS (SELECT 1 FROM orders o WHERE o.customer_id = c.id AND o.amount > c.credit)"),
+                vec!["Ann"]
+            );
+            assert_eq!(
+                names(&engine, "SELECT name FROM customers WHERE credit > 10 AND id IN (SELECT customer_id FROM orders WHERE amount < 8)"),
+                vec!["Ben"]
+            );
+            let plan = engine.create_logical_plan("SELECT name FROM customers WHERE id NOT IN (SELECT customer_id FROM orders)").unwrap();
+            assert!(plan.to_string().contains("NullAwareAntiJoin: id = customer_id"), "{}", plan);
+        }
+
+        #[test]
+        fn test_not_in_follows_sql_null_semantics() {
+            let engine = shop();
+            // orders.customer_id holds a NULL, so NOT IN is never true
+            assert!(names(&engine, "SELECT name FROM customers WHERE id NOT IN (SELECT customer_id FROM orders)").is_empty());
+            assert_eq!(
+                names(&engine, "SELECT name FROM customers WHERE id NOT IN (SELECT customer_id FROM orders WHERE customer_id IS NOT NULL) ORDER BY name"),
+                vec!["Cy", "Di"]
+            );
+            assert_eq!(
+                names(&engine, "SELECT name FROM customers WHERE NOT id IN (SELECT customer_id FROM orders WHERE amount > 100)").len(),
+                4
+            );
+            assert!(engine
+                .execute("SELECT name FROM customers c WHERE id NOT IN (SELECT customer_id FROM orders o WHERE o.amount > c.credit)")
+                .is_err());
+            assert!(engine.execute("SELECT name FROM customers WHERE id IN (SELECT MAX(customer_id) FROM orders)").is_err());
+            assert!(engine.execute("SELECT name FROM customers WHERE id IN (SELECT customer_id, amount FROM orders)").is_err());
+        }
+    }
+
+    mod join_key_tests {
+        use super::*;
+
+        #[derive(Debug, Clone, PartialEq)]
+        struct Sale {
+            region: String,
+            year: i32,
+            amount: i64,
+        }
+
+        #[derive(Debug, Clone, PartialEq)]
+        struct Target {
+            region: &'static str,
+            year: i64,
+            goal: i64,
+        }
+
+        fn sales() -> Vec<Sale> {
+            let regions = ["north", "south", "east", "west"];
+            let mut sales: Vec<Sale> = (0..3000)
+                .map(|i| Sale { region: regions[i % 4].to_string(), year: 2020 + (i % 5) as i32, amount: i as i64 })
+                .collect();
+            sales.sort_by(|a, b| (&a.region, a.year).cmp(&(&b.region, b.year)));
+            sales
+        }
+
+        fn targets() -> Vec<Target> {
+            let mut targets = Vec::new();
+            for region in ["east", "north", "up"] {
+                for year in [2019, 2021, 2022] {
+                    targets.push(Target { region, year, goal: year * 10 });
+                }
+            }
+            targets
+        }
+
+        // Composite keys of a String and an i32 on one side, a &str and an i64 on the other
+        fn sale_key(sale: &Sale) -> (String, i64) {
+            (sale.region.clone(), sale.year as i64)
+        }
+
+        fn target_key(target: &Target) -> (String, i64) {
+            (target.region.to_string(), target.year)
+        }
+
+        #[test]
+        fn test_composite_keys_over_any_row_type() {
+            let (sales, targets) = (sales(), targets());
+            let mut expected = sequential_hash_join_by(&sales, &targets, sale_key, target_key);
+            // north and east each have 150 sales in both 2021 and 2022
+            assert_eq!(expected.len(), 600);
+            assert!(expected.iter().all(|(sale, target)| sale.region == target.region && sale.year as i64 == target.year));
+
+            let sort = |pairs: &mut Vec<(Sale, Target)>| pairs.sort_by_key(|(sale, target)| (sale.amount, target.year));
+            let mut parallel = parallel_hash_join_by(&sales, &targets, sale_key, target_key);
+            sort(&mut expected);
+            sort(&mut parallel);
+            assert_eq!(parallel, expected);
+
+            // Merge joins take the same key functions on inputs sorted by key
+            let mut targets = targets;
+            targets.sort_by_key(target_key);
+            let mut merged = sequential_merge_join_by(&sales, &targets, sale_key, target_key);
+            sort(&mut merged);
+            assert_eq!(merged, expected);
+            assert_eq!(
+                parallel_merge_join_by(&sales, &targets, sale_key, target_key),
+                sequential_merge_join_by(&sales, &targets, sale_key, target_key)
+            );
+
+            let outer = sequential_outer_merge_join_by(&sales, &targets, sale_key, target_key, JoinType::Full);
+            assert_eq!(outer, parallel_outer_merge_join_by(&sales, &targets, sale_key, target_key, JoinType::Full));
+            let unmatched_targets = outer.iter().filter(|(sale, _)| sale.is_none()).count();
+            // 2019 for east and north, and every year of "up"
+            assert_eq!(unmatched_targets, 5);
+            assert_eq!(outer.len(), 600 + (3000 - 600) + 5);
+
+            let semi = sequential_semi_merge_join_by(&sales, &targets, sale_key, target_key);
+            let anti = sequential_anti_merge_join_by(&sales, &targets, sale_key, target_key);
+            assert_eq!(semi.len(), 600);
+            assert_eq!(anti, parallel_anti_merge_join_by(&sales, &targets, sale_key, target_key));
+            assert_eq!(semi.len() + anti.len(), sales.len());
+        }
+
+        #[test]
+        fn test_key_types_are_coerced() {
+            assert_eq!(join_key_type(DataType::Int32, DataType::Int32), Some(DataType::Int32));
+            assert_eq!(join_key_type(DataType::Int32, DataType::Int64), Some(DataType::Int64));
+            assert_eq!(join_key_type(DataType::Int64, DataType::Float64), Some(DataType::Float64));
+            assert_eq!(join_key_type(DataType::Null, DataType::Utf8), Some(DataType::Utf8));
+            assert_eq!(join_key_type(DataType::Utf8, DataType::Int32), None);
+            assert_eq!(join_key_type(DataType::Date, DataType::Int32), None);
+
+            let batch = |name: &str, column: ColumnData| {
+                RecordBatch::try_new(Schema::new(vec![Field::new(name, column.data_type(), false)]), vec![column.into()]).unwrap()
+            };
+            let left = batch("a", ColumnData::Int32(vec![1, 2, 3]));
+            let joined = sequential_hash_join_batches(&left, &batch("b", ColumnData::Int64(vec![3, 1, 1])), &[0], &[0]);
+            assert_eq!(joined.num_rows(), 3);
+            // The output keeps the type of each side
+            assert_eq!(joined.schema().fields[0].data_type, DataType::Int32);
+            assert_eq!(joined.schema().fields[1].data_type, DataType::Int64);
+
+            let floats = batch("f", ColumnData::Float64(vec![2.0, 2.5]));
+            let joined = adaptive_hash_join_batches(&left, &floats, &[0], &[0]).0;
+            assert_eq!(joined.rows(), vec![vec![Value::Int32(2), Value::Float64(2.0)]]);
+        }
+
+        #[test]
+        fn test_float_keys_compare_as_numbers() {
+            let batch = |name: &str, values: Vec<f64>| {
+                RecordBatch::try_new(Schema::new(vec![Field::new(name, DataType::Float64, false)]), vec![ColumnData::Float64(values).into()])
+                    .unwrap()
+            };
+            let left = batch("a", vec![0.0, -0.0, f64::NAN, 1.0]);
+            let right = batch("b", vec![-0.0, f64::NAN, 2.0]);
+
+            // -0.0 and 0.0 are one key, and NaN matches nothing, not even itself
+            let joined = sequential_hash_join_batches(&left, &right, &[0], &[0]);
+            assert_eq!(joined.num_rows(), 2);
+            assert!(joined.rows().iter().all(|row| row[0] == Value::Float64(0.0) || row[0] == Value::Float64(-0.0)));
+            assert_eq!(sequential_semi_join_batches(&left, &right, &[0], &[0]).num_rows(), 2);
+            for null_aware in [false, true] {
+                let anti = sequential_anti_join_batches(&left, &right, &[0], &[0], null_aware);
+                assert_eq!(anti.num_rows(), 2);
+                assert_eq!(anti.rows()[1], vec![Value::Float64(1.0)]);
+                assert_eq!(parallel_anti_join_batches(&left, &right, &[0], &[0], null_aware).num_rows(), 2);
+            }
+            let full = sequential_outer_hash_join_batches(&left, &right, &[0], &[0], JoinType::Full);
+            assert_eq!(full.num_rows(), 2 + 2 + 2);
+            assert_eq!(parallel_outer_hash_join_batches(&left, &right, &[0], &[0], JoinType::Full).num_rows(), 6);
+
+            // A point of -0.0 lies in a range that starts at 0.0, and NaN in none
+            let ranges = RecordBatch::try_new(
+                Schema::new(vec![Field::new("lo", DataType::Float64, false), Field::new("hi", DataType::Float64, false)]),
+                vec![ColumnData::Float64(vec![0.0, f64::NAN]).into(), ColumnData::Float64(vec![0.5, 1.0]).into()],
+            )
+            .unwrap();
+            let bound = |column| Some(BandBound { column, inclusive: true });
+            let mut pairs = sequential_band_join_pairs(&left, &ranges, 0, bound(0), bound(1));
+            pairs.sort_unstable();
+            assert_eq!(pairs, vec![(0, 0), (1, 0)]);
+
+            // Joins, filters and grouping agree on float equality within one query
+            let mut engine = Engine::new();
+            engine.register_table("l", left);
+            engine.register_table("r", right);
+            let result = engine
+                .execute("SELECT a, COUNT(*) FROM l JOIN r ON a = b WHERE a = 0 AND b = 0 GROUP BY a")
+                .unwrap();
+            assert_eq!(result.rows(), vec![vec![Value::Float64(0.0), Value::Int64(2)]]);
+            let result = engine.execute("SELECT COUNT(*) FROM l JOIN r ON a = b WHERE a <> b").unwrap();
+            assert_eq!(result.rows(), vec![vec![Value::Int64(0)]]);
+        }
+
+        fn engine() -> Engine {
+            let mut engine = Engine::new();
+            engine.register_table(
+                "stock",
+                RecordBatch::try_new(
+                    Schema::new(vec![
+                        Field::new("store", DataType::Utf8, false),
+                        Field::new("item", DataType::Int32, false),
+                        Field::new("qty", DataType::Int32, false),
+                    ]),
+                    vec![
+                        ColumnData::Utf8(vec!["a".into(), "a".into(), "b".into(), "b".into()]).into(),
+                        ColumnData::Int32(vec![1, 2, 1, 3]).into(),
+                        ColumnData::Int32(vec![5, 0, 7, 2]).into(),
+                    ],
+                )
+                .unwrap(),
+            );
+            engine.register_table(
+                "prices",
+                RecordBatch::try_new(
+                    Schema::new(vec![
+                        Field::new("store", DataType::Utf8, false),
+                        Field::new("item", DataType::Int64, false),
+                        Field::new("price", DataType::Float64, false),
+                    ]),
+                    vec![
+                        ColumnData::Utf8(vec!["a".into(), "b".into(), "b".into(), "c".into()]).into(),
+                        ColumnData::Int64(vec![1, 1, 3, 1]).into(),
+                        ColumnData::Float64(vec![1.5, 2.0, 4.0, 9.0]).into(),
+                    ],
+                )
+                .unwrap(),
+            );
+            engine
+        }
+
+        #[test]
+        fn test_sql_joins_on_composite_keys_of_mixed_types() {
+            let engine = engine();
+            let result = engine
+                .execute(
+                    "SELECT s.store, s.item, s.qty * p.price AS value FROM stock s JOIN prices p \
+                     ON s.store = p.store AND s.item = p.item ORDER BY s.store, s.item",
+                )
+                .unwrap();
+            assert_eq!(
+                result.rows(),
+                vec![
+                    vec![Value::Utf8("a".into()), Value::Int32(1), Value::Float64(7.5)],
+                    vec![Value::Utf8("b".into()), Value::Int32(1), Value::Float64(14.0)],
+                    vec![Value::Utf8("b".into()), Value::Int32(3), Value::Float64(8.0)],
+                ]
+            );
+
+            let result = engine
+                .execute("SELECT s.item, p.price FROM stock s LEFT JOIN prices p ON p.item = s.item AND p.store = s.store WHERE s.store = 'a'")
+                .unwrap();
+            assert_eq!(result.num_rows(), 2);
+            assert_eq!(result.rows()[1], vec![Value::Int32(2), Value::Null]);
+
+            // An Int32 column against an Int64 subquery column
+            let result = engine
+                .execute("SELECT DISTINCT store FROM stock WHERE item IN (SELECT item FROM prices WHERE price > 3) ORDER BY store")
+                .unwrap();
+            assert_eq!(result.rows(), vec![vec![Value::Utf8("a".into())], vec![Value::Utf8("b".into())]]);
+
+            assert!(engine.execute("SELECT * FROM stock s JOIN prices p ON s.store = p.item").is_err());
+            assert!(engine.execute("SELECT * FROM stock WHERE store IN (SELECT item FROM prices)").is_err());
+        }
+    }
+
+    mod theta_join_tests {
+        use super::*;
+
+        fn records(n: i32, step: i32) -> Vec<Record> {
+            (0..n).map(|i| Record { id: (i * step) % 101, value: i.to_string() }).collect()
+        }
+
+        fn sorted<T: Ord>(mut pairs: Vec<T>) -> Vec<T> {
+            pairs.sort();
+            pairs
+        }
+
+        fn ids(pairs: Vec<(Record, Record)>) -> Vec<(i32, String, i32, String)> {
+            sorted(pairs.into_iter().map(|(l, r)| (l.id, l.value, r.id, r.value)).collect())
+        }
+
+        #[test]
+        fn test_nested_loop_join() {
+            let (left, right) = (records(300, 7), records(200, 13));
+            let predicate = |l: &Record, r: &Record| l.id + r.id == 100 || (l.id > 95 && r.id < 3);
+            let mut expected = Vec::new();
+            for l in &left {
+                for r in &right {
+                    if predicate(l, r) {
+                        expected.push((l.clone(), r.clone()));
+                    }
+                }
+            }
+            let sequential = sequential_nested_loop_join_by(&left, &right, predicate);
+            assert_eq!(sequential, parallel_nested_loop_join_by(&left, &right, predicate));
+            assert_eq!(ids(sequential), ids(expected));
+            assert!(sequential_nested_loop_join_by(&left, &[], predicate).is_empty());
+        }
+
+        #[test]
+        fn test_band_join_matches_nested_loop() {
+            let left = records(500, 17);
+            // Windows around each id, some empty and some crossed
+            let windows: Vec<(i32, i32)> = (0..120).map(|i| (i - 3, i + (i % 7) - 4)).collect();
+            let bounds = [
+                |(lo, hi): &(i32, i32)| (Bound::Included(*lo), Bound::Included(*hi)),
+                |(lo, hi): &(i32, i32)| (Bound::Excluded(*lo), Bound::Excluded(*hi)),
+                |(lo, _): &(i32, i32)| (Bound::Included(*lo), Bound::Unbounded),
+                |(_, hi): &(i32, i32)| (Bound::Unbounded, Bound::Excluded(*hi)),
+            ];
+            for range in bounds {
+                let band = sequential_band_join_by(&left, &windows, |l| l.id, range);
+                assert_eq!(band, parallel_band_join_by(&left, &windows, |l| l.id, range));
+                let expected = sequential_nested_loop_join_by(&left, &windows, |l, w| range(w).contains(&l.id));
+                let key = |pairs: Vec<(Record, (i32, i32))>| sorted(pairs.into_iter().map(|(l, w)| (l.id, l.value, w)).collect());
+                assert_eq!(key(band), key(expected));
+            }
+        }
+
+        fn events() -> RecordBatch {
+            let ts = [Value::Int64(5), Value::Int64(12), Value::Null, Value::Int64(20), Value::Int64(30), Value::Int64(10)];
+            RecordBatch::try_new(
+                Schema::new(vec![Field::new("id", DataType::Int32, false), Field::new("ts", DataType::Int64, true)]),
+                vec![ColumnData::Int32((0..6).collect()).into(), Column::from_values(DataType::Int64, &ts).unwrap()],
+            )
+            .unwrap()
+        }
+
+        fn windows() -> RecordBatch {
+            let stop = [Value::Int32(12), Value::Int32(25), Value::Null, Value::Int32(3)];
+            RecordBatch::try_new(
+                Schema::new(vec![
+                    Field::new("name", DataType::Utf8, false),
+                    Field::new("start", DataType::Int32, false),
+                    Field::new("stop", DataType::Int32, true),
+                ]),
+                vec![
+                    ColumnData::Utf8(vec!["a".into(), "b".into(), "c".into(), "d".into()]).into(),
+                    ColumnData::Int32(vec![0, 10, 28, 4]).into(),
+                    Column::from_values(DataType::Int32, &stop).unwrap(),
+                ],
+            )
+            .unwrap()
+        }
+
+        #[test]
+        fn test_band_and_nested_loop_pairs_over_batches() {
+            let (events, windows) = (events(), windows());
+            let lower = Some(BandBound { column: 1, inclusive: true });
+            let upper = Some(BandBound { column: 2, inclusive: false });
+            // Int64 points against Int32 bounds; NULL points and bounds are in no range
+            let pairs = sequential_band_join_pairs(&events, &windows, 1, lower, upper);
+            assert_eq!(sorted(pairs.clone()), vec![(0, 0), (1, 1), (3, 1), (5, 0), (5, 1)]);
+            assert_eq!(parallel_band_join_pairs(&events, &windows, 1, lower, upper), pairs);
+            assert_eq!(sorted(adaptive_band_join_pairs(&events, &windows, 1, lower, None).0).len(), 15);
+
+            let fields: Vec<Field> = events.schema().fields.iter().chain(&windows.schema().fields).cloned().collect();
+            let predicate = ScalarExpr::bind_schema(&parse_expr("ts >= start AND ts < stop").unwrap(), &Schema::new(fields)).unwrap();
+            let nested = sequential_nested_loop_join_pairs(&events, &windows, &predicate).unwrap();
+            assert_eq!(nested, sorted(pairs));
+            assert_eq!(parallel_nested_loop_join_pairs(&events, &windows, &predicate).unwrap(), nested);
+
+            let all: Vec<(usize, usize)> = (0..6).flat_map(|e| (0..4).map(move |w| (e, w))).collect();
+            assert_eq!(sequential_filter_pairs(&events, &windows, &all, &predicate).unwrap(), nested);
+            assert_eq!(parallel_filter_pairs(&events, &windows, &all, &predicate).unwrap(), nested);
+        }
+
+        fn engine() -> Engine {
+            let mut engine = Engine::new();
+            engine.register_table("events", events());
+            engine.register_table("windows", windows());
+            engine
+        }
+
+        fn rows(engine: &Engine, sql: &str) -> Vec<String> {
+            let result = engine.execute(sql).unwrap();
+            result
+                .rows()
+                .iter()
+                .map(|row| row.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" "))
+                .collect()
+        }
+
+        #[test]
+        fn test_sql_range_joins() {
+            let engine = engine();
+            assert_eq!(
+                rows(&engine, "SELECT e.id, w.name FROM events e JOIN windows w ON e.ts BETWEEN w.start AND w.stop ORDER BY e.id, w.name"),
+                vec!["0 a", "1 a", "1 b", "3 b", "5 a", "5 b"]
+            );
+            // The point on the right side, bounds from two comparisons, and another condition
+            assert_eq!(
+                rows(
+                    &engine,
+                    "SELECT w.name, e.id FROM windows w JOIN events e ON w.start <= e.ts AND e.ts < w.stop AND e.id <> 5 ORDER BY w.name, e.id"
+                ),
+                vec!["a 0", "b 1", "b 3"]
+            );
+            let plan = engine.create_logical_plan("SELECT * FROM events e JOIN windows w ON e.ts < w.start").unwrap();
+            assert!(plan.to_string().contains("Join: e.ts < w.start"), "{}", plan);
+            assert_eq!(rows(&engine, "SELECT COUNT(*) FROM events e JOIN windows w ON e.ts < w.start"), vec!["5"]);
+            assert_eq!(
+                rows(&engine, "SELECT e.id, w.name FROM events e LEFT JOIN windows w ON e.ts BETWEEN w.start AND w.stop ORDER BY e.id, w.name"),
+                vec!["0 a", "1 a", "1 b", "2 NULL", "3 b", "4 NULL", "5 a", "5 b"]
+            );
+            assert_eq!(
+                rows(&engine, "SELECT w.name FROM events e RIGHT JOIN windows w ON e.ts > w.stop + 20 WHERE e.id IS NULL ORDER BY w.name"),
+                vec!["a", "b", "c"]
+            );
+        }
+
+        #[test]
+        fn test_sql_nested_loop_joins() {
+            let engine = engine();
+            assert_eq!(
+                rows(&engine, "SELECT e.id, w.name FROM events e JOIN windows w ON e.ts = w.start + 2 OR e.id = w.stop ORDER BY e.id"),
+                vec!["1 b", "3 d", "4 c"]
+            );
+            assert_eq!(
+                rows(&engine, "SELECT name FROM windows w WHERE NOT EXISTS (SELECT 1 FROM events e WHERE e.ts > w.start + 20) ORDER BY name"),
+                vec!["b", "c"]
+            );
+            assert_eq!(
+                rows(&engine, "SELECT name FROM windows w WHERE EXISTS (SELECT 1 FROM events e WHERE e.ts BETWEEN w.start AND w.stop) ORDER BY name"),
+                vec!["a", "b"]
+            );
+        }
+    }
+}
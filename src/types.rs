@@ -0,0 +1,284 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DataType {
+    Int32,
+    Int64,
+    Float64,
+    Utf8,
+    Boolean,
+    // Days since 1970-01-01
+    Date,
+    // Type of an untyped `NULL` literal, compatible with every other type
+    Null,
+}
+
+impl DataType {
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, DataType::Int32 | DataType::Int64 | DataType::Float64)
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(self, DataType::Int32 | DataType::Int64)
+    }
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+pub type Row = Vec<Value>;
+
+// A single scalar value flowing between operators.
+//
+// Floats are compared and hashed through the total ordering of `float_key` so that values can
+// be used as group and join keys and sorted without special cases. `Null` equals itself and sorts before
+// everything else here; SQL comparison semantics are applied by the expression evaluator.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Int32(i32),
+    Int64(i64),
+    Float64(f64),
+    Utf8(String),
+    Boolean(bool),
+    Date(i32),
+    Null,
+}
+
+impl Value {
+    pub fn data_type(&self) -> DataType {
+        match self {
+            Value::Int32(_) => DataType::Int32,
+            Value::Int64(_) => DataType::Int64,
+            Value::Float64(_) => DataType::Float64,
+            Value::Utf8(_) => DataType::Utf8,
+            Value::Boolean(_) => DataType::Boolean,
+            Value::Date(_) => DataType::Date,
+            Value::Null => DataType::Null,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Int32(v) => Some(*v as f64),
+            Value::Int64(v) => Some(*v as f64),
+            Value::Float64(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Int32(v) => Some(*v as i64),
+            Value::Int64(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            Value::Null => 0,
+            Value::Boolean(_) => 1,
+            Value::Int32(_) => 2,
+            Value::Int64(_) => 3,
+            Value::Float64(_) => 4,
+            Value::Utf8(_) => 5,
+            Value::Date(_) => 6,
+        }
+    }
+}
+
+// A float as a key: -0.0 is the same key as 0.0, and every NaN is one key that sorts after all
+// other floats. Grouping, DISTINCT, sorting and joins all see floats through it.
+pub fn float_key(v: f64) -> f64 {
+    if v.is_nan() {
+        f64::NAN
+    } else {
+        // Adding 0.0 turns -0.0 into 0.0 and leaves every other float as it is
+        v + 0.0
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Value {}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Value {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Value::Int32(a), Value::Int32(b)) => a.cmp(b),
+            (Value::Int64(a), Value::Int64(b)) => a.cmp(b),
+            (Value::Float64(a), Value::Float64(b)) => float_key(*a).total_cmp(&float_key(*b)),
+            (Value::Utf8(a), Value::Utf8(b)) => a.cmp(b),
+            (Value::Boolean(a), Value::Boolean(b)) => a.cmp(b),
+            (Value::Date(a), Value::Date(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.rank().hash(state);
+        match self {
+            Value::Int32(v) => v.hash(state),
+            Value::Int64(v) => v.hash(state),
+            Value::Float64(v) => float_key(*v).to_bits().hash(state),
+            Value::Utf8(v) => v.hash(state),
+            Value::Boolean(v) => v.hash(state),
+            Value::Date(v) => v.hash(state),
+            Value::Null => {}
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int32(v) => write!(f, "{}", v),
+            Value::Int64(v) => write!(f, "{}", v),
+            Value::Float64(v) => write!(f, "{}", v),
+            Value::Utf8(v) => write!(f, "{}", v),
+            Value::Boolean(v) => write!(f, "{}", v),
+            Value::Date(v) => write!(f, "{}", format_date(*v)),
+            Value::Null => write!(f, "NULL"),
+        }
+    }
+}
+
+// A fixed-point number: `value` scaled down by 10^`scale`, so 1234 at scale 2 is 12.34.
+// Numbers of different scales compare and add up by their numeric value.
+#[derive(Debug, Clone, Copy)]
+pub struct Decimal {
+    value: i128,
+    scale: u32,
+}
+
+impl Decimal {
+    pub const ZERO: Decimal = Decimal { value: 0, scale: 0 };
+
+    pub fn new(value: i128, scale: u32) -> Self {
+        Decimal { value, scale }
+    }
+
+    pub fn value(&self) -> i128 {
+        self.value
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    // The unscaled value at a scale at least as large as this one; None on overflow
+    fn rescale(&self, scale: u32) -> Option<i128> {
+        10i128.checked_pow(scale - self.scale)?.checked_mul(self.value)
+    }
+
+    // The sum at the larger of both scales; None when it does not fit
+    pub fn checked_add(self, other: Decimal) -> Option<Decimal> {
+        let scale = self.scale.max(other.scale);
+        let value = self.rescale(scale)?.checked_add(other.rescale(scale)?)?;
+        Some(Decimal::new(value, scale))
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.value as f64 / 10f64.powi(self.scale as i32)
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let scale = self.scale.max(other.scale);
+        match (self.rescale(scale), other.rescale(scale)) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            // One side is too large to rescale, which the floats still tell apart
+            _ => self.to_f64().total_cmp(&other.to_f64()),
+        }
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.value < 0 { "-" } else { "" };
+        let digits = self.value.unsigned_abs();
+        match 10u128.checked_pow(self.scale) {
+            Some(1) => write!(f, "{}{}", sign, digits),
+            Some(unit) => write!(f, "{}{}.{:0width$}", sign, digits / unit, digits % unit, width = self.scale as usize),
+            // Scales past 38 digits only ever hold a fraction
+            None => write!(f, "{}0.{:0>width$}", sign, digits, width = self.scale as usize),
+        }
+    }
+}
+
+// Civil date conversions, valid for the proleptic Gregorian calendar
+
+pub fn parse_date(s: &str) -> Option<i32> {
+    let mut parts = s.trim().splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    i32::try_from(era * 146097 + day_of_era - 719468).ok()
+}
+
+pub fn format_date(days: i32) -> String {
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}